@@ -0,0 +1,191 @@
+//! `process_instruction` on arbitrary instructions against the LiteSVM pool
+//! of the mixer's instruction tests.
+//!
+//! Each input is a short sequence of steps. A step starts from a well-formed
+//! instruction, or from empty data, then overwrites data bytes and swaps in
+//! accounts from a fixed set of candidates: the pool's own accounts, a
+//! signer-less stranger, and copies of the state at the wrong address or
+//! with the wrong owner. After every step the pool must still be sound:
+//!
+//! - nothing panicked, and a failed instruction changed no account;
+//! - the state PDA is still owned by the mixer and decodes, with a valid
+//!   tree depth, leaf count and root index;
+//! - outside `Initialize`, the denomination, curve and tree depth are
+//!   unchanged and the leaf count did not decrease;
+//! - the vault paid out at most one denomination.
+//!
+//! `solana-program-test` stands in for the runtime's CPI syscall when the
+//! mixer runs natively, and it panics on errors the runtime returns, such as
+//! a CPI asking for a privilege the caller lacks. Such a panic ends the
+//! input like a failed transaction; any other panic is a finding.
+
+#![no_main]
+
+#[allow(dead_code)]
+#[path = "../../programs/mixer/tests/harness/mod.rs"]
+mod harness;
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::OnceLock,
+};
+
+use arbitrary::Arbitrary;
+use harness::{field, Harness, DENOMINATION};
+use libfuzzer_sys::fuzz_target;
+use mixer::{cpi, curve::Curve, events, MixerInstruction, MixerState};
+use solana_account::Account;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+const MAX_STEPS: usize = 4;
+
+#[derive(Arbitrary, Debug)]
+struct Step {
+    /// Index into the templates; out of range starts from empty data.
+    template: u8,
+    /// Length to truncate or zero-extend the data to.
+    len: Option<u8>,
+    /// `(index, value)` overwrites of the data.
+    data: Vec<(u8, u8)>,
+    /// `(index, account)` replacements; an index past the end appends.
+    accounts: Vec<(u8, Meta)>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Meta {
+    candidate: u8,
+    is_signer: bool,
+    is_writable: bool,
+}
+
+struct Fixture {
+    pool: Harness,
+    recipient: Pubkey,
+    candidates: Vec<Pubkey>,
+}
+
+fn fixture() -> &'static Fixture {
+    static FIXTURE: OnceLock<Fixture> = OnceLock::new();
+    FIXTURE.get_or_init(|| {
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !from_cpi_stub(info.location()) {
+                report(info);
+            }
+        }));
+
+        let mut pool = Harness::new();
+        let recipient = Pubkey::new_unique();
+        let state = pool.account(&pool.state).unwrap();
+        let misplaced = Pubkey::new_unique();
+        pool.set_account(misplaced, state.clone());
+        let foreign = Pubkey::new_unique();
+        pool.set_account(foreign, Account { owner: Pubkey::new_unique(), ..state });
+        let nullifier = Pubkey::find_program_address(&[b"nullifier", &field(7)], &pool.program_id).0;
+        let candidates = vec![
+            pool.authority,
+            pool.state,
+            pool.vault,
+            mixer::LatestRoot::find_address(&pool.program_id).0,
+            mixer::PoolStats::find_address(&pool.program_id).0,
+            nullifier,
+            mixer::WithdrawReceipt::find_address(&pool.program_id, &field(7)).0,
+            mixer::DepositCounter::find_address(&pool.program_id, &pool.authority).0,
+            recipient,
+            pool.verifier_id,
+            solana_sdk_ids::system_program::id(),
+            events::event_authority(&pool.program_id).0,
+            pool.program_id,
+            Pubkey::new_unique(),
+            misplaced,
+            foreign,
+        ];
+        Fixture { pool, recipient, candidates }
+    })
+}
+
+fn from_cpi_stub(location: Option<&panic::Location>) -> bool {
+    location.is_some_and(|location| location.file().contains("solana-program-test"))
+}
+
+fn instruction(fixture: &Fixture, step: &Step) -> Instruction {
+    let pool = &fixture.pool;
+    let mut instruction = match step.template {
+        0 => cpi::initialize_instruction(
+            &pool.program_id,
+            &pool.authority,
+            DENOMINATION,
+            0,
+            0,
+            Curve::Bn254,
+            MixerState::DEFAULT_TREE_DEPTH,
+            &pool.verifier_id,
+        ),
+        1 => pool.push_root(field(2)),
+        2 => pool.withdraw(field(7), &fixture.recipient),
+        _ => Instruction::new_with_bytes(pool.program_id, &[], vec![]),
+    };
+    if let Some(len) = step.len {
+        instruction.data.resize(len.into(), 0);
+    }
+    for &(index, value) in &step.data {
+        if let Some(byte) = instruction.data.get_mut(usize::from(index)) {
+            *byte = value;
+        }
+    }
+    for (index, meta) in &step.accounts {
+        let candidates = &fixture.candidates;
+        let pubkey = candidates[usize::from(meta.candidate) % candidates.len()];
+        let meta = AccountMeta { pubkey, is_signer: meta.is_signer, is_writable: meta.is_writable };
+        match instruction.accounts.get_mut(usize::from(*index)) {
+            Some(slot) => *slot = meta,
+            None => instruction.accounts.push(meta),
+        }
+    }
+    instruction
+}
+
+fn check_state(pool: &Harness) -> MixerState {
+    let account = pool.account(&pool.state).expect("state account closed");
+    assert_eq!(account.owner, pool.program_id, "state account reassigned");
+    let state = mixer::unpack_state(&account.data).expect("state no longer decodes");
+    assert!((1..=MixerState::MAX_TREE_DEPTH).contains(&state.tree_depth));
+    assert!(state.leaf_count <= state.tree_capacity());
+    assert!((state.current_root_index as usize) < MixerState::ROOT_HISTORY_SIZE);
+    state
+}
+
+fuzz_target!(|steps: Vec<Step>| {
+    let fixture = fixture();
+    let mut pool = fixture.pool.clone();
+    for step in steps.iter().take(MAX_STEPS) {
+        let instruction = instruction(fixture, step);
+        let initialize = matches!(
+            MixerInstruction::unpack(&instruction.data),
+            Ok(MixerInstruction::Initialize { .. })
+        );
+        let before = check_state(&pool);
+        let vault_before = pool.account(&pool.vault).map_or(0, |a| a.lamports);
+
+        let Ok(outcome) = panic::catch_unwind(AssertUnwindSafe(|| pool.run(instruction))) else {
+            return;
+        };
+        if outcome.result.is_err() {
+            assert!(outcome.diffs.is_empty(), "failed instruction changed {:?}", outcome.changed());
+            continue;
+        }
+
+        let after = check_state(&pool);
+        if !initialize {
+            assert_eq!(after.denomination, before.denomination);
+            assert_eq!(after.curve, before.curve);
+            assert_eq!(after.tree_depth, before.tree_depth);
+            assert!(after.leaf_count >= before.leaf_count);
+        }
+        let vault_after = pool.account(&pool.vault).map_or(0, |a| a.lamports);
+        assert!(vault_before.saturating_sub(vault_after) <= before.denomination);
+    }
+});
@@ -0,0 +1,134 @@
+//! `MixerInstruction::unpack` on arbitrary bytes: it must return, never
+//! panic, and re-encoding what it decoded through the instruction builders
+//! in `mixer::cpi`, `mixer::backup` and `mixer::view` must decode to the same
+//! instruction. The builders emit the current version, so input of another
+//! version is compared with the current version byte in place of its own.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mixer::{admin, backup, cpi, view, MixerInstruction, INSTRUCTION_VERSION, VERSION_FLAG};
+use solana_program::pubkey::Pubkey;
+
+/// `data` as the builders encode it, with the current version byte.
+fn versioned(data: &[u8]) -> Vec<u8> {
+    let payload = match data.first() {
+        Some(first) if first & VERSION_FLAG != 0 => &data[1..],
+        _ => data,
+    };
+    [&[VERSION_FLAG | INSTRUCTION_VERSION], payload].concat()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(instruction) = MixerInstruction::unpack(data) else {
+        return;
+    };
+    let program_id = Pubkey::new_from_array([1; 32]);
+    let key = Pubkey::new_from_array([2; 32]);
+    match instruction {
+        MixerInstruction::Initialize { denomination, flags, admin_delay, curve, tree_depth } => {
+            let encoded = cpi::initialize_instruction(
+                &program_id,
+                &key,
+                denomination,
+                flags,
+                admin_delay,
+                curve,
+                tree_depth,
+                &key,
+            );
+            let Ok(MixerInstruction::Initialize {
+                denomination: d,
+                flags: f,
+                admin_delay: a,
+                curve: c,
+                tree_depth: t,
+            }) = MixerInstruction::unpack(&encoded.data)
+            else {
+                panic!("Initialize did not round-trip");
+            };
+            assert_eq!((d, f, a, c, t), (denomination, flags, admin_delay, curve, tree_depth));
+        }
+        MixerInstruction::PushRoot {
+            new_root,
+            leaf_count,
+            receipt,
+        } => {
+            let mut encoded =
+                cpi::push_root_instruction(&program_id, &key, &key, new_root, leaf_count);
+            if receipt {
+                encoded = mixer::receipt::with_receipt(encoded, &key, &key);
+            }
+            let Ok(MixerInstruction::PushRoot {
+                new_root: r,
+                leaf_count: l,
+                receipt: t,
+            }) = MixerInstruction::unpack(&encoded.data)
+            else {
+                panic!("PushRoot did not round-trip");
+            };
+            assert_eq!((r, l, t), (new_root, leaf_count, receipt));
+        }
+        MixerInstruction::Withdraw {
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+        } => {
+            let encoded = cpi::withdraw_instruction(
+                &program_id,
+                &key,
+                &key,
+                &key,
+                &key,
+                &key,
+                &key,
+                root,
+                nullifier_hash,
+                recipient_field,
+                circuit_version,
+                tree_id.unwrap_or(0),
+                relayer_fee,
+                &proof,
+            );
+            let mut expected = versioned(data);
+            if tree_id.is_none() {
+                // Data before version 2 has no tree byte after circuit_version.
+                expected.insert(2 + 32 * 3 + 2, 0);
+            }
+            assert_eq!(encoded.data, expected, "Withdraw has a single encoding");
+        }
+        MixerInstruction::AppendBackup { vault_id, ciphertext } => {
+            let encoded = backup::append_backup_instruction(&program_id, &key, vault_id, &ciphertext);
+            assert_eq!(encoded.data, versioned(data), "AppendBackup has a single encoding");
+        }
+        MixerInstruction::CreateLatestRoot => {
+            let encoded = cpi::create_latest_root_instruction(&program_id, &key);
+            assert_eq!(encoded.data, versioned(data), "CreateLatestRoot has a single encoding");
+        }
+        MixerInstruction::CreatePoolStats => {
+            let encoded = cpi::create_pool_stats_instruction(&program_id, &key);
+            assert_eq!(encoded.data, versioned(data), "CreatePoolStats has a single encoding");
+        }
+        MixerInstruction::CloseWithdrawReceipt { nullifier_hash } => {
+            let encoded = cpi::close_withdraw_receipt_instruction(&program_id, &key, nullifier_hash);
+            assert_eq!(encoded.data, versioned(data), "CloseWithdrawReceipt has a single encoding");
+        }
+        MixerInstruction::CloseAccount { target } => {
+            let encoded = cpi::close_account_instruction(&program_id, &key, target);
+            assert_eq!(encoded.data, versioned(data), "CloseAccount has a single encoding");
+        }
+        MixerInstruction::FinalizeMigration => {
+            let encoded = admin::finalize_migration_instruction(&program_id);
+            assert_eq!(encoded.data, versioned(data), "FinalizeMigration has a single encoding");
+        }
+        MixerInstruction::GetState => {
+            let encoded = view::get_state_instruction(&program_id);
+            assert_eq!(encoded.data, versioned(data), "GetState has a single encoding");
+        }
+        _ => {}
+    }
+});
@@ -0,0 +1,430 @@
+//! Typed CPI into the mixer, laid out like the `cpi` module Anchor's
+//! `declare_program!` generates: one accounts struct per instruction in
+//! [`cpi::accounts`], and one function per instruction in [`cpi`] that takes
+//! a [`CpiContext`] over it. Forgetting an account, or passing them in the
+//! wrong order, is then a compile error rather than a failed transaction.
+//!
+//! An Anchor program fills the structs with `to_account_info()`; a native
+//! program passes its `AccountInfo`s. The functions wrap [`mixer::cpi`],
+//! which this crate keeps in step with. The mixer publishes no IDL to
+//! generate them from, as its payloads are not plain borsh.
+//!
+//! ```ignore
+//! let ctx = CpiContext::new_with_signer(
+//!     mixer_program,
+//!     cpi::accounts::Deposit { depositor: treasury, /* ... */ },
+//!     &[&[b"treasury", employer.as_ref(), &[bump]]],
+//! );
+//! cpi::deposit(ctx, new_root, Some(leaf_count))?;
+//! ```
+
+use solana_program::account_info::AccountInfo;
+
+pub use mixer;
+
+/// The mixer program, the accounts of one of its instructions and the seeds
+/// the caller signs with, as Anchor's `CpiContext` holds them.
+pub struct CpiContext<'a, 'b, 'c, 'info, T> {
+    pub accounts: T,
+    pub program: AccountInfo<'info>,
+    pub signer_seeds: &'a [&'b [&'c [u8]]],
+}
+
+impl<'a, 'b, 'c, 'info, T> CpiContext<'a, 'b, 'c, 'info, T> {
+    pub fn new(program: AccountInfo<'info>, accounts: T) -> Self {
+        Self { accounts, program, signer_seeds: &[] }
+    }
+
+    pub fn new_with_signer(
+        program: AccountInfo<'info>,
+        accounts: T,
+        signer_seeds: &'a [&'b [&'c [u8]]],
+    ) -> Self {
+        Self { accounts, program, signer_seeds }
+    }
+
+    pub fn with_signer(mut self, signer_seeds: &'a [&'b [&'c [u8]]]) -> Self {
+        self.signer_seeds = signer_seeds;
+        self
+    }
+}
+
+pub mod cpi {
+    use mixer::{cpi as raw, view::StateView};
+    use solana_program::{entrypoint::ProgramResult, program_error::ProgramError};
+
+    use super::CpiContext;
+
+    pub mod accounts {
+        use solana_program::account_info::AccountInfo;
+
+        /// Accounts of [`deposit`](super::deposit).
+        pub struct Deposit<'info> {
+            /// Pays the denomination. Must sign the outer instruction or be
+            /// covered by the context's signer seeds.
+            pub depositor: AccountInfo<'info>,
+            pub mixer_state: AccountInfo<'info>,
+            pub mixer_vault: AccountInfo<'info>,
+            pub system_program: AccountInfo<'info>,
+            pub event_authority: AccountInfo<'info>,
+            pub latest_root: AccountInfo<'info>,
+            pub pool_stats: AccountInfo<'info>,
+            /// The depositor's deposit counter, for pools that limit
+            /// deposits per depositor.
+            pub deposit_counter: Option<AccountInfo<'info>>,
+            /// The commitment tree of the pool's current tree, when that
+            /// tree is full and the deposit archives it.
+            pub commitment_tree: Option<AccountInfo<'info>>,
+            /// Mints a deposit receipt; needs a leaf count.
+            pub receipt: Option<DepositReceipt<'info>>,
+        }
+
+        /// Accounts of a deposit receipt; see [`mixer::receipt`].
+        pub struct DepositReceipt<'info> {
+            pub leaf_owner: AccountInfo<'info>,
+            pub receipt_authority: AccountInfo<'info>,
+            pub tree_config: AccountInfo<'info>,
+            pub merkle_tree: AccountInfo<'info>,
+            pub log_wrapper: AccountInfo<'info>,
+            pub compression_program: AccountInfo<'info>,
+            pub bubblegum_program: AccountInfo<'info>,
+        }
+
+        /// Accounts of [`withdraw`](super::withdraw).
+        pub struct Withdraw<'info> {
+            /// Funds the nullifier marker and receives the relayer fee. Must
+            /// sign the outer instruction or be covered by the context's
+            /// signer seeds.
+            pub relayer: AccountInfo<'info>,
+            pub mixer_state: AccountInfo<'info>,
+            pub nullifier: AccountInfo<'info>,
+            pub mixer_vault: AccountInfo<'info>,
+            pub recipient: AccountInfo<'info>,
+            pub verifier_program: AccountInfo<'info>,
+            pub system_program: AccountInfo<'info>,
+            pub event_authority: AccountInfo<'info>,
+            pub pool_stats: AccountInfo<'info>,
+            /// The commitment tree of the tree the root is from.
+            pub commitment_tree: AccountInfo<'info>,
+            /// The note's withdraw receipt, paid by the relayer.
+            pub withdraw_receipt: Option<AccountInfo<'info>>,
+        }
+
+        /// Accounts of [`get_state`](super::get_state).
+        pub struct GetState<'info> {
+            pub mixer_state: AccountInfo<'info>,
+        }
+    }
+
+    /// Deposit one denomination and record `new_root`, which must already
+    /// include the depositor's commitment; see [`mixer::cpi::deposit`].
+    pub fn deposit<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, accounts::Deposit<'info>>,
+        new_root: [u8; 32],
+        leaf_count: Option<u64>,
+    ) -> ProgramResult {
+        let a = &ctx.accounts;
+        let receipt = a.receipt.as_ref().map(|r| raw::ReceiptAccounts {
+            leaf_owner: &r.leaf_owner,
+            receipt_authority: &r.receipt_authority,
+            tree_config: &r.tree_config,
+            merkle_tree: &r.merkle_tree,
+            log_wrapper: &r.log_wrapper,
+            compression_program: &r.compression_program,
+            bubblegum_program: &r.bubblegum_program,
+        });
+        raw::deposit(
+            raw::DepositAccounts {
+                depositor: &a.depositor,
+                mixer_state: &a.mixer_state,
+                mixer_vault: &a.mixer_vault,
+                system_program: &a.system_program,
+                event_authority: &a.event_authority,
+                mixer_program: &ctx.program,
+                latest_root: &a.latest_root,
+                pool_stats: &a.pool_stats,
+                deposit_counter: a.deposit_counter.as_ref(),
+                commitment_tree: a.commitment_tree.as_ref(),
+                receipt,
+            },
+            new_root,
+            leaf_count,
+            ctx.signer_seeds,
+        )
+    }
+
+    /// Withdraw to the recipient with a proof for `root` and
+    /// `nullifier_hash`; see [`mixer::cpi::withdraw`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn withdraw<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, accounts::Withdraw<'info>>,
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient_field: [u8; 32],
+        circuit_version: u16,
+        tree_id: u8,
+        relayer_fee: u64,
+        proof: &[u8],
+    ) -> ProgramResult {
+        let a = &ctx.accounts;
+        raw::withdraw(
+            raw::WithdrawAccounts {
+                relayer: &a.relayer,
+                mixer_state: &a.mixer_state,
+                nullifier: &a.nullifier,
+                mixer_vault: &a.mixer_vault,
+                recipient: &a.recipient,
+                verifier_program: &a.verifier_program,
+                system_program: &a.system_program,
+                event_authority: &a.event_authority,
+                mixer_program: &ctx.program,
+                pool_stats: &a.pool_stats,
+                commitment_tree: &a.commitment_tree,
+                receipt: a.withdraw_receipt.as_ref(),
+            },
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+            ctx.signer_seeds,
+        )
+    }
+
+    /// The pool state, through `GetState`; see [`mixer::cpi::get_state`].
+    pub fn get_state<'info>(
+        ctx: CpiContext<'_, '_, '_, 'info, accounts::GetState<'info>>,
+    ) -> Result<StateView, ProgramError> {
+        raw::get_state(&ctx.accounts.mixer_state, &ctx.program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, sync::Once};
+
+    use mixer::{cpi as raw, receipt, view, MixerState};
+    use solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
+        instruction::Instruction,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+        pubkey::Pubkey,
+    };
+
+    use super::{cpi, CpiContext};
+
+    thread_local! {
+        /// Instructions invoked on this thread, with the keys of the
+        /// accounts passed along.
+        static INVOKED: RefCell<Vec<(Instruction, Vec<Pubkey>)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct RecordInvokes;
+
+    impl SyscallStubs for RecordInvokes {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            let keys = account_infos.iter().map(|info| *info.key).collect();
+            INVOKED.with(|invoked| invoked.borrow_mut().push((instruction.clone(), keys)));
+            Ok(())
+        }
+    }
+
+    /// Run `call` and return the one instruction it invoked, with the keys of
+    /// the accounts it passed.
+    fn invoked(call: impl FnOnce()) -> (Instruction, Vec<Pubkey>) {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(RecordInvokes));
+        });
+        INVOKED.with(|invoked| invoked.borrow_mut().clear());
+        call();
+        let mut invoked = INVOKED.with(|invoked| invoked.take());
+        assert_eq!(invoked.len(), 1, "expected one CPI");
+        invoked.pop().unwrap()
+    }
+
+    /// The keys `instruction` names, in order.
+    fn keys(instruction: &Instruction) -> Vec<Pubkey> {
+        instruction.accounts.iter().map(|meta| meta.pubkey).collect()
+    }
+
+    /// An account at `key`; the test's accounts live as long as it does.
+    fn account(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            true,
+            Box::leak(Box::new(1)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+        )
+    }
+
+    /// The account `instruction` names at `index`.
+    fn at(instruction: &Instruction, index: usize) -> AccountInfo<'static> {
+        account(instruction.accounts[index].pubkey, Pubkey::default(), vec![])
+    }
+
+    fn program(program_id: Pubkey) -> AccountInfo<'static> {
+        account(program_id, Pubkey::default(), vec![])
+    }
+
+    fn state_address(program_id: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(&[b"mixer_state"], program_id).0
+    }
+
+    #[test]
+    fn deposit_invokes_push_root() {
+        let program_id = Pubkey::new_unique();
+        let depositor = Pubkey::new_unique();
+        let expected = raw::push_root_instruction(
+            &program_id,
+            &depositor,
+            &state_address(&program_id),
+            [7; 32],
+            Some(3),
+        );
+        let ix = &expected;
+        let (instruction, passed) = invoked(|| {
+            let accounts = cpi::accounts::Deposit {
+                depositor: at(ix, 0),
+                mixer_state: at(ix, 1),
+                event_authority: at(ix, 2),
+                mixer_vault: at(ix, 4),
+                latest_root: at(ix, 5),
+                pool_stats: at(ix, 6),
+                system_program: at(ix, 7),
+                deposit_counter: None,
+                commitment_tree: None,
+                receipt: None,
+            };
+            cpi::deposit(CpiContext::new(program(program_id), accounts), [7; 32], Some(3)).unwrap();
+        });
+        assert_eq!(instruction, expected);
+        assert_eq!(passed, keys(&expected));
+    }
+
+    #[test]
+    fn deposit_with_every_option_invokes_push_root() {
+        let program_id = Pubkey::new_unique();
+        let depositor = Pubkey::new_unique();
+        let state = state_address(&program_id);
+        let mut pool = mixer::unpack_state(&[0; MixerState::LEN]).unwrap();
+        pool.tree_id = 3;
+        let mut state_data = vec![0; MixerState::LEN];
+        mixer::pack_state(&pool, &mut state_data).unwrap();
+        let receipt_tree = Pubkey::new_unique();
+        let leaf_owner = Pubkey::new_unique();
+        let expected = receipt::with_receipt(
+            raw::with_tree_rotation(
+                raw::push_root_with_counter_instruction(
+                    &program_id,
+                    &depositor,
+                    &state,
+                    [7; 32],
+                    Some(3),
+                ),
+                3,
+            ),
+            &receipt_tree,
+            &leaf_owner,
+        );
+        let ix = &expected;
+        let (instruction, passed) = invoked(|| {
+            let accounts = cpi::accounts::Deposit {
+                depositor: at(ix, 0),
+                mixer_state: account(state, program_id, state_data),
+                event_authority: at(ix, 2),
+                mixer_vault: at(ix, 4),
+                latest_root: at(ix, 5),
+                pool_stats: at(ix, 6),
+                system_program: at(ix, 7),
+                deposit_counter: Some(at(ix, 8)),
+                commitment_tree: Some(at(ix, 10)),
+                receipt: Some(cpi::accounts::DepositReceipt {
+                    leaf_owner: at(ix, 12),
+                    receipt_authority: at(ix, 13),
+                    tree_config: at(ix, 14),
+                    merkle_tree: at(ix, 15),
+                    log_wrapper: at(ix, 16),
+                    compression_program: at(ix, 17),
+                    bubblegum_program: at(ix, 18),
+                }),
+            };
+            cpi::deposit(CpiContext::new(program(program_id), accounts), [7; 32], Some(3)).unwrap();
+        });
+        assert_eq!(instruction, expected);
+        assert_eq!(passed, keys(&expected));
+    }
+
+    #[test]
+    fn withdraw_invokes_withdraw() {
+        let program_id = Pubkey::new_unique();
+        for (relayer_fee, with_receipt) in [(0, false), (5, true)] {
+            let withdraw = raw::withdraw_instruction(
+                &program_id,
+                &Pubkey::new_unique(),
+                &state_address(&program_id),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                [1; 32],
+                [2; 32],
+                [3; 32],
+                4,
+                5,
+                relayer_fee,
+                &[6; 10],
+            );
+            let expected = match with_receipt {
+                true => raw::with_withdraw_receipt(withdraw),
+                false => withdraw,
+            };
+            let ix = &expected;
+            let (instruction, passed) = invoked(|| {
+                let accounts = cpi::accounts::Withdraw {
+                    relayer: at(ix, 0),
+                    mixer_state: at(ix, 1),
+                    nullifier: at(ix, 2),
+                    mixer_vault: at(ix, 3),
+                    recipient: at(ix, 4),
+                    verifier_program: at(ix, 5),
+                    system_program: at(ix, 6),
+                    event_authority: at(ix, 7),
+                    pool_stats: at(ix, 9),
+                    commitment_tree: at(ix, 10),
+                    withdraw_receipt: with_receipt.then(|| at(ix, 11)),
+                };
+                let ctx = CpiContext::new(program(program_id), accounts);
+                cpi::withdraw(ctx, [1; 32], [2; 32], [3; 32], 4, 5, relayer_fee, &[6; 10])
+                    .unwrap();
+            });
+            assert_eq!(instruction, expected);
+            assert_eq!(passed, keys(&expected));
+        }
+    }
+
+    #[test]
+    fn get_state_invokes_get_state() {
+        let program_id = Pubkey::new_unique();
+        let expected = view::get_state_instruction(&program_id);
+        let ix = &expected;
+        let (instruction, passed) = invoked(|| {
+            let accounts = cpi::accounts::GetState { mixer_state: at(ix, 0) };
+            // The stub returns no data, which the wrapper reports as an error.
+            assert!(cpi::get_state(CpiContext::new(program(program_id), accounts)).is_err());
+        });
+        assert_eq!(instruction, expected);
+        assert_eq!(passed, [expected.accounts[0].pubkey, program_id]);
+    }
+}
@@ -0,0 +1,62 @@
+//! State account encoding and the root ring buffer, as run by every
+//! instruction that touches the pool.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mixer::MixerState;
+use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
+
+fn root(tag: usize) -> [u8; 32] {
+    let mut root = [0u8; 32];
+    root[24..].copy_from_slice(&(tag as u64 + 1).to_be_bytes());
+    root
+}
+
+/// A pool with a full root history, so lookups walk real entries.
+fn state() -> MixerState {
+    let mut state = mixer::unpack_state(&[0u8; MixerState::LEN]).unwrap();
+    state.denomination = 1_000_000_000;
+    state.tree_depth = MixerState::DEFAULT_TREE_DEPTH;
+    for i in 0..MixerState::ROOT_HISTORY_SIZE {
+        state.push_root(root(i), i as i64, i as u64);
+    }
+    state
+}
+
+fn serde(c: &mut Criterion) {
+    let program_id = Pubkey::new_unique();
+    let key = Pubkey::find_program_address(&[b"mixer_state"], &program_id).0;
+    let mut lamports = 0;
+    let mut data = vec![0u8; MixerState::LEN];
+    let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &program_id, false);
+    let state = state();
+    mixer::store_state(&account, &state).unwrap();
+
+    c.bench_function("load_state", |b| {
+        b.iter(|| mixer::load_state(black_box(&program_id), black_box(&account)).unwrap())
+    });
+    c.bench_function("store_state", |b| {
+        b.iter(|| mixer::store_state(black_box(&account), black_box(&state)).unwrap())
+    });
+}
+
+fn roots(c: &mut Criterion) {
+    let state = state();
+    let newest = root(MixerState::ROOT_HISTORY_SIZE - 1);
+    let oldest = root(0);
+    let unknown = root(MixerState::ROOT_HISTORY_SIZE);
+
+    c.bench_function("push_root", |b| {
+        let mut state = state;
+        b.iter(|| state.push_root(black_box(newest), black_box(0), black_box(0)))
+    });
+    let mut group = c.benchmark_group("root_index");
+    for (name, root) in [("newest", newest), ("oldest", oldest), ("unknown", unknown)] {
+        group.bench_function(name, |b| b.iter(|| state.root_index(black_box(&root))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, serde, roots);
+criterion_main!(benches);
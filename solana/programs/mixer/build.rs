@@ -0,0 +1,368 @@
+//! With the `circuit-artifacts` feature, compile the Noir circuit in
+//! `circuits/` and embed its public-input layout and Groth16 verifying key as
+//! constants in `mixer::circuit`.
+//!
+//! - `nargo compile` produces `target/circuits.json`, whose ABI gives the
+//!   public inputs in order. The build fails unless they match the order the
+//!   withdraw path passes them in. The length of its `merkle_proof`
+//!   parameter is the tree depth the circuit was compiled for.
+//! - `sunspot setup` produces `target/circuits.vk`, a gnark verifying key.
+//!   Its points are re-encoded in the EIP-197 layout of
+//!   `verifier::Groth16VerifyingKey`.
+//!
+//! Environment overrides: `NARGO` (binary, default `nargo`),
+//! `MIXER_CIRCUIT_DIR` (default `../../../circuits`) and `MIXER_CIRCUIT_VK`
+//! (default `<circuit dir>/target/circuits.vk`).
+//!
+//! Without the feature this script does nothing, so the program builds
+//! without a Noir toolchain.
+
+fn main() {
+    #[cfg(feature = "circuit-artifacts")]
+    if let Err(e) = artifacts::generate() {
+        panic!("circuit-artifacts: {e}");
+    }
+}
+
+#[cfg(feature = "circuit-artifacts")]
+mod artifacts {
+    use std::{
+        env,
+        fmt::Write as _,
+        fs,
+        path::{Path, PathBuf},
+        process::Command,
+    };
+
+    use ark_bn254::{Fq, Fq2, G1Affine, G2Affine};
+    use ark_ff::{BigInteger, PrimeField};
+    use sha2::{Digest, Sha256};
+
+    type Result<T> = std::result::Result<T, String>;
+
+    /// Public inputs in the order `spend_note` passes them to the verifier.
+    const EXPECTED_PUBLIC_INPUTS: [&str; 3] = ["root", "nullifier_hash", "recipient"];
+
+    // Flags in the top two bits of gnark's BN254 point encodings.
+    const FLAG_MASK: u8 = 0b11 << 6;
+    const FLAG_UNCOMPRESSED: u8 = 0b00 << 6;
+    const FLAG_INFINITY: u8 = 0b01 << 6;
+    const FLAG_COMPRESSED_SMALLEST: u8 = 0b10 << 6;
+    const FLAG_COMPRESSED_LARGEST: u8 = 0b11 << 6;
+
+    pub fn generate() -> Result<()> {
+        let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+        let circuit_dir = match env::var_os("MIXER_CIRCUIT_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => manifest_dir.join("../../../circuits"),
+        };
+        let vk_path = match env::var_os("MIXER_CIRCUIT_VK") {
+            Some(path) => PathBuf::from(path),
+            None => circuit_dir.join("target/circuits.vk"),
+        };
+        for var in ["NARGO", "MIXER_CIRCUIT_DIR", "MIXER_CIRCUIT_VK"] {
+            println!("cargo:rerun-if-env-changed={var}");
+        }
+        println!("cargo:rerun-if-changed={}", circuit_dir.join("Nargo.toml").display());
+        println!("cargo:rerun-if-changed={}", circuit_dir.join("src").display());
+        println!("cargo:rerun-if-changed={}", vk_path.display());
+
+        let nargo = env::var("NARGO").unwrap_or_else(|_| "nargo".into());
+        let status = Command::new(&nargo)
+            .arg("compile")
+            .current_dir(&circuit_dir)
+            .status()
+            .map_err(|e| format!("running `{nargo} compile`: {e}"))?;
+        if !status.success() {
+            return Err(format!("`{nargo} compile` failed with {status}"));
+        }
+
+        let artifact_path = circuit_dir.join("target/circuits.json");
+        let artifact: serde_json::Value = serde_json::from_slice(&read(&artifact_path)?)
+            .map_err(|e| format!("parsing {}: {e}", artifact_path.display()))?;
+        let public_inputs = public_inputs(&artifact)?;
+        if public_inputs != EXPECTED_PUBLIC_INPUTS {
+            return Err(format!(
+                "circuit public inputs {public_inputs:?} do not match the withdraw layout {EXPECTED_PUBLIC_INPUTS:?}"
+            ));
+        }
+        let tree_depth = tree_depth(&artifact)?;
+        let bytecode = artifact["bytecode"]
+            .as_str()
+            .ok_or("circuit artifact has no bytecode")?;
+
+        let vk = decode_gnark_vk(&read(&vk_path)?, public_inputs.len())
+            .map_err(|e| format!("decoding {}: {e}", vk_path.display()))?;
+
+        let out = Path::new(&env::var("OUT_DIR").unwrap()).join("circuit_artifacts.rs");
+        fs::write(&out, render(&public_inputs, tree_depth, bytecode.as_bytes(), &vk))
+            .map_err(|e| format!("writing {}: {e}", out.display()))
+    }
+
+    fn read(path: &Path) -> Result<Vec<u8>> {
+        fs::read(path).map_err(|e| format!("reading {}: {e}", path.display()))
+    }
+
+    /// Names of the circuit's public parameters, in ABI order. Only scalar
+    /// fields are supported, since each maps to one verifier input.
+    fn public_inputs(artifact: &serde_json::Value) -> Result<Vec<String>> {
+        let abi = &artifact["abi"];
+        if !abi["return_type"].is_null() {
+            return Err("circuit must not return public values".into());
+        }
+        let parameters = abi["parameters"]
+            .as_array()
+            .ok_or("circuit artifact has no ABI parameters")?;
+        let mut names = Vec::new();
+        for param in parameters {
+            if param["visibility"] != "public" {
+                continue;
+            }
+            let name = param["name"].as_str().ok_or("ABI parameter without a name")?;
+            if param["type"]["kind"] != "field" {
+                return Err(format!("public input `{name}` is not a single field"));
+            }
+            names.push(name.to_string());
+        }
+        Ok(names)
+    }
+
+    /// Merkle tree depth of the circuit: the length of its `merkle_proof`
+    /// parameter.
+    fn tree_depth(artifact: &serde_json::Value) -> Result<u8> {
+        let parameters = artifact["abi"]["parameters"]
+            .as_array()
+            .ok_or("circuit artifact has no ABI parameters")?;
+        let merkle_proof = parameters
+            .iter()
+            .find(|param| param["name"] == "merkle_proof")
+            .ok_or("circuit has no `merkle_proof` parameter")?;
+        if merkle_proof["type"]["kind"] != "array" {
+            return Err("`merkle_proof` is not an array".into());
+        }
+        merkle_proof["type"]["length"]
+            .as_u64()
+            .and_then(|len| u8::try_from(len).ok())
+            .ok_or_else(|| "`merkle_proof` has no valid length".into())
+    }
+
+    /// The verifying key in `Groth16VerifyingKey` field order.
+    struct VerifyingKey {
+        alpha_g1: [u8; 64],
+        beta_g2: [u8; 128],
+        gamma_g2: [u8; 128],
+        delta_g2: [u8; 128],
+        ic: Vec<[u8; 64]>,
+    }
+
+    impl VerifyingKey {
+        /// Borsh encoding, i.e. the verifying key account data.
+        fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            out.extend_from_slice(&self.alpha_g1);
+            out.extend_from_slice(&self.beta_g2);
+            out.extend_from_slice(&self.gamma_g2);
+            out.extend_from_slice(&self.delta_g2);
+            for ic in &self.ic {
+                out.extend_from_slice(ic);
+            }
+            out
+        }
+    }
+
+    struct Reader<'a> {
+        data: &'a [u8],
+        compressed: bool,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+            if self.data.len() < len {
+                return Err("unexpected end of verifying key".into());
+            }
+            let (head, rest) = self.data.split_at(len);
+            self.data = rest;
+            Ok(head)
+        }
+
+        fn u32(&mut self) -> Result<u32> {
+            Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+        }
+
+        fn g1(&mut self) -> Result<[u8; 64]> {
+            let len = if self.compressed { 32 } else { 64 };
+            let mut bytes = self.take(len)?.to_vec();
+            let flag = take_flag(&mut bytes)?;
+            let x = fq(&bytes[..32])?;
+            let point = match flag {
+                FLAG_UNCOMPRESSED => G1Affine::new_unchecked(x, fq(&bytes[32..])?),
+                _ => G1Affine::get_point_from_x_unchecked(x, flag == FLAG_COMPRESSED_LARGEST)
+                    .ok_or("G1 x coordinate is not on the curve")?,
+            };
+            if !point.is_on_curve() {
+                return Err("G1 point is not on the curve".into());
+            }
+            let mut out = [0u8; 64];
+            out[..32].copy_from_slice(&fq_bytes(&point.x));
+            out[32..].copy_from_slice(&fq_bytes(&point.y));
+            Ok(out)
+        }
+
+        fn g2(&mut self) -> Result<[u8; 128]> {
+            let len = if self.compressed { 64 } else { 128 };
+            let mut bytes = self.take(len)?.to_vec();
+            let flag = take_flag(&mut bytes)?;
+            // gnark, like EIP-197, writes the imaginary part first.
+            let x = Fq2::new(fq(&bytes[32..64])?, fq(&bytes[..32])?);
+            let point = match flag {
+                FLAG_UNCOMPRESSED => {
+                    G2Affine::new_unchecked(x, Fq2::new(fq(&bytes[96..])?, fq(&bytes[64..96])?))
+                }
+                _ => G2Affine::get_point_from_x_unchecked(x, flag == FLAG_COMPRESSED_LARGEST)
+                    .ok_or("G2 x coordinate is not on the curve")?,
+            };
+            if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+                return Err("G2 point is not in the prime-order subgroup".into());
+            }
+            let mut out = [0u8; 128];
+            out[..32].copy_from_slice(&fq_bytes(&point.x.c1));
+            out[32..64].copy_from_slice(&fq_bytes(&point.x.c0));
+            out[64..96].copy_from_slice(&fq_bytes(&point.y.c1));
+            out[96..].copy_from_slice(&fq_bytes(&point.y.c0));
+            Ok(out)
+        }
+    }
+
+    /// Strip and return the flag bits of an encoded point. The identity never
+    /// appears in a valid verifying key.
+    fn take_flag(bytes: &mut [u8]) -> Result<u8> {
+        let flag = bytes[0] & FLAG_MASK;
+        bytes[0] &= !FLAG_MASK;
+        match flag {
+            FLAG_INFINITY => Err("verifying key contains the point at infinity".into()),
+            _ => Ok(flag),
+        }
+    }
+
+    fn fq(bytes: &[u8]) -> Result<Fq> {
+        let value = Fq::from_be_bytes_mod_order(bytes);
+        if fq_bytes(&value) != bytes {
+            return Err("coordinate is not a canonical field element".into());
+        }
+        Ok(value)
+    }
+
+    fn fq_bytes(value: &Fq) -> [u8; 32] {
+        value.into_bigint().to_bytes_be().try_into().unwrap()
+    }
+
+    /// Decode a gnark BN254 Groth16 verifying key (`WriteTo` or `WriteRawTo`):
+    /// `alpha_g1 || beta_g1 || beta_g2 || gamma_g2 || delta_g1 || delta_g2 ||
+    /// u32 len || K[len]`, followed by commitment data this verifier does not
+    /// support.
+    fn decode_gnark_vk(data: &[u8], num_public_inputs: usize) -> Result<VerifyingKey> {
+        let first = *data.first().ok_or("verifying key is empty")?;
+        let compressed = matches!(
+            first & FLAG_MASK,
+            FLAG_COMPRESSED_SMALLEST | FLAG_COMPRESSED_LARGEST
+        );
+        let mut r = Reader { data, compressed };
+        let alpha_g1 = r.g1()?;
+        let _beta_g1 = r.g1()?;
+        let beta_g2 = r.g2()?;
+        let gamma_g2 = r.g2()?;
+        let _delta_g1 = r.g1()?;
+        let delta_g2 = r.g2()?;
+        let k_len = r.u32()? as usize;
+        if k_len != num_public_inputs + 1 {
+            return Err(format!(
+                "verifying key has {k_len} input points, the circuit needs {}",
+                num_public_inputs + 1
+            ));
+        }
+        let ic = (0..k_len).map(|_| r.g1()).collect::<Result<_>>()?;
+
+        // Newer gnark versions append the Pedersen commitment layout.
+        if !r.data.is_empty() {
+            let committed = r.u32()?;
+            if committed != 0 {
+                return Err("verifying keys with commitments are not supported".into());
+            }
+            if !r.data.is_empty() && r.u32()? != 0 {
+                return Err("verifying keys with commitment keys are not supported".into());
+            }
+        }
+        Ok(VerifyingKey { alpha_g1, beta_g2, gamma_g2, delta_g2, ic })
+    }
+
+    fn bytes_literal(bytes: &[u8]) -> String {
+        let mut out = String::from("[");
+        for (i, b) in bytes.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            write!(out, "0x{b:02x}").unwrap();
+        }
+        out.push(']');
+        out
+    }
+
+    fn render(
+        public_inputs: &[String],
+        tree_depth: u8,
+        bytecode: &[u8],
+        vk: &VerifyingKey,
+    ) -> String {
+        let mut out = String::new();
+        writeln!(out, "// @generated by build.rs from the Noir circuit. Do not edit.").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "/// Public inputs of the compiled circuit, in ABI order.").unwrap();
+        writeln!(
+            out,
+            "pub const PUBLIC_INPUTS: [&str; {}] = {:?};",
+            public_inputs.len(),
+            public_inputs
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "/// Merkle tree depth of the compiled circuit.").unwrap();
+        writeln!(out, "pub const TREE_DEPTH: u8 = {tree_depth};").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "/// SHA-256 of the compiled ACIR bytecode (base64, as in `circuits.json`).")
+            .unwrap();
+        writeln!(
+            out,
+            "pub const ACIR_HASH: [u8; 32] = {};",
+            bytes_literal(&Sha256::digest(bytecode))
+        )
+        .unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "/// Verifying key from `sunspot setup`, in the native backend's encoding.")
+            .unwrap();
+        writeln!(out, "pub const VERIFYING_KEY: Groth16VerifyingKey = Groth16VerifyingKey {{")
+            .unwrap();
+        writeln!(out, "    alpha_g1: {},", bytes_literal(&vk.alpha_g1)).unwrap();
+        writeln!(out, "    beta_g2: {},", bytes_literal(&vk.beta_g2)).unwrap();
+        writeln!(out, "    gamma_g2: {},", bytes_literal(&vk.gamma_g2)).unwrap();
+        writeln!(out, "    delta_g2: {},", bytes_literal(&vk.delta_g2)).unwrap();
+        writeln!(out, "    ic: [").unwrap();
+        for ic in &vk.ic {
+            writeln!(out, "        {},", bytes_literal(ic)).unwrap();
+        }
+        writeln!(out, "    ],").unwrap();
+        writeln!(out, "}};").unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "/// `verifier_account_hash` of [`VERIFYING_KEY`], the value for `SetVerifierHash`."
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "pub const VERIFYING_KEY_HASH: [u8; 32] = {};",
+            bytes_literal(&Sha256::digest(vk.to_bytes()))
+        )
+        .unwrap();
+        out
+    }
+}
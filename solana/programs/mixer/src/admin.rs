@@ -0,0 +1,875 @@
+//! Pool administration behind a timelock.
+//!
+//! The pool authority (the `Initialize` signer) cannot change parameters
+//! directly. It queues an [`AdminAction`], which can only be executed once
+//! `MixerState::admin_delay` seconds have passed. Every queued action is
+//! announced with an `AdminActionQueued` event, so users who object to a
+//! change can withdraw before it applies.
+//!
+//! Pausing is the exception: it takes effect immediately, and besides the
+//! authority an optional guardian key may pause (but never unpause), so a hot
+//! key can react to incidents while the authority stays cold.
+//!
+//! Each pending action lives in a PDA of `["admin_action", action_id]` that
+//! holds a borsh-encoded [`PendingAdminAction`] and is closed on execution or
+//! cancellation.
+//!
+//! Fee parameters are ordinary admin actions, but each has a hard-coded
+//! upper bound ([`MAX_PROTOCOL_FEE_BPS`], [`MAX_RELAYER_FEE_CAP_BPS`],
+//! [`MAX_MIN_WITHDRAW_DELAY`]) checked when the action is queued, so not even
+//! the authority can set a confiscatory fee. Executing one also emits
+//! `ParametersChanged` with the full new parameter set.
+//!
+//! The emergency drain is a last resort for a broken circuit or verifier: it
+//! moves the whole vault to a recovery address, and so is held to a stricter
+//! bar than other actions. It can only be queued and executed while the pool
+//! is paused, its delay is at least [`EMERGENCY_DRAIN_MIN_DELAY`] whatever the
+//! admin delay, and every step emits an event.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{rent::Rent, Sysvar},
+};
+use solana_system_interface::instruction as system_instruction;
+
+use crate::{
+    check_vault, close_account, events, load_state, store_state,
+    verifier::{CircuitVersion, VerifierBackend},
+    MixerError, MixerState,
+};
+
+pub const ADMIN_ACTION_SEED: &[u8] = b"admin_action";
+pub const EMERGENCY_DRAIN_SEED: &[u8] = b"emergency_drain";
+
+/// Minimum seconds between queueing and executing an emergency drain.
+pub const EMERGENCY_DRAIN_MIN_DELAY: i64 = 14 * 24 * 60 * 60;
+
+/// Upper bound on `MixerState::protocol_fee_bps` (2%).
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 200;
+/// Upper bound on `MixerState::relayer_fee_cap_bps` (10%).
+pub const MAX_RELAYER_FEE_CAP_BPS: u16 = 1_000;
+/// Upper bound on `MixerState::min_withdraw_delay` (7 days).
+pub const MAX_MIN_WITHDRAW_DELAY: u64 = 7 * 24 * 60 * 60;
+
+/// A parameter change subject to the admin delay.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminAction {
+    /// Pin withdrawals to a new verifier program.
+    SetVerifier { verifier_program: [u8; 32] },
+    /// Hand the pool to a new authority.
+    SetAuthority { authority: [u8; 32] },
+    /// Change the delay for actions queued from now on.
+    SetAdminDelay { delay_secs: u64 },
+    /// Replace the guardian; all zeroes removes it.
+    SetGuardian { guardian: [u8; 32] },
+    /// Change the share of each withdrawal kept by the pool.
+    SetProtocolFee { fee_bps: u16 },
+    /// Change the largest relayer fee a withdraw may pay its relayer, which
+    /// also caps the bounty a scheduled withdraw may pay its keeper.
+    SetRelayerFeeCap { cap_bps: u16 },
+    /// Change how old a root must be before notes under it can be withdrawn.
+    SetMinWithdrawDelay { delay_secs: u64 },
+    /// Switch proof verification to another backend. `verifier` is the
+    /// verifier program for CPI backends and the verifying key account for
+    /// the native one.
+    SetVerifierBackend { backend: u8, verifier: [u8; 32] },
+    /// Register a circuit version, or replace the entry already holding
+    /// `version`; see [`crate::verifier::CircuitVersion`]. A new entry takes
+    /// an empty slot or one whose version is deprecated by execution time.
+    SetCircuitVersion {
+        version: u16,
+        backend: u8,
+        verifier: [u8; 32],
+        activation_slot: u64,
+        deprecation_slot: u64,
+    },
+    /// Stop accepting proofs for a registered circuit version from
+    /// `deprecation_slot` on.
+    DeprecateCircuitVersion { version: u16, deprecation_slot: u64 },
+    /// Bind the default verifier to an artifact hash; all zeroes removes the
+    /// binding. `SetVerifier` and `SetVerifierBackend` clear it.
+    SetVerifierHash { hash: [u8; 32] },
+    /// Limit deposits per epoch, in total and per depositor; 0 lifts a
+    /// limit. Deposits already counted in the current epoch still count.
+    SetDepositLimits { per_epoch: u32, per_depositor: u32 },
+    /// Cap the lamports the vault may hold after a deposit; 0 lifts the cap.
+    /// Lowering it below the vault balance stops deposits until withdrawals
+    /// bring the vault under it.
+    SetVaultCap { max_vault_lamports: u64 },
+    /// Mint deposit receipts into a Bubblegum tree whose delegate is the
+    /// receipt authority PDA; all zeroes disables receipts.
+    SetReceiptTree { tree: [u8; 32] },
+    /// Change the pool's denomination, e.g. to retire a mispriced pool
+    /// without moving it to a new address. Executes only while the pool is
+    /// paused and holds no unspent notes, as their denomination is the one
+    /// they were deposited with.
+    SetDenomination { denomination: u64 },
+    /// Start moving the pool to a new circuit: register `version` for the
+    /// new verifier, active from execution, and keep accepting default
+    /// (version 0) proofs for `window_slots` more slots. After that only
+    /// the new version verifies, and `FinalizeMigration` makes it the
+    /// default. Replaces a migration already pending, whose version stops
+    /// verifying.
+    MigratePool {
+        version: u16,
+        backend: u8,
+        verifier: [u8; 32],
+        window_slots: u64,
+    },
+}
+
+impl AdminAction {
+    /// Check parameters, including that a new backend supports the pool's
+    /// curve.
+    fn check_bounds(&self, state: &MixerState) -> ProgramResult {
+        let curve = state.curve()?;
+        let supported = |backend: &u8| {
+            VerifierBackend::try_from(*backend).is_ok_and(|backend| curve.supports(backend))
+        };
+        let in_bounds = match self {
+            AdminAction::SetProtocolFee { fee_bps } => *fee_bps <= MAX_PROTOCOL_FEE_BPS,
+            AdminAction::SetRelayerFeeCap { cap_bps } => *cap_bps <= MAX_RELAYER_FEE_CAP_BPS,
+            AdminAction::SetMinWithdrawDelay { delay_secs } => {
+                *delay_secs <= MAX_MIN_WITHDRAW_DELAY
+            }
+            AdminAction::SetVerifier { verifier_program } => *verifier_program != [0u8; 32],
+            AdminAction::SetVerifierBackend { backend, verifier } => {
+                supported(backend) && *verifier != [0u8; 32]
+            }
+            AdminAction::SetCircuitVersion {
+                version,
+                backend,
+                verifier,
+                activation_slot,
+                deprecation_slot,
+            } => {
+                *version != 0
+                    && supported(backend)
+                    && *verifier != [0u8; 32]
+                    && activation_slot < deprecation_slot
+            }
+            AdminAction::DeprecateCircuitVersion { version, .. } => *version != 0,
+            AdminAction::SetDenomination { denomination } => *denomination != 0,
+            AdminAction::MigratePool {
+                version,
+                backend,
+                verifier,
+                window_slots,
+            } => *version != 0 && supported(backend) && *verifier != [0u8; 32] && *window_slots != 0,
+            _ => true,
+        };
+        if !in_bounds {
+            msg!("Admin action parameter out of bounds");
+            return Err(MixerError::ParameterOutOfBounds.into());
+        }
+        Ok(())
+    }
+
+    fn changes_parameters(&self) -> bool {
+        matches!(
+            self,
+            AdminAction::SetProtocolFee { .. }
+                | AdminAction::SetRelayerFeeCap { .. }
+                | AdminAction::SetMinWithdrawDelay { .. }
+        )
+    }
+
+    fn apply(&self, state: &mut MixerState, slot: u64) -> ProgramResult {
+        match self {
+            AdminAction::SetVerifier { verifier_program } => {
+                state.verifier_program = Pubkey::new_from_array(*verifier_program);
+                state.verifier_hash = [0u8; 32];
+            }
+            AdminAction::SetAuthority { authority } => {
+                state.authority = Pubkey::new_from_array(*authority);
+            }
+            AdminAction::SetAdminDelay { delay_secs } => state.admin_delay = *delay_secs,
+            AdminAction::SetGuardian { guardian } => {
+                state.guardian = Pubkey::new_from_array(*guardian);
+            }
+            AdminAction::SetProtocolFee { fee_bps } => state.protocol_fee_bps = *fee_bps,
+            AdminAction::SetRelayerFeeCap { cap_bps } => state.relayer_fee_cap_bps = *cap_bps,
+            AdminAction::SetMinWithdrawDelay { delay_secs } => {
+                state.min_withdraw_delay = *delay_secs;
+            }
+            AdminAction::SetVerifierBackend { backend, verifier } => {
+                state.verifier_backend = *backend;
+                state.verifier_program = Pubkey::new_from_array(*verifier);
+                state.verifier_hash = [0u8; 32];
+            }
+            AdminAction::SetCircuitVersion {
+                version,
+                backend,
+                verifier,
+                activation_slot,
+                deprecation_slot,
+            } => register_circuit_version(
+                state,
+                CircuitVersion {
+                    version: *version,
+                    backend: *backend,
+                    verifier: Pubkey::new_from_array(*verifier),
+                    activation_slot: *activation_slot,
+                    deprecation_slot: *deprecation_slot,
+                },
+                slot,
+            )?,
+            AdminAction::DeprecateCircuitVersion {
+                version,
+                deprecation_slot,
+            } => {
+                let entry = state
+                    .circuit_versions
+                    .iter_mut()
+                    .find(|e| e.version == *version)
+                    .ok_or(MixerError::UnknownCircuitVersion)?;
+                entry.deprecation_slot = *deprecation_slot;
+            }
+            AdminAction::SetVerifierHash { hash } => state.verifier_hash = *hash,
+            AdminAction::SetDepositLimits {
+                per_epoch,
+                per_depositor,
+            } => {
+                state.max_deposits_per_epoch = *per_epoch;
+                state.max_deposits_per_depositor = *per_depositor;
+            }
+            AdminAction::SetVaultCap { max_vault_lamports } => {
+                state.max_vault_lamports = *max_vault_lamports;
+            }
+            AdminAction::SetReceiptTree { tree } => {
+                state.receipt_tree = Pubkey::new_from_array(*tree);
+            }
+            AdminAction::SetDenomination { denomination } => {
+                if !state.paused {
+                    msg!("Pause the pool before changing its denomination");
+                    return Err(MixerError::NotPaused.into());
+                }
+                if state.outstanding_notes() != 0 {
+                    msg!("{} notes are still unspent", state.outstanding_notes());
+                    return Err(MixerError::NotesOutstanding.into());
+                }
+                state.denomination = *denomination;
+            }
+            AdminAction::MigratePool {
+                version,
+                backend,
+                verifier,
+                window_slots,
+            } => {
+                // The replaced migration never becomes the default, so its
+                // version would otherwise stay registered with no end.
+                let pending = state.migration_version;
+                if pending != 0 && pending != *version {
+                    if let Some(replaced) =
+                        state.circuit_versions.iter_mut().find(|e| e.version == pending)
+                    {
+                        replaced.deprecation_slot = replaced.deprecation_slot.min(slot);
+                    }
+                }
+                register_circuit_version(
+                    state,
+                    CircuitVersion {
+                        version: *version,
+                        backend: *backend,
+                        verifier: Pubkey::new_from_array(*verifier),
+                        activation_slot: slot,
+                        deprecation_slot: u64::MAX,
+                    },
+                    slot,
+                )?;
+                state.migration_version = *version;
+                state.migration_end_slot = slot.saturating_add(*window_slots);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Store `entry` in the slot already holding its version, or else in an
+/// empty slot or one deprecated by `slot`.
+fn register_circuit_version(
+    state: &mut MixerState,
+    entry: CircuitVersion,
+    slot: u64,
+) -> ProgramResult {
+    let index = match state.circuit_versions.iter().position(|e| e.version == entry.version) {
+        Some(i) => i,
+        None => state
+            .circuit_versions
+            .iter()
+            .position(|e| e.version == 0 || e.deprecation_slot <= slot)
+            .ok_or_else(|| {
+                msg!("Circuit version registry is full");
+                MixerError::ParameterOutOfBounds
+            })?,
+    };
+    state.circuit_versions[index] = entry;
+    Ok(())
+}
+
+/// Contents of a pending admin action account.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingAdminAction {
+    /// Unix timestamp from which the action may be executed.
+    pub eta: i64,
+    pub action: AdminAction,
+}
+
+/// Contents of the pending emergency drain account.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingEmergencyDrain {
+    /// Unix timestamp from which the drain may be executed.
+    pub eta: i64,
+    pub recovery: [u8; 32],
+}
+
+impl PendingEmergencyDrain {
+    pub const LEN: usize = 8 + 32;
+}
+
+pub fn find_emergency_drain_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EMERGENCY_DRAIN_SEED], program_id)
+}
+
+pub fn find_pending_action_address(program_id: &Pubkey, action_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ADMIN_ACTION_SEED, &action_id.to_le_bytes()], program_id)
+}
+
+/// Check that `authority` signed and is the pool authority.
+pub(crate) fn check_authority(state: &MixerState, authority: &AccountInfo) -> ProgramResult {
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if authority.key != &state.authority {
+        msg!("Signer is not the pool authority");
+        return Err(MixerError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+fn load_pending_action(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    action_id: u64,
+) -> Result<PendingAdminAction, ProgramError> {
+    if account.owner != program_id
+        || account.key != &find_pending_action_address(program_id, action_id).0
+    {
+        return Err(MixerError::InvalidAdminAction.into());
+    }
+    let data = account.data.borrow();
+    PendingAdminAction::deserialize(&mut &data[..])
+        .map_err(|_| MixerError::InvalidAdminAction.into())
+}
+
+pub(crate) fn process_queue_admin_action(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    action_id: u64,
+    action: AdminAction,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pending_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    action.check_bounds(&state)?;
+
+    let (expected_pending, bump) = find_pending_action_address(program_id, action_id);
+    if pending_account.key != &expected_pending {
+        msg!("Invalid admin action PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let delay = i64::try_from(state.admin_delay).map_err(|_| ProgramError::ArithmeticOverflow)?;
+    let eta = Clock::get()?
+        .unix_timestamp
+        .checked_add(delay)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let pending = PendingAdminAction { eta, action };
+    let data = borsh::to_vec(&pending).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let rent = Rent::get()?;
+    let create_ix = system_instruction::create_account(
+        authority.key,
+        pending_account.key,
+        rent.minimum_balance(data.len()),
+        data.len() as u64,
+        program_id,
+    );
+    let pending_seeds: &[&[u8]] = &[ADMIN_ACTION_SEED, &action_id.to_le_bytes(), &[bump]];
+    invoke_signed(
+        &create_ix,
+        &[
+            authority.clone(),
+            pending_account.clone(),
+            system_program.clone(),
+        ],
+        &[pending_seeds],
+    )?;
+    pending_account.try_borrow_mut_data()?.copy_from_slice(&data);
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::AdminActionQueued {
+            action_id,
+            eta,
+            action: pending.action,
+        },
+    )
+}
+
+pub(crate) fn process_execute_admin_action(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    action_id: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pending_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let mut state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    let pending = load_pending_action(program_id, pending_account, action_id)?;
+    let clock = Clock::get()?;
+    if clock.unix_timestamp < pending.eta {
+        msg!("Admin action executable from {}", pending.eta);
+        return Err(MixerError::AdminActionNotReady.into());
+    }
+
+    let previous_denomination = state.denomination;
+    pending.action.apply(&mut state, clock.slot)?;
+    store_state(state_account, &state)?;
+    close_account(pending_account, authority)?;
+
+    if pending.action.changes_parameters() {
+        events::emit_cpi(
+            program_id,
+            event_authority,
+            program,
+            &events::ParametersChanged {
+                protocol_fee_bps: state.protocol_fee_bps,
+                relayer_fee_cap_bps: state.relayer_fee_cap_bps,
+                min_withdraw_delay: state.min_withdraw_delay,
+            },
+        )?;
+    }
+
+    if state.denomination != previous_denomination {
+        events::emit_cpi(
+            program_id,
+            event_authority,
+            program,
+            &events::DenominationChanged {
+                previous: previous_denomination,
+                denomination: state.denomination,
+            },
+        )?;
+    }
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::AdminActionExecuted {
+            action_id,
+            action: pending.action,
+        },
+    )
+}
+
+pub(crate) fn process_cancel_admin_action(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    action_id: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pending_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    load_pending_action(program_id, pending_account, action_id)?;
+    close_account(pending_account, authority)?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::AdminActionCancelled { action_id },
+    )
+}
+
+pub(crate) fn process_set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    paused: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let signer = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let mut state = load_state(program_id, state_account)?;
+    let is_guardian = state.guardian != Pubkey::default() && signer.key == &state.guardian;
+    if !(paused && is_guardian && signer.is_signer) {
+        check_authority(&state, signer)?;
+    }
+
+    state.paused = paused;
+    store_state(state_account, &state)?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::PauseChanged {
+            paused,
+            by: signer.key.to_bytes(),
+        },
+    )
+}
+
+fn load_emergency_drain(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<PendingEmergencyDrain, ProgramError> {
+    if account.owner != program_id || account.key != &find_emergency_drain_address(program_id).0 {
+        return Err(MixerError::InvalidAdminAction.into());
+    }
+    let data = account.data.borrow();
+    PendingEmergencyDrain::deserialize(&mut &data[..])
+        .map_err(|_| MixerError::InvalidAdminAction.into())
+}
+
+fn check_paused(state: &MixerState) -> ProgramResult {
+    if !state.paused {
+        msg!("Emergency drain requires the pool to be paused");
+        return Err(MixerError::NotPaused.into());
+    }
+    Ok(())
+}
+
+pub(crate) fn process_queue_emergency_drain(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    recovery: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pending_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    check_paused(&state)?;
+
+    let (expected_pending, bump) = find_emergency_drain_address(program_id);
+    if pending_account.key != &expected_pending {
+        msg!("Invalid emergency drain PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let delay = i64::try_from(state.admin_delay)
+        .map_err(|_| ProgramError::ArithmeticOverflow)?
+        .max(EMERGENCY_DRAIN_MIN_DELAY);
+    let eta = Clock::get()?
+        .unix_timestamp
+        .checked_add(delay)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    let rent = Rent::get()?;
+    let create_ix = system_instruction::create_account(
+        authority.key,
+        pending_account.key,
+        rent.minimum_balance(PendingEmergencyDrain::LEN),
+        PendingEmergencyDrain::LEN as u64,
+        program_id,
+    );
+    invoke_signed(
+        &create_ix,
+        &[
+            authority.clone(),
+            pending_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[EMERGENCY_DRAIN_SEED, &[bump]]],
+    )?;
+    let pending = PendingEmergencyDrain { eta, recovery };
+    pending
+        .serialize(&mut &mut pending_account.try_borrow_mut_data()?[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    msg!(
+        "EMERGENCY DRAIN QUEUED to {} executable from {}",
+        Pubkey::new_from_array(recovery),
+        eta
+    );
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::EmergencyDrainQueued { recovery, eta },
+    )
+}
+
+pub(crate) fn process_execute_emergency_drain(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pending_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let recovery_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    check_paused(&state)?;
+    let pending = load_emergency_drain(program_id, pending_account)?;
+    if recovery_account.key.as_ref() != pending.recovery {
+        return Err(MixerError::InvalidAdminAction.into());
+    }
+    if Clock::get()?.unix_timestamp < pending.eta {
+        msg!("Emergency drain executable from {}", pending.eta);
+        return Err(MixerError::AdminActionNotReady.into());
+    }
+
+    let vault_bump = check_vault(program_id, vault_account)?;
+    let amount = vault_account.lamports();
+    let transfer_ix =
+        system_instruction::transfer(vault_account.key, recovery_account.key, amount);
+    invoke_signed(
+        &transfer_ix,
+        &[
+            vault_account.clone(),
+            recovery_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"mixer_vault", &[vault_bump]]],
+    )?;
+    close_account(pending_account, authority)?;
+
+    msg!("EMERGENCY DRAIN EXECUTED: {} lamports to {}", amount, recovery_account.key);
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::EmergencyDrainExecuted {
+            recovery: pending.recovery,
+            amount,
+        },
+    )
+}
+
+pub(crate) fn process_cancel_emergency_drain(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pending_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    let pending = load_emergency_drain(program_id, pending_account)?;
+    close_account(pending_account, authority)?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::EmergencyDrainCancelled {
+            recovery: pending.recovery,
+        },
+    )
+}
+
+pub(crate) fn process_collect_protocol_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let destination = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let mut state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    let vault_bump = check_vault(program_id, vault_account)?;
+
+    let amount = state.protocol_fees;
+    state.protocol_fees = 0;
+    store_state(state_account, &state)?;
+
+    let transfer_ix = system_instruction::transfer(vault_account.key, destination.key, amount);
+    invoke_signed(
+        &transfer_ix,
+        &[
+            vault_account.clone(),
+            destination.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"mixer_vault", &[vault_bump]]],
+    )?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::ProtocolFeesCollected {
+            destination: destination.key.to_bytes(),
+            amount,
+        },
+    )
+}
+
+pub(crate) fn process_sweep_excess(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let destination = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    let vault_bump = check_vault(program_id, vault_account)?;
+
+    let liabilities = state.liabilities().ok_or(ProgramError::ArithmeticOverflow)?;
+    if vault_account.lamports() < liabilities {
+        msg!("Vault holds {} lamports but owes {}", vault_account.lamports(), liabilities);
+        return Err(MixerError::VaultInsolvent.into());
+    }
+    let reserve = liabilities
+        .checked_add(Rent::get()?.minimum_balance(0))
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let amount = vault_account.lamports().saturating_sub(reserve);
+
+    let transfer_ix = system_instruction::transfer(vault_account.key, destination.key, amount);
+    invoke_signed(
+        &transfer_ix,
+        &[
+            vault_account.clone(),
+            destination.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"mixer_vault", &[vault_bump]]],
+    )?;
+    // Solvency: the sweep leaves every note and the accrued fees covered.
+    if vault_account.lamports() < liabilities {
+        return Err(MixerError::VaultInsolvent.into());
+    }
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::ExcessSwept {
+            destination: destination.key.to_bytes(),
+            amount,
+        },
+    )
+}
+
+pub(crate) fn process_finalize_migration(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let state_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let mut state = load_state(program_id, state_account)?;
+    if state.migration_version == 0 {
+        msg!("No migration is pending");
+        return Err(MixerError::InvalidAdminAction.into());
+    }
+    let slot = Clock::get()?.slot;
+    if slot < state.migration_end_slot {
+        msg!("Migration window ends at slot {}", state.migration_end_slot);
+        return Err(MixerError::AdminActionNotReady.into());
+    }
+    let entry = *state
+        .circuit_versions
+        .iter()
+        .find(|e| e.version == state.migration_version)
+        .ok_or(MixerError::UnknownCircuitVersion)?;
+    if !entry.is_active(slot) {
+        msg!("Circuit version {} is no longer active", entry.version);
+        return Err(MixerError::CircuitVersionNotActive.into());
+    }
+
+    state.verifier_backend = entry.backend;
+    state.verifier_program = entry.verifier;
+    state.verifier_hash = [0u8; 32];
+    state.migration_version = 0;
+    state.migration_end_slot = 0;
+    store_state(state_account, &state)?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::PoolMigrated {
+            version: entry.version,
+            backend: entry.backend,
+            verifier: entry.verifier.to_bytes(),
+        },
+    )
+}
+
+/// `FinalizeMigration` instruction for the pool at `["mixer_state"]`.
+pub fn finalize_migration_instruction(mixer_program_id: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(
+                Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(events::event_authority(mixer_program_id).0, false),
+            AccountMeta::new_readonly(*mixer_program_id, false),
+        ],
+        data: crate::instruction_data(30, 0),
+    }
+}
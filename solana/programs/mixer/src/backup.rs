@@ -0,0 +1,151 @@
+//! Encrypted note backups kept on-chain.
+//!
+//! A depositor can append the ciphertext of each note it creates to a
+//! backup vault, a PDA of `["backup_vault", vault_id]`, and pays the rent
+//! the ciphertext takes up. `vault_id` is derived from the owner's viewing
+//! key off-chain, so the address says nothing about the key, and whoever
+//! holds the key can find the vault and decrypt its notes again after
+//! losing local storage. The program neither sees nor checks the plaintext.
+//!
+//! The vault is a plain list of entries, `u16 LE length || ciphertext`, in
+//! append order. Anyone can append to any vault, so readers must skip
+//! entries that do not decrypt under their key.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{rent::Rent, Sysvar},
+};
+use solana_system_interface::instruction as system_instruction;
+
+pub const BACKUP_VAULT_SEED: &[u8] = b"backup_vault";
+
+/// Largest ciphertext a single `AppendBackup` stores.
+pub const MAX_BACKUP_CIPHERTEXT_LEN: usize = 256;
+
+/// Length prefix of each entry.
+const ENTRY_HEADER_LEN: usize = 2;
+
+pub fn find_backup_vault_address(program_id: &Pubkey, vault_id: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BACKUP_VAULT_SEED, vault_id], program_id)
+}
+
+/// Split backup vault data into its entries' ciphertexts. Stops at a
+/// truncated entry.
+pub fn backup_entries(data: &[u8]) -> Vec<&[u8]> {
+    let mut entries = Vec::new();
+    let mut rest = data;
+    while rest.len() >= ENTRY_HEADER_LEN {
+        let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        let Some(entry) = rest.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + len) else {
+            break;
+        };
+        entries.push(entry);
+        rest = &rest[ENTRY_HEADER_LEN + len..];
+    }
+    entries
+}
+
+pub(crate) fn process_append_backup(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: [u8; 32],
+    ciphertext: Vec<u8>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let depositor = next_account_info(account_info_iter)?;
+    let backup_vault = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !depositor.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (expected_vault, bump) = find_backup_vault_address(program_id, &vault_id);
+    if backup_vault.key != &expected_vault {
+        msg!("Invalid backup vault PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let rent = Rent::get()?;
+    let start = backup_vault.data_len();
+    let new_len = start + ENTRY_HEADER_LEN + ciphertext.len();
+    if backup_vault.lamports() == 0 {
+        let create_ix = system_instruction::create_account(
+            depositor.key,
+            backup_vault.key,
+            rent.minimum_balance(new_len),
+            new_len as u64,
+            program_id,
+        );
+        invoke_signed(
+            &create_ix,
+            &[
+                depositor.clone(),
+                backup_vault.clone(),
+                system_program.clone(),
+            ],
+            &[&[BACKUP_VAULT_SEED, &vault_id, &[bump]]],
+        )?;
+    } else {
+        if backup_vault.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let top_up = rent
+            .minimum_balance(new_len)
+            .saturating_sub(backup_vault.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(depositor.key, backup_vault.key, top_up),
+                &[
+                    depositor.clone(),
+                    backup_vault.clone(),
+                    system_program.clone(),
+                ],
+            )?;
+        }
+        backup_vault.resize(new_len)?;
+    }
+
+    let mut data = backup_vault.try_borrow_mut_data()?;
+    data[start..start + ENTRY_HEADER_LEN].copy_from_slice(&(ciphertext.len() as u16).to_le_bytes());
+    data[start + ENTRY_HEADER_LEN..].copy_from_slice(&ciphertext);
+    Ok(())
+}
+
+/// `AppendBackup` instruction. `depositor` pays the rent for `ciphertext`.
+pub fn append_backup_instruction(
+    mixer_program_id: &Pubkey,
+    depositor: &Pubkey,
+    vault_id: [u8; 32],
+    ciphertext: &[u8],
+) -> Instruction {
+    let mut data = crate::instruction_data(24, 32 + ciphertext.len());
+    data.extend_from_slice(&vault_id);
+    data.extend_from_slice(ciphertext);
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*depositor, true),
+            AccountMeta::new(find_backup_vault_address(mixer_program_id, &vault_id).0, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_stop_at_a_truncated_entry() {
+        let data = [&[3, 0, 1, 2, 3][..], &[0, 0], &[5, 0, 9, 9]].concat();
+        assert_eq!(backup_entries(&data), vec![&[1, 2, 3][..], &[]]);
+        assert!(backup_entries(&[7]).is_empty());
+    }
+}
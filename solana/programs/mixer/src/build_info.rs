@@ -0,0 +1,129 @@
+//! Build provenance recorded on-chain.
+//!
+//! A program cannot contain its own hash, so after each deployment the pool
+//! authority records the git commit the bytecode was built from and the
+//! sha256 of the deployed `.so` in a PDA of `["build_info"]`. Anyone can
+//! then rebuild that commit (`mixer-deploy verify-build`) and compare the
+//! result with both the recorded hash and the bytecode actually deployed.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{rent::Rent, Sysvar},
+};
+use solana_system_interface::instruction as system_instruction;
+
+use crate::{admin::check_authority, events, load_state};
+
+pub const BUILD_INFO_SEED: &[u8] = b"build_info";
+
+/// Contents of the build info account.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Git commit (SHA-1) the deployed program was built from.
+    pub commit: [u8; 20],
+    /// sha256 of the deployed program bytecode.
+    pub build_hash: [u8; 32],
+    /// Unix timestamp of the last update.
+    pub updated_at: i64,
+}
+
+impl BuildInfo {
+    pub const LEN: usize = 20 + 32 + 8;
+}
+
+pub fn find_build_info_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[BUILD_INFO_SEED], program_id)
+}
+
+pub(crate) fn process_set_build_info(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    commit: [u8; 20],
+    build_hash: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let build_info_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+
+    let (expected_build_info, bump) = find_build_info_address(program_id);
+    if build_info_account.key != &expected_build_info {
+        msg!("Invalid build info PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if build_info_account.lamports() == 0 {
+        let rent = Rent::get()?;
+        let create_ix = system_instruction::create_account(
+            authority.key,
+            build_info_account.key,
+            rent.minimum_balance(BuildInfo::LEN),
+            BuildInfo::LEN as u64,
+            program_id,
+        );
+        invoke_signed(
+            &create_ix,
+            &[
+                authority.clone(),
+                build_info_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[BUILD_INFO_SEED, &[bump]]],
+        )?;
+    } else if build_info_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let info = BuildInfo {
+        commit,
+        build_hash,
+        updated_at: Clock::get()?.unix_timestamp,
+    };
+    info.serialize(&mut &mut build_info_account.try_borrow_mut_data()?[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::BuildInfoUpdated { commit, build_hash },
+    )
+}
+
+/// `SetBuildInfo` instruction.
+pub fn set_build_info_instruction(
+    mixer_program_id: &Pubkey,
+    authority: &Pubkey,
+    commit: [u8; 20],
+    build_hash: [u8; 32],
+) -> Instruction {
+    let mut data = crate::instruction_data(19, 52);
+    data.extend_from_slice(&commit);
+    data.extend_from_slice(&build_hash);
+    let (mixer_state, _) = Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id);
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(mixer_state, false),
+            AccountMeta::new(find_build_info_address(mixer_program_id).0, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            AccountMeta::new_readonly(events::event_authority(mixer_program_id).0, false),
+            AccountMeta::new_readonly(*mixer_program_id, false),
+        ],
+        data,
+    }
+}
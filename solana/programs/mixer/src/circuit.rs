@@ -0,0 +1,34 @@
+//! Artifacts of the committed Noir circuit, generated by `build.rs` with the
+//! `circuit-artifacts` feature.
+//!
+//! The build fails if the compiled circuit's public inputs differ from the
+//! order the withdraw path passes them in, so the program cannot drift from
+//! `circuits/`. [`VERIFYING_KEY`] is the key to store with
+//! `CreateVerifyingKey` for the native backend, and [`VERIFYING_KEY_HASH`]
+//! the matching `SetVerifierHash` value. [`TREE_DEPTH`] is the depth to pass
+//! to `Initialize` for a pool using this circuit.
+
+use crate::verifier::{Groth16VerifyingKey, NUM_PUBLIC_INPUTS};
+
+include!(concat!(env!("OUT_DIR"), "/circuit_artifacts.rs"));
+
+const _: () = assert!(PUBLIC_INPUTS.len() == NUM_PUBLIC_INPUTS);
+const _: () = assert!(TREE_DEPTH >= 1 && TREE_DEPTH <= crate::MixerState::MAX_TREE_DEPTH);
+
+#[cfg(test)]
+mod tests {
+    use borsh::to_vec;
+
+    use super::*;
+    use crate::verifier::{verifier_account_hash, VerifierBackend};
+
+    #[test]
+    fn verifying_key_hash_matches_account_data() {
+        let data = to_vec(&VERIFYING_KEY).unwrap();
+        assert_eq!(data.len(), Groth16VerifyingKey::LEN);
+        assert_eq!(
+            verifier_account_hash(VerifierBackend::NativeGroth16, &data),
+            VERIFYING_KEY_HASH
+        );
+    }
+}
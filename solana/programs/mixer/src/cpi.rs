@@ -0,0 +1,523 @@
+//! Helpers for programs that compose with the mixer via CPI.
+//!
+//! Depend on this crate with the `no-entrypoint` feature and call
+//! [`deposit`] / [`withdraw`] with the accounts listed on each. Signer seeds
+//! are forwarded unchanged, so a PDA of the calling program can act as
+//! depositor or relayer.
+//!
+//! A deposit here is the same instruction an off-chain client sends:
+//! `PushRoot` with the new Merkle root, which moves the pool denomination
+//! from the depositor into the vault.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{get_return_data, invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use borsh::BorshDeserialize;
+
+use crate::{
+    curve::Curve, events, instruction_data, load_state, receipt, verifier::Groth16VerifyingKey,
+    view, CloseTarget, CommitmentTree, DepositCounter, LatestRoot, MixerInstruction, PoolStats,
+    ProofBuffer, WithdrawReceipt,
+};
+
+/// Accounts for [`deposit`].
+pub struct DepositAccounts<'a, 'info> {
+    /// Funds the deposit and signs `PushRoot`. Must be a signer of the outer
+    /// instruction or a PDA of the caller covered by `signer_seeds`.
+    pub depositor: &'a AccountInfo<'info>,
+    pub mixer_state: &'a AccountInfo<'info>,
+    pub mixer_vault: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub event_authority: &'a AccountInfo<'info>,
+    pub mixer_program: &'a AccountInfo<'info>,
+    /// The pool's [`LatestRoot`].
+    pub latest_root: &'a AccountInfo<'info>,
+    /// The pool's [`PoolStats`].
+    pub pool_stats: &'a AccountInfo<'info>,
+    /// The depositor's [`DepositCounter`]; required if the pool limits
+    /// deposits per depositor, in which case the depositor pays its rent.
+    pub deposit_counter: Option<&'a AccountInfo<'info>>,
+    /// The [`CommitmentTree`] of the pool's current tree; required when that
+    /// tree is full, in which case the depositor pays its rent.
+    pub commitment_tree: Option<&'a AccountInfo<'info>>,
+    /// Mints a deposit receipt (see [`crate::receipt`]), which needs a leaf
+    /// count.
+    pub receipt: Option<ReceiptAccounts<'a, 'info>>,
+}
+
+/// Accounts for a deposit receipt, in the order `PushRoot` expects them
+/// after the system program.
+pub struct ReceiptAccounts<'a, 'info> {
+    /// Owner of the receipt; need not sign.
+    pub leaf_owner: &'a AccountInfo<'info>,
+    pub receipt_authority: &'a AccountInfo<'info>,
+    pub tree_config: &'a AccountInfo<'info>,
+    /// The pool's receipt tree.
+    pub merkle_tree: &'a AccountInfo<'info>,
+    pub log_wrapper: &'a AccountInfo<'info>,
+    pub compression_program: &'a AccountInfo<'info>,
+    pub bubblegum_program: &'a AccountInfo<'info>,
+}
+
+/// Accounts for [`withdraw`], in the order `Withdraw` expects them.
+pub struct WithdrawAccounts<'a, 'info> {
+    /// Funds the nullifier marker. Must be a signer of the outer instruction
+    /// or a PDA of the caller covered by `signer_seeds`.
+    pub relayer: &'a AccountInfo<'info>,
+    pub mixer_state: &'a AccountInfo<'info>,
+    pub nullifier: &'a AccountInfo<'info>,
+    pub mixer_vault: &'a AccountInfo<'info>,
+    pub recipient: &'a AccountInfo<'info>,
+    pub verifier_program: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub event_authority: &'a AccountInfo<'info>,
+    pub mixer_program: &'a AccountInfo<'info>,
+    /// The pool's [`PoolStats`].
+    pub pool_stats: &'a AccountInfo<'info>,
+    /// The [`CommitmentTree`] of the tree the root is from.
+    pub commitment_tree: &'a AccountInfo<'info>,
+    /// The note's [`WithdrawReceipt`], to leave the recipient proof of the
+    /// payment; the relayer pays its rent.
+    pub receipt: Option<&'a AccountInfo<'info>>,
+}
+
+/// `Initialize` instruction for the pool at `["mixer_state"]`, with `payer`
+/// as its authority, pinning withdrawals to `verifier_program_id`.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_instruction(
+    mixer_program_id: &Pubkey,
+    payer: &Pubkey,
+    denomination: u64,
+    flags: u8,
+    admin_delay: u64,
+    curve: Curve,
+    tree_depth: u8,
+    verifier_program_id: &Pubkey,
+) -> Instruction {
+    let mut data = instruction_data(0, 19);
+    data.extend_from_slice(&denomination.to_le_bytes());
+    data.push(flags);
+    data.extend_from_slice(&admin_delay.to_le_bytes());
+    data.push(curve as u8);
+    data.push(tree_depth);
+    let (mixer_state, _) = Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id);
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(mixer_state, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            AccountMeta::new_readonly(*verifier_program_id, false),
+        ],
+        data,
+    }
+}
+
+/// `PushRoot` instruction; `authority` pays the deposit.
+pub fn push_root_instruction(
+    mixer_program_id: &Pubkey,
+    authority: &Pubkey,
+    mixer_state: &Pubkey,
+    new_root: [u8; 32],
+    leaf_count: Option<u64>,
+) -> Instruction {
+    let mut data = instruction_data(1, 32 + 8);
+    data.extend_from_slice(&new_root);
+    if let Some(leaf_count) = leaf_count {
+        data.extend_from_slice(&leaf_count.to_le_bytes());
+    }
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(*mixer_state, false),
+            AccountMeta::new_readonly(events::event_authority(mixer_program_id).0, false),
+            AccountMeta::new_readonly(*mixer_program_id, false),
+            AccountMeta::new(
+                Pubkey::find_program_address(&[b"mixer_vault"], mixer_program_id).0,
+                false,
+            ),
+            AccountMeta::new(LatestRoot::find_address(mixer_program_id).0, false),
+            AccountMeta::new(PoolStats::find_address(mixer_program_id).0, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+        data,
+    }
+}
+
+/// `CreateLatestRoot` instruction; `payer` pays the rent of the pool's
+/// [`LatestRoot`].
+pub fn create_latest_root_instruction(mixer_program_id: &Pubkey, payer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(
+                Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id).0,
+                false,
+            ),
+            AccountMeta::new(LatestRoot::find_address(mixer_program_id).0, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+        data: instruction_data(26, 0),
+    }
+}
+
+/// `CreatePoolStats` instruction; `payer` pays the rent of the pool's
+/// [`PoolStats`].
+pub fn create_pool_stats_instruction(mixer_program_id: &Pubkey, payer: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(
+                Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id).0,
+                false,
+            ),
+            AccountMeta::new(PoolStats::find_address(mixer_program_id).0, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+        ],
+        data: instruction_data(27, 0),
+    }
+}
+
+/// `ResizeState` instruction; `payer` tops up the rent of the grown state,
+/// and `upgrade_authority` must be the mixer program's. A pool that predates
+/// them takes `authority` and `verifier_program`.
+pub fn resize_state_instruction(
+    mixer_program_id: &Pubkey,
+    payer: &Pubkey,
+    upgrade_authority: &Pubkey,
+    authority: &Pubkey,
+    verifier_program: &Pubkey,
+) -> Instruction {
+    let mut data = instruction_data(31, 64);
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(verifier_program.as_ref());
+    let program_data = Pubkey::find_program_address(
+        &[mixer_program_id.as_ref()],
+        &solana_sdk_ids::bpf_loader_upgradeable::ID,
+    )
+    .0;
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(
+                Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(
+                Pubkey::find_program_address(&[b"mixer_vault"], mixer_program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            AccountMeta::new_readonly(*upgrade_authority, true),
+            AccountMeta::new_readonly(program_data, false),
+        ],
+        data,
+    }
+}
+
+/// `PushRoot` instruction for a pool that limits deposits per depositor,
+/// passing `authority`'s [`DepositCounter`], which it pays for on its first
+/// deposit.
+pub fn push_root_with_counter_instruction(
+    mixer_program_id: &Pubkey,
+    authority: &Pubkey,
+    mixer_state: &Pubkey,
+    new_root: [u8; 32],
+    leaf_count: Option<u64>,
+) -> Instruction {
+    let mut instruction =
+        push_root_instruction(mixer_program_id, authority, mixer_state, new_root, leaf_count);
+    let counter = DepositCounter::find_address(mixer_program_id, authority).0;
+    instruction.accounts.push(AccountMeta::new(counter, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(solana_system_interface::program::ID, false));
+    instruction
+}
+
+/// Ask `instruction`, a `PushRoot` into the pool's full tree `tree_id`, to
+/// archive that tree. The authority pays for the [`CommitmentTree`]. Apply
+/// before [`receipt::with_receipt`].
+pub fn with_tree_rotation(mut instruction: Instruction, tree_id: u8) -> Instruction {
+    let tree = CommitmentTree::find_address(&instruction.program_id, tree_id).0;
+    instruction.accounts.push(AccountMeta::new(tree, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(solana_system_interface::program::ID, false));
+    instruction
+}
+
+/// `Withdraw` instruction. `proof` is `proof_bytes || public_witness_bytes`;
+/// `circuit_version` is 0 unless the pool registered other circuit versions,
+/// and `tree_id` is the pool tree `root` is from.
+/// `relayer_fee` goes to `relayer` and must be within the pool's cap; a
+/// nonzero fee makes `recipient` a signer.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_instruction(
+    mixer_program_id: &Pubkey,
+    relayer: &Pubkey,
+    mixer_state: &Pubkey,
+    nullifier: &Pubkey,
+    mixer_vault: &Pubkey,
+    recipient: &Pubkey,
+    verifier_program_id: &Pubkey,
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: u8,
+    relayer_fee: u64,
+    proof: &[u8],
+) -> Instruction {
+    let mut data = instruction_data(2, 107 + proof.len());
+    data.extend_from_slice(&root);
+    data.extend_from_slice(&nullifier_hash);
+    data.extend_from_slice(&recipient_field);
+    data.extend_from_slice(&circuit_version.to_le_bytes());
+    data.push(tree_id);
+    data.extend_from_slice(&relayer_fee.to_le_bytes());
+    data.extend_from_slice(proof);
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*relayer, true),
+            AccountMeta::new(*mixer_state, false),
+            AccountMeta::new(*nullifier, false),
+            AccountMeta::new(*mixer_vault, false),
+            AccountMeta::new(*recipient, relayer_fee > 0),
+            AccountMeta::new_readonly(*verifier_program_id, false),
+            AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            AccountMeta::new_readonly(events::event_authority(mixer_program_id).0, false),
+            AccountMeta::new_readonly(*mixer_program_id, false),
+            AccountMeta::new(PoolStats::find_address(mixer_program_id).0, false),
+            AccountMeta::new_readonly(
+                CommitmentTree::find_address(mixer_program_id, tree_id).0,
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+/// Ask `instruction`, a `Withdraw` or `WithdrawFromBuffer`, to create the
+/// [`WithdrawReceipt`] of its nullifier hash.
+///
+/// # Panics
+///
+/// If `instruction` is neither.
+pub fn with_withdraw_receipt(mut instruction: Instruction) -> Instruction {
+    let nullifier_hash = match MixerInstruction::unpack(&instruction.data) {
+        Ok(MixerInstruction::Withdraw { nullifier_hash, .. })
+        | Ok(MixerInstruction::WithdrawFromBuffer { nullifier_hash, .. }) => nullifier_hash,
+        _ => panic!("a withdraw receipt needs a Withdraw or WithdrawFromBuffer"),
+    };
+    let receipt = WithdrawReceipt::find_address(&instruction.program_id, &nullifier_hash).0;
+    instruction.accounts.push(AccountMeta::new(receipt, false));
+    instruction
+}
+
+/// `CloseWithdrawReceipt` instruction, returning the receipt's rent to
+/// `recipient`.
+pub fn close_withdraw_receipt_instruction(
+    mixer_program_id: &Pubkey,
+    recipient: &Pubkey,
+    nullifier_hash: [u8; 32],
+) -> Instruction {
+    let mut data = instruction_data(28, 32);
+    data.extend_from_slice(&nullifier_hash);
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![
+            AccountMeta::new(*recipient, true),
+            AccountMeta::new(
+                WithdrawReceipt::find_address(mixer_program_id, &nullifier_hash).0,
+                false,
+            ),
+        ],
+        data,
+    }
+}
+
+/// `CloseAccount` instruction for `target`, signed by `owner`: the buffer
+/// authority or depositor the account belongs to, or for a verifying key
+/// the pool authority. The rent goes to `owner`.
+pub fn close_account_instruction(
+    mixer_program_id: &Pubkey,
+    owner: &Pubkey,
+    target: CloseTarget,
+) -> Instruction {
+    let mut data = instruction_data(29, 9);
+    let mut accounts = vec![AccountMeta::new(*owner, true)];
+    match target {
+        CloseTarget::ProofBuffer { buffer_id } => {
+            data.push(0);
+            data.extend_from_slice(&buffer_id.to_le_bytes());
+            let buffer = ProofBuffer::find_address(mixer_program_id, owner, buffer_id).0;
+            accounts.push(AccountMeta::new(buffer, false));
+        }
+        CloseTarget::DepositCounter => {
+            data.push(1);
+            let counter = DepositCounter::find_address(mixer_program_id, owner).0;
+            accounts.push(AccountMeta::new(counter, false));
+        }
+        CloseTarget::VerifyingKey { vk_id } => {
+            data.push(2);
+            data.extend_from_slice(&vk_id.to_le_bytes());
+            let vk = Groth16VerifyingKey::find_address(mixer_program_id, vk_id).0;
+            accounts.push(AccountMeta::new(vk, false));
+            accounts.push(AccountMeta::new_readonly(
+                Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id).0,
+                false,
+            ));
+        }
+    }
+    Instruction { program_id: *mixer_program_id, accounts, data }
+}
+
+/// Move the pool denomination from `depositor` into the vault and record
+/// `new_root`, which must already include the depositor's commitment.
+/// `leaf_count` is the number of leaves under `new_root`, if known.
+pub fn deposit(
+    accounts: DepositAccounts,
+    new_root: [u8; 32],
+    leaf_count: Option<u64>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut push_root_infos = vec![
+        accounts.depositor.clone(),
+        accounts.mixer_state.clone(),
+        accounts.event_authority.clone(),
+        accounts.mixer_program.clone(),
+        accounts.mixer_vault.clone(),
+        accounts.latest_root.clone(),
+        accounts.pool_stats.clone(),
+        accounts.system_program.clone(),
+    ];
+    let push_root_ix = match accounts.deposit_counter {
+        Some(counter) => {
+            push_root_infos.extend([counter.clone(), accounts.system_program.clone()]);
+            push_root_with_counter_instruction(
+                accounts.mixer_program.key,
+                accounts.depositor.key,
+                accounts.mixer_state.key,
+                new_root,
+                leaf_count,
+            )
+        }
+        None => push_root_instruction(
+            accounts.mixer_program.key,
+            accounts.depositor.key,
+            accounts.mixer_state.key,
+            new_root,
+            leaf_count,
+        ),
+    };
+    let push_root_ix = match accounts.commitment_tree {
+        Some(tree) => {
+            let tree_id = load_state(accounts.mixer_program.key, accounts.mixer_state)?.tree_id;
+            push_root_infos.extend([tree.clone(), accounts.system_program.clone()]);
+            with_tree_rotation(push_root_ix, tree_id)
+        }
+        None => push_root_ix,
+    };
+    let push_root_ix = match &accounts.receipt {
+        Some(receipt) => {
+            if leaf_count.is_none() {
+                msg!("A deposit receipt needs a leaf count");
+                return Err(ProgramError::InvalidArgument);
+            }
+            push_root_infos.extend([
+                receipt.leaf_owner.clone(),
+                receipt.receipt_authority.clone(),
+                receipt.tree_config.clone(),
+                receipt.merkle_tree.clone(),
+                receipt.log_wrapper.clone(),
+                receipt.compression_program.clone(),
+                receipt.bubblegum_program.clone(),
+                accounts.system_program.clone(),
+            ]);
+            receipt::with_receipt(push_root_ix, receipt.merkle_tree.key, receipt.leaf_owner.key)
+        }
+        None => push_root_ix,
+    };
+    invoke_signed(&push_root_ix, &push_root_infos, signer_seeds)
+}
+
+/// Withdraw to `accounts.recipient` with a proof for `root`/`nullifier_hash`.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw(
+    accounts: WithdrawAccounts,
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: u8,
+    relayer_fee: u64,
+    proof: &[u8],
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let mut ix = withdraw_instruction(
+        accounts.mixer_program.key,
+        accounts.relayer.key,
+        accounts.mixer_state.key,
+        accounts.nullifier.key,
+        accounts.mixer_vault.key,
+        accounts.recipient.key,
+        accounts.verifier_program.key,
+        root,
+        nullifier_hash,
+        recipient_field,
+        circuit_version,
+        tree_id,
+        relayer_fee,
+        proof,
+    );
+    let mut infos = vec![
+        accounts.relayer.clone(),
+        accounts.mixer_state.clone(),
+        accounts.nullifier.clone(),
+        accounts.mixer_vault.clone(),
+        accounts.recipient.clone(),
+        accounts.verifier_program.clone(),
+        accounts.system_program.clone(),
+        accounts.event_authority.clone(),
+        accounts.mixer_program.clone(),
+        accounts.pool_stats.clone(),
+        accounts.commitment_tree.clone(),
+    ];
+    if let Some(receipt) = accounts.receipt {
+        ix = with_withdraw_receipt(ix);
+        infos.push(receipt.clone());
+    }
+    invoke_signed(&ix, &infos, signer_seeds)
+}
+
+/// The state of the pool at `mixer_state`, read through `GetState` rather
+/// than the account's byte layout.
+pub fn get_state<'info>(
+    mixer_state: &AccountInfo<'info>,
+    mixer_program: &AccountInfo<'info>,
+) -> Result<view::StateView, ProgramError> {
+    invoke(
+        &view::get_state_instruction(mixer_program.key),
+        &[mixer_state.clone(), mixer_program.clone()],
+    )?;
+    match get_return_data() {
+        Some((program_id, data)) if program_id == *mixer_program.key => {
+            view::StateView::deserialize(&mut data.as_slice())
+                .map_err(|_| ProgramError::InvalidAccountData)
+        }
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
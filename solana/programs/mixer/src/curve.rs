@@ -0,0 +1,71 @@
+//! Curves a pool's circuit can be defined over.
+//!
+//! A pool picks its curve at `Initialize`, recorded as `MixerState::curve`,
+//! and keeps it: the deposits in its tree are hashed over that curve's scalar
+//! field. The curve decides
+//!
+//! - the field the public inputs must be canonical in ([`Curve::is_canonical`]);
+//! - which verifier backends can check its proofs ([`Curve::supports`]).
+//!   Only BN254 has alt_bn128 syscalls, so [`VerifierBackend::NativeGroth16`]
+//!   is BN254-only. A BLS12-381 pool uses a CPI backend whose verifier
+//!   program implements the curve, e.g. a Sunspot verifier built for
+//!   BLS12-381.
+//!
+//! Hashing happens off-chain. Clients build the Merkle tree and notes with
+//! the Poseidon instance matching the pool's curve; the program only stores
+//! roots and never hashes field elements itself.
+
+use crate::{verifier::VerifierBackend, MixerError};
+
+/// BN254 scalar field modulus, big-endian.
+pub use crate::public_inputs::SCALAR_FIELD_MODULUS as BN254_SCALAR_FIELD_MODULUS;
+
+/// BLS12-381 scalar field modulus, big-endian.
+pub const BLS12_381_SCALAR_FIELD_MODULUS: [u8; 32] = [
+    0x73, 0xed, 0xa7, 0x53, 0x29, 0x9d, 0x7d, 0x48, 0x33, 0x39, 0xd8, 0x08, 0x09, 0xa1, 0xd8, 0x05,
+    0x53, 0xbd, 0xa4, 0x02, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01,
+];
+
+/// The curve of a pool's circuit; stored as `MixerState::curve`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Curve {
+    #[default]
+    Bn254 = 0,
+    Bls12_381 = 1,
+}
+
+impl TryFrom<u8> for Curve {
+    type Error = MixerError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Curve::Bn254),
+            1 => Ok(Curve::Bls12_381),
+            _ => Err(MixerError::UnsupportedCurve),
+        }
+    }
+}
+
+impl Curve {
+    /// Scalar field modulus, big-endian.
+    pub fn scalar_field_modulus(self) -> &'static [u8; 32] {
+        match self {
+            Curve::Bn254 => &BN254_SCALAR_FIELD_MODULUS,
+            Curve::Bls12_381 => &BLS12_381_SCALAR_FIELD_MODULUS,
+        }
+    }
+
+    /// Whether `field`, read big-endian, is below the scalar field modulus.
+    pub fn is_canonical(self, field: &[u8; 32]) -> bool {
+        field < self.scalar_field_modulus()
+    }
+
+    /// Whether `backend` can verify proofs over this curve.
+    pub fn supports(self, backend: VerifierBackend) -> bool {
+        match backend {
+            VerifierBackend::NativeGroth16 => self == Curve::Bn254,
+            VerifierBackend::SunspotCpi | VerifierBackend::UltraHonkCpi => true,
+        }
+    }
+}
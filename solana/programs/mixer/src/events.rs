@@ -0,0 +1,315 @@
+//! Structured events emitted via self-CPI.
+//!
+//! Program logs are truncated once a transaction exceeds the log budget, so
+//! indexers cannot rely on `msg!` output. Instead, every event is serialized
+//! with borsh and passed as instruction data to a CPI back into this program,
+//! signed by the event authority PDA. The inner instruction is recorded in the
+//! transaction metadata in full and can be decoded by indexers.
+//!
+//! The framing follows Anchor's `emit_cpi!` so existing tooling can decode it:
+//!   EVENT_IX_TAG_LE (8 bytes) || event discriminator (8 bytes) || borsh(event)
+//! where the discriminator is `sha256("event:<Name>")[..8]`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::admin::AdminAction;
+
+/// Seed of the PDA that signs event self-CPIs.
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+/// Anchor's `EVENT_IX_TAG` (`sha256("anchor:event")[..8]` as a u64) in
+/// little-endian byte order.
+pub const EVENT_IX_TAG_LE: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+/// A borsh-encoded event with a fixed discriminator.
+pub trait Event: BorshSerialize {
+    const DISCRIMINATOR: [u8; 8];
+
+    /// Full self-CPI instruction data for this event.
+    fn data(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut data = Vec::with_capacity(16);
+        data.extend_from_slice(&EVENT_IX_TAG_LE);
+        data.extend_from_slice(&Self::DISCRIMINATOR);
+        self.serialize(&mut data)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        Ok(data)
+    }
+}
+
+/// A new Merkle root was recorded.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RootPushed {
+    pub root: [u8; 32],
+    /// Slot in the root ring buffer the root was written to.
+    pub root_index: u8,
+}
+
+impl Event for RootPushed {
+    // sha256("event:RootPushed")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x55, 0x83, 0xee, 0xa4, 0xdd, 0x12, 0x7a, 0x3f];
+}
+
+/// The pool's tree `tree_id` filled up and was archived; deposits moved on
+/// to tree `tree_id + 1`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TreeRotated {
+    pub tree_id: u8,
+    /// Last root of the archived tree.
+    pub root: [u8; 32],
+}
+
+impl Event for TreeRotated {
+    // sha256("event:TreeRotated")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xe0, 0xbb, 0x47, 0xff, 0x6e, 0xa8, 0x1c, 0x28];
+}
+
+/// A note was spent and the vault paid out.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Withdraw {
+    pub root: [u8; 32],
+    pub nullifier_hash: [u8; 32],
+    pub recipient: [u8; 32],
+    pub amount: u64,
+}
+
+impl Event for Withdraw {
+    // sha256("event:Withdraw")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xc0, 0xf1, 0xc9, 0xd9, 0x46, 0x96, 0x5a, 0xf7];
+}
+
+/// The pool authority queued a timelocked parameter change.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminActionQueued {
+    pub action_id: u64,
+    /// Unix timestamp from which the action may be executed.
+    pub eta: i64,
+    pub action: AdminAction,
+}
+
+impl Event for AdminActionQueued {
+    // sha256("event:AdminActionQueued")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xb8, 0x69, 0x9d, 0xc7, 0x04, 0x45, 0x1c, 0x82];
+}
+
+/// A queued parameter change was applied.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminActionExecuted {
+    pub action_id: u64,
+    pub action: AdminAction,
+}
+
+impl Event for AdminActionExecuted {
+    // sha256("event:AdminActionExecuted")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xa6, 0x1e, 0x3b, 0x4f, 0xc6, 0x99, 0x40, 0x4e];
+}
+
+/// A queued parameter change was dropped.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminActionCancelled {
+    pub action_id: u64,
+}
+
+impl Event for AdminActionCancelled {
+    // sha256("event:AdminActionCancelled")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x6f, 0x0a, 0xa4, 0x95, 0x65, 0x4d, 0x65, 0x8b];
+}
+
+/// The pool was paused or unpaused.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PauseChanged {
+    pub paused: bool,
+    /// Authority or guardian that signed.
+    pub by: [u8; 32],
+}
+
+impl Event for PauseChanged {
+    // sha256("event:PauseChanged")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xee, 0xbc, 0xd5, 0x4e, 0x86, 0xd1, 0xb2, 0xda];
+}
+
+/// A fee parameter changed; carries the full parameter set after the change.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ParametersChanged {
+    pub protocol_fee_bps: u16,
+    pub relayer_fee_cap_bps: u16,
+    pub min_withdraw_delay: u64,
+}
+
+impl Event for ParametersChanged {
+    // sha256("event:ParametersChanged")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xc7, 0x50, 0xd6, 0x62, 0x0f, 0x46, 0xb4, 0x76];
+}
+
+/// Accrued protocol fees were paid out of the vault.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolFeesCollected {
+    pub destination: [u8; 32],
+    pub amount: u64,
+}
+
+impl Event for ProtocolFeesCollected {
+    // sha256("event:ProtocolFeesCollected")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xa5, 0x22, 0x7d, 0x9b, 0x0f, 0x56, 0x63, 0xbf];
+}
+
+/// The recorded build provenance changed.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfoUpdated {
+    pub commit: [u8; 20],
+    pub build_hash: [u8; 32],
+}
+
+impl Event for BuildInfoUpdated {
+    // sha256("event:BuildInfoUpdated")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x61, 0xb3, 0x66, 0xf5, 0xf3, 0x8f, 0x4e, 0x03];
+}
+
+/// `VerifyVkBinding` hashed the pool's verifier. On a mismatch the pool was
+/// paused.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerifierBindingChecked {
+    pub verifier: [u8; 32],
+    pub expected_hash: [u8; 32],
+    pub actual_hash: [u8; 32],
+    pub paused: bool,
+}
+
+impl Event for VerifierBindingChecked {
+    // sha256("event:VerifierBindingChecked")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xea, 0xe7, 0xfe, 0xaf, 0xc9, 0xeb, 0x63, 0xb0];
+}
+
+/// A verifying key for the native verifier backend was stored.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerifyingKeyCreated {
+    pub vk_id: u64,
+    pub address: [u8; 32],
+}
+
+impl Event for VerifyingKeyCreated {
+    // sha256("event:VerifyingKeyCreated")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x07, 0xf1, 0xdc, 0x8c, 0x54, 0xe8, 0x09, 0xb9];
+}
+
+/// An emergency drain of the whole vault was queued.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyDrainQueued {
+    pub recovery: [u8; 32],
+    /// Unix timestamp from which the drain may be executed.
+    pub eta: i64,
+}
+
+impl Event for EmergencyDrainQueued {
+    // sha256("event:EmergencyDrainQueued")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x43, 0x11, 0xd1, 0x89, 0xb5, 0x89, 0x6e, 0x4c];
+}
+
+/// The vault was drained to the recovery address.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyDrainExecuted {
+    pub recovery: [u8; 32],
+    pub amount: u64,
+}
+
+impl Event for EmergencyDrainExecuted {
+    // sha256("event:EmergencyDrainExecuted")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x8c, 0x89, 0x7e, 0xe9, 0x19, 0x77, 0xcc, 0x0d];
+}
+
+/// A queued emergency drain was dropped.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmergencyDrainCancelled {
+    pub recovery: [u8; 32],
+}
+
+impl Event for EmergencyDrainCancelled {
+    // sha256("event:EmergencyDrainCancelled")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0x50, 0x1c, 0xd8, 0xca, 0xb2, 0xef, 0xa7, 0x1c];
+}
+
+/// Vault lamports beyond the pool's liabilities were swept out.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ExcessSwept {
+    pub destination: [u8; 32],
+    pub amount: u64,
+}
+
+impl Event for ExcessSwept {
+    // sha256("event:ExcessSwept")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xe7, 0xb0, 0xaf, 0x41, 0x92, 0x02, 0xd1, 0x9d];
+}
+
+/// A `SetDenomination` admin action changed the pool's denomination.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DenominationChanged {
+    pub previous: u64,
+    pub denomination: u64,
+}
+
+impl Event for DenominationChanged {
+    // sha256("event:DenominationChanged")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xc2, 0x4b, 0xa4, 0x00, 0x51, 0xfd, 0x43, 0x03];
+}
+
+/// A pool migration was finalized: the circuit version it registered is now
+/// the pool's default verifier.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PoolMigrated {
+    pub version: u16,
+    pub backend: u8,
+    pub verifier: [u8; 32],
+}
+
+impl Event for PoolMigrated {
+    // sha256("event:PoolMigrated")[..8]
+    const DISCRIMINATOR: [u8; 8] = [0xfa, 0xcc, 0x18, 0xc3, 0x25, 0xfd, 0x98, 0x06];
+}
+
+pub fn event_authority(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], program_id)
+}
+
+/// Check that `event_authority` is this program's event authority PDA and
+/// return its bump.
+pub fn check_event_authority(
+    program_id: &Pubkey,
+    event_authority: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (expected, bump) = self::event_authority(program_id);
+    if event_authority.key != &expected {
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(bump)
+}
+
+/// Emit `event` by CPI-ing into this program with the event authority as signer.
+pub fn emit_cpi<'a, E: Event>(
+    program_id: &Pubkey,
+    event_authority: &AccountInfo<'a>,
+    program: &AccountInfo<'a>,
+    event: &E,
+) -> ProgramResult {
+    let bump = check_event_authority(program_id, event_authority)?;
+    if program.key != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let ix = Instruction {
+        program_id: *program_id,
+        accounts: vec![AccountMeta::new_readonly(*event_authority.key, true)],
+        data: event.data()?,
+    };
+    invoke_signed(
+        &ix,
+        &[event_authority.clone(), program.clone()],
+        &[&[EVENT_AUTHORITY_SEED, &[bump]]],
+    )
+}
@@ -4,28 +4,72 @@
 //! - Track a fixed-denomination pool of lamports.
 //! - Store a rolling history of Poseidon2 Merkle roots for deposits.
 //! - Track spent nullifier hashes to prevent double-withdraw.
-//! - Verify Groth16 proofs via CPI into a Sunspot-generated verifier program.
+//! - Verify Groth16 proofs via CPI into a Sunspot-generated verifier program,
+//!   or natively with the alt_bn128 syscalls; see [`verifier`].
 //!
 //! The verifier program is assumed to be produced by Sunspot for the Noir
 //! circuit in `circuits/src/main.nr`, with public inputs:
 //!   0: root
 //!   1: nullifier_hash
 //!   2: recipient (as field-encoded address).
+//!
+//! Successful state transitions are reported as structured events via
+//! self-CPI; see [`events`]. Other programs can deposit and withdraw via
+//! the helpers in [`cpi`] by depending on this crate with the
+//! `no-entrypoint` feature.
+//!
+//! The deployed program embeds a `security.txt` (see `solana-security-txt`)
+//! and records its build provenance on-chain; see [`build_info`].
 
+pub mod admin;
+pub mod backup;
+pub mod build_info;
+#[cfg(feature = "circuit-artifacts")]
+pub mod circuit;
+pub mod cpi;
+pub mod curve;
+pub mod events;
+pub mod points;
+pub mod public_inputs;
+pub mod receipt;
+pub mod verifier;
+pub mod view;
+
+use solana_curve25519::edwards::{validate_edwards, PodEdwardsPoint};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
+};
+use solana_stake_interface::{
+    instruction as stake_instruction,
+    state::{Authorized, Lockup, StakeAuthorize, StakeStateV2},
 };
 use solana_system_interface::instruction as system_instruction;
 use thiserror::Error;
 
-entrypoint!(process_instruction);
+use borsh::BorshDeserialize;
+
+use crate::curve::Curve;
+use crate::events::emit_cpi;
+use crate::verifier::{CircuitVersion, Groth16VerifyingKey, VerifierBackend, MAX_CIRCUIT_VERSIONS};
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_security_txt::security_txt! {
+    name: "Hollow mixer",
+    project_url: "https://github.com/MrSufferer/hollow-sol",
+    contacts: "link:https://github.com/MrSufferer/hollow-sol/security/advisories/new",
+    policy: "https://github.com/MrSufferer/hollow-sol/security/policy",
+    source_code: "https://github.com/MrSufferer/hollow-sol"
+}
 
 #[derive(Error, Debug, Copy, Clone)]
 pub enum MixerError {
@@ -37,6 +81,54 @@ pub enum MixerError {
     NullifierUsed,
     #[error("Verification failed")]
     VerificationFailed,
+    #[error("Invalid proof buffer")]
+    InvalidProofBuffer,
+    #[error("Invalid withdraw call")]
+    InvalidWithdrawCall,
+    #[error("Recipient is not on the ed25519 curve")]
+    OffCurveRecipient,
+    #[error("Invalid scheduled withdraw")]
+    InvalidScheduledWithdraw,
+    #[error("Scheduled withdraw not yet executable")]
+    ScheduleNotReady,
+    #[error("Signer is not the pool authority")]
+    Unauthorized,
+    #[error("Verifier program does not match the pool")]
+    InvalidVerifier,
+    #[error("Invalid admin action")]
+    InvalidAdminAction,
+    #[error("Admin action still timelocked")]
+    AdminActionNotReady,
+    #[error("Pool is paused")]
+    Paused,
+    #[error("Pool is not paused")]
+    NotPaused,
+    #[error("Parameter outside its allowed bounds")]
+    ParameterOutOfBounds,
+    #[error("Root is newer than the pool's minimum withdrawal delay")]
+    RootTooRecent,
+    #[error("Circuit version is not registered with the pool")]
+    UnknownCircuitVersion,
+    #[error("Circuit version is not active at this slot")]
+    CircuitVersionNotActive,
+    #[error("Proof contains a malformed curve point")]
+    MalformedProof,
+    #[error("Curve is not supported here")]
+    UnsupportedCurve,
+    #[error("Relayer fee exceeds the pool's relayer fee cap")]
+    RelayerFeeTooHigh,
+    #[error("Deposit limit for this epoch reached")]
+    DepositRateLimited,
+    #[error("Deposit would take the vault past the pool's cap")]
+    VaultCapExceeded,
+    #[error("Vault holds less than the pool owes")]
+    VaultInsolvent,
+    #[error("Pool does not mint deposit receipts")]
+    ReceiptsDisabled,
+    #[error("Account is still in use")]
+    AccountInUse,
+    #[error("Pool still holds unspent notes")]
+    NotesOutstanding,
 }
 
 impl From<MixerError> for ProgramError {
@@ -59,397 +151,3916 @@ pub struct MixerState {
     pub roots: [[u8; 32]; MixerState::ROOT_HISTORY_SIZE],
     /// Index of the latest root in the ring buffer.
     pub current_root_index: u8,
+    /// Pool options, see the `FLAG_*` constants. Fixed at initialization.
+    pub flags: u8,
+    /// Signs admin actions; see [`admin`].
+    pub authority: Pubkey,
+    /// Verifier every withdraw must CPI into. Only pools initialized before a
+    /// verifier was required can hold all zeroes, and they can't withdraw
+    /// until `SetVerifier` pins one.
+    pub verifier_program: Pubkey,
+    /// Seconds an admin action must wait between being queued and executed.
+    pub admin_delay: u64,
+    /// May pause the pool but do nothing else. All zeroes means no guardian.
+    pub guardian: Pubkey,
+    /// While set, deposits and withdrawals are rejected.
+    pub paused: bool,
+    /// Share of each withdrawal kept by the pool, in basis points.
+    pub protocol_fee_bps: u16,
+    /// Largest relayer fee or scheduled-withdraw bounty, in basis points of
+    /// the denomination. 0 at initialization, so relayers are paid only once
+    /// the authority raises it.
+    pub relayer_fee_cap_bps: u16,
+    /// Seconds a root must have been on-chain before notes under it can be
+    /// withdrawn.
+    pub min_withdraw_delay: u64,
+    /// Protocol fees held in the vault and not yet collected, in lamports.
+    pub protocol_fees: u64,
+    /// Unix timestamp at which each root in `roots` was pushed.
+    pub root_times: [i64; MixerState::ROOT_HISTORY_SIZE],
+    /// A [`verifier::VerifierBackend`]; selects how `verifier_program` is used.
+    pub verifier_backend: u8,
+    /// Circuit versions accepted besides the default (version 0).
+    pub circuit_versions: [CircuitVersion; MAX_CIRCUIT_VERSIONS],
+    /// Expected hash of the default verifier, see
+    /// [`verifier::verifier_account_hash`]. All zeroes disables the check.
+    pub verifier_hash: [u8; 32],
+    /// A [`curve::Curve`]; fixed at `Initialize`.
+    pub curve: u8,
+    /// Depth of the pool's Merkle tree, which must match the circuit's;
+    /// fixed at `Initialize`.
+    pub tree_depth: u8,
+    /// Leaves of the current tree, one for each `PushRoot` since the tree
+    /// started. Never exceeds `2^tree_depth`.
+    pub leaf_count: u64,
+    /// Deposits the pool accepts per epoch; 0 for no limit.
+    pub max_deposits_per_epoch: u32,
+    /// Deposits one depositor may make per epoch, counted in its
+    /// [`DepositCounter`]; 0 for no limit.
+    pub max_deposits_per_depositor: u32,
+    /// Epoch that `epoch_deposits` counts deposits of.
+    pub deposit_epoch: u64,
+    /// Deposits made in `deposit_epoch`.
+    pub epoch_deposits: u32,
+    /// Most lamports the vault may hold after a deposit; 0 for no cap.
+    pub max_vault_lamports: u64,
+    /// Deposits made, each by a `PushRoot` that took a denomination into
+    /// the vault.
+    pub deposit_count: u64,
+    /// Notes spent by any withdraw variant.
+    pub withdraw_count: u64,
+    /// Bubblegum tree that deposit receipts are minted into; all zeroes
+    /// when receipts are disabled. See [`receipt`].
+    pub receipt_tree: Pubkey,
+    /// Circuit version the pool is migrating to with
+    /// `admin::AdminAction::MigratePool`; 0 when no migration is pending.
+    pub migration_version: u16,
+    /// First slot at which a pending migration stops accepting proofs for
+    /// the default verifier.
+    pub migration_end_slot: u64,
+    /// Tree that deposits go into and `roots` belong to. Earlier trees are
+    /// full and archived in [`CommitmentTree`] accounts.
+    pub tree_id: u8,
+    /// Slot at which each root in `roots` was pushed, next to `root_times`;
+    /// 0 for roots pushed before slots were recorded.
+    pub root_slots: [u64; MixerState::ROOT_HISTORY_SIZE],
 }
 
 impl MixerState {
     pub const ROOT_HISTORY_SIZE: usize = 30;
-    pub const LEN: usize = 8 + 32 * Self::ROOT_HISTORY_SIZE + 1;
+    const ROOTS_OFFSET: usize = 8;
+    const CURRENT_ROOT_INDEX_OFFSET: usize = Self::ROOTS_OFFSET + 32 * Self::ROOT_HISTORY_SIZE;
+    const FLAGS_OFFSET: usize = Self::CURRENT_ROOT_INDEX_OFFSET + 1;
+    const AUTHORITY_OFFSET: usize = Self::FLAGS_OFFSET + 1;
+    const VERIFIER_OFFSET: usize = Self::AUTHORITY_OFFSET + 32;
+    const ADMIN_DELAY_OFFSET: usize = Self::VERIFIER_OFFSET + 32;
+    const GUARDIAN_OFFSET: usize = Self::ADMIN_DELAY_OFFSET + 8;
+    const PAUSED_OFFSET: usize = Self::GUARDIAN_OFFSET + 32;
+    const PROTOCOL_FEE_BPS_OFFSET: usize = Self::PAUSED_OFFSET + 1;
+    const RELAYER_FEE_CAP_BPS_OFFSET: usize = Self::PROTOCOL_FEE_BPS_OFFSET + 2;
+    const MIN_WITHDRAW_DELAY_OFFSET: usize = Self::RELAYER_FEE_CAP_BPS_OFFSET + 2;
+    const PROTOCOL_FEES_OFFSET: usize = Self::MIN_WITHDRAW_DELAY_OFFSET + 8;
+    const ROOT_TIMES_OFFSET: usize = Self::PROTOCOL_FEES_OFFSET + 8;
+    const VERIFIER_BACKEND_OFFSET: usize = Self::ROOT_TIMES_OFFSET + 8 * Self::ROOT_HISTORY_SIZE;
+    const CIRCUIT_VERSIONS_OFFSET: usize = Self::VERIFIER_BACKEND_OFFSET + 1;
+    const VERIFIER_HASH_OFFSET: usize =
+        Self::CIRCUIT_VERSIONS_OFFSET + CircuitVersion::LEN * MAX_CIRCUIT_VERSIONS;
+    const CURVE_OFFSET: usize = Self::VERIFIER_HASH_OFFSET + 32;
+    const TREE_DEPTH_OFFSET: usize = Self::CURVE_OFFSET + 1;
+    const LEAF_COUNT_OFFSET: usize = Self::TREE_DEPTH_OFFSET + 1;
+    const MAX_DEPOSITS_PER_EPOCH_OFFSET: usize = Self::LEAF_COUNT_OFFSET + 8;
+    const MAX_DEPOSITS_PER_DEPOSITOR_OFFSET: usize = Self::MAX_DEPOSITS_PER_EPOCH_OFFSET + 4;
+    const DEPOSIT_EPOCH_OFFSET: usize = Self::MAX_DEPOSITS_PER_DEPOSITOR_OFFSET + 4;
+    const EPOCH_DEPOSITS_OFFSET: usize = Self::DEPOSIT_EPOCH_OFFSET + 8;
+    const MAX_VAULT_LAMPORTS_OFFSET: usize = Self::EPOCH_DEPOSITS_OFFSET + 4;
+    const DEPOSIT_COUNT_OFFSET: usize = Self::MAX_VAULT_LAMPORTS_OFFSET + 8;
+    const WITHDRAW_COUNT_OFFSET: usize = Self::DEPOSIT_COUNT_OFFSET + 8;
+    const RECEIPT_TREE_OFFSET: usize = Self::WITHDRAW_COUNT_OFFSET + 8;
+    const MIGRATION_VERSION_OFFSET: usize = Self::RECEIPT_TREE_OFFSET + 32;
+    const MIGRATION_END_SLOT_OFFSET: usize = Self::MIGRATION_VERSION_OFFSET + 2;
+    const TREE_ID_OFFSET: usize = Self::MIGRATION_END_SLOT_OFFSET + 8;
+    const ROOT_SLOTS_OFFSET: usize = Self::TREE_ID_OFFSET + 1;
+    pub const LEN: usize = Self::ROOT_SLOTS_OFFSET + 8 * Self::ROOT_HISTORY_SIZE;
+    /// Length of the state of pools created before anything followed the
+    /// root history; `ResizeState` grows them to `LEN`.
+    pub const BASELINE_LEN: usize = Self::CURRENT_ROOT_INDEX_OFFSET + 1;
 
-    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
-        if root == &[0u8; 32] {
-            return false;
+    /// Tree depth of pools that don't choose one, and of the committed
+    /// circuit.
+    pub const DEFAULT_TREE_DEPTH: u8 = 20;
+    /// Deepest tree a pool can use.
+    pub const MAX_TREE_DEPTH: u8 = 32;
+
+    /// Allow withdrawals to off-curve recipients (PDAs such as escrows or
+    /// protocol vaults). Off by default: a transfer to an off-curve key that
+    /// no program controls is unrecoverable.
+    pub const FLAG_ALLOW_OFF_CURVE_RECIPIENTS: u8 = 1 << 0;
+
+    pub fn allows_off_curve_recipients(&self) -> bool {
+        self.flags & Self::FLAG_ALLOW_OFF_CURVE_RECIPIENTS != 0
+    }
+
+    /// Number of leaves the pool's tree can hold.
+    pub fn tree_capacity(&self) -> u64 {
+        1u64 << self.tree_depth.min(Self::MAX_TREE_DEPTH)
+    }
+
+    /// Leaves across all of the pool's trees. Archived trees are always full.
+    pub fn total_leaves(&self) -> u64 {
+        (self.tree_id as u64)
+            .saturating_mul(self.tree_capacity())
+            .saturating_add(self.leaf_count)
+    }
+
+    /// Archive the current tree, which must be full, and start the next one
+    /// with an empty root history.
+    pub fn rotate_tree(&mut self) -> Result<CommitmentTree, MixerError> {
+        let next = self.tree_id.checked_add(1).ok_or(MixerError::ParameterOutOfBounds)?;
+        let archived = CommitmentTree {
+            tree_id: self.tree_id,
+            leaf_count: self.leaf_count,
+            current_root_index: self.current_root_index,
+            roots: self.roots,
+            root_times: self.root_times,
+            root_slots: self.root_slots,
+        };
+        self.tree_id = next;
+        self.leaf_count = 0;
+        self.current_root_index = 0;
+        self.roots = [[0u8; 32]; Self::ROOT_HISTORY_SIZE];
+        self.root_times = [0; Self::ROOT_HISTORY_SIZE];
+        self.root_slots = [0; Self::ROOT_HISTORY_SIZE];
+        Ok(archived)
+    }
+
+    pub fn curve(&self) -> Result<Curve, MixerError> {
+        Curve::try_from(self.curve)
+    }
+
+    fn check_not_paused(&self) -> ProgramResult {
+        if self.paused {
+            msg!("Pool is paused");
+            return Err(MixerError::Paused.into());
         }
-        let mut idx = self.current_root_index as usize;
-        for _ in 0..Self::ROOT_HISTORY_SIZE {
-            if &self.roots[idx] == root {
-                return true;
-            }
-            if idx == 0 {
-                idx = Self::ROOT_HISTORY_SIZE - 1;
-            } else {
-                idx -= 1;
+        Ok(())
+    }
+
+    /// Protocol fee taken from each withdrawal.
+    pub fn protocol_fee(&self) -> u64 {
+        (self.denomination as u128 * self.protocol_fee_bps as u128 / 10_000) as u64
+    }
+
+    /// Lamports leaving the vault for a withdrawal, after the protocol fee.
+    pub fn withdraw_amount(&self) -> u64 {
+        self.denomination - self.protocol_fee()
+    }
+
+    /// Largest relayer fee or bounty a withdraw may take out of the
+    /// withdraw amount.
+    pub fn max_relayer_fee(&self) -> u64 {
+        (self.denomination as u128 * self.relayer_fee_cap_bps as u128 / 10_000) as u64
+    }
+
+    /// Count a deposit made in `epoch` against the pool's per-epoch limit.
+    pub fn count_deposit(&mut self, epoch: u64) -> Result<(), MixerError> {
+        count_in_epoch(
+            &mut self.deposit_epoch,
+            &mut self.epoch_deposits,
+            epoch,
+            self.max_deposits_per_epoch,
+        )
+    }
+
+    /// Notes deposited and not yet withdrawn: `PushRoot`s, each of which took
+    /// a denomination into the vault, less spent nullifiers. Nothing checks
+    /// that the pushed roots commit to those deposits.
+    pub fn outstanding_notes(&self) -> u64 {
+        self.deposit_count.saturating_sub(self.withdraw_count)
+    }
+
+    /// Lamports the vault owes: a denomination for every outstanding note,
+    /// plus the accrued protocol fees.
+    pub fn liabilities(&self) -> Option<u64> {
+        self.outstanding_notes()
+            .checked_mul(self.denomination)?
+            .checked_add(self.protocol_fees)
+    }
+
+    /// Lamports left for the recipient once the relayer takes `relayer_fee`.
+    pub fn recipient_amount(&self, relayer_fee: u64) -> Result<u64, MixerError> {
+        if relayer_fee > self.max_relayer_fee() {
+            return Err(MixerError::RelayerFeeTooHigh);
+        }
+        Ok(self.withdraw_amount() - relayer_fee)
+    }
+
+    pub fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        self.root_index(root).is_some()
+    }
+
+    /// Position of `root` in the ring buffer, newest match first.
+    pub fn root_index(&self, root: &[u8; 32]) -> Option<usize> {
+        ring_position(&self.roots, self.current_root_index, root)
+    }
+
+    /// Slot at which `root` was pushed, if it is in the root history.
+    pub fn root_slot(&self, root: &[u8; 32]) -> Option<u64> {
+        self.root_index(root).map(|i| self.root_slots[i])
+    }
+
+    /// Whether `vk` is the native verifying key of the default verifier, or of
+    /// a circuit version not deprecated by `slot`. Queued admin actions are
+    /// not checked.
+    pub fn uses_verifying_key(&self, vk: &Pubkey, slot: u64) -> bool {
+        let native = VerifierBackend::NativeGroth16 as u8;
+        (self.verifier_backend == native && &self.verifier_program == vk)
+            || self.circuit_versions.iter().any(|entry| {
+                entry.version != 0
+                    && entry.backend == native
+                    && &entry.verifier == vk
+                    && slot < entry.deprecation_slot
+            })
+    }
+
+    /// Backend and verifier for proofs of `circuit_version` at `slot`.
+    /// Version 0 is the pool's default verifier and is accepted unless a
+    /// pending migration's window has passed.
+    pub fn circuit_verifier(
+        &self,
+        circuit_version: u16,
+        slot: u64,
+    ) -> Result<(VerifierBackend, Pubkey), MixerError> {
+        if circuit_version == 0 {
+            if self.migration_version != 0 && slot >= self.migration_end_slot {
+                return Err(MixerError::CircuitVersionNotActive);
             }
+            return Ok((
+                VerifierBackend::try_from(self.verifier_backend)?,
+                self.verifier_program,
+            ));
         }
-        false
+        let entry = self
+            .circuit_versions
+            .iter()
+            .find(|entry| entry.version == circuit_version)
+            .ok_or(MixerError::UnknownCircuitVersion)?;
+        if !entry.is_active(slot) {
+            return Err(MixerError::CircuitVersionNotActive);
+        }
+        Ok((VerifierBackend::try_from(entry.backend)?, entry.verifier))
     }
 
-    pub fn push_root(&mut self, root: [u8; 32]) {
+    pub fn push_root(&mut self, root: [u8; 32], now: i64, slot: u64) {
         let next = (self.current_root_index as usize + 1) % Self::ROOT_HISTORY_SIZE;
         self.roots[next] = root;
+        self.root_times[next] = now;
+        self.root_slots[next] = slot;
         self.current_root_index = next as u8;
     }
 }
 
+/// Position of `root` in a root history ring whose latest entry is at
+/// `current`, newest match first.
+fn ring_position(
+    roots: &[[u8; 32]; MixerState::ROOT_HISTORY_SIZE],
+    current: u8,
+    root: &[u8; 32],
+) -> Option<usize> {
+    if root == &[0u8; 32] {
+        return None;
+    }
+    let mut idx = current as usize;
+    for _ in 0..MixerState::ROOT_HISTORY_SIZE {
+        if &roots[idx] == root {
+            return Some(idx);
+        }
+        if idx == 0 {
+            idx = MixerState::ROOT_HISTORY_SIZE - 1;
+        } else {
+            idx -= 1;
+        }
+    }
+    None
+}
+
+/// A full Merkle tree of the pool, archived when deposits moved on to the
+/// next one. A PDA of `["commitment_tree", tree_id]`, created by the
+/// `PushRoot` that rotates the pool past it, holding the tree's root history
+/// as it was then. Withdraws name the tree their root is from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommitmentTree {
+    pub tree_id: u8,
+    /// Leaves covered by the tree's last root; the tree's capacity.
+    pub leaf_count: u64,
+    pub current_root_index: u8,
+    pub roots: [[u8; 32]; MixerState::ROOT_HISTORY_SIZE],
+    pub root_times: [i64; MixerState::ROOT_HISTORY_SIZE],
+    pub root_slots: [u64; MixerState::ROOT_HISTORY_SIZE],
+}
+
+impl CommitmentTree {
+    pub const SEED: &'static [u8] = b"commitment_tree";
+    const ROOTS_OFFSET: usize = 1 + 8 + 1;
+    const ROOT_TIMES_OFFSET: usize = Self::ROOTS_OFFSET + 32 * MixerState::ROOT_HISTORY_SIZE;
+    const ROOT_SLOTS_OFFSET: usize = Self::ROOT_TIMES_OFFSET + 8 * MixerState::ROOT_HISTORY_SIZE;
+    pub const LEN: usize = Self::ROOT_SLOTS_OFFSET + 8 * MixerState::ROOT_HISTORY_SIZE;
+
+    pub fn find_address(program_id: &Pubkey, tree_id: u8) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, &[tree_id]], program_id)
+    }
+
+    /// Unix timestamp at which `root` was pushed, if it is in the archived
+    /// root history.
+    pub fn root_time(&self, root: &[u8; 32]) -> Option<i64> {
+        ring_position(&self.roots, self.current_root_index, root).map(|i| self.root_times[i])
+    }
+
+    /// Slot at which `root` was pushed, if it is in the archived root history.
+    pub fn root_slot(&self, root: &[u8; 32]) -> Option<u64> {
+        ring_position(&self.roots, self.current_root_index, root).map(|i| self.root_slots[i])
+    }
+
+    /// Decode commitment tree account data, e.g. as fetched over RPC.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        let mut roots = [[0u8; 32]; MixerState::ROOT_HISTORY_SIZE];
+        let mut root_times = [0i64; MixerState::ROOT_HISTORY_SIZE];
+        for (i, root) in roots.iter_mut().enumerate() {
+            let start = Self::ROOTS_OFFSET + i * 32;
+            root.copy_from_slice(&data[start..start + 32]);
+        }
+        for (i, time) in root_times.iter_mut().enumerate() {
+            let start = Self::ROOT_TIMES_OFFSET + i * 8;
+            *time = i64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+        }
+        let mut root_slots = [0u64; MixerState::ROOT_HISTORY_SIZE];
+        for (i, slot) in root_slots.iter_mut().enumerate() {
+            let start = Self::ROOT_SLOTS_OFFSET + i * 8;
+            *slot = u64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+        }
+        let tree = Self {
+            tree_id: data[0],
+            leaf_count: u64::from_le_bytes(data[1..9].try_into().unwrap()),
+            current_root_index: data[9],
+            roots,
+            root_times,
+            root_slots,
+        };
+        if tree.current_root_index as usize >= MixerState::ROOT_HISTORY_SIZE {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(tree)
+    }
+
+    fn pack(&self, data: &mut [u8]) {
+        data[0] = self.tree_id;
+        data[1..9].copy_from_slice(&self.leaf_count.to_le_bytes());
+        data[9] = self.current_root_index;
+        for (i, root) in self.roots.iter().enumerate() {
+            let start = Self::ROOTS_OFFSET + i * 32;
+            data[start..start + 32].copy_from_slice(root);
+        }
+        for (i, time) in self.root_times.iter().enumerate() {
+            let start = Self::ROOT_TIMES_OFFSET + i * 8;
+            data[start..start + 8].copy_from_slice(&time.to_le_bytes());
+        }
+        for (i, slot) in self.root_slots.iter().enumerate() {
+            let start = Self::ROOT_SLOTS_OFFSET + i * 8;
+            data[start..start + 8].copy_from_slice(&slot.to_le_bytes());
+        }
+    }
+}
+
+/// Staging account for proof payloads too large to fit in one transaction.
+///
+/// Layout: `authority: [u8; 32] || payload`, where the payload is the same
+/// `proof_bytes || public_witness_bytes` a plain `Withdraw` carries inline.
+/// The buffer is a PDA of the mixer program derived from its authority, so
+/// only that authority can write to or consume it.
+pub struct ProofBuffer;
+
+impl ProofBuffer {
+    pub const SEED: &'static [u8] = b"proof_buffer";
+    pub const HEADER_LEN: usize = 32;
+    /// Largest payload that fits in an account created via CPI.
+    pub const MAX_PAYLOAD_LEN: usize =
+        solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE - Self::HEADER_LEN;
+    /// Largest payload `ExtendProofBuffer` can grow a buffer to, enough for
+    /// an UltraHonk proof.
+    pub const MAX_EXTENDED_PAYLOAD_LEN: usize = 32 * 1024;
+
+    pub fn find_address(program_id: &Pubkey, authority: &Pubkey, buffer_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[Self::SEED, authority.as_ref(), &buffer_id.to_le_bytes()],
+            program_id,
+        )
+    }
+
+    /// Check that `buffer_account` is a proof buffer owned by this program and
+    /// controlled by `authority`.
+    fn check(
+        program_id: &Pubkey,
+        buffer_account: &AccountInfo,
+        authority: &Pubkey,
+    ) -> Result<(), ProgramError> {
+        if buffer_account.owner != program_id {
+            return Err(MixerError::InvalidProofBuffer.into());
+        }
+        let data = buffer_account.data.borrow();
+        if data.len() < Self::HEADER_LEN || &data[0..Self::HEADER_LEN] != authority.as_ref() {
+            return Err(MixerError::InvalidProofBuffer.into());
+        }
+        Ok(())
+    }
+}
+
+/// A withdraw queued for later execution by any keeper.
+///
+/// The proof payload stays in the scheduler's proof buffer; this account is a
+/// PDA derived from that buffer and records what the proof will be spent on.
+/// Delaying execution past a chosen slot further decouples the withdraw from
+/// the moment the note holder was online.
+pub struct ScheduledWithdraw {
+    /// Owner of the proof buffer; receives both accounts' rent on close.
+    pub authority: Pubkey,
+    /// Only account the payout may go to.
+    pub recipient: Pubkey,
+    pub root: [u8; 32],
+    pub nullifier_hash: [u8; 32],
+    pub recipient_field: [u8; 32],
+    pub circuit_version: u16,
+    /// Tree `root` is from; `None` for the pool's current tree at execution.
+    pub tree_id: Option<u8>,
+    /// First slot at which the withdraw may be executed.
+    pub earliest_slot: u64,
+    /// Lamports paid out of the withdraw amount to the executing keeper.
+    pub bounty: u64,
+}
+
+impl ScheduledWithdraw {
+    pub const SEED: &'static [u8] = b"scheduled_withdraw";
+    pub const LEN: usize = 32 * 5 + 8 + 8 + 2 + 2;
+    /// Length of schedules made before they named a tree.
+    const UNTREED_LEN: usize = Self::LEN - 2;
+
+    pub fn find_address(program_id: &Pubkey, proof_buffer: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, proof_buffer.as_ref()], program_id)
+    }
+
+    /// Load the schedule for `proof_buffer` from `account`.
+    fn load(
+        program_id: &Pubkey,
+        account: &AccountInfo,
+        proof_buffer: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        if account.owner != program_id
+            || account.key != &Self::find_address(program_id, proof_buffer).0
+        {
+            return Err(MixerError::InvalidScheduledWithdraw.into());
+        }
+        let data = account.data.borrow();
+        if data.len() < Self::UNTREED_LEN {
+            return Err(MixerError::InvalidScheduledWithdraw.into());
+        }
+        let key = |start: usize| -> [u8; 32] { data[start..start + 32].try_into().unwrap() };
+        Ok(Self {
+            authority: Pubkey::new_from_array(key(0)),
+            recipient: Pubkey::new_from_array(key(32)),
+            root: key(64),
+            nullifier_hash: key(96),
+            recipient_field: key(128),
+            earliest_slot: u64::from_le_bytes(data[160..168].try_into().unwrap()),
+            bounty: u64::from_le_bytes(data[168..176].try_into().unwrap()),
+            circuit_version: u16::from_le_bytes(data[176..178].try_into().unwrap()),
+            tree_id: match data.get(178..180) {
+                Some(&[1, tree_id]) => Some(tree_id),
+                _ => None,
+            },
+        })
+    }
+
+    fn store(&self, account: &AccountInfo) -> ProgramResult {
+        let mut data = account.try_borrow_mut_data()?;
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        data[0..32].copy_from_slice(self.authority.as_ref());
+        data[32..64].copy_from_slice(self.recipient.as_ref());
+        data[64..96].copy_from_slice(&self.root);
+        data[96..128].copy_from_slice(&self.nullifier_hash);
+        data[128..160].copy_from_slice(&self.recipient_field);
+        data[160..168].copy_from_slice(&self.earliest_slot.to_le_bytes());
+        data[168..176].copy_from_slice(&self.bounty.to_le_bytes());
+        data[176..178].copy_from_slice(&self.circuit_version.to_le_bytes());
+        data[178..180].copy_from_slice(&match self.tree_id {
+            Some(tree_id) => [1, tree_id],
+            None => [0, 0],
+        });
+        Ok(())
+    }
+}
+
+/// Deposits one depositor made in an epoch, for pools that set
+/// `MixerState::max_deposits_per_depositor`. A PDA of
+/// `["deposit_counter", depositor]`, created by the depositor's first
+/// counted deposit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepositCounter {
+    pub epoch: u64,
+    pub count: u32,
+}
+
+impl DepositCounter {
+    pub const SEED: &'static [u8] = b"deposit_counter";
+    pub const LEN: usize = 8 + 4;
+
+    pub fn find_address(program_id: &Pubkey, depositor: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, depositor.as_ref()], program_id)
+    }
+
+    fn unpack(data: &[u8]) -> Self {
+        Self {
+            epoch: u64::from_le_bytes(data[0..8].try_into().unwrap()),
+            count: u32::from_le_bytes(data[8..12].try_into().unwrap()),
+        }
+    }
+
+    fn pack(&self, data: &mut [u8]) {
+        data[0..8].copy_from_slice(&self.epoch.to_le_bytes());
+        data[8..12].copy_from_slice(&self.count.to_le_bytes());
+    }
+}
+
+/// The pool's latest root, kept apart from the large state account so
+/// wallets can poll or subscribe to 48 bytes. A PDA of `["latest_root"]`,
+/// created by `CreateLatestRoot` and rewritten by every `PushRoot`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LatestRoot {
+    pub root: [u8; 32],
+    /// `MixerState::leaf_count` after the push.
+    pub leaf_count: u64,
+    /// Slot the root was pushed in.
+    pub slot: u64,
+}
+
+impl LatestRoot {
+    pub const SEED: &'static [u8] = b"latest_root";
+    pub const LEN: usize = 32 + 8 + 8;
+
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED], program_id)
+    }
+
+    /// Decode latest root account data, e.g. as fetched over RPC.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Ok(Self {
+            root: data[0..32].try_into().unwrap(),
+            leaf_count: u64::from_le_bytes(data[32..40].try_into().unwrap()),
+            slot: u64::from_le_bytes(data[40..48].try_into().unwrap()),
+        })
+    }
+
+    fn pack(&self, data: &mut [u8]) {
+        data[0..32].copy_from_slice(&self.root);
+        data[32..40].copy_from_slice(&self.leaf_count.to_le_bytes());
+        data[40..48].copy_from_slice(&self.slot.to_le_bytes());
+    }
+}
+
+/// Running totals for explorers, so basic pool health needs no indexer. A
+/// PDA of `["pool_stats"]`, created by `CreatePoolStats` and updated by every
+/// `PushRoot` and withdraw variant. It counts from its creation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub deposits: u64,
+    pub withdrawals: u64,
+    /// Lamports deposited, one denomination per `PushRoot`.
+    pub deposit_volume: u64,
+    /// Lamports paid out to recipients and relayers.
+    pub withdraw_volume: u64,
+    /// Protocol fees accrued, whether collected since or not.
+    pub fee_revenue: u64,
+    /// Slot of the latest deposit or withdrawal.
+    pub last_activity_slot: u64,
+}
+
+impl PoolStats {
+    pub const SEED: &'static [u8] = b"pool_stats";
+    pub const LEN: usize = 6 * 8;
+
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED], program_id)
+    }
+
+    /// Decode pool stats account data, e.g. as fetched over RPC.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        let u64_at = |i: usize| u64::from_le_bytes(data[8 * i..8 * i + 8].try_into().unwrap());
+        Ok(Self {
+            deposits: u64_at(0),
+            withdrawals: u64_at(1),
+            deposit_volume: u64_at(2),
+            withdraw_volume: u64_at(3),
+            fee_revenue: u64_at(4),
+            last_activity_slot: u64_at(5),
+        })
+    }
+
+    fn pack(&self, data: &mut [u8]) {
+        let fields = [
+            self.deposits,
+            self.withdrawals,
+            self.deposit_volume,
+            self.withdraw_volume,
+            self.fee_revenue,
+            self.last_activity_slot,
+        ];
+        for (i, field) in fields.iter().enumerate() {
+            data[8 * i..8 * i + 8].copy_from_slice(&field.to_le_bytes());
+        }
+    }
+
+    /// Load the stats at `account`, which must be the created PDA, apply
+    /// `update` and store them.
+    fn update(
+        program_id: &Pubkey,
+        account: &AccountInfo,
+        update: impl FnOnce(&mut Self),
+    ) -> ProgramResult {
+        if account.key != &Self::find_address(program_id).0 {
+            msg!("Invalid pool stats PDA");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if account.owner != program_id {
+            msg!("Pool stats account missing; send CreatePoolStats first");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let mut data = account.try_borrow_mut_data()?;
+        let mut stats = Self::unpack(&data)?;
+        update(&mut stats);
+        stats.pack(&mut data);
+        Ok(())
+    }
+}
+
+/// Proof of payment for one withdrawal, for recipients such as merchants
+/// or payroll that must show they were paid. A PDA of
+/// `["withdraw_receipt", nullifier_hash]`, created by `Withdraw` or
+/// `WithdrawFromBuffer` when the sender passes it, and closed by the
+/// recipient with `CloseWithdrawReceipt`. It says nothing about the deposit:
+/// the nullifier hash in its address is already public once the note is
+/// spent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WithdrawReceipt {
+    /// The recipient paid, who alone may close the receipt.
+    pub recipient: Pubkey,
+    /// Lamports paid to the recipient, after the relayer fee.
+    pub amount: u64,
+    /// Slot of the withdrawal.
+    pub slot: u64,
+}
+
+impl WithdrawReceipt {
+    pub const SEED: &'static [u8] = b"withdraw_receipt";
+    pub const LEN: usize = 32 + 8 + 8;
+
+    pub fn find_address(program_id: &Pubkey, nullifier_hash: &[u8; 32]) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::SEED, nullifier_hash], program_id)
+    }
+
+    /// Decode withdraw receipt account data, e.g. as fetched over RPC.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        Ok(Self {
+            recipient: Pubkey::new_from_array(data[0..32].try_into().unwrap()),
+            amount: u64::from_le_bytes(data[32..40].try_into().unwrap()),
+            slot: u64::from_le_bytes(data[40..48].try_into().unwrap()),
+        })
+    }
+
+    fn pack(&self, data: &mut [u8]) {
+        data[0..32].copy_from_slice(self.recipient.as_ref());
+        data[32..40].copy_from_slice(&self.amount.to_le_bytes());
+        data[40..48].copy_from_slice(&self.slot.to_le_bytes());
+    }
+}
+
+/// Count one event in `epoch`, restarting the count when the epoch changed,
+/// and fail once `limit` (0 for none) events were already counted.
+fn count_in_epoch(
+    counted_epoch: &mut u64,
+    count: &mut u32,
+    epoch: u64,
+    limit: u32,
+) -> Result<(), MixerError> {
+    if *counted_epoch != epoch {
+        *counted_epoch = epoch;
+        *count = 0;
+    }
+    if limit != 0 && *count >= limit {
+        return Err(MixerError::DepositRateLimited);
+    }
+    *count = count.saturating_add(1);
+    Ok(())
+}
+
+/// An auxiliary account `CloseAccount` can reclaim, with what locates it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseTarget {
+    /// A [`ProofBuffer`] of the signer, e.g. one never spent.
+    ProofBuffer { buffer_id: u64 },
+    /// The signer's [`DepositCounter`], once its epoch has passed.
+    DepositCounter,
+    /// A verifying key no longer named by the pool or a circuit version that
+    /// can still be used; see [`verifier`].
+    VerifyingKey { vk_id: u64 },
+}
+
 /// Instructions supported by the mixer.
 pub enum MixerInstruction {
     /// Initialize the mixer state.
     ///
+    /// Fails once the pool exists, even for its authority: every later change
+    /// goes through the timelocked admin actions.
+    ///
     /// Accounts:
     ///   0. [signer]  Payer / authority.
     ///   1. [writable] Mixer state account (PDA).
     ///   2. []        System program.
+    ///   3. []        Verifier program every withdraw is pinned to.
     ///
     /// Data:
     ///   - denomination: u64
-    Initialize { denomination: u64 },
+    ///   - flags: u8 (optional, defaults to 0; see `MixerState::FLAG_*`)
+    ///   - admin_delay: u64 (optional, requires flags; seconds, defaults to 0)
+    ///   - curve: u8 (optional, requires admin_delay; a `curve::Curve`,
+    ///     defaults to BN254)
+    ///   - tree_depth: u8 (optional, requires curve; 1 to
+    ///     `MixerState::MAX_TREE_DEPTH`, defaults to
+    ///     `MixerState::DEFAULT_TREE_DEPTH`)
+    Initialize {
+        denomination: u64,
+        flags: u8,
+        admin_delay: u64,
+        curve: Curve,
+        tree_depth: u8,
+    },
 
-    /// Record a new Merkle root for deposits.
+    /// Deposit one denomination and record a new Merkle root that includes
+    /// the deposit's commitment.
     ///
-    /// This does not itself move funds; the client is responsible for sending
-    /// lamports into the mixer vault account in a separate instruction.
+    /// The push transfers the pool denomination from the depositor into the
+    /// vault, so every root, and every deposit counted against the pool's
+    /// liabilities and per-epoch limits, is paid for. It fails if the vault
+    /// then holds more than the pool's cap.
     ///
     /// Accounts:
-    ///   0. [signer]   Authority.
+    ///   0. [signer, writable] Authority (the depositor; pays the deposit).
     ///   1. [writable] Mixer state account (PDA).
+    ///   2. []         Event authority (PDA).
+    ///   3. []         Mixer program.
+    ///   4. [writable] Mixer vault account (PDA).
+    ///   5. [writable] Latest root (PDA of ["latest_root"]).
+    ///   6. [writable] Pool stats (PDA of ["pool_stats"]).
+    ///   7. []         System program.
+    ///   8. [writable] Deposit counter (PDA of ["deposit_counter", depositor]);
+    ///      only if the pool limits deposits per depositor, in which case
+    ///      the depositor also pays its rent.
+    ///   9. []         System program; only with the deposit counter.
+    ///
+    /// When the pool's current tree is full, the push goes into the next
+    /// tree, and the full one is archived. After those:
+    ///   - [writable] Commitment tree (PDA of ["commitment_tree", tree_id])
+    ///     of the full tree, paid by the depositor.
+    ///   - []         System program.
+    ///
+    /// With a receipt (see [`receipt`]), after those:
+    ///   - []         Receipt owner, chosen by the depositor.
+    ///   - []         Receipt authority (PDA of ["receipt_authority"]).
+    ///   - [writable] Bubblegum tree config of the receipt tree.
+    ///   - [writable] The pool's receipt tree.
+    ///   - []         SPL Noop program.
+    ///   - []         SPL Account Compression program.
+    ///   - []         Bubblegum program.
+    ///   - []         System program.
     ///
     /// Data:
     ///   - new_root: [u8; 32]
-    PushRoot { new_root: [u8; 32] },
+    ///   - leaf_count: u64 (optional; leaves under `new_root`, which must be
+    ///     the pool's current `leaf_count` plus the one this push adds,
+    ///     counted from 0 again in a new tree)
+    ///   - receipt: u8 (optional, requires leaf_count; 1 mints a receipt for
+    ///     the deposit at `leaf_count - 1`, counting the leaves of the
+    ///     pool's earlier trees)
+    PushRoot {
+        new_root: [u8; 32],
+        leaf_count: Option<u64>,
+        receipt: bool,
+    },
 
     /// Withdraw funds by presenting a valid ZK proof and public inputs.
     ///
+    /// The proof binds neither the relayer nor the relayer fee, and the
+    /// recipient account is not bound either: the program does not compare
+    /// it to `recipient_field`, and the circuit's recipient constraint is
+    /// disabled. Anyone who saw the proof can resubmit it with another
+    /// recipient and relayer. A nonzero fee needs the recipient's signature,
+    /// which only shows that the named recipient agreed to the fee. The
+    /// pool's relayer fee cap bounds the fee.
+    ///
     /// Accounts:
-    ///   0. [signer]   Relayer / transaction sender.
+    ///   0. [signer]   Relayer / transaction sender; receives the relayer fee.
     ///   1. [writable] Mixer state account (PDA).
     ///   2. [writable] Nullifier account (PDA derived from nullifier hash).
     ///   3. [writable] Mixer vault account holding lamports.
-    ///   4. [writable] Recipient account.
+    ///   4. [writable] Recipient account; a signer if `relayer_fee` is
+    ///      nonzero.
     ///   5. []         Verifier program (Sunspot-generated).
     ///   6. []         System program.
+    ///   7. []         Event authority (PDA).
+    ///   8. []         Mixer program.
+    ///   9. [writable] Pool stats (PDA of ["pool_stats"]).
+    ///   10. []        Commitment tree (PDA of ["commitment_tree", tree_id]);
+    ///       version 2 only. Read when `tree_id` names an archived tree. In
+    ///       version 2 the accounts after it, in every withdraw variant, move
+    ///       up by one.
+    ///   11. [writable] Withdraw receipt (optional; PDA of
+    ///       ["withdraw_receipt", nullifier_hash], paid by the relayer).
     ///
     /// Data:
     ///   - root: [u8; 32]
     ///   - nullifier_hash: [u8; 32]
     ///   - recipient_field: [u8; 32] (field-encoded address, must correspond to recipient)
+    ///   - circuit_version: u16 (0 for the pool's default verifier)
+    ///   - tree_id: u8 (version 2 only; the tree `root` is from. Earlier
+    ///     versions use the pool's current tree)
+    ///   - relayer_fee: u64 (lamports paid to the relayer out of the withdraw
+    ///     amount, at most `MixerState::max_relayer_fee`)
     ///   - proof: Vec<u8> (Groth16 proof bytes as expected by Sunspot verifier)
     Withdraw {
         root: [u8; 32],
         nullifier_hash: [u8; 32],
         recipient_field: [u8; 32],
+        circuit_version: u16,
+        tree_id: Option<u8>,
+        relayer_fee: u64,
         proof: Vec<u8>,
     },
-}
-
-impl MixerInstruction {
-    pub fn unpack(input: &[u8]) -> Result<Self, MixerError> {
-        let (tag, rest) = input.split_first().ok_or(MixerError::InvalidInstruction)?;
-        Ok(match tag {
-            0 => {
-                if rest.len() != 8 {
-                    return Err(MixerError::InvalidInstruction);
-                }
-                let denomination = u64::from_le_bytes(rest.try_into().unwrap());
-                MixerInstruction::Initialize { denomination }
-            }
-            1 => {
-                if rest.len() != 32 {
-                    return Err(MixerError::InvalidInstruction);
-                }
-                let mut root = [0u8; 32];
-                root.copy_from_slice(rest);
-                MixerInstruction::PushRoot { new_root: root }
-            }
-            2 => {
-                if rest.len() < 32 + 32 + 32 {
-                    return Err(MixerError::InvalidInstruction);
-                }
-                let mut root = [0u8; 32];
-                root.copy_from_slice(&rest[0..32]);
-                let mut nullifier_hash = [0u8; 32];
-                nullifier_hash.copy_from_slice(&rest[32..64]);
-                let mut recipient_field = [0u8; 32];
-                recipient_field.copy_from_slice(&rest[64..96]);
-                let proof = rest[96..].to_vec();
-                MixerInstruction::Withdraw {
-                    root,
-                    nullifier_hash,
-                    recipient_field,
-                    proof,
-                }
-            }
-            _ => return Err(MixerError::InvalidInstruction),
-        })
-    }
-}
-
-pub fn process_instruction(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    instruction_data: &[u8],
-) -> ProgramResult {
-    let instruction = MixerInstruction::unpack(instruction_data).map_err(ProgramError::from)?;
-
-    match instruction {
-        MixerInstruction::Initialize { denomination } => {
-            process_initialize(program_id, accounts, denomination)
-        }
-        MixerInstruction::PushRoot { new_root } => process_push_root(program_id, accounts, new_root),
-        MixerInstruction::Withdraw {
-            root,
-            nullifier_hash,
-            recipient_field,
-            proof,
-        } => process_withdraw(program_id, accounts, root, nullifier_hash, recipient_field, proof),
-    }
-}
 
-fn process_initialize(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    denomination: u64,
-) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter();
-    let payer = next_account_info(account_info_iter)?;
-    let state_account = next_account_info(account_info_iter)?;
-    let system_program = next_account_info(account_info_iter)?;
+    /// Create an empty proof buffer for a multi-transaction withdraw payload.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Buffer authority (pays rent).
+    ///   1. [writable]         Proof buffer (PDA of ["proof_buffer", authority, buffer_id]).
+    ///   2. []                 System program.
+    ///
+    /// Data:
+    ///   - buffer_id: u64
+    ///   - payload_len: u32
+    CreateProofBuffer { buffer_id: u64, payload_len: u32 },
 
-    if !payer.is_signer {
-        return Err(ProgramError::MissingRequiredSignature);
-    }
+    /// Write a chunk of the payload into a proof buffer.
+    ///
+    /// Accounts:
+    ///   0. [signer]   Buffer authority.
+    ///   1. [writable] Proof buffer.
+    ///
+    /// Data:
+    ///   - offset: u32 (into the payload)
+    ///   - chunk: Vec<u8>
+    WriteProofChunk { offset: u32, chunk: Vec<u8> },
 
-    let rent = Rent::get()?;
-    let required_lamports = rent.minimum_balance(MixerState::LEN);
+    /// Same as `Withdraw`, but the proof payload is read from a proof buffer,
+    /// which is closed afterwards with its rent returned to the authority.
+    ///
+    /// Accounts:
+    ///   0-9. As for `Withdraw` (0-10 in version 2); the relayer must be the
+    ///        buffer authority.
+    ///   10.  [writable] Proof buffer.
+    ///   11.  [writable] Withdraw receipt (optional, as for `Withdraw`).
+    ///
+    /// Data:
+    ///   - root: [u8; 32]
+    ///   - nullifier_hash: [u8; 32]
+    ///   - recipient_field: [u8; 32]
+    ///   - circuit_version: u16 (0 for the pool's default verifier)
+    ///   - tree_id: u8 (version 2 only, as for `Withdraw`)
+    ///   - relayer_fee: u64 (lamports paid to the relayer out of the withdraw
+    ///     amount, at most `MixerState::max_relayer_fee`)
+    WithdrawFromBuffer {
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient_field: [u8; 32],
+        circuit_version: u16,
+        tree_id: Option<u8>,
+        relayer_fee: u64,
+    },
+
+    /// Same as `Withdraw`, then CPI into a target program with the recipient
+    /// as the first account, e.g. to deposit the payout into a protocol.
+    ///
+    /// The call data is not bound by the proof. The mixer only forwards
+    /// signatures already present on the transaction and never signs with its
+    /// own PDAs, so any call that spends the recipient's funds requires the
+    /// recipient to sign. The target may not be the mixer or the verifier, and
+    /// mixer-owned accounts and the relayer (unless it is the recipient) may
+    /// not be forwarded.
+    ///
+    /// Accounts:
+    ///   0-9. As for `Withdraw` (0-10 in version 2).
+    ///   10.  []  Target program.
+    ///   11.. Additional accounts for the target instruction, passed after the recipient.
+    ///
+    /// Data:
+    ///   - root: [u8; 32]
+    ///   - nullifier_hash: [u8; 32]
+    ///   - recipient_field: [u8; 32]
+    ///   - circuit_version: u16 (0 for the pool's default verifier)
+    ///   - tree_id: u8 (version 2 only, as for `Withdraw`)
+    ///   - relayer_fee: u64 (lamports paid to the relayer out of the withdraw
+    ///     amount, at most `MixerState::max_relayer_fee`)
+    ///   - proof_len: u32
+    ///   - proof: [u8; proof_len]
+    ///   - call_data: Vec<u8> (remaining bytes)
+    WithdrawAndCall {
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient_field: [u8; 32],
+        circuit_version: u16,
+        tree_id: Option<u8>,
+        relayer_fee: u64,
+        proof: Vec<u8>,
+        call_data: Vec<u8>,
+    },
+
+    /// Same as `Withdraw`, but instead of paying the recipient directly the
+    /// denomination funds a new stake account delegated to `vote_account`,
+    /// with the recipient as both staker and withdrawer.
+    ///
+    /// The stake account is a PDA derived from the nullifier hash, so each
+    /// note can create exactly one. Delegation is signed by the stake
+    /// authority PDA, which hands the staker role to the recipient in the
+    /// same instruction; the recipient does not need to sign.
+    ///
+    /// Accounts:
+    ///   0-9. As for `Withdraw` (0-10 in version 2); the recipient receives
+    ///        no lamports directly.
+    ///   10.  [writable] Stake account (PDA of ["stake_account", nullifier_hash]).
+    ///   11.  []         Vote account to delegate to.
+    ///   12.  []         Stake authority (PDA of ["stake_authority"]).
+    ///   13.  []         Stake program.
+    ///   14.  []         Rent sysvar.
+    ///   15.  []         Clock sysvar.
+    ///   16.  []         Stake history sysvar.
+    ///   17.  []         Stake config account.
+    ///
+    /// Data:
+    ///   - As for `Withdraw`.
+    WithdrawToStake {
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient_field: [u8; 32],
+        circuit_version: u16,
+        tree_id: Option<u8>,
+        relayer_fee: u64,
+        proof: Vec<u8>,
+    },
+
+    /// Queue a withdraw of the proof in a proof buffer for execution by any
+    /// keeper once `earliest_slot` is reached; see [`ScheduledWithdraw`].
+    ///
+    /// If the root falls out of the root history before execution, the proof
+    /// can no longer be spent this way and the schedule should be cancelled.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Buffer authority (pays rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. []                 Proof buffer, fully written.
+    ///   3. [writable]         Scheduled withdraw (PDA of ["scheduled_withdraw", proof_buffer]).
+    ///   4. []                 Recipient account; a signer if `bounty` is
+    ///      nonzero, as for a relayer fee in `Withdraw`.
+    ///   5. []                 System program.
+    ///
+    /// Data:
+    ///   - root: [u8; 32]
+    ///   - nullifier_hash: [u8; 32]
+    ///   - recipient_field: [u8; 32]
+    ///   - circuit_version: u16 (0 for the pool's default verifier)
+    ///   - tree_id: u8 (version 2 only, as for `Withdraw`)
+    ///   - earliest_slot: u64
+    ///   - bounty: u64 (lamports, at most `MixerState::max_relayer_fee`)
+    ScheduleWithdraw {
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient_field: [u8; 32],
+        circuit_version: u16,
+        tree_id: Option<u8>,
+        earliest_slot: u64,
+        bounty: u64,
+    },
+
+    /// Execute a scheduled withdraw. The relayer is the keeper: it funds the
+    /// nullifier marker and receives the bounty, while the recipient receives
+    /// the rest of the withdraw amount. Both the proof buffer and the schedule
+    /// are closed with their rent returned to the buffer authority.
+    ///
+    /// Accounts:
+    ///   0-9. As for `Withdraw`; the recipient must match the schedule.
+    ///   10.  [writable] Proof buffer.
+    ///   11.  [writable] Scheduled withdraw.
+    ///   12.  [writable] Buffer authority.
+    ///   13.  []         Commitment tree, as for `Withdraw`; only if the
+    ///        schedule names a tree.
+    ExecuteScheduledWithdraw,
+
+    /// Cancel a scheduled withdraw, closing the schedule and, if it still
+    /// exists, the proof buffer.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Buffer authority.
+    ///   1. [writable]         Proof buffer.
+    ///   2. [writable]         Scheduled withdraw.
+    CancelScheduledWithdraw,
+
+    /// Queue an admin action, executable once the pool's admin delay has
+    /// passed; see [`admin`].
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (pays rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Pending admin action (PDA of ["admin_action", action_id]).
+    ///   3. []                 System program.
+    ///   4. []                 Event authority (PDA).
+    ///   5. []                 Mixer program.
+    ///
+    /// Data:
+    ///   - action_id: u64
+    ///   - action: borsh(AdminAction)
+    QueueAdminAction {
+        action_id: u64,
+        action: admin::AdminAction,
+    },
+
+    /// Apply a queued admin action whose delay has passed and close it.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (receives the rent).
+    ///   1. [writable]         Mixer state account (PDA).
+    ///   2. [writable]         Pending admin action.
+    ///   3. []                 Event authority (PDA).
+    ///   4. []                 Mixer program.
+    ///
+    /// Data:
+    ///   - action_id: u64
+    ExecuteAdminAction { action_id: u64 },
+
+    /// Drop a queued admin action without applying it.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (receives the rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Pending admin action.
+    ///   3. []                 Event authority (PDA).
+    ///   4. []                 Mixer program.
+    ///
+    /// Data:
+    ///   - action_id: u64
+    CancelAdminAction { action_id: u64 },
+
+    /// Pause or unpause deposits and withdrawals, effective immediately. The
+    /// authority may do either; the guardian may only pause.
+    ///
+    /// Accounts:
+    ///   0. [signer]   Pool authority or guardian.
+    ///   1. [writable] Mixer state account (PDA).
+    ///   2. []         Event authority (PDA).
+    ///   3. []         Mixer program.
+    ///
+    /// Data:
+    ///   - paused: u8 (0 or 1)
+    SetPaused { paused: bool },
+
+    /// Queue moving the entire vault balance to a recovery address, for
+    /// catastrophic circuit or verifier bugs. Only allowed while paused, and
+    /// executable after the longer of the admin delay and
+    /// `admin::EMERGENCY_DRAIN_MIN_DELAY`.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (pays rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Pending drain (PDA of ["emergency_drain"]).
+    ///   3. []                 System program.
+    ///   4. []                 Event authority (PDA).
+    ///   5. []                 Mixer program.
+    ///
+    /// Data:
+    ///   - recovery: [u8; 32]
+    QueueEmergencyDrain { recovery: [u8; 32] },
+
+    /// Execute a queued drain whose delay has passed. The pool must still be
+    /// paused.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (receives the pending drain's rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Pending drain.
+    ///   3. [writable]         Mixer vault.
+    ///   4. [writable]         Recovery account.
+    ///   5. []                 System program.
+    ///   6. []                 Event authority (PDA).
+    ///   7. []                 Mixer program.
+    ExecuteEmergencyDrain,
+
+    /// Drop a queued drain.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (receives the rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Pending drain.
+    ///   3. []                 Event authority (PDA).
+    ///   4. []                 Mixer program.
+    CancelEmergencyDrain,
+
+    /// Pay the accrued protocol fees out of the vault. Takes effect
+    /// immediately; the fee rate itself is changed through the timelock.
+    ///
+    /// Accounts:
+    ///   0. [signer]   Pool authority.
+    ///   1. [writable] Mixer state account (PDA).
+    ///   2. [writable] Mixer vault.
+    ///   3. [writable] Destination account.
+    ///   4. []         System program.
+    ///   5. []         Event authority (PDA).
+    ///   6. []         Mixer program.
+    CollectProtocolFees,
+
+    /// Pay out whatever the vault holds beyond what the pool owes
+    /// (`MixerState::liabilities`) and its rent-exempt reserve, such as
+    /// transfers sent to it by mistake. Fails if the vault holds less than
+    /// the pool owes.
+    ///
+    /// Accounts:
+    ///   0. [signer]   Pool authority.
+    ///   1. []         Mixer state account (PDA).
+    ///   2. [writable] Mixer vault.
+    ///   3. [writable] Destination account (the treasury).
+    ///   4. []         System program.
+    ///   5. []         Event authority (PDA).
+    ///   6. []         Mixer program.
+    SweepExcess,
+
+    /// Append an encrypted note to a backup vault, creating it on first use;
+    /// see [`backup`].
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Depositor (pays the rent).
+    ///   1. [writable]         Backup vault (PDA of ["backup_vault", vault_id]).
+    ///   2. []                 System program.
+    ///
+    /// Data:
+    ///   - vault_id: [u8; 32]
+    ///   - ciphertext: [u8] (rest of the data, 1 to MAX_BACKUP_CIPHERTEXT_LEN bytes)
+    AppendBackup { vault_id: [u8; 32], ciphertext: Vec<u8> },
+
+    /// Write the pool's state as a borsh [`view::StateView`] to the
+    /// transaction's return data. Changes nothing; meant for CPI callers and
+    /// simulation.
+    ///
+    /// Accounts:
+    ///   0. [] Mixer state account (PDA).
+    GetState,
+
+    /// Create the pool's [`LatestRoot`] account from its current state.
+    /// Anyone may call this, once; `PushRoot` fails until it has been.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Payer (pays the rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Latest root (PDA of ["latest_root"]).
+    ///   3. []                 System program.
+    CreateLatestRoot,
+
+    /// Create the pool's [`PoolStats`] account, with every total at zero.
+    /// Anyone may call this, once; deposits and withdrawals fail until it
+    /// has been.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Payer (pays the rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Pool stats (PDA of ["pool_stats"]).
+    ///   3. []                 System program.
+    CreatePoolStats,
+
+    /// Close a [`WithdrawReceipt`], returning its rent to the recipient it
+    /// names.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Recipient named by the receipt.
+    ///   1. [writable]         Withdraw receipt (PDA of ["withdraw_receipt", nullifier_hash]).
+    ///
+    /// Data:
+    ///   - nullifier_hash: [u8; 32]
+    CloseWithdrawReceipt { nullifier_hash: [u8; 32] },
+
+    /// Close an auxiliary account that is no longer needed, returning its
+    /// rent to the signer; see [`CloseTarget`]. A proof buffer or deposit
+    /// counter is closed by the account it belongs to, which paid its rent,
+    /// and a verifying key by the pool authority. A deposit counter of the
+    /// current epoch, or a verifying key still in use, fails with
+    /// `AccountInUse`.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Buffer authority, depositor or pool authority.
+    ///   1. [writable]         Account to close.
+    ///   2. []                 Mixer state account (PDA); verifying keys only.
+    ///
+    /// Data:
+    ///   - target: u8 (0 proof buffer, 1 deposit counter, 2 verifying key)
+    ///   - buffer_id or vk_id: u64 (proof buffers and verifying keys only)
+    CloseAccount { target: CloseTarget },
+
+    /// Complete a pool migration whose window has passed: the circuit
+    /// version it registered becomes the pool's default verifier, so
+    /// version 0 proofs verify against the new circuit. Anyone may call
+    /// this; see `admin::AdminAction::MigratePool`.
+    ///
+    /// Accounts:
+    ///   0. [writable] Mixer state account (PDA).
+    ///   1. []         Event authority (PDA).
+    ///   2. []         Mixer program.
+    FinalizeMigration,
+
+    /// Grow the state account of a pool created before the state reached
+    /// [`MixerState::LEN`], zero-filling the fields it lacks, so the pool can
+    /// be used again. A pool that predates `authority` and
+    /// `verifier_program` takes the ones given, neither of which may be all
+    /// zeroes; a pool that has them keeps its own. A pool that predates
+    /// `deposit_count` counts every denomination the vault holds, beyond its
+    /// rent and protocol fees, as an outstanding note. Only the mixer
+    /// program's upgrade authority may call this, and the payer tops up the
+    /// rent; a pool that is already current is left as it is.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Payer.
+    ///   1. [writable]         Mixer state account (PDA).
+    ///   2. []                 Mixer vault (PDA).
+    ///   3. []                 System program.
+    ///   4. [signer]           Upgrade authority of the mixer program.
+    ///   5. []                 Mixer program data account.
+    ///
+    /// Data:
+    ///   - authority: [u8; 32]
+    ///   - verifier_program: [u8; 32]
+    ResizeState {
+        authority: Pubkey,
+        verifier_program: Pubkey,
+    },
+
+    /// Record the commit and bytecode hash of the deployed program; see
+    /// [`build_info`].
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (pays rent on first use).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Build info (PDA of ["build_info"]).
+    ///   3. []                 System program.
+    ///   4. []                 Event authority (PDA).
+    ///   5. []                 Mixer program.
+    ///
+    /// Data:
+    ///   - commit: [u8; 20]
+    ///   - build_hash: [u8; 32]
+    SetBuildInfo {
+        commit: [u8; 20],
+        build_hash: [u8; 32],
+    },
+
+    /// Store a Groth16 verifying key for the native verifier backend; see
+    /// [`verifier`]. Keys are immutable once created.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Pool authority (pays rent).
+    ///   1. []                 Mixer state account (PDA).
+    ///   2. [writable]         Verifying key (PDA of ["verifying_key", vk_id]).
+    ///   3. []                 System program.
+    ///   4. []                 Event authority (PDA).
+    ///   5. []                 Mixer program.
+    ///
+    /// Data:
+    ///   - vk_id: u64
+    ///   - vk: Groth16VerifyingKey (borsh)
+    CreateVerifyingKey {
+        vk_id: u64,
+        vk: Box<Groth16VerifyingKey>,
+    },
+
+    /// Grow a proof buffer past the size it can be created with, for proofs
+    /// such as UltraHonk that exceed `ProofBuffer::MAX_PAYLOAD_LEN`.
+    ///
+    /// Accounts:
+    ///   0. [signer, writable] Buffer authority (pays the extra rent).
+    ///   1. [writable]         Proof buffer.
+    ///   2. []                 System program.
+    ///
+    /// Data:
+    ///   - additional_len: u32 (at most MAX_PERMITTED_DATA_INCREASE)
+    ExtendProofBuffer { additional_len: u32 },
+
+    /// Check the pool's default verifier against `MixerState::verifier_hash`.
+    /// Anyone may call this; if the hashes differ the pool is paused. See
+    /// [`verifier::verifier_account_hash`] for what is hashed.
+    ///
+    /// Accounts:
+    ///   0. [writable] Mixer state account (PDA).
+    ///   1. []         Verifying key account (native backend), or the verifier
+    ///      program's program data account (CPI backends).
+    ///   2. []         Event authority (PDA).
+    ///   3. []         Mixer program.
+    VerifyVkBinding,
+
+    /// Self-CPI target used to record events; see [`events`]. Rejected unless
+    /// signed by the event authority PDA, so it can only be reached from
+    /// within this program.
+    ///
+    /// Accounts:
+    ///   0. [signer] Event authority (PDA).
+    ///
+    /// Data:
+    ///   - EVENT_IX_TAG_LE || discriminator || borsh(event)
+    EmitEvent,
+}
+
+/// Marks the first byte of instruction data as a format version, as
+/// `VERSION_FLAG | version`. No version 0 tag has the bit set.
+pub const VERSION_FLAG: u8 = 0x80;
+
+/// Instruction format the builders in this crate emit: `VERSION_FLAG | 2`,
+/// then the tag and payload. Version 1 has the payloads of version 0, and
+/// version 2 adds a `tree_id` to the withdraw variants, after their
+/// `circuit_version`.
+///
+/// Version 0 data is unprefixed, with the payloads of version 1, and is
+/// still accepted. It is not what clients sent before versioning: the
+/// payloads had grown by then, and a `Withdraw` without `circuit_version`
+/// and `relayer_fee` is misread and fails. A change to a payload now gets a
+/// new version, decoded next to the old ones until clients have moved.
+pub const INSTRUCTION_VERSION: u8 = 2;
+
+/// Versioned data for the instruction with `tag`, with room for a payload of
+/// `payload_len` bytes.
+pub fn instruction_data(tag: u8, payload_len: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(2 + payload_len);
+    data.push(VERSION_FLAG | INSTRUCTION_VERSION);
+    data.push(tag);
+    data
+}
+
+impl MixerInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, MixerError> {
+        if input.starts_with(&events::EVENT_IX_TAG_LE) {
+            return Ok(MixerInstruction::EmitEvent);
+        }
+        let (version, tagged) = match input.split_first() {
+            Some((&first, rest)) if first & VERSION_FLAG != 0 => match first & !VERSION_FLAG {
+                version @ 1..=INSTRUCTION_VERSION => (version, rest),
+                _ => return Err(MixerError::InvalidInstruction),
+            },
+            // Version 0.
+            _ => (0, input),
+        };
+        // Version 2 inserts the withdraw variants' tree id after the tag and
+        // their root, nullifier hash, recipient field and circuit version.
+        const TREE_ID_AT: usize = 1 + 32 * 3 + 2;
+        match tagged.first() {
+            Some(2 | 5..=8) if version >= 2 => {
+                let tree_id = *tagged.get(TREE_ID_AT).ok_or(MixerError::InvalidInstruction)?;
+                let mut tagged = tagged.to_vec();
+                tagged.remove(TREE_ID_AT);
+                Self::unpack_tagged(&tagged, Some(tree_id))
+            }
+            _ => Self::unpack_tagged(tagged, None),
+        }
+    }
+
+    /// Decode `tag || payload` in the layout of versions 0 and 1, with the
+    /// `tree_id` of a version 2 withdraw variant.
+    fn unpack_tagged(input: &[u8], tree_id: Option<u8>) -> Result<Self, MixerError> {
+        let (tag, rest) = input.split_first().ok_or(MixerError::InvalidInstruction)?;
+        Ok(match tag {
+            0 => {
+                if ![8, 9, 17, 18, 19].contains(&rest.len()) {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let denomination = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+                let flags = rest.get(8).copied().unwrap_or(0);
+                let admin_delay = rest
+                    .get(9..17)
+                    .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+                    .unwrap_or(0);
+                let curve = rest.get(17).map_or(Ok(Curve::Bn254), |c| Curve::try_from(*c))?;
+                let tree_depth = rest.get(18).copied().unwrap_or(MixerState::DEFAULT_TREE_DEPTH);
+                MixerInstruction::Initialize {
+                    denomination,
+                    flags,
+                    admin_delay,
+                    curve,
+                    tree_depth,
+                }
+            }
+            1 => {
+                let receipt = match rest.len() {
+                    32 | 40 => false,
+                    41 if rest[40] == 1 => true,
+                    _ => return Err(MixerError::InvalidInstruction),
+                };
+                let mut root = [0u8; 32];
+                root.copy_from_slice(&rest[..32]);
+                let leaf_count = rest
+                    .get(32..40)
+                    .map(|b| u64::from_le_bytes(b.try_into().unwrap()));
+                MixerInstruction::PushRoot {
+                    new_root: root,
+                    leaf_count,
+                    receipt,
+                }
+            }
+            2 => {
+                if rest.len() < 32 + 32 + 32 + 2 + 8 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let mut root = [0u8; 32];
+                root.copy_from_slice(&rest[0..32]);
+                let mut nullifier_hash = [0u8; 32];
+                nullifier_hash.copy_from_slice(&rest[32..64]);
+                let mut recipient_field = [0u8; 32];
+                recipient_field.copy_from_slice(&rest[64..96]);
+                let circuit_version = u16::from_le_bytes(rest[96..98].try_into().unwrap());
+                let relayer_fee = u64::from_le_bytes(rest[98..106].try_into().unwrap());
+                let proof = rest[106..].to_vec();
+                MixerInstruction::Withdraw {
+                    root,
+                    nullifier_hash,
+                    recipient_field,
+                    circuit_version,
+                    tree_id,
+                    relayer_fee,
+                    proof,
+                }
+            }
+            3 => {
+                if rest.len() != 8 + 4 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let buffer_id = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+                let payload_len = u32::from_le_bytes(rest[8..12].try_into().unwrap());
+                MixerInstruction::CreateProofBuffer {
+                    buffer_id,
+                    payload_len,
+                }
+            }
+            4 => {
+                if rest.len() < 4 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let offset = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+                let chunk = rest[4..].to_vec();
+                MixerInstruction::WriteProofChunk { offset, chunk }
+            }
+            5 => {
+                if rest.len() != 32 + 32 + 32 + 2 + 8 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let mut root = [0u8; 32];
+                root.copy_from_slice(&rest[0..32]);
+                let mut nullifier_hash = [0u8; 32];
+                nullifier_hash.copy_from_slice(&rest[32..64]);
+                let mut recipient_field = [0u8; 32];
+                recipient_field.copy_from_slice(&rest[64..96]);
+                let circuit_version = u16::from_le_bytes(rest[96..98].try_into().unwrap());
+                let relayer_fee = u64::from_le_bytes(rest[98..106].try_into().unwrap());
+                MixerInstruction::WithdrawFromBuffer {
+                    root,
+                    nullifier_hash,
+                    recipient_field,
+                    circuit_version,
+                    tree_id,
+                    relayer_fee,
+                }
+            }
+            6 => {
+                if rest.len() < 32 + 32 + 32 + 2 + 8 + 4 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let mut root = [0u8; 32];
+                root.copy_from_slice(&rest[0..32]);
+                let mut nullifier_hash = [0u8; 32];
+                nullifier_hash.copy_from_slice(&rest[32..64]);
+                let mut recipient_field = [0u8; 32];
+                recipient_field.copy_from_slice(&rest[64..96]);
+                let circuit_version = u16::from_le_bytes(rest[96..98].try_into().unwrap());
+                let relayer_fee = u64::from_le_bytes(rest[98..106].try_into().unwrap());
+                let proof_len = u32::from_le_bytes(rest[106..110].try_into().unwrap()) as usize;
+                let proof_end = 110usize
+                    .checked_add(proof_len)
+                    .filter(|end| *end <= rest.len())
+                    .ok_or(MixerError::InvalidInstruction)?;
+                let proof = rest[110..proof_end].to_vec();
+                let call_data = rest[proof_end..].to_vec();
+                MixerInstruction::WithdrawAndCall {
+                    root,
+                    nullifier_hash,
+                    recipient_field,
+                    circuit_version,
+                    tree_id,
+                    relayer_fee,
+                    proof,
+                    call_data,
+                }
+            }
+            7 => {
+                if rest.len() < 32 + 32 + 32 + 2 + 8 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let mut root = [0u8; 32];
+                root.copy_from_slice(&rest[0..32]);
+                let mut nullifier_hash = [0u8; 32];
+                nullifier_hash.copy_from_slice(&rest[32..64]);
+                let mut recipient_field = [0u8; 32];
+                recipient_field.copy_from_slice(&rest[64..96]);
+                let circuit_version = u16::from_le_bytes(rest[96..98].try_into().unwrap());
+                let relayer_fee = u64::from_le_bytes(rest[98..106].try_into().unwrap());
+                let proof = rest[106..].to_vec();
+                MixerInstruction::WithdrawToStake {
+                    root,
+                    nullifier_hash,
+                    recipient_field,
+                    circuit_version,
+                    tree_id,
+                    relayer_fee,
+                    proof,
+                }
+            }
+            8 => {
+                if rest.len() != 32 + 32 + 32 + 2 + 8 + 8 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let mut root = [0u8; 32];
+                root.copy_from_slice(&rest[0..32]);
+                let mut nullifier_hash = [0u8; 32];
+                nullifier_hash.copy_from_slice(&rest[32..64]);
+                let mut recipient_field = [0u8; 32];
+                recipient_field.copy_from_slice(&rest[64..96]);
+                let circuit_version = u16::from_le_bytes(rest[96..98].try_into().unwrap());
+                let earliest_slot = u64::from_le_bytes(rest[98..106].try_into().unwrap());
+                let bounty = u64::from_le_bytes(rest[106..114].try_into().unwrap());
+                MixerInstruction::ScheduleWithdraw {
+                    root,
+                    nullifier_hash,
+                    recipient_field,
+                    circuit_version,
+                    tree_id,
+                    earliest_slot,
+                    bounty,
+                }
+            }
+            9 => MixerInstruction::ExecuteScheduledWithdraw,
+            10 => MixerInstruction::CancelScheduledWithdraw,
+            11..=13 => {
+                if rest.len() < 8 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let action_id = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+                match tag {
+                    11 => MixerInstruction::QueueAdminAction {
+                        action_id,
+                        action: admin::AdminAction::try_from_slice(&rest[8..])
+                            .map_err(|_| MixerError::InvalidInstruction)?,
+                    },
+                    _ if rest.len() != 8 => return Err(MixerError::InvalidInstruction),
+                    12 => MixerInstruction::ExecuteAdminAction { action_id },
+                    _ => MixerInstruction::CancelAdminAction { action_id },
+                }
+            }
+            14 => match rest {
+                [0] => MixerInstruction::SetPaused { paused: false },
+                [1] => MixerInstruction::SetPaused { paused: true },
+                _ => return Err(MixerError::InvalidInstruction),
+            },
+            15 => {
+                if rest.len() != 32 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let mut recovery = [0u8; 32];
+                recovery.copy_from_slice(rest);
+                MixerInstruction::QueueEmergencyDrain { recovery }
+            }
+            16 => MixerInstruction::ExecuteEmergencyDrain,
+            17 => MixerInstruction::CancelEmergencyDrain,
+            18 => MixerInstruction::CollectProtocolFees,
+            19 => {
+                if rest.len() != 52 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                MixerInstruction::SetBuildInfo {
+                    commit: rest[..20].try_into().unwrap(),
+                    build_hash: rest[20..].try_into().unwrap(),
+                }
+            }
+            20 => {
+                if rest.len() != 8 + Groth16VerifyingKey::LEN {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                let (vk_id, mut vk) = rest.split_at(8);
+                MixerInstruction::CreateVerifyingKey {
+                    vk_id: u64::from_le_bytes(vk_id.try_into().unwrap()),
+                    vk: Box::new(
+                        Groth16VerifyingKey::deserialize(&mut vk)
+                            .map_err(|_| MixerError::InvalidInstruction)?,
+                    ),
+                }
+            }
+            21 => {
+                if rest.len() != 4 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                MixerInstruction::ExtendProofBuffer {
+                    additional_len: u32::from_le_bytes(rest.try_into().unwrap()),
+                }
+            }
+            22 => MixerInstruction::VerifyVkBinding,
+            23 => MixerInstruction::SweepExcess,
+            24 => {
+                if !(32 + 1..=32 + backup::MAX_BACKUP_CIPHERTEXT_LEN).contains(&rest.len()) {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                MixerInstruction::AppendBackup {
+                    vault_id: rest[..32].try_into().unwrap(),
+                    ciphertext: rest[32..].to_vec(),
+                }
+            }
+            25 => MixerInstruction::GetState,
+            26 => MixerInstruction::CreateLatestRoot,
+            27 => MixerInstruction::CreatePoolStats,
+            28 => {
+                if rest.len() != 32 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                MixerInstruction::CloseWithdrawReceipt {
+                    nullifier_hash: rest.try_into().unwrap(),
+                }
+            }
+            29 => {
+                let id = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap());
+                let target = match rest {
+                    [0, buffer_id @ ..] if buffer_id.len() == 8 => {
+                        CloseTarget::ProofBuffer { buffer_id: id(buffer_id) }
+                    }
+                    [1] => CloseTarget::DepositCounter,
+                    [2, vk_id @ ..] if vk_id.len() == 8 => {
+                        CloseTarget::VerifyingKey { vk_id: id(vk_id) }
+                    }
+                    _ => return Err(MixerError::InvalidInstruction),
+                };
+                MixerInstruction::CloseAccount { target }
+            }
+            30 => MixerInstruction::FinalizeMigration,
+            31 => {
+                if rest.len() != 64 {
+                    return Err(MixerError::InvalidInstruction);
+                }
+                MixerInstruction::ResizeState {
+                    authority: Pubkey::new_from_array(rest[..32].try_into().unwrap()),
+                    verifier_program: Pubkey::new_from_array(rest[32..].try_into().unwrap()),
+                }
+            }
+            _ => return Err(MixerError::InvalidInstruction),
+        })
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let instruction = MixerInstruction::unpack(instruction_data).map_err(ProgramError::from)?;
+
+    match instruction {
+        MixerInstruction::Initialize {
+            denomination,
+            flags,
+            admin_delay,
+            curve,
+            tree_depth,
+        } => process_initialize(
+            program_id,
+            accounts,
+            denomination,
+            flags,
+            admin_delay,
+            curve,
+            tree_depth,
+        ),
+        MixerInstruction::PushRoot {
+            new_root,
+            leaf_count,
+            receipt,
+        } => process_push_root(program_id, accounts, new_root, leaf_count, receipt),
+        MixerInstruction::Withdraw {
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+        } => {
+            let (withdraw_accounts, rest) = split_withdraw_accounts(accounts, tree_id)?;
+            process_withdraw(
+                program_id,
+                withdraw_accounts,
+                None,
+                rest.first(),
+                root,
+                nullifier_hash,
+                recipient_field,
+                circuit_version,
+                tree_id,
+                relayer_fee,
+                proof,
+            )
+        }
+        MixerInstruction::CreateProofBuffer {
+            buffer_id,
+            payload_len,
+        } => process_create_proof_buffer(program_id, accounts, buffer_id, payload_len),
+        MixerInstruction::WriteProofChunk { offset, chunk } => {
+            process_write_proof_chunk(program_id, accounts, offset, chunk)
+        }
+        MixerInstruction::WithdrawFromBuffer {
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+        } => process_withdraw_from_buffer(
+            program_id,
+            accounts,
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+        ),
+        MixerInstruction::WithdrawAndCall {
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+            call_data,
+        } => process_withdraw_and_call(
+            program_id,
+            accounts,
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+            call_data,
+        ),
+        MixerInstruction::WithdrawToStake {
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+        } => process_withdraw_to_stake(
+            program_id,
+            accounts,
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            relayer_fee,
+            proof,
+        ),
+        MixerInstruction::ScheduleWithdraw {
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            earliest_slot,
+            bounty,
+        } => process_schedule_withdraw(
+            program_id,
+            accounts,
+            root,
+            nullifier_hash,
+            recipient_field,
+            circuit_version,
+            tree_id,
+            earliest_slot,
+            bounty,
+        ),
+        MixerInstruction::ExecuteScheduledWithdraw => {
+            process_execute_scheduled_withdraw(program_id, accounts)
+        }
+        MixerInstruction::CancelScheduledWithdraw => {
+            process_cancel_scheduled_withdraw(program_id, accounts)
+        }
+        MixerInstruction::QueueAdminAction { action_id, action } => {
+            admin::process_queue_admin_action(program_id, accounts, action_id, action)
+        }
+        MixerInstruction::ExecuteAdminAction { action_id } => {
+            admin::process_execute_admin_action(program_id, accounts, action_id)
+        }
+        MixerInstruction::CancelAdminAction { action_id } => {
+            admin::process_cancel_admin_action(program_id, accounts, action_id)
+        }
+        MixerInstruction::SetPaused { paused } => {
+            admin::process_set_paused(program_id, accounts, paused)
+        }
+        MixerInstruction::QueueEmergencyDrain { recovery } => {
+            admin::process_queue_emergency_drain(program_id, accounts, recovery)
+        }
+        MixerInstruction::ExecuteEmergencyDrain => {
+            admin::process_execute_emergency_drain(program_id, accounts)
+        }
+        MixerInstruction::CancelEmergencyDrain => {
+            admin::process_cancel_emergency_drain(program_id, accounts)
+        }
+        MixerInstruction::CollectProtocolFees => {
+            admin::process_collect_protocol_fees(program_id, accounts)
+        }
+        MixerInstruction::SweepExcess => admin::process_sweep_excess(program_id, accounts),
+        MixerInstruction::AppendBackup { vault_id, ciphertext } => {
+            backup::process_append_backup(program_id, accounts, vault_id, ciphertext)
+        }
+        MixerInstruction::GetState => view::process_get_state(program_id, accounts),
+        MixerInstruction::CreateLatestRoot => process_create_latest_root(program_id, accounts),
+        MixerInstruction::CreatePoolStats => process_create_pool_stats(program_id, accounts),
+        MixerInstruction::CloseWithdrawReceipt { nullifier_hash } => {
+            process_close_withdraw_receipt(program_id, accounts, nullifier_hash)
+        }
+        MixerInstruction::CloseAccount { target } => {
+            process_close_account(program_id, accounts, target)
+        }
+        MixerInstruction::FinalizeMigration => {
+            admin::process_finalize_migration(program_id, accounts)
+        }
+        MixerInstruction::ResizeState {
+            authority,
+            verifier_program,
+        } => process_resize_state(program_id, accounts, authority, verifier_program),
+        MixerInstruction::SetBuildInfo { commit, build_hash } => {
+            build_info::process_set_build_info(program_id, accounts, commit, build_hash)
+        }
+        MixerInstruction::CreateVerifyingKey { vk_id, vk } => {
+            verifier::process_create_verifying_key(program_id, accounts, vk_id, vk)
+        }
+        MixerInstruction::ExtendProofBuffer { additional_len } => {
+            process_extend_proof_buffer(program_id, accounts, additional_len)
+        }
+        MixerInstruction::VerifyVkBinding => {
+            verifier::process_verify_vk_binding(program_id, accounts)
+        }
+        MixerInstruction::EmitEvent => process_emit_event(program_id, accounts),
+    }
+}
+
+fn process_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    denomination: u64,
+    flags: u8,
+    admin_delay: u64,
+    curve: Curve,
+    tree_depth: u8,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let verifier_program = next_account_info(account_info_iter)?;
+
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if verifier_program.key == &Pubkey::default() {
+        msg!("Pool needs a verifier program");
+        return Err(MixerError::InvalidVerifier.into());
+    }
+    if !(1..=MixerState::MAX_TREE_DEPTH).contains(&tree_depth) {
+        msg!("Tree depth must be between 1 and {}", MixerState::MAX_TREE_DEPTH);
+        return Err(MixerError::ParameterOutOfBounds.into());
+    }
+    if state_account.owner == program_id {
+        msg!("Mixer state is already initialized; change it through admin actions");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let rent = Rent::get()?;
+    let required_lamports = rent.minimum_balance(MixerState::LEN);
 
     if state_account.lamports() == 0 {
         msg!("Creating mixer state account");
-        
+
         // Verify this is the correct PDA
         let (expected_pda, bump) = Pubkey::find_program_address(&[b"mixer_state"], program_id);
         if state_account.key != &expected_pda {
             return Err(ProgramError::InvalidArgument);
         }
-        
-        // Create account with PDA signing
-        // The payer funds the account, but the program signs for the PDA
+
+        // Create account with PDA signing
+        // The payer funds the account, but the program signs for the PDA
+        let create_ix = system_instruction::create_account(
+            payer.key,
+            state_account.key,
+            required_lamports,
+            MixerState::LEN as u64,
+            program_id,
+        );
+
+        // Sign with PDA seeds - this allows the program to create the PDA account
+        let seeds: &[&[u8]] = &[b"mixer_state", &[bump]];
+        invoke_signed(
+            &create_ix,
+            &[payer.clone(), state_account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+    }
+
+    // Create the mixer vault PDA (system-owned, 0 data) if it doesn't exist yet.
+    // This vault holds the fixed-denomination lamports and will be PDA-signed for withdrawals.
+    //
+    // Accounts are not passed explicitly for the vault; we derive and expect the client to pass it
+    // to Withdraw. For Initialize, we lazily create it here without storing it in state.
+    {
+        let (vault_pda, vault_bump) =
+            Pubkey::find_program_address(&[b"mixer_vault"], program_id);
+        // The vault account will be created by the client via a system transfer if it already exists.
+        // If it does not exist, we create it here in a rent-exempt manner.
+        // NOTE: We cannot access the vault AccountInfo here because it's not in the accounts list.
+        // So we only log its expected address for clients.
+        msg!("Expected mixer vault PDA: {}", vault_pda);
+        msg!("Mixer vault bump: {}", vault_bump);
+    }
+
+    // Initialize state
+    store_state(
+        state_account,
+        &MixerState {
+            denomination,
+            roots: [[0u8; 32]; MixerState::ROOT_HISTORY_SIZE],
+            current_root_index: 0,
+            flags,
+            authority: *payer.key,
+            verifier_program: *verifier_program.key,
+            admin_delay,
+            guardian: Pubkey::default(),
+            paused: false,
+            protocol_fee_bps: 0,
+            relayer_fee_cap_bps: 0,
+            min_withdraw_delay: 0,
+            protocol_fees: 0,
+            root_times: [0; MixerState::ROOT_HISTORY_SIZE],
+            verifier_backend: VerifierBackend::SunspotCpi as u8,
+            circuit_versions: [CircuitVersion::default(); MAX_CIRCUIT_VERSIONS],
+            verifier_hash: [0u8; 32],
+            curve: curve as u8,
+            tree_depth,
+            leaf_count: 0,
+            max_deposits_per_epoch: 0,
+            max_deposits_per_depositor: 0,
+            deposit_epoch: 0,
+            epoch_deposits: 0,
+            max_vault_lamports: 0,
+            deposit_count: 0,
+            withdraw_count: 0,
+            receipt_tree: Pubkey::default(),
+            migration_version: 0,
+            migration_end_slot: 0,
+            tree_id: 0,
+            root_slots: [0; MixerState::ROOT_HISTORY_SIZE],
+        },
+    )
+}
+
+/// Load the pool state. The account must be the `["mixer_state"]` PDA and
+/// owned by the program; any other account, including the program's own
+/// proof buffers, could carry forged roots, authority or verifier.
+pub fn load_state(
+    program_id: &Pubkey,
+    state_account: &AccountInfo,
+) -> Result<MixerState, ProgramError> {
+    let (expected_state, _) = Pubkey::find_program_address(&[b"mixer_state"], program_id);
+    if state_account.key != &expected_state {
+        msg!("Invalid mixer state PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if state_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if state_account.data_len() < MixerState::LEN {
+        msg!("Mixer state predates this program; send ResizeState first");
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    unpack_state(&state_account.data.borrow())
+}
+
+/// Decode mixer state account data, e.g. as fetched over RPC. The state of a
+/// pool created before it grew to [`MixerState::LEN`] decodes with every
+/// later field zeroed, except for a tree of the default depth.
+pub fn unpack_state(data: &[u8]) -> Result<MixerState, ProgramError> {
+    if data.len() < MixerState::BASELINE_LEN {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    let has_tree_depth = data.len() > MixerState::TREE_DEPTH_OFFSET;
+    let mut padded = Vec::new();
+    let data = if data.len() < MixerState::LEN {
+        padded.resize(MixerState::LEN, 0);
+        padded[..data.len()].copy_from_slice(data);
+        &padded[..]
+    } else {
+        data
+    };
+    let mut roots = [[0u8; 32]; MixerState::ROOT_HISTORY_SIZE];
+    let denomination = u64::from_le_bytes(data[0..8].try_into().unwrap());
+    for (i, root) in roots.iter_mut().enumerate() {
+        let start = MixerState::ROOTS_OFFSET + i * 32;
+        root.copy_from_slice(&data[start..start + 32]);
+    }
+    let key_at = |start: usize| -> Pubkey {
+        Pubkey::new_from_array(data[start..start + 32].try_into().unwrap())
+    };
+    let mut root_times = [0i64; MixerState::ROOT_HISTORY_SIZE];
+    for (i, time) in root_times.iter_mut().enumerate() {
+        let start = MixerState::ROOT_TIMES_OFFSET + i * 8;
+        *time = i64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+    }
+    let mut root_slots = [0u64; MixerState::ROOT_HISTORY_SIZE];
+    for (i, slot) in root_slots.iter_mut().enumerate() {
+        let start = MixerState::ROOT_SLOTS_OFFSET + i * 8;
+        *slot = u64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+    }
+    let mut circuit_versions = [CircuitVersion::default(); MAX_CIRCUIT_VERSIONS];
+    for (i, entry) in circuit_versions.iter_mut().enumerate() {
+        let start = MixerState::CIRCUIT_VERSIONS_OFFSET + i * CircuitVersion::LEN;
+        *entry = CircuitVersion::unpack(&data[start..start + CircuitVersion::LEN]);
+    }
+    let u16_at = |start: usize| u16::from_le_bytes(data[start..start + 2].try_into().unwrap());
+    let u32_at = |start: usize| u32::from_le_bytes(data[start..start + 4].try_into().unwrap());
+    let u64_at = |start: usize| u64::from_le_bytes(data[start..start + 8].try_into().unwrap());
+    Ok(MixerState {
+        denomination,
+        roots,
+        current_root_index: data[MixerState::CURRENT_ROOT_INDEX_OFFSET],
+        flags: data[MixerState::FLAGS_OFFSET],
+        authority: key_at(MixerState::AUTHORITY_OFFSET),
+        verifier_program: key_at(MixerState::VERIFIER_OFFSET),
+        admin_delay: u64_at(MixerState::ADMIN_DELAY_OFFSET),
+        guardian: key_at(MixerState::GUARDIAN_OFFSET),
+        paused: data[MixerState::PAUSED_OFFSET] != 0,
+        protocol_fee_bps: u16_at(MixerState::PROTOCOL_FEE_BPS_OFFSET),
+        relayer_fee_cap_bps: u16_at(MixerState::RELAYER_FEE_CAP_BPS_OFFSET),
+        min_withdraw_delay: u64_at(MixerState::MIN_WITHDRAW_DELAY_OFFSET),
+        protocol_fees: u64_at(MixerState::PROTOCOL_FEES_OFFSET),
+        root_times,
+        verifier_backend: data[MixerState::VERIFIER_BACKEND_OFFSET],
+        circuit_versions,
+        verifier_hash: data[MixerState::VERIFIER_HASH_OFFSET..MixerState::VERIFIER_HASH_OFFSET + 32]
+            .try_into()
+            .unwrap(),
+        curve: data[MixerState::CURVE_OFFSET],
+        tree_depth: if has_tree_depth {
+            data[MixerState::TREE_DEPTH_OFFSET]
+        } else {
+            MixerState::DEFAULT_TREE_DEPTH
+        },
+        leaf_count: u64_at(MixerState::LEAF_COUNT_OFFSET),
+        max_deposits_per_epoch: u32_at(MixerState::MAX_DEPOSITS_PER_EPOCH_OFFSET),
+        max_deposits_per_depositor: u32_at(MixerState::MAX_DEPOSITS_PER_DEPOSITOR_OFFSET),
+        deposit_epoch: u64_at(MixerState::DEPOSIT_EPOCH_OFFSET),
+        epoch_deposits: u32_at(MixerState::EPOCH_DEPOSITS_OFFSET),
+        max_vault_lamports: u64_at(MixerState::MAX_VAULT_LAMPORTS_OFFSET),
+        deposit_count: u64_at(MixerState::DEPOSIT_COUNT_OFFSET),
+        withdraw_count: u64_at(MixerState::WITHDRAW_COUNT_OFFSET),
+        receipt_tree: key_at(MixerState::RECEIPT_TREE_OFFSET),
+        migration_version: u16_at(MixerState::MIGRATION_VERSION_OFFSET),
+        migration_end_slot: u64_at(MixerState::MIGRATION_END_SLOT_OFFSET),
+        tree_id: data[MixerState::TREE_ID_OFFSET],
+        root_slots,
+    })
+}
+
+/// Write `state` back to the pool's state account.
+pub fn store_state(
+    state_account: &AccountInfo,
+    state: &MixerState,
+) -> Result<(), ProgramError> {
+    pack_state(state, &mut state_account.data.borrow_mut())
+}
+
+/// Encode `state` into the start of `data`, as `unpack_state` decodes it.
+pub fn pack_state(state: &MixerState, data: &mut [u8]) -> Result<(), ProgramError> {
+    if data.len() < MixerState::LEN {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    data[0..8].copy_from_slice(&state.denomination.to_le_bytes());
+    for i in 0..MixerState::ROOT_HISTORY_SIZE {
+        let start = MixerState::ROOTS_OFFSET + i * 32;
+        data[start..start + 32].copy_from_slice(&state.roots[i]);
+    }
+    data[MixerState::CURRENT_ROOT_INDEX_OFFSET] = state.current_root_index;
+    data[MixerState::FLAGS_OFFSET] = state.flags;
+    data[MixerState::AUTHORITY_OFFSET..MixerState::AUTHORITY_OFFSET + 32]
+        .copy_from_slice(state.authority.as_ref());
+    data[MixerState::VERIFIER_OFFSET..MixerState::VERIFIER_OFFSET + 32]
+        .copy_from_slice(state.verifier_program.as_ref());
+    data[MixerState::ADMIN_DELAY_OFFSET..MixerState::ADMIN_DELAY_OFFSET + 8]
+        .copy_from_slice(&state.admin_delay.to_le_bytes());
+    data[MixerState::GUARDIAN_OFFSET..MixerState::GUARDIAN_OFFSET + 32]
+        .copy_from_slice(state.guardian.as_ref());
+    data[MixerState::PAUSED_OFFSET] = state.paused as u8;
+    data[MixerState::PROTOCOL_FEE_BPS_OFFSET..MixerState::PROTOCOL_FEE_BPS_OFFSET + 2]
+        .copy_from_slice(&state.protocol_fee_bps.to_le_bytes());
+    data[MixerState::RELAYER_FEE_CAP_BPS_OFFSET..MixerState::RELAYER_FEE_CAP_BPS_OFFSET + 2]
+        .copy_from_slice(&state.relayer_fee_cap_bps.to_le_bytes());
+    data[MixerState::MIN_WITHDRAW_DELAY_OFFSET..MixerState::MIN_WITHDRAW_DELAY_OFFSET + 8]
+        .copy_from_slice(&state.min_withdraw_delay.to_le_bytes());
+    data[MixerState::PROTOCOL_FEES_OFFSET..MixerState::PROTOCOL_FEES_OFFSET + 8]
+        .copy_from_slice(&state.protocol_fees.to_le_bytes());
+    for (i, time) in state.root_times.iter().enumerate() {
+        let start = MixerState::ROOT_TIMES_OFFSET + i * 8;
+        data[start..start + 8].copy_from_slice(&time.to_le_bytes());
+    }
+    data[MixerState::VERIFIER_BACKEND_OFFSET] = state.verifier_backend;
+    for (i, entry) in state.circuit_versions.iter().enumerate() {
+        let start = MixerState::CIRCUIT_VERSIONS_OFFSET + i * CircuitVersion::LEN;
+        entry.pack(&mut data[start..start + CircuitVersion::LEN]);
+    }
+    data[MixerState::VERIFIER_HASH_OFFSET..MixerState::VERIFIER_HASH_OFFSET + 32]
+        .copy_from_slice(&state.verifier_hash);
+    data[MixerState::CURVE_OFFSET] = state.curve;
+    data[MixerState::TREE_DEPTH_OFFSET] = state.tree_depth;
+    data[MixerState::LEAF_COUNT_OFFSET..MixerState::LEAF_COUNT_OFFSET + 8]
+        .copy_from_slice(&state.leaf_count.to_le_bytes());
+    data[MixerState::MAX_DEPOSITS_PER_EPOCH_OFFSET..MixerState::MAX_DEPOSITS_PER_EPOCH_OFFSET + 4]
+        .copy_from_slice(&state.max_deposits_per_epoch.to_le_bytes());
+    data[MixerState::MAX_DEPOSITS_PER_DEPOSITOR_OFFSET
+        ..MixerState::MAX_DEPOSITS_PER_DEPOSITOR_OFFSET + 4]
+        .copy_from_slice(&state.max_deposits_per_depositor.to_le_bytes());
+    data[MixerState::DEPOSIT_EPOCH_OFFSET..MixerState::DEPOSIT_EPOCH_OFFSET + 8]
+        .copy_from_slice(&state.deposit_epoch.to_le_bytes());
+    data[MixerState::EPOCH_DEPOSITS_OFFSET..MixerState::EPOCH_DEPOSITS_OFFSET + 4]
+        .copy_from_slice(&state.epoch_deposits.to_le_bytes());
+    data[MixerState::MAX_VAULT_LAMPORTS_OFFSET..MixerState::MAX_VAULT_LAMPORTS_OFFSET + 8]
+        .copy_from_slice(&state.max_vault_lamports.to_le_bytes());
+    data[MixerState::DEPOSIT_COUNT_OFFSET..MixerState::DEPOSIT_COUNT_OFFSET + 8]
+        .copy_from_slice(&state.deposit_count.to_le_bytes());
+    data[MixerState::WITHDRAW_COUNT_OFFSET..MixerState::WITHDRAW_COUNT_OFFSET + 8]
+        .copy_from_slice(&state.withdraw_count.to_le_bytes());
+    data[MixerState::RECEIPT_TREE_OFFSET..MixerState::RECEIPT_TREE_OFFSET + 32]
+        .copy_from_slice(state.receipt_tree.as_ref());
+    data[MixerState::MIGRATION_VERSION_OFFSET..MixerState::MIGRATION_VERSION_OFFSET + 2]
+        .copy_from_slice(&state.migration_version.to_le_bytes());
+    data[MixerState::MIGRATION_END_SLOT_OFFSET..MixerState::MIGRATION_END_SLOT_OFFSET + 8]
+        .copy_from_slice(&state.migration_end_slot.to_le_bytes());
+    data[MixerState::TREE_ID_OFFSET] = state.tree_id;
+    for (i, slot) in state.root_slots.iter().enumerate() {
+        let start = MixerState::ROOT_SLOTS_OFFSET + i * 8;
+        data[start..start + 8].copy_from_slice(&slot.to_le_bytes());
+    }
+    Ok(())
+}
+
+fn process_push_root(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_root: [u8; 32],
+    leaf_count: Option<u64>,
+    receipt: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let latest_root_account = next_account_info(account_info_iter)?;
+    let pool_stats = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if system_program.key != &solana_system_interface::program::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut state = load_state(program_id, state_account)?;
+    state.check_not_paused()?;
+    if latest_root_account.key != &LatestRoot::find_address(program_id).0 {
+        msg!("Invalid latest root PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if latest_root_account.owner != program_id {
+        msg!("Latest root account missing; send CreateLatestRoot first");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let (expected_vault, _) = Pubkey::find_program_address(&[b"mixer_vault"], program_id);
+    if vault_account.key != &expected_vault {
+        msg!("Invalid mixer vault PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    // Every push pays for exactly one deposit, so the program counts the
+    // leaves itself and a reported count only has to agree. A full tree
+    // takes no more leaves, so the push starts the next one.
+    let rotate = state.leaf_count >= state.tree_capacity();
+    if rotate && state.tree_id == u8::MAX {
+        msg!("All {} trees of the pool are full", u8::MAX as u16 + 1);
+        return Err(MixerError::ParameterOutOfBounds.into());
+    }
+    let next_leaf_count = if rotate { 1 } else { state.leaf_count + 1 };
+    if leaf_count.is_some_and(|leaf_count| leaf_count != next_leaf_count) {
+        msg!("PushRoot adds one leaf, for a leaf count of {}", next_leaf_count);
+        return Err(MixerError::ParameterOutOfBounds.into());
+    }
+    invoke(
+        &system_instruction::transfer(authority.key, vault_account.key, state.denomination),
+        &[authority.clone(), vault_account.clone(), system_program.clone()],
+    )?;
+    if state.max_vault_lamports != 0 && vault_account.lamports() > state.max_vault_lamports {
+        msg!("Vault is capped at {} lamports", state.max_vault_lamports);
+        return Err(MixerError::VaultCapExceeded.into());
+    }
+    let clock = Clock::get()?;
+    state.count_deposit(clock.epoch).inspect_err(|_| {
+        msg!("Pool accepts {} deposits per epoch", state.max_deposits_per_epoch)
+    })?;
+    if state.max_deposits_per_depositor != 0 {
+        let counter_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        count_depositor_deposit(
+            program_id,
+            authority,
+            counter_account,
+            system_program,
+            clock.epoch,
+            state.max_deposits_per_depositor,
+        )?;
+    }
+    if rotate {
+        let tree_account = next_account_info(account_info_iter)?;
+        let system_program = next_account_info(account_info_iter)?;
+        let archived =
+            archive_tree(program_id, &mut state, authority, tree_account, system_program)?;
+        emit_cpi(
+            program_id,
+            event_authority,
+            program,
+            &events::TreeRotated {
+                tree_id: archived.tree_id,
+                root: archived.roots[archived.current_root_index as usize],
+            },
+        )?;
+    }
+    state.leaf_count = next_leaf_count;
+    state.deposit_count = state.deposit_count.saturating_add(1);
+    state.push_root(new_root, clock.unix_timestamp, clock.slot);
+    store_state(state_account, &state)?;
+    LatestRoot {
+        root: new_root,
+        leaf_count: state.leaf_count,
+        slot: clock.slot,
+    }
+    .pack(&mut latest_root_account.try_borrow_mut_data()?);
+    PoolStats::update(program_id, pool_stats, |stats| {
+        stats.deposits = stats.deposits.saturating_add(1);
+        stats.deposit_volume = stats.deposit_volume.saturating_add(state.denomination);
+        stats.last_activity_slot = clock.slot;
+    })?;
+    if receipt {
+        let leaf_index = state.total_leaves() - 1;
+        receipt::mint_receipt(
+            program_id,
+            &state,
+            state_account,
+            authority,
+            leaf_index,
+            account_info_iter,
+        )?;
+    }
+
+    emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::RootPushed {
+            root: new_root,
+            root_index: state.current_root_index,
+        },
+    )
+}
+
+/// Move `state` on to its next tree and archive the full one in
+/// `tree_account`, paid by `payer`.
+fn archive_tree<'info>(
+    program_id: &Pubkey,
+    state: &mut MixerState,
+    payer: &AccountInfo<'info>,
+    tree_account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<CommitmentTree, ProgramError> {
+    let (expected_tree, bump) = CommitmentTree::find_address(program_id, state.tree_id);
+    if tree_account.key != &expected_tree {
+        msg!("Invalid commitment tree PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let archived = state.rotate_tree().inspect_err(|_| msg!("Pool has no trees left"))?;
+    create_pool_account(
+        program_id,
+        payer,
+        tree_account,
+        system_program,
+        &[CommitmentTree::SEED, &[archived.tree_id], &[bump]],
+        CommitmentTree::LEN,
+    )?;
+    archived.pack(&mut tree_account.try_borrow_mut_data()?);
+    Ok(archived)
+}
+
+fn process_create_latest_root(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let latest_root_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_account)?;
+    let (expected_latest_root, bump) = LatestRoot::find_address(program_id);
+    if latest_root_account.key != &expected_latest_root {
+        msg!("Invalid latest root PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    create_pool_account(
+        program_id,
+        payer,
+        latest_root_account,
+        system_program,
+        &[LatestRoot::SEED, &[bump]],
+        LatestRoot::LEN,
+    )?;
+    LatestRoot {
+        root: state.roots[state.current_root_index as usize],
+        leaf_count: state.leaf_count,
+        slot: Clock::get()?.slot,
+    }
+    .pack(&mut latest_root_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_create_pool_stats(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let pool_stats = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    load_state(program_id, state_account)?;
+    let (expected_pool_stats, bump) = PoolStats::find_address(program_id);
+    if pool_stats.key != &expected_pool_stats {
+        msg!("Invalid pool stats PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    create_pool_account(
+        program_id,
+        payer,
+        pool_stats,
+        system_program,
+        &[PoolStats::SEED, &[bump]],
+        PoolStats::LEN,
+    )
+}
+
+fn process_resize_state(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    authority: Pubkey,
+    verifier_program: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let upgrade_authority = next_account_info(account_info_iter)?;
+    let program_data = next_account_info(account_info_iter)?;
+
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_upgrade_authority(program_id, upgrade_authority, program_data)?;
+    let (expected_state, _) = Pubkey::find_program_address(&[b"mixer_state"], program_id);
+    if state_account.key != &expected_state {
+        msg!("Invalid mixer state PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if state_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let (expected_vault, _) = Pubkey::find_program_address(&[b"mixer_vault"], program_id);
+    if vault_account.key != &expected_vault {
+        msg!("Invalid vault PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let old_len = state_account.data_len();
+    if old_len >= MixerState::LEN {
+        msg!("Mixer state is already current");
+        return Ok(());
+    }
+
+    let rent = Rent::get()?;
+    let mut state = unpack_state(&state_account.data.borrow())?;
+    if old_len < MixerState::VERIFIER_OFFSET + 32 {
+        if authority == Pubkey::default() || verifier_program == Pubkey::default() {
+            msg!("Pool needs an authority and a verifier program");
+            return Err(MixerError::InvalidVerifier.into());
+        }
+        state.authority = authority;
+        state.verifier_program = verifier_program;
+    }
+    if old_len < MixerState::WITHDRAW_COUNT_OFFSET + 8 {
+        let held = vault_account
+            .lamports()
+            .saturating_sub(rent.minimum_balance(0))
+            .saturating_sub(state.protocol_fees);
+        let notes = match state.denomination {
+            0 => 0,
+            denomination => held.div_ceil(denomination),
+        };
+        state.deposit_count = state.withdraw_count.saturating_add(notes);
+    }
+
+    let shortfall = rent
+        .minimum_balance(MixerState::LEN)
+        .saturating_sub(state_account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, state_account.key, shortfall),
+            &[payer.clone(), state_account.clone(), system_program.clone()],
+        )?;
+    }
+    state_account.resize(MixerState::LEN)?;
+    store_state(state_account, &state)
+}
+
+/// Fail unless `signer` signed and is the upgrade authority recorded in
+/// `program_data`, the program data account of the mixer.
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    signer: &AccountInfo,
+    program_data: &AccountInfo,
+) -> ProgramResult {
+    if !signer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let loader = solana_sdk_ids::bpf_loader_upgradeable::ID;
+    if program_data.key != &Pubkey::find_program_address(&[program_id.as_ref()], &loader).0
+        || program_data.owner != &loader
+    {
+        msg!("Invalid program data account");
+        return Err(ProgramError::InvalidArgument);
+    }
+    // `UpgradeableLoaderState::ProgramData`: u32 variant 3, u64 slot, then
+    // the upgrade authority as a bincode `Option<Pubkey>`.
+    let data = program_data.data.borrow();
+    let authority = match data.get(..45) {
+        Some(meta) if meta[..4] == 3u32.to_le_bytes() && meta[12] == 1 => &meta[13..45],
+        _ => {
+            msg!("Mixer program is not upgradeable");
+            return Err(MixerError::Unauthorized.into());
+        }
+    };
+    if authority != signer.key.as_ref() {
+        msg!("Signer is not the mixer program's upgrade authority");
+        return Err(MixerError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+/// Create the zeroed, program-owned account of `len` bytes at the PDA with
+/// `seeds`, which must not exist yet, paid by `payer`.
+fn create_pool_account<'info>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    len: usize,
+) -> ProgramResult {
+    if account.owner == program_id {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    let rent_exempt = Rent::get()?.minimum_balance(len);
+    let infos = [payer.clone(), account.clone(), system_program.clone()];
+    if account.lamports() == 0 {
+        let create_ix = system_instruction::create_account(
+            payer.key,
+            account.key,
+            rent_exempt,
+            len as u64,
+            program_id,
+        );
+        return invoke_signed(&create_ix, &infos, &[seeds]);
+    }
+    // Lamports sent to the address beforehand would make `create_account`
+    // fail and leave the pool unusable, so take the account over.
+    let top_up = rent_exempt.saturating_sub(account.lamports());
+    if top_up > 0 {
+        invoke(&system_instruction::transfer(payer.key, account.key, top_up), &infos)?;
+    }
+    invoke_signed(&system_instruction::allocate(account.key, len as u64), &infos, &[seeds])?;
+    invoke_signed(&system_instruction::assign(account.key, program_id), &infos, &[seeds])
+}
+
+/// Count a deposit by `depositor` in `epoch` in its deposit counter,
+/// creating the counter on its first deposit.
+fn count_depositor_deposit<'info>(
+    program_id: &Pubkey,
+    depositor: &AccountInfo<'info>,
+    counter_account: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    epoch: u64,
+    limit: u32,
+) -> ProgramResult {
+    let (expected_counter, bump) = DepositCounter::find_address(program_id, depositor.key);
+    if counter_account.key != &expected_counter {
+        msg!("Invalid deposit counter PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if counter_account.owner != program_id {
+        let create_ix = system_instruction::create_account(
+            depositor.key,
+            counter_account.key,
+            Rent::get()?.minimum_balance(DepositCounter::LEN),
+            DepositCounter::LEN as u64,
+            program_id,
+        );
+        let counter_seeds: &[&[u8]] = &[DepositCounter::SEED, depositor.key.as_ref(), &[bump]];
+        invoke_signed(
+            &create_ix,
+            &[depositor.clone(), counter_account.clone(), system_program.clone()],
+            &[counter_seeds],
+        )?;
+    }
+    let mut data = counter_account.try_borrow_mut_data()?;
+    let mut counter = DepositCounter::unpack(&data);
+    count_in_epoch(&mut counter.epoch, &mut counter.count, epoch, limit)
+        .inspect_err(|_| msg!("Depositor may make {} deposits per epoch", limit))?;
+    counter.pack(&mut data);
+    Ok(())
+}
+
+/// Accounts shared by every withdraw variant, in instruction order.
+struct WithdrawAccounts<'a, 'info> {
+    relayer: &'a AccountInfo<'info>,
+    state_account: &'a AccountInfo<'info>,
+    nullifier_account: &'a AccountInfo<'info>,
+    vault_account: &'a AccountInfo<'info>,
+    recipient_account: &'a AccountInfo<'info>,
+    verifier_program: &'a AccountInfo<'info>,
+    system_program: &'a AccountInfo<'info>,
+    event_authority: &'a AccountInfo<'info>,
+    program: &'a AccountInfo<'info>,
+    pool_stats: &'a AccountInfo<'info>,
+    /// Set when the proof was read from a proof buffer; backends that
+    /// verify from an account need it.
+    proof_buffer: Option<&'a AccountInfo<'info>>,
+    /// The commitment tree of a withdraw that names one.
+    commitment_tree: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> WithdrawAccounts<'a, 'info> {
+    fn parse(accounts: &'a [AccountInfo<'info>]) -> Result<Self, ProgramError> {
+        let account_info_iter = &mut accounts.iter();
+        Ok(Self {
+            relayer: next_account_info(account_info_iter)?,
+            state_account: next_account_info(account_info_iter)?,
+            nullifier_account: next_account_info(account_info_iter)?,
+            vault_account: next_account_info(account_info_iter)?,
+            recipient_account: next_account_info(account_info_iter)?,
+            verifier_program: next_account_info(account_info_iter)?,
+            system_program: next_account_info(account_info_iter)?,
+            event_authority: next_account_info(account_info_iter)?,
+            program: next_account_info(account_info_iter)?,
+            pool_stats: next_account_info(account_info_iter)?,
+            proof_buffer: None,
+            commitment_tree: account_info_iter.next(),
+        })
+    }
+}
+
+/// Split off the accounts every withdraw variant starts with, including the
+/// commitment tree that follows them when the withdraw names a tree.
+fn split_withdraw_accounts<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    tree_id: Option<u8>,
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>]), ProgramError> {
+    let shared = WITHDRAW_ACCOUNTS + usize::from(tree_id.is_some());
+    if accounts.len() < shared {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    Ok(accounts.split_at(shared))
+}
+
+/// Unix timestamp at which `root` was pushed, looked up in tree `tree_id`
+/// or, without one, in the pool's current tree.
+fn root_time(
+    program_id: &Pubkey,
+    state: &MixerState,
+    commitment_tree: Option<&AccountInfo>,
+    root: &[u8; 32],
+    tree_id: Option<u8>,
+) -> Result<Option<i64>, ProgramError> {
+    let tree_id = match tree_id {
+        Some(tree_id) if tree_id < state.tree_id => tree_id,
+        Some(tree_id) if tree_id > state.tree_id => {
+            msg!("Pool has no tree {}", tree_id);
+            return Ok(None);
+        }
+        _ => return Ok(state.root_index(root).map(|i| state.root_times[i])),
+    };
+    let tree_account = commitment_tree.ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if tree_account.key != &CommitmentTree::find_address(program_id, tree_id).0 {
+        msg!("Invalid commitment tree PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if tree_account.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(CommitmentTree::unpack(&tree_account.data.borrow())?.root_time(root))
+}
+
+/// Check the root, mark the nullifier as spent, verify the proof and accrue
+/// the protocol fee. Returns the mixer state for the payout that follows,
+/// which must be `MixerState::withdraw_amount`.
+#[allow(clippy::too_many_arguments)]
+fn spend_note(
+    program_id: &Pubkey,
+    accounts: &WithdrawAccounts,
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: Option<u8>,
+    proof: Vec<u8>,
+) -> Result<MixerState, ProgramError> {
+    let WithdrawAccounts {
+        relayer,
+        state_account,
+        nullifier_account,
+        recipient_account,
+        verifier_program,
+        system_program,
+        pool_stats,
+        proof_buffer,
+        commitment_tree,
+        ..
+    } = *accounts;
+
+    // Load and check root
+    let mut state = load_state(program_id, state_account)?;
+    state.check_not_paused()?;
+    let Some(pushed_at) = root_time(program_id, &state, commitment_tree, &root, tree_id)? else {
+        msg!("Unknown root");
+        return Err(MixerError::UnknownRoot.into());
+    };
+    let clock = Clock::get()?;
+    let root_age = clock.unix_timestamp.saturating_sub(pushed_at);
+    if root_age < state.min_withdraw_delay as i64 {
+        msg!("Root must be {} seconds old to withdraw", state.min_withdraw_delay);
+        return Err(MixerError::RootTooRecent.into());
+    }
+
+    let (backend, expected_verifier) = state
+        .circuit_verifier(circuit_version, clock.slot)
+        .inspect_err(|_| msg!("Circuit version {} is not accepted", circuit_version))?;
+    let curve = state.curve()?;
+    if !curve.supports(backend) {
+        msg!("Verifier backend does not support the pool's curve");
+        return Err(MixerError::UnsupportedCurve.into());
+    }
+    if ![root, nullifier_hash, recipient_field].iter().all(|f| curve.is_canonical(f)) {
+        msg!("Public input is not a canonical field element");
+        return Err(MixerError::VerificationFailed.into());
+    }
+    // Every backend is pinned: `Initialize`, `ResizeState` and the verifier
+    // admin actions never leave an all-zero verifier, and a pool that still
+    // has one can't withdraw until `SetVerifier` pins it.
+    if expected_verifier == Pubkey::default() || verifier_program.key != &expected_verifier {
+        msg!("Verifier program does not match the pool");
+        return Err(MixerError::InvalidVerifier.into());
+    }
+
+    if !state.allows_off_curve_recipients()
+        && !validate_edwards(&PodEdwardsPoint(recipient_account.key.to_bytes()))
+    {
+        msg!("Off-curve recipient not allowed by this pool");
+        return Err(MixerError::OffCurveRecipient.into());
+    }
+
+    // Nullifier account being non-zero lamports means it is already used.
+    if nullifier_account.lamports() > 0 {
+        msg!("Nullifier already used");
+        return Err(MixerError::NullifierUsed.into());
+    }
+
+    // Mark nullifier as used by creating a small account; this is a simple pattern
+    // that avoids building a custom bitmap.
+    {
+        // Use the relayer (tx signer) to fund the marker account.
+        // (The recipient is not required to be a signer.)
+        let payer = relayer;
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // Nullifier account must be the expected PDA so we can sign for it.
+        let (expected_nullifier, nullifier_bump) = Pubkey::find_program_address(
+            &[b"nullifier", &nullifier_hash],
+            program_id,
+        );
+        if nullifier_account.key != &expected_nullifier {
+            msg!("Invalid nullifier PDA");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let rent = Rent::get()?;
+        let lamports = rent.minimum_balance(0);
         let create_ix = system_instruction::create_account(
             payer.key,
-            state_account.key,
-            required_lamports,
-            MixerState::LEN as u64,
-            program_id,
+            nullifier_account.key,
+            lamports,
+            0,
+            system_program.key, // system-owned marker
         );
-        
-        // Sign with PDA seeds - this allows the program to create the PDA account
-        let seeds: &[&[u8]] = &[b"mixer_state", &[bump]];
+        let nullifier_seeds: &[&[u8]] = &[b"nullifier", &nullifier_hash, &[nullifier_bump]];
         invoke_signed(
             &create_ix,
-            &[payer.clone(), state_account.clone(), system_program.clone()],
-            &[seeds],
+            &[
+                payer.clone(),
+                nullifier_account.clone(),
+                system_program.clone(),
+            ],
+            &[nullifier_seeds],
+        )?;
+    }
+
+    match backend {
+        VerifierBackend::SunspotCpi => {
+            // Build instruction data for the verifier: proof_bytes || public_witness_bytes
+            // The public_witness_bytes is the .pw file from Sunspot containing public inputs.
+            // According to Sunspot/Noir examples, the format is: proof || public_witness
+            // where public_witness contains root || nullifier_hash || recipient_field.
+            // The client should concatenate proof + public_witness before passing to this instruction.
+            // The verifier only checks the proof against the witness, so the
+            // witness has to carry the inputs this instruction spends.
+            let witness_at = proof
+                .len()
+                .checked_sub(public_inputs::SUNSPOT_PUBLIC_WITNESS_LEN)
+                .ok_or_else(|| {
+                    msg!("Proof is missing its public witness");
+                    MixerError::VerificationFailed
+                })?;
+            let witness = public_inputs::PublicInputs::from_sunspot_witness_in(curve, &proof[witness_at..])
+                .map_err(|err| {
+                    msg!("Invalid public witness: {}", err);
+                    MixerError::VerificationFailed
+                })?;
+            if witness.to_array() != [root, nullifier_hash, recipient_field] {
+                msg!("Public witness does not match the withdraw");
+                return Err(MixerError::VerificationFailed.into());
+            }
+            let instruction_data = proof;
+
+            let verify_ix = Instruction {
+                program_id: *verifier_program.key,
+                accounts: vec![],
+                data: instruction_data,
+            };
+
+            // CPI into verifier program
+            // NOTE: The verifier is expected to revert on invalid proofs.
+            invoke(&verify_ix, &[]).map_err(|_| MixerError::VerificationFailed)?;
+        }
+        VerifierBackend::UltraHonkCpi => {
+            // UltraHonk proofs are larger than CPI instruction data allows,
+            // so the verifier reads the proof from the buffer and the mixer
+            // passes the public inputs from the instruction.
+            let proof_buffer = proof_buffer.ok_or_else(|| {
+                msg!("UltraHonk proofs must be withdrawn from a proof buffer");
+                MixerError::InvalidProofBuffer
+            })?;
+            drop(proof);
+            let mut public_inputs = Vec::with_capacity(96);
+            public_inputs.extend_from_slice(&root);
+            public_inputs.extend_from_slice(&nullifier_hash);
+            public_inputs.extend_from_slice(&recipient_field);
+
+            let verify_ix = Instruction {
+                program_id: *verifier_program.key,
+                accounts: vec![AccountMeta::new_readonly(*proof_buffer.key, false)],
+                data: public_inputs,
+            };
+            invoke(&verify_ix, std::slice::from_ref(proof_buffer))
+                .map_err(|_| MixerError::VerificationFailed)?;
+        }
+        VerifierBackend::NativeGroth16 => {
+            if circuit_version == 0 {
+                verifier::check_verifier_hash(&state.verifier_hash, verifier_program)?;
+            }
+            let vk = Groth16VerifyingKey::load(program_id, verifier_program)?;
+            verifier::verify_groth16(&vk, &proof, &[root, nullifier_hash, recipient_field])?;
+        }
+    }
+
+    state.protocol_fees = state
+        .protocol_fees
+        .checked_add(state.protocol_fee())
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    state.withdraw_count = state.withdraw_count.saturating_add(1);
+    store_state(state_account, &state)?;
+    PoolStats::update(program_id, pool_stats, |stats| {
+        stats.withdrawals = stats.withdrawals.saturating_add(1);
+        stats.withdraw_volume = stats.withdraw_volume.saturating_add(state.withdraw_amount());
+        stats.fee_revenue = stats.fee_revenue.saturating_add(state.protocol_fee());
+        stats.last_activity_slot = clock.slot;
+    })?;
+
+    Ok(state)
+}
+
+/// Check that `vault_account` is the mixer vault PDA and return its bump.
+pub(crate) fn check_vault(program_id: &Pubkey, vault_account: &AccountInfo) -> Result<u8, ProgramError> {
+    // Vault must be the correct PDA so we can sign for it.
+    let (expected_vault, vault_bump) =
+        Pubkey::find_program_address(&[b"mixer_vault"], program_id);
+    if vault_account.key != &expected_vault {
+        msg!("Invalid vault PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(vault_bump)
+}
+
+/// Lamports left for `recipient` once the relayer takes `relayer_fee`. A fee
+/// needs the recipient's signature, which shows the recipient agreed to it.
+/// It does not stop a replay: the recipient is not bound to the proof, so
+/// whoever resubmits the proof can name another.
+fn recipient_payout(
+    state: &MixerState,
+    recipient: &AccountInfo,
+    relayer_fee: u64,
+) -> Result<u64, ProgramError> {
+    if relayer_fee > 0 && !recipient.is_signer {
+        msg!("A relayer fee needs the recipient's signature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let payout = state.recipient_amount(relayer_fee).inspect_err(|_| {
+        msg!("Relayer fee exceeds the pool's cap of {}", state.max_relayer_fee())
+    })?;
+    Ok(payout)
+}
+
+/// Transfer `lamports` from the vault to `destination`.
+fn pay_from_vault<'info>(
+    program_id: &Pubkey,
+    accounts: &WithdrawAccounts<'_, 'info>,
+    destination: &AccountInfo<'info>,
+    lamports: u64,
+) -> ProgramResult {
+    let vault_bump = check_vault(program_id, accounts.vault_account)?;
+    let transfer_ix =
+        system_instruction::transfer(accounts.vault_account.key, destination.key, lamports);
+    let vault_seeds: &[&[u8]] = &[b"mixer_vault", &[vault_bump]];
+    invoke_signed(
+        &transfer_ix,
+        &[
+            accounts.vault_account.clone(),
+            destination.clone(),
+            accounts.system_program.clone(),
+        ],
+        &[vault_seeds],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_withdraw<'a, 'info>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    proof_buffer: Option<&'a AccountInfo<'info>>,
+    receipt: Option<&'a AccountInfo<'info>>,
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: Option<u8>,
+    relayer_fee: u64,
+    proof: Vec<u8>,
+) -> ProgramResult {
+    let mut accounts = WithdrawAccounts::parse(accounts)?;
+    accounts.proof_buffer = proof_buffer;
+    let state = spend_note(
+        program_id,
+        &accounts,
+        root,
+        nullifier_hash,
+        recipient_field,
+        circuit_version,
+        tree_id,
+        proof,
+    )?;
+
+    // Transfer funds from vault to recipient, less the relayer's fee
+    let payout = recipient_payout(&state, accounts.recipient_account, relayer_fee)?;
+    pay_from_vault(program_id, &accounts, accounts.recipient_account, payout)?;
+    if relayer_fee > 0 {
+        pay_from_vault(program_id, &accounts, accounts.relayer, relayer_fee)?;
+    }
+    if let Some(receipt) = receipt {
+        create_withdraw_receipt(program_id, &accounts, receipt, &nullifier_hash, payout)?;
+    }
+
+    emit_cpi(
+        program_id,
+        accounts.event_authority,
+        accounts.program,
+        &events::Withdraw {
+            root,
+            nullifier_hash,
+            recipient: accounts.recipient_account.key.to_bytes(),
+            amount: payout,
+        },
+    )
+}
+
+fn process_create_proof_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    buffer_id: u64,
+    payload_len: u32,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let buffer_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let payload_len = payload_len as usize;
+    if payload_len == 0 || payload_len > ProofBuffer::MAX_PAYLOAD_LEN {
+        msg!("Invalid proof buffer length");
+        return Err(MixerError::InvalidProofBuffer.into());
+    }
+
+    let (expected_buffer, bump) = ProofBuffer::find_address(program_id, authority.key, buffer_id);
+    if buffer_account.key != &expected_buffer {
+        msg!("Invalid proof buffer PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let space = ProofBuffer::HEADER_LEN + payload_len;
+    let rent = Rent::get()?;
+    let create_ix = system_instruction::create_account(
+        authority.key,
+        buffer_account.key,
+        rent.minimum_balance(space),
+        space as u64,
+        program_id,
+    );
+    let buffer_seeds: &[&[u8]] = &[
+        ProofBuffer::SEED,
+        authority.key.as_ref(),
+        &buffer_id.to_le_bytes(),
+        &[bump],
+    ];
+    invoke_signed(
+        &create_ix,
+        &[
+            authority.clone(),
+            buffer_account.clone(),
+            system_program.clone(),
+        ],
+        &[buffer_seeds],
+    )?;
+
+    let mut data = buffer_account.try_borrow_mut_data()?;
+    data[0..ProofBuffer::HEADER_LEN].copy_from_slice(authority.key.as_ref());
+    Ok(())
+}
+
+fn process_write_proof_chunk(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    offset: u32,
+    chunk: Vec<u8>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let buffer_account = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    ProofBuffer::check(program_id, buffer_account, authority.key)?;
+
+    let mut data = buffer_account.try_borrow_mut_data()?;
+    let start = ProofBuffer::HEADER_LEN
+        .checked_add(offset as usize)
+        .ok_or(MixerError::InvalidProofBuffer)?;
+    let end = start
+        .checked_add(chunk.len())
+        .ok_or(MixerError::InvalidProofBuffer)?;
+    if end > data.len() {
+        msg!("Proof chunk out of bounds");
+        return Err(MixerError::InvalidProofBuffer.into());
+    }
+    data[start..end].copy_from_slice(&chunk);
+    Ok(())
+}
+
+fn process_extend_proof_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    additional_len: u32,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let buffer_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    ProofBuffer::check(program_id, buffer_account, authority.key)?;
+
+    let additional_len = additional_len as usize;
+    let new_len = buffer_account.data_len() + additional_len;
+    if additional_len == 0
+        || additional_len > solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE
+        || new_len > ProofBuffer::HEADER_LEN + ProofBuffer::MAX_EXTENDED_PAYLOAD_LEN
+    {
+        msg!("Invalid proof buffer length");
+        return Err(MixerError::InvalidProofBuffer.into());
+    }
+
+    let shortfall = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(buffer_account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(authority.key, buffer_account.key, shortfall),
+            &[
+                authority.clone(),
+                buffer_account.clone(),
+                system_program.clone(),
+            ],
         )?;
     }
+    buffer_account.resize(new_len)
+}
+
+/// Create the [`WithdrawReceipt`] for a withdrawal that paid `amount` to the
+/// recipient, at the relayer's expense.
+fn create_withdraw_receipt<'info>(
+    program_id: &Pubkey,
+    accounts: &WithdrawAccounts<'_, 'info>,
+    receipt_account: &AccountInfo<'info>,
+    nullifier_hash: &[u8; 32],
+    amount: u64,
+) -> ProgramResult {
+    let (expected_receipt, bump) = WithdrawReceipt::find_address(program_id, nullifier_hash);
+    if receipt_account.key != &expected_receipt {
+        msg!("Invalid withdraw receipt PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    create_pool_account(
+        program_id,
+        accounts.relayer,
+        receipt_account,
+        accounts.system_program,
+        &[WithdrawReceipt::SEED, nullifier_hash, &[bump]],
+        WithdrawReceipt::LEN,
+    )?;
+    let receipt = WithdrawReceipt {
+        recipient: *accounts.recipient_account.key,
+        amount,
+        slot: Clock::get()?.slot,
+    };
+    receipt.pack(&mut receipt_account.try_borrow_mut_data()?);
+    Ok(())
+}
+
+fn process_close_withdraw_receipt(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    nullifier_hash: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let recipient = next_account_info(account_info_iter)?;
+    let receipt_account = next_account_info(account_info_iter)?;
+
+    if !recipient.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if receipt_account.key != &WithdrawReceipt::find_address(program_id, &nullifier_hash).0 {
+        msg!("Invalid withdraw receipt PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if receipt_account.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let receipt = WithdrawReceipt::unpack(&receipt_account.data.borrow())?;
+    if &receipt.recipient != recipient.key {
+        msg!("Only the receipt's recipient may close it");
+        return Err(MixerError::Unauthorized.into());
+    }
+    close_account(receipt_account, recipient)
+}
+
+fn process_close_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    target: CloseTarget,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let owner = next_account_info(account_info_iter)?;
+    let account = next_account_info(account_info_iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Buffers and counters are PDAs of the signer, so matching the address
+    // is what shows they are its own.
+    let expected = match target {
+        CloseTarget::ProofBuffer { buffer_id } => {
+            ProofBuffer::find_address(program_id, owner.key, buffer_id).0
+        }
+        CloseTarget::DepositCounter => DepositCounter::find_address(program_id, owner.key).0,
+        CloseTarget::VerifyingKey { vk_id } => {
+            Groth16VerifyingKey::find_address(program_id, vk_id).0
+        }
+    };
+    if account.key != &expected {
+        msg!("Account is not the PDA of the close target");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if account.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    match target {
+        CloseTarget::ProofBuffer { .. } => {}
+        CloseTarget::DepositCounter => {
+            // Closing it mid-epoch would reset the depositor's count.
+            let counter = DepositCounter::unpack(&account.data.borrow());
+            if counter.epoch >= Clock::get()?.epoch {
+                msg!("Deposit counter still counts the current epoch");
+                return Err(MixerError::AccountInUse.into());
+            }
+        }
+        CloseTarget::VerifyingKey { .. } => {
+            let state_account = next_account_info(account_info_iter)?;
+            let state = load_state(program_id, state_account)?;
+            admin::check_authority(&state, owner)?;
+            if state.uses_verifying_key(account.key, Clock::get()?.slot) {
+                msg!("Verifying key is still used by the pool");
+                return Err(MixerError::AccountInUse.into());
+            }
+        }
+    }
+    close_account(account, owner)
+}
 
-    // Create the mixer vault PDA (system-owned, 0 data) if it doesn't exist yet.
-    // This vault holds the fixed-denomination lamports and will be PDA-signed for withdrawals.
-    //
-    // Accounts are not passed explicitly for the vault; we derive and expect the client to pass it
-    // to Withdraw. For Initialize, we lazily create it here without storing it in state.
-    {
-        let (vault_pda, vault_bump) =
-            Pubkey::find_program_address(&[b"mixer_vault"], program_id);
-        // The vault account will be created by the client via a system transfer if it already exists.
-        // If it does not exist, we create it here in a rent-exempt manner.
-        // NOTE: We cannot access the vault AccountInfo here because it's not in the accounts list.
-        // So we only log its expected address for clients.
-        msg!("Expected mixer vault PDA: {}", vault_pda);
-        msg!("Mixer vault bump: {}", vault_bump);
-    }
+/// Number of accounts taken by `Withdraw`; extended variants append theirs.
+const WITHDRAW_ACCOUNTS: usize = 10;
 
-    // Initialize state
-    let mut data = state_account.try_borrow_mut_data()?;
-    if data.len() < MixerState::LEN {
-        return Err(ProgramError::AccountDataTooSmall);
-    }
-    // denomination
-    data[0..8].copy_from_slice(&denomination.to_le_bytes());
-    // roots
-    for i in 0..MixerState::ROOT_HISTORY_SIZE {
-        let start = 8 + i * 32;
-        data[start..start + 32].copy_from_slice(&[0u8; 32]);
+#[allow(clippy::too_many_arguments)]
+fn process_withdraw_from_buffer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: Option<u8>,
+    relayer_fee: u64,
+) -> ProgramResult {
+    let (withdraw_accounts, rest) = split_withdraw_accounts(accounts, tree_id)?;
+    let relayer = &withdraw_accounts[0];
+    let buffer_account = rest.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    if !relayer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
-    // current_root_index
-    data[8 + 32 * MixerState::ROOT_HISTORY_SIZE] = 0;
+    ProofBuffer::check(program_id, buffer_account, relayer.key)?;
+    let proof = buffer_account.data.borrow()[ProofBuffer::HEADER_LEN..].to_vec();
 
-    Ok(())
+    process_withdraw(
+        program_id,
+        withdraw_accounts,
+        Some(buffer_account),
+        rest.get(1),
+        root,
+        nullifier_hash,
+        recipient_field,
+        circuit_version,
+        tree_id,
+        relayer_fee,
+        proof,
+    )?;
+
+    close_account(buffer_account, relayer)
 }
 
-fn load_state<'a>(state_account: &'a AccountInfo) -> Result<MixerState, ProgramError> {
-    let data = state_account.data.borrow();
-    if data.len() < MixerState::LEN {
-        return Err(ProgramError::AccountDataTooSmall);
+#[allow(clippy::too_many_arguments)]
+fn process_withdraw_and_call(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: Option<u8>,
+    relayer_fee: u64,
+    proof: Vec<u8>,
+    call_data: Vec<u8>,
+) -> ProgramResult {
+    let (withdraw_accounts, call_accounts) = split_withdraw_accounts(accounts, tree_id)?;
+    let relayer = &withdraw_accounts[0];
+    let recipient = &withdraw_accounts[4];
+    let verifier_program = &withdraw_accounts[5];
+    let (target_program, extra_accounts) =
+        call_accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    if target_program.key == program_id || target_program.key == verifier_program.key {
+        msg!("Invalid withdraw call target");
+        return Err(MixerError::InvalidWithdrawCall.into());
     }
-    let mut roots = [[0u8; 32]; MixerState::ROOT_HISTORY_SIZE];
-    let denomination = u64::from_le_bytes(data[0..8].try_into().unwrap());
-    for i in 0..MixerState::ROOT_HISTORY_SIZE {
-        let start = 8 + i * 32;
-        roots[i].copy_from_slice(&data[start..start + 32]);
+    // Forwarded accounts may not include anything the mixer controls, nor the
+    // relayer's signature unless the relayer is also the recipient.
+    for account in extra_accounts {
+        let is_mixer_account = account.owner == program_id
+            || withdraw_accounts[1..4]
+                .iter()
+                .chain(withdraw_accounts[7..].iter())
+                .any(|a| a.key == account.key);
+        let is_foreign_relayer = account.key == relayer.key && relayer.key != recipient.key;
+        if is_mixer_account || is_foreign_relayer {
+            msg!("Account {} may not be forwarded", account.key);
+            return Err(MixerError::InvalidWithdrawCall.into());
+        }
     }
-    let current_root_index = data[8 + 32 * MixerState::ROOT_HISTORY_SIZE];
-    Ok(MixerState {
-        denomination,
-        roots,
-        current_root_index,
-    })
+
+    process_withdraw(
+        program_id,
+        withdraw_accounts,
+        None,
+        None,
+        root,
+        nullifier_hash,
+        recipient_field,
+        circuit_version,
+        tree_id,
+        relayer_fee,
+        proof,
+    )?;
+
+    let mut metas = Vec::with_capacity(1 + extra_accounts.len());
+    metas.push(AccountMeta {
+        pubkey: *recipient.key,
+        is_signer: recipient.is_signer,
+        is_writable: true,
+    });
+    metas.extend(extra_accounts.iter().map(|a| AccountMeta {
+        pubkey: *a.key,
+        is_signer: a.is_signer,
+        is_writable: a.is_writable,
+    }));
+    let call_ix = Instruction {
+        program_id: *target_program.key,
+        accounts: metas,
+        data: call_data,
+    };
+    let mut call_infos = Vec::with_capacity(2 + extra_accounts.len());
+    call_infos.push(recipient.clone());
+    call_infos.extend(extra_accounts.iter().cloned());
+    call_infos.push(target_program.clone());
+    invoke(&call_ix, &call_infos)
 }
 
-fn store_state(state_account: &AccountInfo, state: &MixerState) -> Result<(), ProgramError> {
-    let mut data = state_account.data.borrow_mut();
-    if data.len() < MixerState::LEN {
-        return Err(ProgramError::AccountDataTooSmall);
+#[allow(clippy::too_many_arguments)]
+fn process_withdraw_to_stake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: Option<u8>,
+    relayer_fee: u64,
+    proof: Vec<u8>,
+) -> ProgramResult {
+    let (withdraw_accounts, stake_accounts) = split_withdraw_accounts(accounts, tree_id)?;
+    let withdraw = WithdrawAccounts::parse(withdraw_accounts)?;
+    let account_info_iter = &mut stake_accounts.iter();
+    let stake_account = next_account_info(account_info_iter)?;
+    let vote_account = next_account_info(account_info_iter)?;
+    let stake_authority = next_account_info(account_info_iter)?;
+    let stake_program = next_account_info(account_info_iter)?;
+    let rent_sysvar = next_account_info(account_info_iter)?;
+    let clock_sysvar = next_account_info(account_info_iter)?;
+    let stake_history_sysvar = next_account_info(account_info_iter)?;
+    let stake_config = next_account_info(account_info_iter)?;
+
+    if stake_program.key != &solana_stake_interface::program::ID {
+        return Err(ProgramError::IncorrectProgramId);
     }
-    data[0..8].copy_from_slice(&state.denomination.to_le_bytes());
-    for i in 0..MixerState::ROOT_HISTORY_SIZE {
-        let start = 8 + i * 32;
-        data[start..start + 32].copy_from_slice(&state.roots[i]);
+    let (expected_stake, stake_bump) =
+        Pubkey::find_program_address(&[b"stake_account", &nullifier_hash], program_id);
+    if stake_account.key != &expected_stake {
+        msg!("Invalid stake account PDA");
+        return Err(ProgramError::InvalidArgument);
     }
-    data[8 + 32 * MixerState::ROOT_HISTORY_SIZE] = state.current_root_index;
-    Ok(())
+    let (expected_authority, authority_bump) =
+        Pubkey::find_program_address(&[b"stake_authority"], program_id);
+    if stake_authority.key != &expected_authority {
+        msg!("Invalid stake authority PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let state = spend_note(
+        program_id,
+        &withdraw,
+        root,
+        nullifier_hash,
+        recipient_field,
+        circuit_version,
+        tree_id,
+        proof,
+    )?;
+    let stake_lamports = recipient_payout(&state, withdraw.recipient_account, relayer_fee)?;
+    if relayer_fee > 0 {
+        pay_from_vault(program_id, &withdraw, withdraw.relayer, relayer_fee)?;
+    }
+    let vault_bump = check_vault(program_id, withdraw.vault_account)?;
+    let recipient = withdraw.recipient_account.key;
+
+    // Fund the stake account straight from the vault.
+    let create_ix = system_instruction::create_account(
+        withdraw.vault_account.key,
+        stake_account.key,
+        stake_lamports,
+        StakeStateV2::size_of() as u64,
+        stake_program.key,
+    );
+    let vault_seeds: &[&[u8]] = &[b"mixer_vault", &[vault_bump]];
+    let stake_seeds: &[&[u8]] = &[b"stake_account", &nullifier_hash, &[stake_bump]];
+    invoke_signed(
+        &create_ix,
+        &[
+            withdraw.vault_account.clone(),
+            stake_account.clone(),
+            withdraw.system_program.clone(),
+        ],
+        &[vault_seeds, stake_seeds],
+    )?;
+
+    // The stake authority PDA is staker just long enough to delegate; the
+    // recipient is withdrawer from the start.
+    let initialize_ix = stake_instruction::initialize(
+        stake_account.key,
+        &Authorized {
+            staker: *stake_authority.key,
+            withdrawer: *recipient,
+        },
+        &Lockup::default(),
+    );
+    invoke(
+        &initialize_ix,
+        &[stake_account.clone(), rent_sysvar.clone(), stake_program.clone()],
+    )?;
+
+    let authority_seeds: &[&[u8]] = &[b"stake_authority", &[authority_bump]];
+    let delegate_ix =
+        stake_instruction::delegate_stake(stake_account.key, stake_authority.key, vote_account.key);
+    invoke_signed(
+        &delegate_ix,
+        &[
+            stake_account.clone(),
+            vote_account.clone(),
+            clock_sysvar.clone(),
+            stake_history_sysvar.clone(),
+            stake_config.clone(),
+            stake_authority.clone(),
+            stake_program.clone(),
+        ],
+        &[authority_seeds],
+    )?;
+
+    let authorize_ix = stake_instruction::authorize(
+        stake_account.key,
+        stake_authority.key,
+        recipient,
+        StakeAuthorize::Staker,
+        None,
+    );
+    invoke_signed(
+        &authorize_ix,
+        &[
+            stake_account.clone(),
+            clock_sysvar.clone(),
+            stake_authority.clone(),
+            stake_program.clone(),
+        ],
+        &[authority_seeds],
+    )?;
+
+    emit_cpi(
+        program_id,
+        withdraw.event_authority,
+        withdraw.program,
+        &events::Withdraw {
+            root,
+            nullifier_hash,
+            recipient: recipient.to_bytes(),
+            amount: stake_lamports,
+        },
+    )
 }
 
-fn process_push_root(
-    _program_id: &Pubkey,
+#[allow(clippy::too_many_arguments)]
+fn process_schedule_withdraw(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
-    new_root: [u8; 32],
+    root: [u8; 32],
+    nullifier_hash: [u8; 32],
+    recipient_field: [u8; 32],
+    circuit_version: u16,
+    tree_id: Option<u8>,
+    earliest_slot: u64,
+    bounty: u64,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let authority = next_account_info(account_info_iter)?;
     let state_account = next_account_info(account_info_iter)?;
+    let buffer_account = next_account_info(account_info_iter)?;
+    let schedule_account = next_account_info(account_info_iter)?;
+    let recipient = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
 
     if !authority.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    ProofBuffer::check(program_id, buffer_account, authority.key)?;
+    let state = load_state(program_id, state_account)?;
+    if bounty > state.max_relayer_fee() {
+        msg!("Bounty exceeds the pool's relayer fee cap");
+        return Err(MixerError::InvalidScheduledWithdraw.into());
+    }
+    if bounty > 0 && !recipient.is_signer {
+        msg!("A bounty needs the recipient's signature");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
 
-    let mut state = load_state(state_account)?;
-    state.push_root(new_root);
-    store_state(state_account, &state)?;
-    Ok(())
+    let (expected_schedule, bump) = ScheduledWithdraw::find_address(program_id, buffer_account.key);
+    if schedule_account.key != &expected_schedule {
+        msg!("Invalid scheduled withdraw PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let rent = Rent::get()?;
+    let create_ix = system_instruction::create_account(
+        authority.key,
+        schedule_account.key,
+        rent.minimum_balance(ScheduledWithdraw::LEN),
+        ScheduledWithdraw::LEN as u64,
+        program_id,
+    );
+    let schedule_seeds: &[&[u8]] = &[ScheduledWithdraw::SEED, buffer_account.key.as_ref(), &[bump]];
+    invoke_signed(
+        &create_ix,
+        &[
+            authority.clone(),
+            schedule_account.clone(),
+            system_program.clone(),
+        ],
+        &[schedule_seeds],
+    )?;
+
+    ScheduledWithdraw {
+        authority: *authority.key,
+        recipient: *recipient.key,
+        root,
+        nullifier_hash,
+        recipient_field,
+        circuit_version,
+        tree_id,
+        earliest_slot,
+        bounty,
+    }
+    .store(schedule_account)
 }
 
-fn process_withdraw(
+fn process_execute_scheduled_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if accounts.len() < WITHDRAW_ACCOUNTS + 3 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let (withdraw_accounts, schedule_accounts) = accounts.split_at(WITHDRAW_ACCOUNTS);
+    let mut withdraw = WithdrawAccounts::parse(withdraw_accounts)?;
+    let buffer_account = &schedule_accounts[0];
+    withdraw.proof_buffer = Some(buffer_account);
+    let schedule_account = &schedule_accounts[1];
+    let authority = &schedule_accounts[2];
+
+    let schedule = ScheduledWithdraw::load(program_id, schedule_account, buffer_account.key)?;
+    if schedule.tree_id.is_some() {
+        withdraw.commitment_tree =
+            Some(schedule_accounts.get(3).ok_or(ProgramError::NotEnoughAccountKeys)?);
+    }
+    if authority.key != &schedule.authority || withdraw.recipient_account.key != &schedule.recipient
+    {
+        return Err(MixerError::InvalidScheduledWithdraw.into());
+    }
+    if Clock::get()?.slot < schedule.earliest_slot {
+        msg!("Scheduled withdraw executable from slot {}", schedule.earliest_slot);
+        return Err(MixerError::ScheduleNotReady.into());
+    }
+    ProofBuffer::check(program_id, buffer_account, authority.key)?;
+    let proof = buffer_account.data.borrow()[ProofBuffer::HEADER_LEN..].to_vec();
+
+    let state = spend_note(
+        program_id,
+        &withdraw,
+        schedule.root,
+        schedule.nullifier_hash,
+        schedule.recipient_field,
+        schedule.circuit_version,
+        schedule.tree_id,
+        proof,
+    )?;
+    let payout = state
+        .withdraw_amount()
+        .checked_sub(schedule.bounty)
+        .ok_or(MixerError::InvalidScheduledWithdraw)?;
+    pay_from_vault(program_id, &withdraw, withdraw.recipient_account, payout)?;
+    if schedule.bounty > 0 {
+        pay_from_vault(program_id, &withdraw, withdraw.relayer, schedule.bounty)?;
+    }
+
+    close_account(buffer_account, authority)?;
+    close_account(schedule_account, authority)?;
+
+    emit_cpi(
+        program_id,
+        withdraw.event_authority,
+        withdraw.program,
+        &events::Withdraw {
+            root: schedule.root,
+            nullifier_hash: schedule.nullifier_hash,
+            recipient: schedule.recipient.to_bytes(),
+            amount: payout,
+        },
+    )
+}
+
+fn process_cancel_scheduled_withdraw(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    root: [u8; 32],
-    nullifier_hash: [u8; 32],
-    _recipient_field: [u8; 32],
-    proof: Vec<u8>,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let relayer = next_account_info(account_info_iter)?;
-    let state_account = next_account_info(account_info_iter)?;
-    let nullifier_account = next_account_info(account_info_iter)?;
-    let vault_account = next_account_info(account_info_iter)?;
-    let recipient_account = next_account_info(account_info_iter)?;
-    let verifier_program = next_account_info(account_info_iter)?;
-    let system_program = next_account_info(account_info_iter)?;
+    let authority = next_account_info(account_info_iter)?;
+    let buffer_account = next_account_info(account_info_iter)?;
+    let schedule_account = next_account_info(account_info_iter)?;
 
-    // Load and check root
-    let state = load_state(state_account)?;
-    if !state.is_known_root(&root) {
-        msg!("Unknown root");
-        return Err(MixerError::UnknownRoot.into());
+    if !authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
+    let schedule = ScheduledWithdraw::load(program_id, schedule_account, buffer_account.key)?;
+    if authority.key != &schedule.authority {
+        return Err(MixerError::InvalidScheduledWithdraw.into());
+    }
+    // The buffer may already be gone if it was spent via WithdrawFromBuffer.
+    if ProofBuffer::check(program_id, buffer_account, authority.key).is_ok() {
+        close_account(buffer_account, authority)?;
+    }
+    close_account(schedule_account, authority)
+}
 
-    // Nullifier account being non-zero lamports means it is already used.
-    if nullifier_account.lamports() > 0 {
-        msg!("Nullifier already used");
-        return Err(MixerError::NullifierUsed.into());
+/// Close a program-owned account, moving its lamports to `destination`.
+pub(crate) fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.resize(0)?;
+    account.assign(&solana_system_interface::program::ID);
+    Ok(())
+}
+
+fn process_emit_event(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let event_authority = next_account_info(account_info_iter)?;
+
+    if !event_authority.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
+    events::check_event_authority(program_id, event_authority)?;
+    Ok(())
+}
 
-    // Mark nullifier as used by creating a small account; this is a simple pattern
-    // that avoids building a custom bitmap.
-    {
-        // Use the relayer (tx signer) to fund the marker account.
-        // (The recipient is not required to be a signer.)
-        let payer = relayer;
-        if !payer.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const SIZE: usize = MixerState::ROOT_HISTORY_SIZE;
+
+    /// An empty pool whose ring buffer starts at `start`.
+    fn state_at(start: usize) -> MixerState {
+        let mut state = unpack_state(&[0u8; MixerState::LEN]).unwrap();
+        state.current_root_index = start as u8;
+        state
+    }
+
+    /// Roots from a small alphabet, including the zero root, so pushes
+    /// repeat and collide; mixed with uniform values.
+    fn root() -> impl Strategy<Value = [u8; 32]> {
+        prop_oneof![
+            (0u8..6).prop_map(|tag| {
+                let mut root = [0u8; 32];
+                root[31] = tag;
+                root
+            }),
+            any::<[u8; 32]>(),
+        ]
+    }
+
+    fn nonzero_root() -> impl Strategy<Value = [u8; 32]> {
+        root().prop_filter("nonzero", |root| root != &[0u8; 32])
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Push([u8; 32]),
+        Lookup([u8; 32]),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![root().prop_map(Op::Push), root().prop_map(Op::Lookup)]
+    }
+
+    /// The ring buffer as a plain history: a root is known if it is nonzero
+    /// and among the last `SIZE` pushes, at the slot of its newest push.
+    struct Model {
+        start: usize,
+        pushes: Vec<[u8; 32]>,
+    }
+
+    impl Model {
+        fn root_index(&self, root: &[u8; 32]) -> Option<usize> {
+            if root == &[0u8; 32] {
+                return None;
+            }
+            let n = self.pushes.len();
+            let age = self.pushes.iter().rev().take(SIZE).position(|r| r == root)?;
+            Some((self.start + n - age) % SIZE)
         }
+    }
 
-        // Nullifier account must be the expected PDA so we can sign for it.
-        let (expected_nullifier, nullifier_bump) = Pubkey::find_program_address(
-            &[b"nullifier", &nullifier_hash],
-            program_id,
-        );
-        if nullifier_account.key != &expected_nullifier {
-            msg!("Invalid nullifier PDA");
-            return Err(ProgramError::InvalidArgument);
+    proptest! {
+        #[test]
+        fn pushed_root_stays_known_for_the_history(
+            start in 0..SIZE,
+            target in nonzero_root(),
+            later in proptest::collection::vec(root(), 0..2 * SIZE),
+        ) {
+            let mut state = state_at(start);
+            state.push_root(target, 0, 0);
+            for (i, root) in later.iter().enumerate() {
+                state.push_root(*root, 0, 0);
+                let within_history = i + 1 < SIZE;
+                let pushed_again = later[..=i].iter().rev().take(SIZE).any(|r| r == &target);
+                prop_assert_eq!(state.is_known_root(&target), within_history || pushed_again);
+            }
         }
 
-        let rent = Rent::get()?;
-        let lamports = rent.minimum_balance(0);
-        let create_ix = system_instruction::create_account(
-            payer.key,
-            nullifier_account.key,
-            lamports,
-            0,
-            &system_program.key, // system-owned marker
-        );
-        let nullifier_seeds: &[&[u8]] = &[b"nullifier", &nullifier_hash, &[nullifier_bump]];
-        invoke_signed(
-            &create_ix,
-            &[
-                payer.clone(),
-                nullifier_account.clone(),
-                system_program.clone(),
-            ],
-            &[nullifier_seeds],
-        )?;
+        #[test]
+        fn zero_root_is_never_known(
+            start in 0..SIZE,
+            roots in proptest::collection::vec(root(), 0..2 * SIZE),
+        ) {
+            let mut state = state_at(start);
+            prop_assert!(!state.is_known_root(&[0u8; 32]));
+            for root in roots {
+                state.push_root(root, 0, 0);
+                prop_assert!(!state.is_known_root(&[0u8; 32]));
+            }
+        }
+
+        #[test]
+        fn indices_stay_in_bounds(
+            start in 0..SIZE,
+            roots in proptest::collection::vec(root(), 0..3 * SIZE),
+        ) {
+            let mut state = state_at(start);
+            for root in &roots {
+                state.push_root(*root, 0, 0);
+                prop_assert!((state.current_root_index as usize) < SIZE);
+                prop_assert_eq!(state.roots[state.current_root_index as usize], *root);
+                if let Some(index) = state.root_index(root) {
+                    prop_assert!(index < SIZE);
+                    prop_assert_eq!(&state.roots[index], root);
+                }
+            }
+        }
+
+        #[test]
+        fn ring_buffer_matches_model(
+            start in 0..SIZE,
+            ops in proptest::collection::vec(op(), 0..4 * SIZE),
+        ) {
+            let mut state = state_at(start);
+            let mut model = Model { start, pushes: Vec::new() };
+            for op in ops {
+                match op {
+                    Op::Push(root) => {
+                        let pushes = model.pushes.len();
+                        state.push_root(root, pushes as i64, pushes as u64);
+                        model.pushes.push(root);
+                        let newest = (start + model.pushes.len()) % SIZE;
+                        prop_assert_eq!(state.current_root_index as usize, newest);
+                        prop_assert_eq!(state.root_times[newest], pushes as i64);
+                        prop_assert_eq!(state.root_slots[newest], pushes as u64);
+                    }
+                    Op::Lookup(root) => {
+                        prop_assert_eq!(state.root_index(&root), model.root_index(&root));
+                        prop_assert_eq!(state.is_known_root(&root), model.root_index(&root).is_some());
+                    }
+                }
+            }
+        }
     }
+}
 
-    // Build instruction data for the verifier: proof_bytes || public_witness_bytes
-    // The public_witness_bytes is the .pw file from Sunspot containing public inputs.
-    // According to Sunspot/Noir examples, the format is: proof || public_witness
-    // where public_witness contains root || nullifier_hash || recipient_field.
-    // The client should concatenate proof + public_witness before passing to this instruction.
-    // We pass the proof parameter directly to the verifier (it should already contain both).
-    let instruction_data = proof;
+/// Kani proofs for the arithmetic and decoding every instruction relies on.
+/// Unlike the property tests above they cover every input, not a sample.
+#[cfg(kani)]
+mod verification {
+    use super::*;
 
-    let verify_ix = solana_program::instruction::Instruction {
-        program_id: *verifier_program.key,
-        accounts: vec![],
-        data: instruction_data,
-    };
+    const SIZE: usize = MixerState::ROOT_HISTORY_SIZE;
+    /// Longer than every fixed-size instruction except `CreateVerifyingKey`.
+    const MAX_INSTRUCTION_LEN: usize = 120;
 
-    // CPI into verifier program
-    // NOTE: The verifier is expected to revert on invalid proofs.
-    invoke(&verify_ix, &[]).map_err(|_| MixerError::VerificationFailed)?;
+    fn empty_state() -> MixerState {
+        unpack_state(&[0u8; MixerState::LEN]).unwrap()
+    }
 
-    // Transfer funds from vault to recipient
-    // Vault must be the correct PDA so we can sign for it.
-    let (expected_vault, vault_bump) =
-        Pubkey::find_program_address(&[b"mixer_vault"], program_id);
-    if vault_account.key != &expected_vault {
-        msg!("Invalid vault PDA");
-        return Err(ProgramError::InvalidArgument);
+    /// A pool with fees inside the bounds `Initialize` and the admin actions
+    /// enforce.
+    fn fee_state() -> MixerState {
+        let mut state = empty_state();
+        state.denomination = kani::any();
+        state.protocol_fee_bps = kani::any();
+        state.relayer_fee_cap_bps = kani::any();
+        kani::assume(state.protocol_fee_bps <= admin::MAX_PROTOCOL_FEE_BPS);
+        kani::assume(state.relayer_fee_cap_bps <= admin::MAX_RELAYER_FEE_CAP_BPS);
+        state
     }
 
-    let transfer_ix = system_instruction::transfer(
-        vault_account.key,
-        recipient_account.key,
-        state.denomination,
-    );
-    let vault_seeds: &[&[u8]] = &[b"mixer_vault", &[vault_bump]];
-    invoke_signed(
-        &transfer_ix,
-        &[
-            vault_account.clone(),
-            recipient_account.clone(),
-            system_program.clone(),
-        ],
-        &[vault_seeds],
-    )?;
+    /// Whatever index it starts from, `push_root` leaves it in bounds and
+    /// the pushed root is the newest match.
+    #[kani::proof]
+    #[kani::unwind(31)]
+    fn push_root_keeps_the_index_in_bounds() {
+        let mut state = empty_state();
+        state.current_root_index = kani::any();
+        state.roots = kani::any();
+        let root: [u8; 32] = kani::any();
+        state.push_root(root, kani::any(), kani::any());
+        let index = state.current_root_index as usize;
+        assert!(index < SIZE);
+        if root != [0u8; 32] {
+            assert_eq!(state.root_index(&root), Some(index));
+        }
+    }
 
-    Ok(())
-}
+    /// `push_root` is the only writer of `current_root_index` after
+    /// `Initialize` zeroes it, so lookups may assume it is in bounds.
+    #[kani::proof]
+    #[kani::unwind(31)]
+    fn root_index_stays_in_bounds() {
+        let mut state = empty_state();
+        state.current_root_index = kani::any();
+        kani::assume((state.current_root_index as usize) < SIZE);
+        state.roots = kani::any();
+        let root: [u8; 32] = kani::any();
+        if let Some(index) = state.root_index(&root) {
+            assert!(index < SIZE);
+            assert_eq!(state.roots[index], root);
+            assert_ne!(root, [0u8; 32]);
+        }
+    }
+
+    #[kani::proof]
+    #[kani::unwind(122)]
+    fn instruction_unpack_never_panics() {
+        let input: [u8; MAX_INSTRUCTION_LEN] = kani::any();
+        let len: usize = kani::any();
+        kani::assume(len <= MAX_INSTRUCTION_LEN);
+        let _ = MixerInstruction::unpack(&input[..len]);
+    }
 
+    /// Decoding never reads past the data: data shorter than the baseline
+    /// state is an error and anything longer decodes, zero-filled up to
+    /// `LEN`.
+    #[kani::proof]
+    #[kani::unwind(31)]
+    fn unpack_state_checks_the_length() {
+        let data: [u8; MixerState::LEN + 1] = kani::any();
+        let len: usize = kani::any();
+        kani::assume(len <= data.len());
+        assert_eq!(unpack_state(&data[..len]).is_ok(), len >= MixerState::BASELINE_LEN);
+    }
 
+    /// The protocol fee never exceeds the denomination, so `withdraw_amount`
+    /// cannot underflow, and the two add back up to the denomination.
+    #[kani::proof]
+    fn withdraw_amount_and_fee_make_up_the_denomination() {
+        let state = fee_state();
+        let fee = state.protocol_fee();
+        assert!(fee <= state.denomination);
+        assert_eq!(state.withdraw_amount() + fee, state.denomination);
+    }
 
+    /// A bounty within `max_relayer_fee` never exceeds the withdraw amount,
+    /// so `ExecuteScheduledWithdraw` can always pay both keeper and recipient.
+    #[kani::proof]
+    fn relayer_fee_cap_fits_in_the_withdraw_amount() {
+        let state = fee_state();
+        assert!(state.max_relayer_fee() <= state.withdraw_amount());
+    }
+}
@@ -0,0 +1,369 @@
+//! Validation of BN254 points taken from proofs and verifying keys.
+//!
+//! The alt_bn128 syscalls validate their inputs, but how strictly depends on
+//! the syscall and the runtime version: the compression syscalls skip the
+//! subgroup check, and a coordinate at or above the modulus would make
+//! `negate_g1` wrap around before any syscall sees it. So the native verifier
+//! checks every point itself first:
+//!
+//! - each coordinate is canonical, i.e. below the base field modulus, so no
+//!   two encodings name the same point;
+//! - the point is not the identity, which no honest proof contains;
+//! - the point satisfies the curve equation, `y^2 = x^3 + 3` for G1 and
+//!   `y^2 = x^3 + 3 / (9 + u)` for G2.
+//!
+//! G1 has cofactor 1, so a G1 point on the curve is in the prime-order
+//! subgroup. G2 does not, and checking G2 membership in the program is far
+//! beyond a withdraw's compute budget. The pairing syscall deserializes its
+//! G2 inputs with full validation, subgroup check included, and rejects the
+//! proof otherwise; the tests in [`crate::verifier`] pin this down with an
+//! on-curve point outside the subgroup.
+//!
+//! Groth16 proofs stay malleable even when every point is valid: anyone can
+//! re-randomize a proof into another valid one for the same inputs. This is
+//! harmless because withdrawals are keyed by the nullifier hash, not by the
+//! proof bytes.
+
+use thiserror::Error;
+
+/// BN254 base field modulus, big-endian.
+pub const BASE_FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x97, 0x81, 0x6a, 0x91, 0x68, 0x71, 0xca, 0x8d, 0x3c, 0x20, 0x8c, 0x16, 0xd8, 0x7c, 0xfd, 0x47,
+];
+
+/// `3`, the constant of the G1 curve, big-endian.
+const G1_B: [u8; 32] = {
+    let mut b = [0u8; 32];
+    b[31] = 3;
+    b
+};
+
+/// `3 / (9 + u)`, the constant of the G2 twist, as `c1 || c0` big-endian.
+const TWIST_B: [u8; 64] = [
+    0x00, 0x97, 0x13, 0xb0, 0x3a, 0xf0, 0xfe, 0xd4, 0xcd, 0x2c, 0xaf, 0xad, 0xee, 0xd8, 0xfd, 0xf4,
+    0xa7, 0x4f, 0xa0, 0x84, 0xe5, 0x2d, 0x18, 0x52, 0xe4, 0xa2, 0xbd, 0x06, 0x85, 0xc3, 0x15, 0xd2,
+    0x2b, 0x14, 0x9d, 0x40, 0xce, 0xb8, 0xaa, 0xae, 0x81, 0xbe, 0x18, 0x99, 0x1b, 0xe0, 0x6a, 0xc3,
+    0xb5, 0xb4, 0xc5, 0xe5, 0x59, 0xdb, 0xef, 0xa3, 0x32, 0x67, 0xe6, 0xdc, 0x24, 0xa1, 0x38, 0xe5,
+];
+
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum PointError {
+    #[error("Coordinate is not below the base field modulus")]
+    NonCanonical,
+    #[error("Point is the identity")]
+    Identity,
+    #[error("Point is not on the curve")]
+    NotOnCurve,
+}
+
+/// Check a G1 point in the EIP-197 encoding, `x || y`.
+pub fn check_g1(point: &[u8; 64]) -> Result<(), PointError> {
+    let x = Fq::from_be_bytes(&point[..32])?;
+    let y = Fq::from_be_bytes(&point[32..])?;
+    if x == Fq::ZERO && y == Fq::ZERO {
+        return Err(PointError::Identity);
+    }
+    let b = Fq::from_be_bytes(&G1_B)?;
+    if y.square() != x.square().mul(&x).add(&b) {
+        return Err(PointError::NotOnCurve);
+    }
+    Ok(())
+}
+
+/// Check a G2 point in the EIP-197 encoding, `x1 || x0 || y1 || y0`. On
+/// success the point is on the curve, but see the module docs on subgroup
+/// membership.
+pub fn check_g2(point: &[u8; 128]) -> Result<(), PointError> {
+    let x = Fq2::from_be_bytes(&point[..64])?;
+    let y = Fq2::from_be_bytes(&point[64..])?;
+    if x == Fq2::ZERO && y == Fq2::ZERO {
+        return Err(PointError::Identity);
+    }
+    let b = Fq2::from_be_bytes(&TWIST_B)?;
+    if y.square() != x.square().mul(&x).add(&b) {
+        return Err(PointError::NotOnCurve);
+    }
+    Ok(())
+}
+
+/// Base field modulus as little-endian limbs.
+const MODULUS: [u64; 4] = [
+    0x3c208c16d87cfd47,
+    0x97816a916871ca8d,
+    0xb85045b68181585d,
+    0x30644e72e131a029,
+];
+
+/// `2^512 mod q`, to move values into Montgomery form.
+const R2: [u64; 4] = [
+    0xf32cfc5b538afa89,
+    0xb5e71911d44501fb,
+    0x47ab1eff0a417ff6,
+    0x06d89f71cab8351f,
+];
+
+/// `-q^-1 mod 2^64`.
+const INV: u64 = 0x87d20782e4866389;
+
+/// Base field element in Montgomery form, little-endian limbs. Only what the
+/// curve equations need.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Fq([u64; 4]);
+
+impl Fq {
+    const ZERO: Fq = Fq([0; 4]);
+
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, PointError> {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            *limb = u64::from_be_bytes(bytes[24 - 8 * i..32 - 8 * i].try_into().unwrap());
+        }
+        if !less_than(&limbs, &MODULUS) {
+            return Err(PointError::NonCanonical);
+        }
+        Ok(Fq(limbs).mul(&Fq(R2)))
+    }
+
+    fn add(&self, other: &Fq) -> Fq {
+        // Both are below q < 2^254, so the sum cannot overflow.
+        let (sum, _) = add_limbs(&self.0, &other.0);
+        if less_than(&sum, &MODULUS) {
+            Fq(sum)
+        } else {
+            Fq(sub_limbs(&sum, &MODULUS).0)
+        }
+    }
+
+    fn sub(&self, other: &Fq) -> Fq {
+        let (diff, borrow) = sub_limbs(&self.0, &other.0);
+        if borrow {
+            Fq(add_limbs(&diff, &MODULUS).0)
+        } else {
+            Fq(diff)
+        }
+    }
+
+    /// Montgomery multiplication (CIOS).
+    fn mul(&self, other: &Fq) -> Fq {
+        let (a, b) = (&self.0, &other.0);
+        let mut t = [0u64; 6];
+        for &bi in b {
+            let mut carry = 0u64;
+            for j in 0..4 {
+                let v = t[j] as u128 + a[j] as u128 * bi as u128 + carry as u128;
+                t[j] = v as u64;
+                carry = (v >> 64) as u64;
+            }
+            let v = t[4] as u128 + carry as u128;
+            t[4] = v as u64;
+            t[5] = (v >> 64) as u64;
+
+            let m = t[0].wrapping_mul(INV);
+            let v = t[0] as u128 + m as u128 * MODULUS[0] as u128;
+            let mut carry = (v >> 64) as u64;
+            for j in 1..4 {
+                let v = t[j] as u128 + m as u128 * MODULUS[j] as u128 + carry as u128;
+                t[j - 1] = v as u64;
+                carry = (v >> 64) as u64;
+            }
+            let v = t[4] as u128 + carry as u128;
+            t[3] = v as u64;
+            t[4] = t[5] + (v >> 64) as u64;
+        }
+        let out = [t[0], t[1], t[2], t[3]];
+        if t[4] != 0 || !less_than(&out, &MODULUS) {
+            Fq(sub_limbs(&out, &MODULUS).0)
+        } else {
+            Fq(out)
+        }
+    }
+
+    fn square(&self) -> Fq {
+        self.mul(self)
+    }
+}
+
+/// `c0 + c1 * u` with `u^2 = -1`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Fq2 {
+    c0: Fq,
+    c1: Fq,
+}
+
+impl Fq2 {
+    const ZERO: Fq2 = Fq2 { c0: Fq::ZERO, c1: Fq::ZERO };
+
+    /// From `c1 || c0`, the EIP-197 order.
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, PointError> {
+        Ok(Fq2 {
+            c1: Fq::from_be_bytes(&bytes[..32])?,
+            c0: Fq::from_be_bytes(&bytes[32..64])?,
+        })
+    }
+
+    fn add(&self, other: &Fq2) -> Fq2 {
+        Fq2 { c0: self.c0.add(&other.c0), c1: self.c1.add(&other.c1) }
+    }
+
+    fn mul(&self, other: &Fq2) -> Fq2 {
+        Fq2 {
+            c0: self.c0.mul(&other.c0).sub(&self.c1.mul(&other.c1)),
+            c1: self.c0.mul(&other.c1).add(&self.c1.mul(&other.c0)),
+        }
+    }
+
+    fn square(&self) -> Fq2 {
+        self.mul(self)
+    }
+}
+
+fn less_than(a: &[u64; 4], b: &[u64; 4]) -> bool {
+    for i in (0..4).rev() {
+        if a[i] != b[i] {
+            return a[i] < b[i];
+        }
+    }
+    false
+}
+
+fn add_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut out = [0u64; 4];
+    let mut carry = false;
+    for i in 0..4 {
+        let (s, c1) = a[i].overflowing_add(b[i]);
+        let (s, c2) = s.overflowing_add(carry as u64);
+        out[i] = s;
+        carry = c1 || c2;
+    }
+    (out, carry)
+}
+
+fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut out = [0u64; 4];
+    let mut borrow = false;
+    for i in 0..4 {
+        let (d, b1) = a[i].overflowing_sub(b[i]);
+        let (d, b2) = d.overflowing_sub(borrow as u64);
+        out[i] = d;
+        borrow = b1 || b2;
+    }
+    (out, borrow)
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::{Fq as ArkFq, Fq2 as ArkFq2, Fr, G1Affine, G2Affine};
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::{BigInteger, PrimeField};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn fq_bytes(value: &ArkFq) -> [u8; 32] {
+        value.into_bigint().to_bytes_be().try_into().unwrap()
+    }
+
+    fn g1_bytes(p: &G1Affine) -> [u8; 64] {
+        let mut out = [0u8; 64];
+        out[..32].copy_from_slice(&fq_bytes(&p.x));
+        out[32..].copy_from_slice(&fq_bytes(&p.y));
+        out
+    }
+
+    fn g2_bytes(p: &G2Affine) -> [u8; 128] {
+        let mut out = [0u8; 128];
+        out[..32].copy_from_slice(&fq_bytes(&p.x.c1));
+        out[32..64].copy_from_slice(&fq_bytes(&p.x.c0));
+        out[64..96].copy_from_slice(&fq_bytes(&p.y.c1));
+        out[96..].copy_from_slice(&fq_bytes(&p.y.c0));
+        out
+    }
+
+    /// `value + q`, which aliases `value` if it still fits in 256 bits.
+    fn alias(value: &[u8]) -> Option<[u8; 32]> {
+        let mut out = [0u8; 32];
+        let mut carry = 0u16;
+        for i in (0..32).rev() {
+            let s = value[i] as u16 + BASE_FIELD_MODULUS[i] as u16 + carry;
+            out[i] = s as u8;
+            carry = s >> 8;
+        }
+        (carry == 0).then_some(out)
+    }
+
+    fn scalar() -> impl Strategy<Value = Fr> {
+        any::<[u8; 32]>().prop_map(|b| Fr::from_be_bytes_mod_order(&b))
+    }
+
+    fn base() -> impl Strategy<Value = ArkFq> {
+        any::<[u8; 32]>().prop_map(|b| ArkFq::from_be_bytes_mod_order(&b))
+    }
+
+    #[test]
+    fn twist_constant_matches_ark() {
+        let b = <ark_bn254::g2::Config as ark_ec::short_weierstrass::SWCurveConfig>::COEFF_B;
+        let mut expected = [0u8; 64];
+        expected[..32].copy_from_slice(&fq_bytes(&b.c1));
+        expected[32..].copy_from_slice(&fq_bytes(&b.c0));
+        assert_eq!(TWIST_B, expected);
+    }
+
+    proptest! {
+        #[test]
+        fn accepts_group_points(k in scalar()) {
+            prop_assume!(k != Fr::from(0u64));
+            let p1 = (G1Affine::generator() * k).into_affine();
+            let p2 = (G2Affine::generator() * k).into_affine();
+            prop_assert_eq!(check_g1(&g1_bytes(&p1)), Ok(()));
+            prop_assert_eq!(check_g2(&g2_bytes(&p2)), Ok(()));
+        }
+
+        #[test]
+        fn g1_curve_check_matches_ark(x in base(), y in base()) {
+            let on_curve = G1Affine::new_unchecked(x, y).is_on_curve();
+            let mut bytes = [0u8; 64];
+            bytes[..32].copy_from_slice(&fq_bytes(&x));
+            bytes[32..].copy_from_slice(&fq_bytes(&y));
+            prop_assert_eq!(check_g1(&bytes).is_ok(), on_curve);
+        }
+
+        #[test]
+        fn g2_curve_check_matches_ark(x0 in base(), x1 in base(), k in scalar()) {
+            // Random x mostly has no point; perturb y of a real point too.
+            let p = (G2Affine::generator() * k).into_affine();
+            for (x, y) in [(ArkFq2::new(x0, x1), p.y), (p.x, p.y + ArkFq2::new(x0, x1))] {
+                let q = G2Affine::new_unchecked(x, y);
+                prop_assert_eq!(check_g2(&g2_bytes(&q)).is_ok(), q.is_on_curve() && !q.is_zero());
+            }
+        }
+
+        #[test]
+        fn rejects_aliased_coordinates(k in scalar(), coordinate in 0usize..6) {
+            prop_assume!(k != Fr::from(0u64));
+            let p1 = g1_bytes(&(G1Affine::generator() * k).into_affine());
+            let p2 = g2_bytes(&(G2Affine::generator() * k).into_affine());
+            if coordinate < 2 {
+                let at = 32 * coordinate;
+                if let Some(a) = alias(&p1[at..at + 32]) {
+                    let mut bad = p1;
+                    bad[at..at + 32].copy_from_slice(&a);
+                    prop_assert_eq!(check_g1(&bad), Err(PointError::NonCanonical));
+                }
+            } else {
+                let at = 32 * (coordinate - 2);
+                if let Some(a) = alias(&p2[at..at + 32]) {
+                    let mut bad = p2;
+                    bad[at..at + 32].copy_from_slice(&a);
+                    prop_assert_eq!(check_g2(&bad), Err(PointError::NonCanonical));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_identity() {
+        assert_eq!(check_g1(&[0u8; 64]), Err(PointError::Identity));
+        assert_eq!(check_g2(&[0u8; 128]), Err(PointError::Identity));
+    }
+}
@@ -0,0 +1,329 @@
+//! Public inputs of the withdraw circuit and their encodings.
+//!
+//! Withdraw instructions carry `root`, `nullifier_hash` and `recipient_field`
+//! as 32-byte **big-endian** BN254 scalar field elements, in that order. The
+//! native verifier reads them this way, and Sunspot's public witness stores
+//! them the same way behind a 12-byte gnark header:
+//!
+//! ```text
+//! u32 BE num_public || u32 BE num_secret (= 0) || u32 BE num_elements
+//!     || element[num_elements] (32 bytes BE each)
+//! ```
+//!
+//! A field is *canonical* if it is below the scalar field modulus. Anything
+//! that reaches a verifier has to be canonical. Values derived from hashes or
+//! keys can be brought into range with [`reduce`]. Clients that wrote
+//! little-endian values can convert them with [`from_le_bytes`]. With the
+//! `ark` feature, fields also convert to and from `ark_bn254::Fr`.
+//!
+//! These defaults are for BN254 pools. The `_in` variants take the pool's
+//! [`Curve`] and check against its scalar field instead.
+
+use thiserror::Error;
+
+use crate::{curve::Curve, verifier::NUM_PUBLIC_INPUTS};
+
+/// BN254 scalar field modulus, big-endian.
+pub const SCALAR_FIELD_MODULUS: [u8; 32] = [
+    0x30, 0x64, 0x4e, 0x72, 0xe1, 0x31, 0xa0, 0x29, 0xb8, 0x50, 0x45, 0xb6, 0x81, 0x81, 0x58, 0x5d,
+    0x28, 0x33, 0xe8, 0x48, 0x79, 0xb9, 0x70, 0x91, 0x43, 0xe1, 0xf5, 0x93, 0xf0, 0x00, 0x00, 0x01,
+];
+
+/// Length of the gnark header in a Sunspot public witness.
+pub const SUNSPOT_WITNESS_HEADER_LEN: usize = 12;
+
+/// Length of the withdraw circuit's Sunspot public witness (`.pw` file).
+pub const SUNSPOT_PUBLIC_WITNESS_LEN: usize = SUNSPOT_WITNESS_HEADER_LEN + 32 * NUM_PUBLIC_INPUTS;
+
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq)]
+pub enum PublicInputError {
+    #[error("Field element is not below the scalar field modulus")]
+    NonCanonical,
+    #[error("Public witness has the wrong length")]
+    WitnessLength,
+    #[error("Public witness header does not describe the withdraw circuit")]
+    WitnessHeader,
+}
+
+/// Whether `field`, read big-endian, is below the scalar field modulus.
+pub fn is_canonical(field: &[u8; 32]) -> bool {
+    // Byte arrays compare lexicographically, i.e. as big-endian integers.
+    field < &SCALAR_FIELD_MODULUS
+}
+
+/// `a - b` for big-endian 256-bit integers with `a >= b`.
+fn sub_be(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    let mut borrow = 0i16;
+    for i in (0..32).rev() {
+        let diff = a[i] as i16 - b[i] as i16 - borrow;
+        borrow = (diff < 0) as i16;
+        out[i] = (diff + 256 * borrow) as u8;
+    }
+    out
+}
+
+/// Reduce a big-endian 256-bit integer modulo the scalar field. Since
+/// `2^256 < 6r`, at most five subtractions are needed.
+pub fn reduce(value: &[u8; 32]) -> [u8; 32] {
+    reduce_in(Curve::Bn254, value)
+}
+
+/// [`reduce`] modulo `curve`'s scalar field.
+pub fn reduce_in(curve: Curve, value: &[u8; 32]) -> [u8; 32] {
+    let mut out = *value;
+    while !curve.is_canonical(&out) {
+        out = sub_be(&out, curve.scalar_field_modulus());
+    }
+    out
+}
+
+/// Convert a little-endian field element to the big-endian encoding,
+/// rejecting out-of-range values rather than silently reducing them.
+pub fn from_le_bytes(bytes: &[u8; 32]) -> Result<[u8; 32], PublicInputError> {
+    let mut field = *bytes;
+    field.reverse();
+    if !is_canonical(&field) {
+        return Err(PublicInputError::NonCanonical);
+    }
+    Ok(field)
+}
+
+/// The withdraw circuit's public inputs, all canonical.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicInputs {
+    pub root: [u8; 32],
+    pub nullifier_hash: [u8; 32],
+    pub recipient_field: [u8; 32],
+}
+
+impl PublicInputs {
+    pub fn new(
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient_field: [u8; 32],
+    ) -> Result<Self, PublicInputError> {
+        Self::from_array([root, nullifier_hash, recipient_field])
+    }
+
+    /// Inputs in circuit order: root, nullifier hash, recipient field.
+    pub fn from_array(fields: [[u8; 32]; NUM_PUBLIC_INPUTS]) -> Result<Self, PublicInputError> {
+        Self::from_array_in(Curve::Bn254, fields)
+    }
+
+    /// [`Self::from_array`] for a pool over `curve`.
+    pub fn from_array_in(
+        curve: Curve,
+        fields: [[u8; 32]; NUM_PUBLIC_INPUTS],
+    ) -> Result<Self, PublicInputError> {
+        if !fields.iter().all(|field| curve.is_canonical(field)) {
+            return Err(PublicInputError::NonCanonical);
+        }
+        let [root, nullifier_hash, recipient_field] = fields;
+        Ok(PublicInputs { root, nullifier_hash, recipient_field })
+    }
+
+    pub fn to_array(&self) -> [[u8; 32]; NUM_PUBLIC_INPUTS] {
+        [self.root, self.nullifier_hash, self.recipient_field]
+    }
+
+    /// Parse a Sunspot public witness.
+    pub fn from_sunspot_witness(data: &[u8]) -> Result<Self, PublicInputError> {
+        Self::from_sunspot_witness_in(Curve::Bn254, data)
+    }
+
+    /// [`Self::from_sunspot_witness`] for a pool over `curve`. gnark uses the
+    /// same header on every curve, and both scalar fields fit in 32 bytes.
+    /// Sunspot withdraws parse their trailing witness with this.
+    pub fn from_sunspot_witness_in(curve: Curve, data: &[u8]) -> Result<Self, PublicInputError> {
+        if data.len() != SUNSPOT_PUBLIC_WITNESS_LEN {
+            return Err(PublicInputError::WitnessLength);
+        }
+        let (header, elements) = data.split_at(SUNSPOT_WITNESS_HEADER_LEN);
+        if header != sunspot_witness_header() {
+            return Err(PublicInputError::WitnessHeader);
+        }
+        let mut fields = [[0u8; 32]; NUM_PUBLIC_INPUTS];
+        for (field, element) in fields.iter_mut().zip(elements.chunks_exact(32)) {
+            field.copy_from_slice(element);
+        }
+        Self::from_array_in(curve, fields)
+    }
+
+    pub fn to_sunspot_witness(&self) -> [u8; SUNSPOT_PUBLIC_WITNESS_LEN] {
+        let mut out = [0u8; SUNSPOT_PUBLIC_WITNESS_LEN];
+        out[..SUNSPOT_WITNESS_HEADER_LEN].copy_from_slice(&sunspot_witness_header());
+        for (chunk, field) in out[SUNSPOT_WITNESS_HEADER_LEN..]
+            .chunks_exact_mut(32)
+            .zip(self.to_array())
+        {
+            chunk.copy_from_slice(&field);
+        }
+        out
+    }
+}
+
+fn sunspot_witness_header() -> [u8; SUNSPOT_WITNESS_HEADER_LEN] {
+    let mut header = [0u8; SUNSPOT_WITNESS_HEADER_LEN];
+    header[..4].copy_from_slice(&(NUM_PUBLIC_INPUTS as u32).to_be_bytes());
+    header[8..].copy_from_slice(&(NUM_PUBLIC_INPUTS as u32).to_be_bytes());
+    header
+}
+
+#[cfg(any(feature = "ark", test))]
+mod ark {
+    use ark_bn254::Fr;
+    use ark_ff::{BigInteger, PrimeField};
+
+    use super::{is_canonical, PublicInputError, PublicInputs};
+    use crate::verifier::NUM_PUBLIC_INPUTS;
+
+    /// Strict conversion: `field` must be canonical.
+    pub fn field_to_fr(field: &[u8; 32]) -> Result<Fr, PublicInputError> {
+        if !is_canonical(field) {
+            return Err(PublicInputError::NonCanonical);
+        }
+        Ok(Fr::from_be_bytes_mod_order(field))
+    }
+
+    /// Conversion that reduces `value` modulo the scalar field.
+    pub fn field_to_fr_reduced(value: &[u8; 32]) -> Fr {
+        Fr::from_be_bytes_mod_order(value)
+    }
+
+    pub fn fr_to_field(value: &Fr) -> [u8; 32] {
+        value.into_bigint().to_bytes_be().try_into().unwrap()
+    }
+
+    impl PublicInputs {
+        pub fn to_fr(&self) -> [Fr; NUM_PUBLIC_INPUTS] {
+            // Canonical by construction.
+            self.to_array().map(|field| Fr::from_be_bytes_mod_order(&field))
+        }
+
+        pub fn from_fr(values: &[Fr; NUM_PUBLIC_INPUTS]) -> Self {
+            let [root, nullifier_hash, recipient_field] = values.map(|v| fr_to_field(&v));
+            PublicInputs { root, nullifier_hash, recipient_field }
+        }
+    }
+}
+
+#[cfg(any(feature = "ark", test))]
+pub use ark::{field_to_fr, field_to_fr_reduced, fr_to_field};
+
+#[cfg(test)]
+mod tests {
+    use ark_bn254::Fr;
+    use ark_ff::{BigInteger, PrimeField};
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Big-endian integers near and above the modulus are where encodings
+    /// go wrong, so mix them in with uniform values.
+    fn field_bytes() -> impl Strategy<Value = [u8; 32]> {
+        prop_oneof![
+            any::<[u8; 32]>(),
+            (0u8..=2).prop_map(|d| {
+                let mut v = SCALAR_FIELD_MODULUS;
+                v[31] = v[31].wrapping_add(d).wrapping_sub(1);
+                v
+            }),
+            Just([0xff; 32]),
+            Just([0u8; 32]),
+        ]
+    }
+
+    fn canonical() -> impl Strategy<Value = [u8; 32]> {
+        field_bytes().prop_map(|v| reduce(&v))
+    }
+
+    fn ark_bytes(value: &Fr) -> [u8; 32] {
+        value.into_bigint().to_bytes_be().try_into().unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn canonical_matches_ark(v in field_bytes()) {
+            let roundtrips = ark_bytes(&Fr::from_be_bytes_mod_order(&v)) == v;
+            prop_assert_eq!(is_canonical(&v), roundtrips);
+        }
+
+        #[test]
+        fn reduce_matches_ark(v in field_bytes()) {
+            let reduced = reduce(&v);
+            prop_assert!(is_canonical(&reduced));
+            prop_assert_eq!(reduced, ark_bytes(&Fr::from_be_bytes_mod_order(&v)));
+            prop_assert_eq!(fr_to_field(&field_to_fr_reduced(&v)), reduced);
+        }
+
+        #[test]
+        fn strict_conversion_rejects_overflow(v in field_bytes()) {
+            match field_to_fr(&v) {
+                Ok(fr) => prop_assert_eq!(fr_to_field(&fr), v),
+                Err(e) => {
+                    prop_assert_eq!(e, PublicInputError::NonCanonical);
+                    prop_assert!(!is_canonical(&v));
+                }
+            }
+        }
+
+        #[test]
+        fn le_conversion_reverses_bytes(v in field_bytes()) {
+            let mut le = v;
+            le.reverse();
+            match from_le_bytes(&le) {
+                Ok(be) => prop_assert_eq!(be, v),
+                Err(_) => prop_assert!(!is_canonical(&v)),
+            }
+        }
+
+        #[test]
+        fn sunspot_witness_roundtrips(root in canonical(), nh in canonical(), rf in canonical()) {
+            let inputs = PublicInputs::new(root, nh, rf).unwrap();
+            let witness = inputs.to_sunspot_witness();
+            prop_assert_eq!(PublicInputs::from_sunspot_witness(&witness), Ok(inputs));
+            prop_assert_eq!(PublicInputs::from_fr(&inputs.to_fr()), inputs);
+        }
+
+        #[test]
+        fn bls12_381_reduce_matches_ark(v in field_bytes()) {
+            use ark_bls12_381::Fr as BlsFr;
+            let reduced = reduce_in(Curve::Bls12_381, &v);
+            prop_assert!(Curve::Bls12_381.is_canonical(&reduced));
+            let expected: [u8; 32] =
+                BlsFr::from_be_bytes_mod_order(&v).into_bigint().to_bytes_be().try_into().unwrap();
+            prop_assert_eq!(reduced, expected);
+        }
+
+        #[test]
+        fn witness_checks_the_pool_curve(root in field_bytes(), nh in canonical(), rf in canonical()) {
+            let inputs = PublicInputs { root, nullifier_hash: nh, recipient_field: rf };
+            let witness = inputs.to_sunspot_witness();
+            for curve in [Curve::Bn254, Curve::Bls12_381] {
+                let parsed = PublicInputs::from_sunspot_witness_in(curve, &witness);
+                prop_assert_eq!(parsed.is_ok(), curve.is_canonical(&root));
+            }
+        }
+
+        #[test]
+        fn sunspot_witness_rejects_overflow(root in field_bytes(), nh in canonical(), rf in canonical()) {
+            let inputs = PublicInputs { root, nullifier_hash: nh, recipient_field: rf };
+            let parsed = PublicInputs::from_sunspot_witness(&inputs.to_sunspot_witness());
+            prop_assert_eq!(parsed.is_ok(), is_canonical(&root));
+        }
+    }
+
+    #[test]
+    fn sunspot_witness_checks_header_and_length() {
+        let inputs = PublicInputs::new([1; 32], [2; 32], [3; 32]).unwrap();
+        let witness = inputs.to_sunspot_witness();
+        assert_eq!(
+            PublicInputs::from_sunspot_witness(&witness[1..]),
+            Err(PublicInputError::WitnessLength)
+        );
+        let mut bad = witness;
+        bad[3] = 2;
+        assert_eq!(PublicInputs::from_sunspot_witness(&bad), Err(PublicInputError::WitnessHeader));
+    }
+}
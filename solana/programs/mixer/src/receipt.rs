@@ -0,0 +1,174 @@
+//! Deposit receipts as compressed NFTs.
+//!
+//! A pool whose authority set a receipt tree (`SetReceiptTree`) mints a
+//! Bubblegum cNFT for every `PushRoot` that asks for one, to an owner the
+//! depositor chooses. The receipt holds only the pool's state account and
+//! the deposit's leaf index, for book-keeping that has to show a deposit
+//! was made. The leaf index is the pool's leaf count after the push, less
+//! one, plus the leaves of the pool's earlier trees; like the root itself,
+//! it is not checked against the commitment.
+//!
+//! A receipt links the deposit to its owner in public, which is what a
+//! deposit into the pool otherwise avoids, so receipts are never minted
+//! unless the depositor passes the flag and accounts for one. Withdrawals
+//! are not affected: a receipt does not name the note's nullifier.
+//!
+//! The tree is created through Bubblegum by the authority, which then makes
+//! the `["receipt_authority"]` PDA its tree delegate so the program can
+//! mint into it.
+
+use mpl_bubblegum::{
+    instructions::{MintV1Cpi, MintV1CpiAccounts, MintV1InstructionArgs},
+    types::{MetadataArgs, TokenProgramVersion, TokenStandard},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{MixerError, MixerState};
+
+/// Seed of the PDA that mints receipts as the tree's delegate.
+pub const RECEIPT_AUTHORITY_SEED: &[u8] = b"receipt_authority";
+
+/// The program's receipt authority.
+pub fn find_receipt_authority(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_AUTHORITY_SEED], program_id)
+}
+
+/// SPL Noop, Bubblegum's log wrapper for V1 trees.
+pub const LOG_WRAPPER_ID: Pubkey =
+    solana_program::pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+/// SPL Account Compression, which owns V1 trees.
+pub const COMPRESSION_PROGRAM_ID: Pubkey =
+    solana_program::pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+
+/// Metadata of the receipt for the deposit at `leaf_index` into the pool at
+/// `mixer_state`. The URI is not fetchable; it only carries the two values.
+pub fn receipt_metadata(mixer_state: &Pubkey, leaf_index: u64) -> MetadataArgs {
+    MetadataArgs {
+        name: format!("Deposit {leaf_index}"),
+        symbol: "MIXER".into(),
+        uri: format!("mixer-receipt:{mixer_state}/{leaf_index}"),
+        seller_fee_basis_points: 0,
+        primary_sale_happened: true,
+        is_mutable: false,
+        edition_nonce: None,
+        token_standard: Some(TokenStandard::NonFungible),
+        collection: None,
+        uses: None,
+        token_program_version: TokenProgramVersion::Original,
+        creators: Vec::new(),
+    }
+}
+
+/// Mint the receipt for the deposit at `leaf_index`, paid by `payer`, from
+/// the receipt accounts of `PushRoot`.
+pub(crate) fn mint_receipt<'a, 'info>(
+    program_id: &Pubkey,
+    state: &MixerState,
+    state_account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    leaf_index: u64,
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'info>>,
+) -> ProgramResult
+where
+    'info: 'a,
+{
+    let leaf_owner = next_account_info(account_info_iter)?;
+    let receipt_authority = next_account_info(account_info_iter)?;
+    let tree_config = next_account_info(account_info_iter)?;
+    let merkle_tree = next_account_info(account_info_iter)?;
+    let log_wrapper = next_account_info(account_info_iter)?;
+    let compression_program = next_account_info(account_info_iter)?;
+    let bubblegum_program = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    if state.receipt_tree == Pubkey::default() {
+        return Err(MixerError::ReceiptsDisabled.into());
+    }
+    if merkle_tree.key != &state.receipt_tree {
+        msg!("Merkle tree is not the pool's receipt tree");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (expected_authority, bump) = find_receipt_authority(program_id);
+    if receipt_authority.key != &expected_authority {
+        msg!("Invalid receipt authority PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if bubblegum_program.key != &mpl_bubblegum::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    MintV1Cpi::new(
+        bubblegum_program,
+        MintV1CpiAccounts {
+            tree_config,
+            leaf_owner,
+            leaf_delegate: leaf_owner,
+            merkle_tree,
+            payer,
+            tree_creator_or_delegate: receipt_authority,
+            log_wrapper,
+            compression_program,
+            system_program,
+        },
+        MintV1InstructionArgs {
+            metadata: receipt_metadata(state_account.key, leaf_index),
+        },
+    )
+    .invoke_signed(&[&[RECEIPT_AUTHORITY_SEED, &[bump]]])
+}
+
+/// Ask `instruction`, a `PushRoot`, to mint a receipt into `receipt_tree`
+/// owned by `leaf_owner`.
+///
+/// # Panics
+///
+/// If `instruction` carries no leaf count.
+pub fn with_receipt(
+    mut instruction: Instruction,
+    receipt_tree: &Pubkey,
+    leaf_owner: &Pubkey,
+) -> Instruction {
+    assert!(
+        matches!(
+            crate::MixerInstruction::unpack(&instruction.data),
+            Ok(crate::MixerInstruction::PushRoot { leaf_count: Some(_), receipt: false, .. })
+        ),
+        "a receipt needs a PushRoot with a leaf count"
+    );
+    instruction.data.push(1);
+    let tree_config = mpl_bubblegum::accounts::TreeConfig::find_pda(receipt_tree).0;
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(*leaf_owner, false),
+        AccountMeta::new_readonly(find_receipt_authority(&instruction.program_id).0, false),
+        AccountMeta::new(tree_config, false),
+        AccountMeta::new(*receipt_tree, false),
+        AccountMeta::new_readonly(LOG_WRAPPER_ID, false),
+        AccountMeta::new_readonly(COMPRESSION_PROGRAM_ID, false),
+        AccountMeta::new_readonly(mpl_bubblegum::ID, false),
+        AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+    ]);
+    instruction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_fits_bubblegum_limits() {
+        // Bubblegum rejects names over 32 bytes, symbols over 10 and URIs
+        // over 200.
+        let metadata = receipt_metadata(&Pubkey::new_from_array([0xff; 32]), u64::MAX);
+        assert!(metadata.name.len() <= 32);
+        assert!(metadata.symbol.len() <= 10);
+        assert!(metadata.uri.len() <= 200);
+    }
+}
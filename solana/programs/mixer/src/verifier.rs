@@ -0,0 +1,691 @@
+//! Proof verification backends.
+//!
+//! Each pool selects a [`VerifierBackend`], and the withdraw path dispatches on
+//! it, so a pool can move to a different circuit toolchain with a timelocked
+//! `SetVerifierBackend` instead of a program redeploy.
+//!
+//! - [`VerifierBackend::SunspotCpi`] CPIs into a Sunspot-generated verifier
+//!   program with `proof_bytes || public_witness_bytes`. The verifier is
+//!   expected to fail the transaction on an invalid proof. It only checks the
+//!   proof against the witness, so the mixer first parses the witness with
+//!   [`PublicInputs::from_sunspot_witness_in`](crate::public_inputs::PublicInputs::from_sunspot_witness_in)
+//!   and requires it to match the instruction's public inputs.
+//! - [`VerifierBackend::UltraHonkCpi`] CPIs into an UltraHonk verifier
+//!   program. The proof only fits in a proof buffer, which is passed as the
+//!   verifier's single read-only account (the proof follows its 32-byte
+//!   header). The instruction data is `root || nullifier_hash ||
+//!   recipient_field`, taken from the withdraw instruction. UltraHonk needs no
+//!   trusted setup but costs far more compute than Groth16.
+//! - [`VerifierBackend::NativeGroth16`] checks a Groth16 proof in this program
+//!   with the alt_bn128 syscalls, against a [`Groth16VerifyingKey`] stored in
+//!   a mixer-owned account. The public inputs are taken from the instruction
+//!   (root, nullifier hash, recipient field), read as big-endian scalars.
+//!   The proof may be sent compressed, in half the bytes, and is then
+//!   decompressed with the alt_bn128 compression syscalls.
+//!
+//! A pool can also register up to [`MAX_CIRCUIT_VERSIONS`] extra
+//! [`CircuitVersion`]s, each with its own backend and verifier and a slot
+//! window. Every withdraw names the version its proof targets, so the old
+//! and new circuits both verify across an upgrade. Version 0 always means
+//! the pool's own `verifier_backend` and `verifier_program`.
+//!
+//! `MixerState::verifier_hash` binds the default verifier to a known
+//! artifact. The native backend checks it on every withdraw. For the CPI
+//! backends, hashing the verifier program is too costly per withdraw, so
+//! anyone can run `VerifyVkBinding`, which pauses the pool on a mismatch.
+//!
+//! Points use the EIP-197 encoding the syscalls expect: big-endian `x || y`
+//! for G1, and `x1 || x0 || y1 || y0` for G2.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_bn254::{
+    compression::prelude::{alt_bn128_g1_decompress_be, alt_bn128_g2_decompress_be},
+    prelude::{alt_bn128_g1_addition_be, alt_bn128_g1_multiplication_be, alt_bn128_pairing_be},
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash::hash,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{rent::Rent, Sysvar},
+};
+use solana_system_interface::instruction as system_instruction;
+
+use crate::{
+    admin::check_authority,
+    events, load_state,
+    points::{check_g1, check_g2, PointError, BASE_FIELD_MODULUS},
+    public_inputs::is_canonical,
+    store_state, MixerError,
+};
+
+pub const VERIFYING_KEY_SEED: &[u8] = b"verifying_key";
+
+/// Public inputs of the withdraw circuit: root, nullifier hash, recipient.
+pub const NUM_PUBLIC_INPUTS: usize = 3;
+
+/// Length of a native Groth16 proof: `a (G1) || b (G2) || c (G1)`.
+pub const GROTH16_PROOF_LEN: usize = 64 + 128 + 64;
+
+/// Length of a compressed native Groth16 proof. Each point is its x
+/// coordinate, with the sign of y and the identity flag in the two top bits
+/// of the first byte: the alt_bn128 compression syscalls' encoding.
+pub const GROTH16_COMPRESSED_PROOF_LEN: usize = 32 + 64 + 32;
+
+/// How a pool verifies withdraw proofs; stored as `MixerState::verifier_backend`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum VerifierBackend {
+    SunspotCpi = 0,
+    NativeGroth16 = 1,
+    UltraHonkCpi = 2,
+}
+
+impl TryFrom<u8> for VerifierBackend {
+    type Error = MixerError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(VerifierBackend::SunspotCpi),
+            1 => Ok(VerifierBackend::NativeGroth16),
+            2 => Ok(VerifierBackend::UltraHonkCpi),
+            _ => Err(MixerError::InvalidVerifier),
+        }
+    }
+}
+
+/// Circuit versions a pool can register besides its default (version 0).
+pub const MAX_CIRCUIT_VERSIONS: usize = 4;
+
+/// A registered circuit version; one slot of `MixerState::circuit_versions`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CircuitVersion {
+    /// Version withdraws name to select this entry. Zero marks an empty slot.
+    pub version: u16,
+    /// A [`VerifierBackend`].
+    pub backend: u8,
+    /// As `MixerState::verifier_program`, for this version.
+    pub verifier: Pubkey,
+    /// First slot at which proofs for this version are accepted.
+    pub activation_slot: u64,
+    /// First slot at which they are no longer accepted; `u64::MAX` for none.
+    pub deprecation_slot: u64,
+}
+
+impl CircuitVersion {
+    pub const LEN: usize = 2 + 1 + 32 + 8 + 8;
+
+    pub fn is_active(&self, slot: u64) -> bool {
+        self.activation_slot <= slot && slot < self.deprecation_slot
+    }
+
+    pub(crate) fn unpack(data: &[u8]) -> Self {
+        Self {
+            version: u16::from_le_bytes(data[0..2].try_into().unwrap()),
+            backend: data[2],
+            verifier: Pubkey::new_from_array(data[3..35].try_into().unwrap()),
+            activation_slot: u64::from_le_bytes(data[35..43].try_into().unwrap()),
+            deprecation_slot: u64::from_le_bytes(data[43..51].try_into().unwrap()),
+        }
+    }
+
+    pub(crate) fn pack(&self, data: &mut [u8]) {
+        data[0..2].copy_from_slice(&self.version.to_le_bytes());
+        data[2] = self.backend;
+        data[3..35].copy_from_slice(self.verifier.as_ref());
+        data[35..43].copy_from_slice(&self.activation_slot.to_le_bytes());
+        data[43..51].copy_from_slice(&self.deprecation_slot.to_le_bytes());
+    }
+}
+
+/// Groth16 verifying key for the withdraw circuit.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Groth16VerifyingKey {
+    pub alpha_g1: [u8; 64],
+    pub beta_g2: [u8; 128],
+    pub gamma_g2: [u8; 128],
+    pub delta_g2: [u8; 128],
+    /// `ic[0] + sum(input_i * ic[i + 1])` is the public input commitment.
+    pub ic: [[u8; 64]; NUM_PUBLIC_INPUTS + 1],
+}
+
+impl Groth16VerifyingKey {
+    pub const LEN: usize = 64 + 128 * 3 + 64 * (NUM_PUBLIC_INPUTS + 1);
+
+    pub fn find_address(program_id: &Pubkey, vk_id: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[VERIFYING_KEY_SEED, &vk_id.to_le_bytes()], program_id)
+    }
+
+    /// Load a verifying key account; it must be owned by the mixer program.
+    pub(crate) fn load(program_id: &Pubkey, account: &AccountInfo) -> Result<Self, ProgramError> {
+        if account.owner != program_id {
+            msg!("Verifying key account is not owned by the mixer");
+            return Err(MixerError::InvalidVerifier.into());
+        }
+        let data = account.data.borrow();
+        Self::deserialize(&mut &data[..]).map_err(|_| MixerError::InvalidVerifier.into())
+    }
+
+    /// Check every point of the key, as for proof points.
+    pub fn check_points(&self) -> Result<(), PointError> {
+        check_g1(&self.alpha_g1)?;
+        check_g2(&self.beta_g2)?;
+        check_g2(&self.gamma_g2)?;
+        check_g2(&self.delta_g2)?;
+        self.ic.iter().try_for_each(check_g1)
+    }
+}
+
+/// Size of the metadata before the bytecode in an upgradeable program's
+/// program data account (`UpgradeableLoaderState::size_of_programdata_metadata`).
+const PROGRAMDATA_METADATA_LEN: usize = 45;
+
+/// Hash compared against `MixerState::verifier_hash`. For the native backend
+/// this is the SHA-256 of the verifying key account data. For CPI backends it
+/// is the SHA-256 of the program data account's bytecode with trailing zero
+/// padding removed, the same hash `mixer-deploy verify` prints.
+pub fn verifier_account_hash(backend: VerifierBackend, data: &[u8]) -> [u8; 32] {
+    let bytes = match backend {
+        VerifierBackend::NativeGroth16 => data,
+        VerifierBackend::SunspotCpi | VerifierBackend::UltraHonkCpi => {
+            let code = data.get(PROGRAMDATA_METADATA_LEN..).unwrap_or_default();
+            let len = code.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+            &code[..len]
+        }
+    };
+    hash(bytes).to_bytes()
+}
+
+/// Fail unless `expected` is unset or matches the verifying key `account`.
+pub(crate) fn check_verifier_hash(expected: &[u8; 32], account: &AccountInfo) -> ProgramResult {
+    if expected == &[0u8; 32] {
+        return Ok(());
+    }
+    let actual = verifier_account_hash(VerifierBackend::NativeGroth16, &account.data.borrow());
+    if &actual != expected {
+        msg!("Verifying key does not match the pool's verifier hash");
+        return Err(MixerError::InvalidVerifier.into());
+    }
+    Ok(())
+}
+
+/// Negate a G1 point: `(x, y) -> (x, q - y)`, with the identity unchanged.
+fn negate_g1(point: &[u8]) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    out[..32].copy_from_slice(&point[..32]);
+    let y: [u8; 32] = point[32..64].try_into().unwrap();
+    if y == [0u8; 32] {
+        return out;
+    }
+    let mut borrow = 0u16;
+    for i in (0..32).rev() {
+        let diff = BASE_FIELD_MODULUS[i] as i16 - y[i] as i16 - borrow as i16;
+        if diff < 0 {
+            out[32 + i] = (diff + 256) as u8;
+            borrow = 1;
+        } else {
+            out[32 + i] = diff as u8;
+            borrow = 0;
+        }
+    }
+    out
+}
+
+/// Expand a compressed proof (see [`GROTH16_COMPRESSED_PROOF_LEN`]).
+///
+/// The x coordinates of the result must equal the compressed ones with the
+/// flag bits cleared, so an x at or above the modulus can't decompress to
+/// the same point as its canonical form.
+pub fn decompress_groth16_proof(proof: &[u8]) -> Result<[u8; GROTH16_PROOF_LEN], MixerError> {
+    if proof.len() != GROTH16_COMPRESSED_PROOF_LEN {
+        return Err(MixerError::VerificationFailed);
+    }
+    let mut out = [0u8; GROTH16_PROOF_LEN];
+    let a = alt_bn128_g1_decompress_be(&proof[..32]).map_err(|_| MixerError::MalformedProof)?;
+    let b = alt_bn128_g2_decompress_be(&proof[32..96]).map_err(|_| MixerError::MalformedProof)?;
+    let c = alt_bn128_g1_decompress_be(&proof[96..]).map_err(|_| MixerError::MalformedProof)?;
+    out[..64].copy_from_slice(&a);
+    out[64..192].copy_from_slice(&b);
+    out[192..].copy_from_slice(&c);
+
+    for (compressed, x) in [(&proof[..32], &a[..32]), (&proof[32..96], &b[..64]), (&proof[96..], &c[..32])] {
+        if compressed[0] & 0x3f != x[0] || compressed[1..] != x[1..] {
+            msg!("Compressed proof point is not canonically encoded");
+            return Err(MixerError::MalformedProof);
+        }
+    }
+    Ok(out)
+}
+
+/// Check the points of an uncompressed proof; see [`crate::points`].
+pub fn check_groth16_proof(proof: &[u8; GROTH16_PROOF_LEN]) -> Result<(), MixerError> {
+    let report = |point: &str, e: PointError| {
+        msg!("Proof point {} is malformed: {}", point, e);
+        MixerError::MalformedProof
+    };
+    check_g1(proof[..64].try_into().unwrap()).map_err(|e| report("A", e))?;
+    check_g2(proof[64..192].try_into().unwrap()).map_err(|e| report("B", e))?;
+    check_g1(proof[192..].try_into().unwrap()).map_err(|e| report("C", e))?;
+    Ok(())
+}
+
+/// Verify a Groth16 `proof` for `public_inputs` against `vk`. The proof may
+/// be uncompressed or compressed, told apart by its length.
+pub fn verify_groth16(
+    vk: &Groth16VerifyingKey,
+    proof: &[u8],
+    public_inputs: &[[u8; 32]; NUM_PUBLIC_INPUTS],
+) -> Result<(), MixerError> {
+    let decompressed;
+    let proof: &[u8; GROTH16_PROOF_LEN] = match proof.len() {
+        GROTH16_PROOF_LEN => proof.try_into().unwrap(),
+        GROTH16_COMPRESSED_PROOF_LEN => {
+            decompressed = decompress_groth16_proof(proof)?;
+            &decompressed
+        }
+        _ => {
+            msg!(
+                "Groth16 proof must be {} bytes, or {} compressed",
+                GROTH16_PROOF_LEN,
+                GROTH16_COMPRESSED_PROOF_LEN
+            );
+            return Err(MixerError::VerificationFailed);
+        }
+    };
+    check_groth16_proof(proof)?;
+    let (a, rest) = proof.split_at(64);
+    let (b, c) = rest.split_at(128);
+
+    let mut vk_x = vk.ic[0];
+    for (input, ic) in public_inputs.iter().zip(&vk.ic[1..]) {
+        if !is_canonical(input) {
+            msg!("Public input is not a canonical field element");
+            return Err(MixerError::VerificationFailed);
+        }
+        let mut mul_input = [0u8; 96];
+        mul_input[..64].copy_from_slice(ic);
+        mul_input[64..].copy_from_slice(input);
+        let term =
+            alt_bn128_g1_multiplication_be(&mul_input).map_err(|_| MixerError::VerificationFailed)?;
+        let mut add_input = [0u8; 128];
+        add_input[..64].copy_from_slice(&vk_x);
+        add_input[64..].copy_from_slice(&term);
+        let sum = alt_bn128_g1_addition_be(&add_input).map_err(|_| MixerError::VerificationFailed)?;
+        vk_x.copy_from_slice(&sum);
+    }
+
+    // e(-a, b) * e(alpha, beta) * e(vk_x, gamma) * e(c, delta) == 1
+    let mut pairing_input = Vec::with_capacity(4 * 192);
+    pairing_input.extend_from_slice(&negate_g1(a));
+    pairing_input.extend_from_slice(b);
+    pairing_input.extend_from_slice(&vk.alpha_g1);
+    pairing_input.extend_from_slice(&vk.beta_g2);
+    pairing_input.extend_from_slice(&vk_x);
+    pairing_input.extend_from_slice(&vk.gamma_g2);
+    pairing_input.extend_from_slice(c);
+    pairing_input.extend_from_slice(&vk.delta_g2);
+    let result =
+        alt_bn128_pairing_be(&pairing_input).map_err(|_| MixerError::VerificationFailed)?;
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    if result != one {
+        msg!("Groth16 pairing check failed");
+        return Err(MixerError::VerificationFailed);
+    }
+    Ok(())
+}
+
+pub(crate) fn process_create_verifying_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vk_id: u64,
+    vk: Box<Groth16VerifyingKey>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority = next_account_info(account_info_iter)?;
+    let state_account = next_account_info(account_info_iter)?;
+    let vk_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_account)?;
+    check_authority(&state, authority)?;
+    if let Err(e) = vk.check_points() {
+        msg!("Verifying key point is malformed: {}", e);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (expected_vk, bump) = Groth16VerifyingKey::find_address(program_id, vk_id);
+    if vk_account.key != &expected_vk {
+        msg!("Invalid verifying key PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Creation fails if the account exists, so a key can never be rewritten
+    // under a pool that has it pinned.
+    let rent = Rent::get()?;
+    let create_ix = system_instruction::create_account(
+        authority.key,
+        vk_account.key,
+        rent.minimum_balance(Groth16VerifyingKey::LEN),
+        Groth16VerifyingKey::LEN as u64,
+        program_id,
+    );
+    invoke_signed(
+        &create_ix,
+        &[
+            authority.clone(),
+            vk_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[VERIFYING_KEY_SEED, &vk_id.to_le_bytes(), &[bump]]],
+    )?;
+    vk.serialize(&mut &mut vk_account.try_borrow_mut_data()?[..])
+        .map_err(|_| ProgramError::AccountDataTooSmall)?;
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::VerifyingKeyCreated {
+            vk_id,
+            address: vk_account.key.to_bytes(),
+        },
+    )
+}
+
+pub(crate) fn process_verify_vk_binding(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let state_account = next_account_info(account_info_iter)?;
+    let verifier_account = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let program = next_account_info(account_info_iter)?;
+
+    let mut state = load_state(program_id, state_account)?;
+    if state.verifier_hash == [0u8; 32] {
+        msg!("Pool has no verifier hash to check");
+        return Err(MixerError::InvalidVerifier.into());
+    }
+
+    let backend = VerifierBackend::try_from(state.verifier_backend)?;
+    let expected_account = match backend {
+        VerifierBackend::NativeGroth16 => state.verifier_program,
+        VerifierBackend::SunspotCpi | VerifierBackend::UltraHonkCpi => {
+            Pubkey::find_program_address(
+                &[state.verifier_program.as_ref()],
+                &solana_sdk_ids::bpf_loader_upgradeable::ID,
+            )
+            .0
+        }
+    };
+    if verifier_account.key != &expected_account {
+        msg!("Account is not the pool's verifier");
+        return Err(MixerError::InvalidVerifier.into());
+    }
+
+    let actual_hash = verifier_account_hash(backend, &verifier_account.data.borrow());
+    let matched = actual_hash == state.verifier_hash;
+    if !matched {
+        msg!("VERIFIER HASH MISMATCH: pausing pool");
+        state.paused = true;
+        store_state(state_account, &state)?;
+    }
+
+    events::emit_cpi(
+        program_id,
+        event_authority,
+        program,
+        &events::VerifierBindingChecked {
+            verifier: state.verifier_program.to_bytes(),
+            expected_hash: state.verifier_hash,
+            actual_hash,
+            paused: !matched,
+        },
+    )
+}
+
+/// Malformed-but-plausible proofs against a key with a known trapdoor, so
+/// valid proofs can be made without a circuit.
+#[cfg(test)]
+mod tests {
+    use ark_bn254::{Fq, Fq2, Fr, G1Affine, G2Affine};
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::{BigInteger, Field, PrimeField};
+
+    use super::*;
+
+    fn fq_bytes(value: &Fq) -> [u8; 32] {
+        value.into_bigint().to_bytes_be().try_into().unwrap()
+    }
+
+    fn g1_bytes(p: &G1Affine) -> [u8; 64] {
+        let mut out = [0u8; 64];
+        out[..32].copy_from_slice(&fq_bytes(&p.x));
+        out[32..].copy_from_slice(&fq_bytes(&p.y));
+        out
+    }
+
+    fn g2_bytes(p: &G2Affine) -> [u8; 128] {
+        let mut out = [0u8; 128];
+        out[..32].copy_from_slice(&fq_bytes(&p.x.c1));
+        out[32..64].copy_from_slice(&fq_bytes(&p.x.c0));
+        out[64..96].copy_from_slice(&fq_bytes(&p.y.c1));
+        out[96..].copy_from_slice(&fq_bytes(&p.y.c0));
+        out
+    }
+
+    /// The syscalls' compressed encoding: x, with the top bit set if y is
+    /// the larger of the two roots.
+    fn compress(proof: &Proof) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (x, larger) in [
+            (fq_bytes(&proof.a.x).to_vec(), proof.a.y > -proof.a.y),
+            ([fq_bytes(&proof.b.x.c1), fq_bytes(&proof.b.x.c0)].concat(), proof.b.y > -proof.b.y),
+            (fq_bytes(&proof.c.x).to_vec(), proof.c.y > -proof.c.y),
+        ] {
+            let start = out.len();
+            out.extend_from_slice(&x);
+            if larger {
+                out[start] |= 0x80;
+            }
+        }
+        out
+    }
+
+    /// `value + q`, which fits in 256 bits for any canonical value.
+    fn alias(value: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        let mut carry = 0u16;
+        for i in (0..32).rev() {
+            let s = value[i] as u16 + BASE_FIELD_MODULUS[i] as u16 + carry;
+            out[i] = s as u8;
+            carry = s >> 8;
+        }
+        assert_eq!(carry, 0);
+        out
+    }
+
+    struct Proof {
+        a: G1Affine,
+        b: G2Affine,
+        c: G1Affine,
+    }
+
+    impl Proof {
+        fn bytes(&self) -> [u8; GROTH16_PROOF_LEN] {
+            let mut out = [0u8; GROTH16_PROOF_LEN];
+            out[..64].copy_from_slice(&g1_bytes(&self.a));
+            out[64..192].copy_from_slice(&g2_bytes(&self.b));
+            out[192..].copy_from_slice(&g1_bytes(&self.c));
+            out
+        }
+    }
+
+    struct Fixture {
+        vk: Groth16VerifyingKey,
+        proof: Proof,
+        inputs: [[u8; 32]; NUM_PUBLIC_INPUTS],
+    }
+
+    /// With `alpha = 3`, `beta = 5`, `gamma = 7`, `delta = 11` and IC
+    /// scalars `k`, `A = r`, `B = s`, `C = (r * s - alpha * beta - vk_x *
+    /// gamma) / delta` is a valid proof. `r` is picked so that `A.x + q`
+    /// leaves the two flag bits of the compressed encoding free.
+    fn fixture() -> Fixture {
+        let (g1, g2) = (G1Affine::generator(), G2Affine::generator());
+        let [alpha, beta, gamma, delta] = [3u64, 5, 7, 11].map(Fr::from);
+        let k = [13u64, 17, 19, 23].map(Fr::from);
+        let x = [101u64, 202, 303].map(Fr::from);
+        let vk_x = k[0] + k[1] * x[0] + k[2] * x[1] + k[3] * x[2];
+        let (r, a) = (29u64..)
+            .map(|r| (Fr::from(r), (g1 * Fr::from(r)).into_affine()))
+            .find(|(_, a)| alias(&fq_bytes(&a.x))[0] < 0x40)
+            .unwrap();
+        let s = Fr::from(31u64);
+        let c = (r * s - alpha * beta - vk_x * gamma) * delta.inverse().unwrap();
+        Fixture {
+            vk: Groth16VerifyingKey {
+                alpha_g1: g1_bytes(&(g1 * alpha).into_affine()),
+                beta_g2: g2_bytes(&(g2 * beta).into_affine()),
+                gamma_g2: g2_bytes(&(g2 * gamma).into_affine()),
+                delta_g2: g2_bytes(&(g2 * delta).into_affine()),
+                ic: k.map(|k| g1_bytes(&(g1 * k).into_affine())),
+            },
+            proof: Proof { a, b: (g2 * s).into_affine(), c: (g1 * c).into_affine() },
+            inputs: x.map(|x| x.into_bigint().to_bytes_be().try_into().unwrap()),
+        }
+    }
+
+    fn verify(f: &Fixture, proof: &[u8]) -> Result<(), MixerError> {
+        verify_groth16(&f.vk, proof, &f.inputs)
+    }
+
+    fn assert_err(result: Result<(), MixerError>, expected: MixerError) {
+        match result {
+            Err(e) => assert_eq!(e as u32, expected as u32, "{e}"),
+            Ok(()) => panic!("malformed proof verified"),
+        }
+    }
+
+    #[test]
+    fn valid_proofs_verify() {
+        let f = fixture();
+        assert!(f.vk.check_points().is_ok());
+        verify(&f, &f.proof.bytes()).unwrap();
+        verify(&f, &compress(&f.proof)).unwrap();
+    }
+
+    #[test]
+    fn rejects_aliased_coordinates() {
+        let f = fixture();
+        let proof = f.proof.bytes();
+        // A.y, B.x0 and C.x, each replaced by itself plus q.
+        for at in [32, 96, 192] {
+            let mut bad = proof;
+            let aliased = alias(&bad[at..at + 32]);
+            bad[at..at + 32].copy_from_slice(&aliased);
+            assert_err(verify(&f, &bad), MixerError::MalformedProof);
+        }
+        let mut bad = compress(&f.proof);
+        let flags = bad[0] & 0xc0;
+        let aliased = alias(&fq_bytes(&f.proof.a.x));
+        bad[..32].copy_from_slice(&aliased);
+        bad[0] |= flags;
+        assert_err(verify(&f, &bad), MixerError::MalformedProof);
+    }
+
+    #[test]
+    fn rejects_points_off_the_curve() {
+        let f = fixture();
+        for at in [63, 191, 255] {
+            let mut bad = f.proof.bytes();
+            bad[at] ^= 1;
+            assert_err(verify(&f, &bad), MixerError::MalformedProof);
+        }
+        // B with the Fp2 components in the wrong order, a common encoding
+        // slip.
+        let b = f.proof.b;
+        let swapped = G2Affine::new_unchecked(Fq2::new(b.x.c1, b.x.c0), Fq2::new(b.y.c1, b.y.c0));
+        let bad = Proof { b: swapped, ..fixture().proof };
+        assert_err(verify(&f, &bad.bytes()), MixerError::MalformedProof);
+    }
+
+    #[test]
+    fn rejects_identity_points() {
+        let f = fixture();
+        for range in [0..64, 64..192, 192..256] {
+            let mut bad = f.proof.bytes();
+            bad[range].fill(0);
+            assert_err(verify(&f, &bad), MixerError::MalformedProof);
+        }
+        let mut bad = compress(&f.proof);
+        bad[0] = 0x40;
+        bad[1..32].fill(0);
+        assert_err(verify(&f, &bad), MixerError::MalformedProof);
+    }
+
+    #[test]
+    fn rejects_g2_point_outside_the_subgroup() {
+        let f = fixture();
+        let b = (1u64..)
+            .find_map(|i| G2Affine::get_point_from_x_unchecked(Fq2::new(Fq::from(i), Fq::ONE), false))
+            .unwrap();
+        assert!(!b.is_in_correct_subgroup_assuming_on_curve());
+        let bad = Proof { b, ..fixture().proof };
+        // On the curve, so only the pairing syscall's validation catches it.
+        check_groth16_proof(&bad.bytes()).unwrap();
+        assert_err(verify(&f, &bad.bytes()), MixerError::VerificationFailed);
+        assert_err(verify(&f, &compress(&bad)), MixerError::VerificationFailed);
+    }
+
+    #[test]
+    fn rejects_negated_points() {
+        let f = fixture();
+        let negated = Proof { a: -f.proof.a, ..fixture().proof };
+        assert_err(verify(&f, &negated.bytes()), MixerError::VerificationFailed);
+        let mut flipped = compress(&f.proof);
+        flipped[0] ^= 0x80;
+        assert_err(verify(&f, &flipped), MixerError::VerificationFailed);
+    }
+
+    /// Re-randomizing yields a different valid proof for the same inputs.
+    /// This is inherent to Groth16, and why the nullifier hash, not the
+    /// proof, is what a withdraw spends.
+    #[test]
+    fn rerandomized_proof_verifies() {
+        let f = fixture();
+        let t = Fr::from(1234u64);
+        let rerandomized = Proof {
+            a: (f.proof.a * t).into_affine(),
+            b: (f.proof.b * t.inverse().unwrap()).into_affine(),
+            c: f.proof.c,
+        };
+        assert_ne!(rerandomized.bytes(), f.proof.bytes());
+        verify(&f, &rerandomized.bytes()).unwrap();
+    }
+
+    #[test]
+    fn rejects_wrong_lengths_and_inputs() {
+        let f = fixture();
+        let proof = f.proof.bytes();
+        assert_err(verify(&f, &proof[1..]), MixerError::VerificationFailed);
+        assert_err(verify(&f, &[proof.as_slice(), &[0]].concat()), MixerError::VerificationFailed);
+        let mut inputs = f.inputs;
+        inputs[1] = crate::public_inputs::SCALAR_FIELD_MODULUS;
+        assert_err(verify_groth16(&f.vk, &proof, &inputs), MixerError::VerificationFailed);
+    }
+
+    #[test]
+    fn rejects_malformed_verifying_keys() {
+        let mut f = fixture();
+        f.vk.gamma_g2[127] ^= 1;
+        assert_eq!(f.vk.check_points(), Err(PointError::NotOnCurve));
+        let mut f = fixture();
+        f.vk.ic[2] = [0u8; 64];
+        assert_eq!(f.vk.check_points(), Err(PointError::Identity));
+    }
+}
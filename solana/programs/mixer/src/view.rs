@@ -0,0 +1,132 @@
+//! Read-only access to pool state through return data.
+//!
+//! `GetState` decodes the state account and writes a borsh [`StateView`] with
+//! `set_return_data`. A program reads it by invoking `GetState` and calling
+//! `get_return_data` (see [`crate::cpi::get_state`]), and a client by
+//! simulating the instruction, so neither depends on the account's byte
+//! layout. Fields are only ever appended to the view; decode it with
+//! `BorshDeserialize::deserialize`, which leaves later fields unread, rather
+//! than `try_from_slice`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{load_state, MixerState};
+
+/// The pool state `GetState` returns. Addresses are raw bytes, as in
+/// [`crate::events`].
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StateView {
+    pub denomination: u64,
+    /// Latest Merkle root, all zeroes before the first deposit.
+    pub latest_root: [u8; 32],
+    pub current_root_index: u8,
+    /// Unix timestamp at which `latest_root` was pushed.
+    pub latest_root_time: i64,
+    pub leaf_count: u64,
+    pub tree_depth: u8,
+    pub flags: u8,
+    pub paused: bool,
+    pub curve: u8,
+    pub verifier_backend: u8,
+    pub protocol_fee_bps: u16,
+    pub relayer_fee_cap_bps: u16,
+    pub min_withdraw_delay: u64,
+    pub protocol_fees: u64,
+    pub deposit_count: u64,
+    pub withdraw_count: u64,
+    pub max_deposits_per_epoch: u32,
+    pub max_deposits_per_depositor: u32,
+    pub max_vault_lamports: u64,
+    pub admin_delay: u64,
+    pub authority: [u8; 32],
+    pub verifier_program: [u8; 32],
+    pub guardian: [u8; 32],
+    /// All zeroes when the pool mints no deposit receipts.
+    pub receipt_tree: [u8; 32],
+    /// Circuit version a pending migration moves to; 0 when none is.
+    pub migration_version: u16,
+    pub migration_end_slot: u64,
+    /// Tree deposits currently go into; see [`crate::CommitmentTree`].
+    pub tree_id: u8,
+    /// Slot at which `latest_root` was pushed.
+    pub latest_root_slot: u64,
+}
+
+impl From<&MixerState> for StateView {
+    fn from(state: &MixerState) -> Self {
+        let index = state.current_root_index as usize;
+        StateView {
+            denomination: state.denomination,
+            latest_root: state.roots[index],
+            current_root_index: state.current_root_index,
+            latest_root_time: state.root_times[index],
+            leaf_count: state.leaf_count,
+            tree_depth: state.tree_depth,
+            flags: state.flags,
+            paused: state.paused,
+            curve: state.curve,
+            verifier_backend: state.verifier_backend,
+            protocol_fee_bps: state.protocol_fee_bps,
+            relayer_fee_cap_bps: state.relayer_fee_cap_bps,
+            min_withdraw_delay: state.min_withdraw_delay,
+            protocol_fees: state.protocol_fees,
+            deposit_count: state.deposit_count,
+            withdraw_count: state.withdraw_count,
+            max_deposits_per_epoch: state.max_deposits_per_epoch,
+            max_deposits_per_depositor: state.max_deposits_per_depositor,
+            max_vault_lamports: state.max_vault_lamports,
+            admin_delay: state.admin_delay,
+            authority: state.authority.to_bytes(),
+            verifier_program: state.verifier_program.to_bytes(),
+            guardian: state.guardian.to_bytes(),
+            receipt_tree: state.receipt_tree.to_bytes(),
+            migration_version: state.migration_version,
+            migration_end_slot: state.migration_end_slot,
+            tree_id: state.tree_id,
+            latest_root_slot: state.root_slots[index],
+        }
+    }
+}
+
+pub(crate) fn process_get_state(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let state_account = next_account_info(account_info_iter)?;
+    let state = load_state(program_id, state_account)?;
+    let data =
+        borsh::to_vec(&StateView::from(&state)).map_err(|_| ProgramError::InvalidAccountData)?;
+    set_return_data(&data);
+    Ok(())
+}
+
+/// `GetState` instruction for the pool at `["mixer_state"]`.
+pub fn get_state_instruction(mixer_program_id: &Pubkey) -> Instruction {
+    let (mixer_state, _) = Pubkey::find_program_address(&[b"mixer_state"], mixer_program_id);
+    Instruction {
+        program_id: *mixer_program_id,
+        accounts: vec![AccountMeta::new_readonly(mixer_state, false)],
+        data: crate::instruction_data(25, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::program::MAX_RETURN_DATA;
+
+    #[test]
+    fn fits_in_return_data() {
+        let state = crate::unpack_state(&[0; MixerState::LEN]).unwrap();
+        let len = borsh::to_vec(&StateView::from(&state)).unwrap().len();
+        assert!(len <= MAX_RETURN_DATA);
+        // `STATE_VIEW_LEN` in ts-client/src/mixer-client.ts.
+        assert_eq!(len, 269);
+    }
+}
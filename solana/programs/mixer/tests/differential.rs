@@ -0,0 +1,230 @@
+//! Differential tests against the EVM `Mixer.sol` reference: the same
+//! deposit and withdraw sequences replayed on a transcription of its root
+//! history and nullifier rules and on the program under LiteSVM must be
+//! accepted and rejected alike.
+//!
+//! `EvmMixer` follows the Tornado-style contract the program was ported
+//! from: `MerkleTreeWithHistory` keeps the last `ROOT_HISTORY_SIZE` roots in
+//! a ring buffer and `isKnownRoot` scans all of them, and `withdraw` requires
+//! an unspent nullifier hash, a known root and a valid proof. The contract's
+//! constructor records the empty tree's root in slot 0 where `Initialize`
+//! leaves it zero; the model keeps the zero, since no note can be proven
+//! under the empty tree either way.
+
+#[allow(dead_code)]
+mod harness;
+
+use std::collections::HashSet;
+
+use harness::{field, Harness, DENOMINATION};
+use mixer::{MixerError, MixerState};
+use mock_verifier::Mode;
+use proptest::prelude::*;
+use solana_keypair::Keypair;
+use solana_program::instruction::InstructionError;
+use solana_signer::Signer;
+use solana_transaction_error::TransactionError;
+
+const ROOT_HISTORY_SIZE: usize = MixerState::ROOT_HISTORY_SIZE;
+
+/// A check of `Mixer.withdraw` that an operation can fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Check {
+    /// `require(!nullifierHashes[_nullifierHash])`
+    Spent,
+    /// `require(isKnownRoot(_root))`
+    UnknownRoot,
+    /// `require(verifier.verifyProof(...))`
+    InvalidProof,
+}
+
+impl Check {
+    fn error(self) -> MixerError {
+        match self {
+            Check::Spent => MixerError::NullifierUsed,
+            Check::UnknownRoot => MixerError::UnknownRoot,
+            Check::InvalidProof => MixerError::VerificationFailed,
+        }
+    }
+}
+
+/// The root history and nullifier set of `Mixer.sol`.
+struct EvmMixer {
+    roots: [[u8; 32]; ROOT_HISTORY_SIZE],
+    current_root_index: u32,
+    nullifier_hashes: HashSet<[u8; 32]>,
+}
+
+impl EvmMixer {
+    /// The contract after the deposit every harness pool starts with.
+    fn new() -> Self {
+        let mut mixer = EvmMixer {
+            roots: [[0u8; 32]; ROOT_HISTORY_SIZE],
+            current_root_index: 0,
+            nullifier_hashes: HashSet::new(),
+        };
+        mixer.insert(Harness::ROOT);
+        mixer
+    }
+
+    /// `_insert`'s update of the root history.
+    fn insert(&mut self, root: [u8; 32]) {
+        let new_root_index = (self.current_root_index + 1) % ROOT_HISTORY_SIZE as u32;
+        self.current_root_index = new_root_index;
+        self.roots[new_root_index as usize] = root;
+    }
+
+    fn is_known_root(&self, root: &[u8; 32]) -> bool {
+        if root == &[0u8; 32] {
+            return false;
+        }
+        let mut i = self.current_root_index;
+        loop {
+            if &self.roots[i as usize] == root {
+                return true;
+            }
+            if i == 0 {
+                i = ROOT_HISTORY_SIZE as u32;
+            }
+            i -= 1;
+            if i == self.current_root_index {
+                return false;
+            }
+        }
+    }
+
+    /// The checks `withdraw` fails; on none, the nullifier hash is spent.
+    fn withdraw(&mut self, root: &[u8; 32], nullifier_hash: [u8; 32], valid: bool) -> Vec<Check> {
+        let mut failed = Vec::new();
+        if self.nullifier_hashes.contains(&nullifier_hash) {
+            failed.push(Check::Spent);
+        }
+        if !self.is_known_root(root) {
+            failed.push(Check::UnknownRoot);
+        }
+        if !valid {
+            failed.push(Check::InvalidProof);
+        }
+        if failed.is_empty() {
+            self.nullifier_hashes.insert(nullifier_hash);
+        }
+        failed
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Deposit,
+    /// A withdraw under the root `age` deposits back, or under a root never
+    /// pushed when there are not that many.
+    Withdraw { age: usize, nullifier: u8, valid: bool },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        2 => Just(Op::Deposit),
+        // Ages past the history reach evicted roots, and the oldest kept and
+        // the newest evicted are drawn often; a small nullifier alphabet
+        // makes repeats common.
+        3 => (age(), 0u8..6, prop::bool::weighted(0.9))
+            .prop_map(|(age, nullifier, valid)| Op::Withdraw { age, nullifier, valid }),
+    ]
+}
+
+fn age() -> impl Strategy<Value = usize> {
+    prop_oneof![
+        2 => 0..ROOT_HISTORY_SIZE + 8,
+        1 => Just(ROOT_HISTORY_SIZE - 1),
+        1 => Just(ROOT_HISTORY_SIZE),
+    ]
+}
+
+/// A distinct nonzero root for the `n`th deposit after the first.
+fn deposit_root(n: u32) -> [u8; 32] {
+    let mut root = field(0);
+    root[..4].copy_from_slice(&n.to_be_bytes());
+    root[31] = 0xd0;
+    root
+}
+
+fn unknown_root() -> [u8; 32] {
+    let mut root = field(0xee);
+    root[0] = 0x1f;
+    root
+}
+
+fn custom_error(result: &Result<(), TransactionError>) -> Option<u32> {
+    match result {
+        Err(TransactionError::InstructionError(_, InstructionError::Custom(code))) => Some(*code),
+        _ => None,
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn program_decides_like_the_evm_mixer(ops in prop::collection::vec(op(), 1..120)) {
+        let mut h = Harness::new();
+        let mut vault = h.account(&h.vault).unwrap();
+        vault.lamports = 100 * DENOMINATION;
+        h.set_account(h.vault, vault);
+        let mut evm = EvmMixer::new();
+        let mut deposits = vec![Harness::ROOT];
+
+        for (step, op) in ops.iter().enumerate() {
+            match *op {
+                Op::Deposit => {
+                    let root = deposit_root(deposits.len() as u32);
+                    evm.insert(root);
+                    deposits.push(root);
+                    h.run(h.push_root(root)).assert_ok();
+                }
+                Op::Withdraw { age, nullifier, valid } => {
+                    let root = match deposits.len().checked_sub(age + 1) {
+                        Some(i) => deposits[i],
+                        None => unknown_root(),
+                    };
+                    let nullifier_hash = field(0x40 + nullifier);
+                    let mode = if valid { Mode::AcceptAll } else { Mode::RejectAll };
+                    let recipient = Keypair::new().pubkey();
+                    let instruction = h.withdraw_under(
+                        root,
+                        nullifier_hash,
+                        &recipient,
+                        0,
+                        &mock_verifier::proof(mode),
+                    );
+
+                    let failed = evm.withdraw(&root, nullifier_hash, valid);
+                    let outcome = h.run(instruction);
+                    prop_assert_eq!(
+                        outcome.result.is_ok(),
+                        failed.is_empty(),
+                        "step {} {:?}: EVM failed {:?}, program returned {:?}",
+                        step,
+                        op,
+                        failed,
+                        outcome.result
+                    );
+                    if !failed.is_empty() {
+                        let code = custom_error(&outcome.result);
+                        prop_assert!(
+                            code.is_some_and(|c| failed.iter().any(|check| check.error() as u32 == c)),
+                            "step {} {:?}: EVM failed {:?}, program returned {:?}",
+                            step,
+                            op,
+                            failed,
+                            outcome.result
+                        );
+                    } else {
+                        prop_assert_eq!(h.account(&recipient).map(|a| a.lamports), Some(DENOMINATION));
+                    }
+                }
+            }
+            let state = h.state();
+            prop_assert_eq!(state.current_root_index as u32, evm.current_root_index);
+            prop_assert_eq!(state.roots, evm.roots);
+        }
+    }
+}
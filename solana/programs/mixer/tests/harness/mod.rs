@@ -0,0 +1,326 @@
+//! LiteSVM harness for per-instruction tests: account fixtures in, account
+//! diffs out.
+//!
+//! Each [`Harness`] is a clone of one pool, built on first use, that is initialized with
+//! `mock-verifier` pinned, holds one deposit under [`Harness::ROOT`], and is
+//! administered by [`Harness::authority`]. Fixtures overwrite accounts with
+//! [`Harness::set_account`]; [`Harness::run`] executes one instruction and
+//! reports every account it changed. Signatures are not verified, so an
+//! instruction can mark any account as a signer without its keypair.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Once, OnceLock},
+};
+
+use litesvm::LiteSVM;
+use mixer::{cpi, curve::Curve, public_inputs::PublicInputs, MixerError, MixerState};
+use solana_account::Account;
+use solana_keypair::Keypair;
+use solana_message::Message;
+use solana_program::{
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use solana_program_runtime::invoke_context::BuiltinFunctionWithContext;
+use solana_program_test::{processor, ProgramTest};
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+pub const DENOMINATION: u64 = 1_000_000_000;
+
+/// Account changes of one instruction: `(before, after)` by address, with
+/// `None` for an account that does not exist. The fee payer is left out.
+pub type Diffs = BTreeMap<Pubkey, (Option<Account>, Option<Account>)>;
+
+pub struct Outcome {
+    pub result: Result<(), TransactionError>,
+    pub logs: Vec<String>,
+    pub diffs: Diffs,
+    /// What the instruction passed to `set_return_data`, empty if nothing.
+    pub return_data: Vec<u8>,
+}
+
+impl Outcome {
+    #[track_caller]
+    pub fn assert_ok(&self) {
+        if let Err(err) = &self.result {
+            panic!("instruction failed with {err:?}; logs: {:#?}", self.logs);
+        }
+    }
+
+    /// Assert the instruction failed with `expected` and changed nothing.
+    #[track_caller]
+    pub fn assert_error(&self, expected: impl Into<ProgramError>) {
+        let expected = InstructionError::from(u64::from(expected.into()));
+        assert_eq!(
+            self.result,
+            Err(TransactionError::InstructionError(0, expected)),
+            "logs: {:#?}",
+            self.logs
+        );
+        assert!(self.diffs.is_empty(), "failed instruction changed {:?}", self.changed());
+    }
+
+    #[track_caller]
+    pub fn assert_mixer_error(&self, expected: MixerError) {
+        self.assert_error(expected);
+    }
+
+    /// Addresses whose account changed.
+    pub fn changed(&self) -> Vec<Pubkey> {
+        self.diffs.keys().copied().collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct Harness {
+    svm: LiteSVM,
+    fee_payer: Arc<Keypair>,
+    pub program_id: Pubkey,
+    pub verifier_id: Pubkey,
+    pub authority: Pubkey,
+    pub state: Pubkey,
+    pub vault: Pubkey,
+    pub latest_root: Pubkey,
+    pub pool_stats: Pubkey,
+}
+
+impl Harness {
+    /// Root of the deposit the pool starts with.
+    pub const ROOT: [u8; 32] = field(1);
+
+    pub fn new() -> Self {
+        static POOL: OnceLock<Harness> = OnceLock::new();
+        POOL.get_or_init(Harness::build).clone()
+    }
+
+    fn build() -> Self {
+        install_syscall_stubs();
+        let program_id = Pubkey::new_unique();
+        let verifier_id = Pubkey::new_unique();
+        let mut svm = LiteSVM::new().with_sigverify(false);
+        add_builtin(&mut svm, program_id, processor!(mixer::process_instruction));
+        add_builtin(&mut svm, verifier_id, processor!(mock_verifier::process_instruction));
+        let fee_payer = Arc::new(Keypair::new());
+        let authority = Keypair::new().pubkey();
+        svm.airdrop(&fee_payer.pubkey(), 100 * DENOMINATION).unwrap();
+        svm.airdrop(&authority, 100 * DENOMINATION).unwrap();
+
+        let mut harness = Harness {
+            svm,
+            fee_payer,
+            program_id,
+            verifier_id,
+            authority,
+            state: Pubkey::find_program_address(&[b"mixer_state"], &program_id).0,
+            vault: Pubkey::find_program_address(&[b"mixer_vault"], &program_id).0,
+            latest_root: mixer::LatestRoot::find_address(&program_id).0,
+            pool_stats: mixer::PoolStats::find_address(&program_id).0,
+        };
+        harness
+            .run(cpi::initialize_instruction(
+                &program_id,
+                &authority,
+                DENOMINATION,
+                0,
+                0,
+                Curve::Bn254,
+                MixerState::DEFAULT_TREE_DEPTH,
+                &verifier_id,
+            ))
+            .assert_ok();
+        harness
+            .run(cpi::create_latest_root_instruction(&program_id, &authority))
+            .assert_ok();
+        harness
+            .run(cpi::create_pool_stats_instruction(&program_id, &authority))
+            .assert_ok();
+        harness.run(harness.push_root(Self::ROOT)).assert_ok();
+        harness
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<Account> {
+        self.svm.get_account(address).filter(|account| account.lamports > 0)
+    }
+
+    pub fn set_account(&mut self, address: Pubkey, account: Account) {
+        self.svm.set_account(address, account).unwrap();
+    }
+
+    pub fn state(&self) -> MixerState {
+        mixer::unpack_state(&self.account(&self.state).unwrap().data).unwrap()
+    }
+
+    pub fn set_state(&mut self, state: &MixerState) {
+        let mut account = self.account(&self.state).unwrap();
+        mixer::pack_state(state, &mut account.data).unwrap();
+        self.set_account(self.state, account);
+    }
+
+    pub fn latest_root(&self) -> mixer::LatestRoot {
+        mixer::LatestRoot::unpack(&self.account(&self.latest_root).unwrap().data).unwrap()
+    }
+
+    pub fn pool_stats(&self) -> mixer::PoolStats {
+        mixer::PoolStats::unpack(&self.account(&self.pool_stats).unwrap().data).unwrap()
+    }
+
+    pub fn warp_to_slot(&mut self, slot: u64) {
+        self.svm.warp_to_slot(slot);
+    }
+
+    /// Move the clock to the start of the next epoch.
+    pub fn advance_epoch(&mut self) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.epoch += 1;
+        self.svm.set_sysvar(&clock);
+    }
+
+    /// Execute `instruction` in its own transaction.
+    pub fn run(&mut self, instruction: Instruction) -> Outcome {
+        let mut addresses: Vec<Pubkey> = instruction.accounts.iter().map(|a| a.pubkey).collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses.retain(|address| address != &self.fee_payer.pubkey());
+        let before: Vec<_> = addresses.iter().map(|a| self.account(a)).collect();
+
+        // Only the fee payer signs. A fresh blockhash gives its signature, and
+        // so the transaction, a new identity even for a repeated instruction.
+        self.svm.expire_blockhash();
+        let blockhash = self.svm.latest_blockhash();
+        let message =
+            Message::new_with_blockhash(&[instruction], Some(&self.fee_payer.pubkey()), &blockhash);
+        let mut transaction = Transaction::new_unsigned(message);
+        transaction.partial_sign(&[&*self.fee_payer], blockhash);
+        let (result, logs, return_data) = match self.svm.send_transaction(transaction) {
+            Ok(meta) => (Ok(()), meta.logs, meta.return_data.data),
+            Err(failed) => (Err(failed.err), failed.meta.logs, failed.meta.return_data.data),
+        };
+
+        let diffs = addresses
+            .into_iter()
+            .zip(before)
+            .filter_map(|(address, before)| {
+                let after = self.account(&address);
+                (before != after).then_some((address, (before, after)))
+            })
+            .collect();
+        Outcome {
+            result,
+            logs,
+            diffs,
+            return_data,
+        }
+    }
+
+    pub fn push_root(&self, root: [u8; 32]) -> Instruction {
+        cpi::push_root_instruction(&self.program_id, &self.authority, &self.state, root, None)
+    }
+
+    /// Withdraw of the starting deposit to `recipient`, accepted by the mock
+    /// verifier.
+    pub fn withdraw(&self, nullifier_hash: [u8; 32], recipient: &Pubkey) -> Instruction {
+        let proof = mock_verifier::proof(mock_verifier::Mode::AcceptAll);
+        self.withdraw_under(Self::ROOT, nullifier_hash, recipient, 0, &proof)
+    }
+
+    /// Withdraw under `root` with a mock verifier `proof`, paying the
+    /// authority, as relayer, `relayer_fee`. The proof is followed by the
+    /// public witness of the withdraw.
+    pub fn withdraw_under(
+        &self,
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient: &Pubkey,
+        relayer_fee: u64,
+        proof: &[u8],
+    ) -> Instruction {
+        let witness =
+            PublicInputs::new(root, nullifier_hash, Self::recipient_field(recipient)).unwrap();
+        let data = [proof, &witness.to_sunspot_witness()].concat();
+        self.withdraw_with(root, nullifier_hash, recipient, relayer_fee, &data)
+    }
+
+    /// [`Self::withdraw_under`] with the verifier data passed as is.
+    pub fn withdraw_with(
+        &self,
+        root: [u8; 32],
+        nullifier_hash: [u8; 32],
+        recipient: &Pubkey,
+        relayer_fee: u64,
+        proof: &[u8],
+    ) -> Instruction {
+        let nullifier =
+            Pubkey::find_program_address(&[b"nullifier", &nullifier_hash], &self.program_id).0;
+        cpi::withdraw_instruction(
+            &self.program_id,
+            &self.authority,
+            &self.state,
+            &nullifier,
+            &self.vault,
+            recipient,
+            &self.verifier_id,
+            root,
+            nullifier_hash,
+            Self::recipient_field(recipient),
+            0,
+            0,
+            relayer_fee,
+            proof,
+        )
+    }
+
+    /// The recipient as a field element. Clearing the top byte keeps it
+    /// canonical; the mock verifier does not check the binding.
+    pub fn recipient_field(recipient: &Pubkey) -> [u8; 32] {
+        let mut out = recipient.to_bytes();
+        out[0] = 0;
+        out
+    }
+}
+
+/// A canonical field element standing in for a Poseidon hash.
+pub const fn field(tag: u8) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[31] = tag;
+    out
+}
+
+/// `processor!` builtins run natively and reach sysvars and CPI through the
+/// syscall stubs of `solana-program-test`, which only installs them when a
+/// `ProgramTest` starts. Start an empty one once so LiteSVM can run them.
+fn install_syscall_stubs() {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(ProgramTest::default().start());
+    });
+}
+
+fn add_builtin(
+    svm: &mut LiteSVM,
+    program_id: Pubkey,
+    entrypoint: Option<BuiltinFunctionWithContext>,
+) {
+    svm.add_builtin(program_id, entrypoint.unwrap());
+    // LiteSVM gives builtins a BPF-loader-owned account, which the runtime
+    // would hand to the BPF loader instead of the builtin.
+    svm.set_account(
+        program_id,
+        Account {
+            lamports: 1,
+            data: vec![],
+            owner: solana_sdk_ids::native_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        },
+    )
+    .unwrap();
+}
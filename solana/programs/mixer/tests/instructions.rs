@@ -0,0 +1,999 @@
+//! Per-instruction edge cases under LiteSVM: malformed data, unsigned
+//! authorities, wrong owners and spoofed PDAs. See `harness` for the
+//! fixture pool each test starts from.
+
+mod harness;
+
+use borsh::BorshDeserialize;
+use harness::{field, Harness, DENOMINATION};
+use mixer::{cpi, curve::Curve, public_inputs::PublicInputs, MixerError, MixerState};
+use solana_account::Account;
+use solana_keypair::Keypair;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+};
+use solana_signer::Signer;
+use solana_system_interface::instruction as system_instruction;
+
+/// Lamports for a fixture account holding `data`.
+fn fixture(owner: Pubkey, data: Vec<u8>) -> Account {
+    Account { lamports: DENOMINATION, data, owner, executable: false, rent_epoch: 0 }
+}
+
+#[test]
+fn truncated_instruction_data_is_rejected() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let initialize = cpi::initialize_instruction(
+        &h.program_id,
+        &h.authority,
+        DENOMINATION,
+        0,
+        0,
+        Curve::Bn254,
+        MixerState::DEFAULT_TREE_DEPTH,
+        &h.verifier_id,
+    );
+    let push_root =
+        cpi::push_root_instruction(&h.program_id, &h.authority, &h.state, field(2), Some(2));
+    let withdraw = h.withdraw(field(7), &recipient);
+    // Every prefix shorter than `len` is malformed except `valid`, the
+    // encodings without Initialize's optional fields or PushRoot's leaf
+    // count. Withdraw accepts any proof length after its fixed fields.
+    let cases: [(Instruction, usize, &[usize]); 3] = [
+        (initialize, 21, &[10, 11, 19, 20]),
+        (push_root, 42, &[34]),
+        (withdraw, 109, &[]),
+    ];
+    for (instruction, len, valid) in cases {
+        for prefix in (0..len).filter(|l| !valid.contains(l)) {
+            let mut truncated = instruction.clone();
+            truncated.data.truncate(prefix);
+            h.run(truncated).assert_mixer_error(MixerError::InvalidInstruction);
+        }
+    }
+    h.run(Instruction::new_with_bytes(h.program_id, &[0xff], vec![]))
+        .assert_mixer_error(MixerError::InvalidInstruction);
+}
+
+#[test]
+fn unversioned_instruction_data_is_version_0() {
+    let mut h = Harness::new();
+    let mut push_root = h.push_root(field(2));
+    assert_eq!(push_root.data[0], mixer::VERSION_FLAG | mixer::INSTRUCTION_VERSION);
+
+    let mut unknown = push_root.clone();
+    unknown.data[0] = mixer::VERSION_FLAG | (mixer::INSTRUCTION_VERSION + 1);
+    h.run(unknown).assert_mixer_error(MixerError::InvalidInstruction);
+
+    // Clients from before versioning send the same payload without the byte.
+    push_root.data.remove(0);
+    h.run(push_root).assert_ok();
+    let state = h.state();
+    assert_eq!(state.roots[state.current_root_index as usize], field(2));
+}
+
+#[test]
+fn push_root_changes_only_the_pool_accounts() {
+    let mut h = Harness::new();
+    h.warp_to_slot(42);
+    let vault_before = h.account(&h.vault).unwrap().lamports;
+    let outcome = h.run(cpi::push_root_instruction(
+        &h.program_id,
+        &h.authority,
+        &h.state,
+        field(2),
+        Some(2),
+    ));
+    outcome.assert_ok();
+    // Besides the pool accounts, only the deposit's depositor and vault.
+    let mut expected = vec![h.authority, h.vault, h.state, h.latest_root, h.pool_stats];
+    expected.sort();
+    assert_eq!(outcome.changed(), expected);
+    assert_eq!(h.account(&h.vault).unwrap().lamports, vault_before + DENOMINATION);
+    let state = h.state();
+    assert_eq!(state.roots[state.current_root_index as usize], field(2));
+    assert_eq!(state.root_slot(&field(2)), Some(42));
+    // The harness's deposit was pushed at slot 0, and keeps its slot.
+    assert_eq!(state.root_slot(&Harness::ROOT), Some(0));
+    assert_eq!(
+        h.latest_root(),
+        mixer::LatestRoot { root: field(2), leaf_count: 2, slot: 42 }
+    );
+    // The harness's own deposit was counted too.
+    let stats = h.pool_stats();
+    assert_eq!((stats.deposits, stats.deposit_volume), (2, 2 * DENOMINATION));
+    assert_eq!(stats.last_activity_slot, 42);
+}
+
+#[test]
+fn push_root_requires_the_latest_root_account() {
+    let mut h = Harness::new();
+    let mut instruction = h.push_root(field(2));
+    instruction.accounts[5].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+
+    h.set_account(h.latest_root, Account::default());
+    h.run(h.push_root(field(2))).assert_error(ProgramError::UninitializedAccount);
+}
+
+#[test]
+fn create_latest_root_copies_the_state_once() {
+    let mut h = Harness::new();
+    // An address funded beforehand is taken over rather than blocking it.
+    h.set_account(h.latest_root, Account { lamports: 1, ..Account::default() });
+    let create = cpi::create_latest_root_instruction(&h.program_id, &h.authority);
+    h.run(create.clone()).assert_ok();
+    let account = h.account(&h.latest_root).unwrap();
+    assert_eq!(account.owner, h.program_id);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(mixer::LatestRoot::LEN));
+    let latest = h.latest_root();
+    assert_eq!((latest.root, latest.leaf_count), (Harness::ROOT, h.state().leaf_count));
+
+    h.run(create).assert_error(ProgramError::AccountAlreadyInitialized);
+}
+
+#[test]
+fn pool_stats_must_exist_at_the_pda() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let mut instruction = h.push_root(field(2));
+    instruction.accounts[6].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+    let mut instruction = h.withdraw(field(7), &recipient);
+    instruction.accounts[9].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+
+    let create = cpi::create_pool_stats_instruction(&h.program_id, &h.authority);
+    h.run(create.clone()).assert_error(ProgramError::AccountAlreadyInitialized);
+
+    h.set_account(h.pool_stats, Account::default());
+    h.run(h.push_root(field(2))).assert_error(ProgramError::UninitializedAccount);
+    h.run(h.withdraw(field(7), &recipient)).assert_error(ProgramError::UninitializedAccount);
+    h.run(create).assert_ok();
+    assert_eq!(h.pool_stats().deposits, 0);
+}
+
+#[test]
+fn push_root_requires_a_signer() {
+    let mut h = Harness::new();
+    let mut instruction = h.push_root(field(2));
+    instruction.accounts[0].is_signer = false;
+    h.run(instruction).assert_error(ProgramError::MissingRequiredSignature);
+}
+
+#[test]
+fn push_root_rejects_a_spoofed_event_authority() {
+    let mut h = Harness::new();
+    let mut instruction = h.push_root(field(2));
+    instruction.accounts[2].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+}
+
+#[test]
+fn push_root_enforces_the_per_epoch_deposit_limit() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.max_deposits_per_epoch = 2;
+    h.set_state(&state);
+    // The starting deposit is the first of the epoch.
+    assert_eq!(state.epoch_deposits, 1);
+    h.run(h.push_root(field(2))).assert_ok();
+    let outcome = h.run(h.push_root(field(3)));
+    outcome.assert_mixer_error(MixerError::DepositRateLimited);
+    assert!(outcome.diffs.is_empty());
+
+    h.advance_epoch();
+    h.run(h.push_root(field(4))).assert_ok();
+    assert_eq!(h.state().epoch_deposits, 1);
+}
+
+#[test]
+fn push_root_counts_deposits_per_depositor() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.max_deposits_per_depositor = 1;
+    h.set_state(&state);
+    let push_root = |h: &Harness, root| {
+        cpi::push_root_with_counter_instruction(&h.program_id, &h.authority, &h.state, root, None)
+    };
+    // Without the counter the deposit can't be counted.
+    h.run(h.push_root(field(2))).assert_error(ProgramError::NotEnoughAccountKeys);
+
+    let counter = mixer::DepositCounter::find_address(&h.program_id, &h.authority).0;
+    let outcome = h.run(push_root(&h, field(2)));
+    outcome.assert_ok();
+    assert_eq!(h.account(&counter).unwrap().owner, h.program_id);
+    h.run(push_root(&h, field(3))).assert_mixer_error(MixerError::DepositRateLimited);
+
+    // Another depositor has its own count.
+    let depositor = Keypair::new().pubkey();
+    let mut funded = fixture(solana_sdk_ids::system_program::id(), vec![]);
+    funded.lamports = 2 * DENOMINATION;
+    h.set_account(depositor, funded);
+    let instruction =
+        cpi::push_root_with_counter_instruction(&h.program_id, &depositor, &h.state, field(3), None);
+    h.run(instruction).assert_ok();
+
+    h.advance_epoch();
+    h.run(push_root(&h, field(4))).assert_ok();
+}
+
+#[test]
+fn push_root_rejects_a_spoofed_deposit_counter() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.max_deposits_per_depositor = 1;
+    h.set_state(&state);
+    let mut instruction =
+        cpi::push_root_with_counter_instruction(&h.program_id, &h.authority, &h.state, field(2), None);
+    instruction.accounts[8].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+}
+
+#[test]
+fn push_root_rejects_deposits_past_the_vault_cap() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.max_vault_lamports = h.account(&h.vault).unwrap().lamports + DENOMINATION;
+    h.set_state(&state);
+    h.run(h.push_root(field(2))).assert_ok();
+
+    let outcome = h.run(h.push_root(field(3)));
+    outcome.assert_mixer_error(MixerError::VaultCapExceeded);
+    assert!(outcome.diffs.is_empty());
+}
+
+/// `PushRoot` of `root` over one more leaf, asking for a receipt into `tree`.
+fn push_root_with_receipt(h: &Harness, root: [u8; 32], tree: &Pubkey) -> Instruction {
+    let leaf_count = Some(h.state().leaf_count + 1);
+    let instruction =
+        cpi::push_root_instruction(&h.program_id, &h.authority, &h.state, root, leaf_count);
+    mixer::receipt::with_receipt(instruction, tree, &Pubkey::new_unique())
+}
+
+#[test]
+fn push_root_receipt_requires_the_pool_tree() {
+    let mut h = Harness::new();
+    let tree = Pubkey::new_unique();
+    h.run(push_root_with_receipt(&h, field(2), &tree))
+        .assert_mixer_error(MixerError::ReceiptsDisabled);
+
+    let mut state = h.state();
+    state.receipt_tree = tree;
+    h.set_state(&state);
+    h.run(push_root_with_receipt(&h, field(2), &Pubkey::new_unique()))
+        .assert_error(ProgramError::InvalidArgument);
+
+    let mut instruction = push_root_with_receipt(&h, field(2), &tree);
+    instruction.accounts[9].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+
+    let mut instruction = push_root_with_receipt(&h, field(2), &tree);
+    instruction.accounts[14].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::IncorrectProgramId);
+
+    let mut instruction = push_root_with_receipt(&h, field(2), &tree);
+    *instruction.data.last_mut().unwrap() = 2;
+    h.run(instruction).assert_mixer_error(MixerError::InvalidInstruction);
+}
+
+#[test]
+fn push_root_rejects_a_spoofed_vault() {
+    let mut h = Harness::new();
+    let mut instruction = h.push_root(field(2));
+    instruction.accounts[4].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+}
+
+#[test]
+fn state_must_be_the_program_owned_pda() {
+    let mut h = Harness::new();
+    let data = h.account(&h.state).unwrap().data;
+
+    // A copy of the state at another address, even one the mixer owns.
+    let elsewhere = Pubkey::new_unique();
+    h.set_account(elsewhere, fixture(h.program_id, data.clone()));
+    let mut instruction = h.push_root(field(2));
+    instruction.accounts[1].pubkey = elsewhere;
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+
+    // The state PDA, owned by another program.
+    let mut h = Harness::new();
+    h.set_account(h.state, fixture(Pubkey::new_unique(), data));
+    h.run(h.push_root(field(2))).assert_error(ProgramError::IncorrectProgramId);
+}
+
+#[test]
+fn copied_state_cannot_withdraw() {
+    // An account the attacker owns, holding state bytes of their choosing.
+    // Withdraw writes the state back unchanged when there is no protocol
+    // fee, so without the address check the runtime would not notice.
+    let mut h = Harness::new();
+    let data = h.account(&h.state).unwrap().data;
+    let copy = Pubkey::new_unique();
+    h.set_account(copy, fixture(Pubkey::new_unique(), data));
+
+    let attacker = Keypair::new().pubkey();
+    let mut instruction = h.withdraw(field(7), &attacker);
+    instruction.accounts[1].pubkey = copy;
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+    assert_eq!(h.account(&h.vault).unwrap().lamports, DENOMINATION);
+}
+
+#[test]
+fn withdraw_rejects_spoofed_pdas() {
+    let recipient = Keypair::new().pubkey();
+    // Nullifier marker, vault and event authority, in `Withdraw` order.
+    for index in [2, 3, 7] {
+        let mut h = Harness::new();
+        let mut instruction = h.withdraw(field(7), &recipient);
+        instruction.accounts[index].pubkey = Pubkey::new_unique();
+        h.run(instruction).assert_error(ProgramError::InvalidArgument);
+    }
+}
+
+#[test]
+fn withdraw_rejects_a_wrong_system_program() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let mut instruction = h.withdraw(field(7), &recipient);
+    instruction.accounts[6].pubkey = Pubkey::new_unique();
+    let outcome = h.run(instruction);
+    assert!(outcome.result.is_err());
+    assert!(outcome.diffs.is_empty());
+}
+
+#[test]
+fn withdraw_requires_the_relayer_signature() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let mut instruction = h.withdraw(field(7), &recipient);
+    instruction.accounts[0].is_signer = false;
+    h.run(instruction).assert_error(ProgramError::MissingRequiredSignature);
+}
+
+#[test]
+fn withdraw_rejects_a_witness_for_other_inputs() {
+    let recipient = Keypair::new().pubkey();
+    let recipient_field = Harness::recipient_field(&recipient);
+    let proof = mock_verifier::proof(mock_verifier::Mode::AcceptAll);
+    // Replaying a proof under another root or nullifier hash, or dropping
+    // the witness, must not reach the verifier.
+    for witness in [
+        PublicInputs::new(field(2), field(7), recipient_field).unwrap().to_sunspot_witness().to_vec(),
+        PublicInputs::new(Harness::ROOT, field(8), recipient_field).unwrap().to_sunspot_witness().to_vec(),
+        vec![],
+    ] {
+        let mut h = Harness::new();
+        let data = [&proof[..], &witness].concat();
+        let outcome = h.run(h.withdraw_with(Harness::ROOT, field(7), &recipient, 0, &data));
+        outcome.assert_mixer_error(MixerError::VerificationFailed);
+        assert!(outcome.diffs.is_empty());
+    }
+}
+
+#[test]
+fn withdraw_diff_covers_the_payout() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let nullifier = Pubkey::find_program_address(&[b"nullifier", &field(7)], &h.program_id).0;
+    let outcome = h.run(h.withdraw(field(7), &recipient));
+    outcome.assert_ok();
+
+    // The state only counts the withdrawal: the pool charges no protocol fee.
+    let mut expected = vec![h.authority, h.state, nullifier, h.vault, recipient, h.pool_stats];
+    expected.sort();
+    assert_eq!(outcome.changed(), expected);
+    let stats = h.pool_stats();
+    assert_eq!((stats.withdrawals, stats.withdraw_volume), (1, DENOMINATION));
+    assert_eq!(stats.fee_revenue, 0);
+    let (_, recipient_after) = &outcome.diffs[&recipient];
+    assert_eq!(recipient_after.as_ref().unwrap().lamports, DENOMINATION);
+    assert_eq!(h.state().withdraw_count, 1);
+    assert_eq!(h.state().protocol_fees, 0);
+    let (vault_before, vault_after) = &outcome.diffs[&h.vault];
+    assert_eq!(vault_before.as_ref().unwrap().lamports, DENOMINATION);
+    assert!(vault_after.is_none());
+    let (_, marker) = &outcome.diffs[&nullifier];
+    assert_eq!(marker.as_ref().unwrap().owner, solana_sdk_ids::system_program::id());
+}
+
+#[test]
+fn withdraw_receipt_stays_until_the_recipient_closes_it() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let receipt = mixer::WithdrawReceipt::find_address(&h.program_id, &field(7)).0;
+    let mut instruction = cpi::with_withdraw_receipt(h.withdraw(field(7), &recipient));
+    instruction.accounts[11].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+
+    h.warp_to_slot(42);
+    let outcome = h.run(cpi::with_withdraw_receipt(h.withdraw(field(7), &recipient)));
+    outcome.assert_ok();
+    // The relayer pays for the receipt, not the recipient.
+    let (_, recipient_after) = &outcome.diffs[&recipient];
+    assert_eq!(recipient_after.as_ref().unwrap().lamports, DENOMINATION);
+    let account = h.account(&receipt).unwrap();
+    assert_eq!(account.owner, h.program_id);
+    assert_eq!(
+        mixer::WithdrawReceipt::unpack(&account.data).unwrap(),
+        mixer::WithdrawReceipt { recipient, amount: DENOMINATION, slot: 42 }
+    );
+
+    h.run(cpi::close_withdraw_receipt_instruction(&h.program_id, &h.authority, field(7)))
+        .assert_mixer_error(MixerError::Unauthorized);
+    let close = cpi::close_withdraw_receipt_instruction(&h.program_id, &recipient, field(7));
+    h.run(close.clone()).assert_ok();
+    assert!(h.account(&receipt).is_none());
+    assert_eq!(h.account(&recipient).unwrap().lamports, DENOMINATION + account.lamports);
+    h.run(close).assert_error(ProgramError::UninitializedAccount);
+}
+
+#[test]
+fn withdraw_pays_the_relayer_fee_out_of_the_payout() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let nullifier = Pubkey::find_program_address(&[b"nullifier", &field(7)], &h.program_id).0;
+    let mut state = h.state();
+    state.relayer_fee_cap_bps = mixer::admin::MAX_RELAYER_FEE_CAP_BPS;
+    h.set_state(&state);
+    let fee = h.state().max_relayer_fee();
+    let proof = mock_verifier::proof(mock_verifier::Mode::AcceptAll);
+    let outcome = h.run(h.withdraw_under(Harness::ROOT, field(7), &recipient, fee, &proof));
+    outcome.assert_ok();
+
+    let (_, recipient_after) = &outcome.diffs[&recipient];
+    assert_eq!(recipient_after.as_ref().unwrap().lamports, DENOMINATION - fee);
+    let (authority_before, authority_after) = &outcome.diffs[&h.authority];
+    let (_, marker) = &outcome.diffs[&nullifier];
+    assert_eq!(
+        authority_after.as_ref().unwrap().lamports,
+        authority_before.as_ref().unwrap().lamports + fee - marker.as_ref().unwrap().lamports
+    );
+}
+
+#[test]
+fn withdraw_fee_needs_the_recipient_signature() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let proof = mock_verifier::proof(mock_verifier::Mode::AcceptAll);
+    let mut instruction = h.withdraw_under(Harness::ROOT, field(7), &recipient, 1, &proof);
+    assert!(instruction.accounts[4].is_signer);
+    instruction.accounts[4].is_signer = false;
+    let outcome = h.run(instruction);
+    outcome.assert_error(ProgramError::MissingRequiredSignature);
+    assert!(outcome.diffs.is_empty());
+}
+
+#[test]
+fn withdraw_rejects_a_relayer_fee_above_the_cap() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    // New pools take no relayer fee until the authority raises the cap.
+    assert_eq!(h.state().relayer_fee_cap_bps, 0);
+    let fee = h.state().max_relayer_fee() + 1;
+    let proof = mock_verifier::proof(mock_verifier::Mode::AcceptAll);
+    let outcome = h.run(h.withdraw_under(Harness::ROOT, field(7), &recipient, fee, &proof));
+    outcome.assert_mixer_error(MixerError::RelayerFeeTooHigh);
+    assert!(outcome.diffs.is_empty());
+}
+
+#[test]
+fn withdraw_rejects_an_unpinned_verifier() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.verifier_program = Pubkey::default();
+    h.set_state(&state);
+    let outcome = h.run(h.withdraw(field(7), &Keypair::new().pubkey()));
+    outcome.assert_mixer_error(MixerError::InvalidVerifier);
+    assert!(outcome.diffs.is_empty());
+}
+
+#[test]
+fn initialize_rejects_out_of_range_tree_depths() {
+    for depth in [0, MixerState::MAX_TREE_DEPTH + 1] {
+        let mut h = Harness::new();
+        let instruction = cpi::initialize_instruction(
+            &h.program_id,
+            &h.authority,
+            DENOMINATION,
+            0,
+            0,
+            Curve::Bn254,
+            depth,
+            &h.verifier_id,
+        );
+        h.run(instruction).assert_mixer_error(MixerError::ParameterOutOfBounds);
+    }
+}
+
+#[test]
+fn initialize_requires_a_verifier() {
+    let mut h = Harness::new();
+    let mut instruction = cpi::initialize_instruction(
+        &h.program_id,
+        &h.authority,
+        DENOMINATION,
+        0,
+        0,
+        Curve::Bn254,
+        MixerState::DEFAULT_TREE_DEPTH,
+        &Pubkey::default(),
+    );
+    h.run(instruction.clone()).assert_mixer_error(MixerError::InvalidVerifier);
+    instruction.accounts.truncate(3);
+    h.run(instruction).assert_error(ProgramError::NotEnoughAccountKeys);
+}
+
+#[test]
+fn reinitialize_fails_even_for_the_authority() {
+    let mut h = Harness::new();
+    let instruction = cpi::initialize_instruction(
+        &h.program_id,
+        &h.authority,
+        DENOMINATION,
+        0,
+        0,
+        Curve::Bn254,
+        MixerState::DEFAULT_TREE_DEPTH,
+        &h.verifier_id,
+    );
+    h.run(instruction).assert_error(ProgramError::AccountAlreadyInitialized);
+}
+
+#[test]
+fn resize_state_upgrades_a_pool_with_the_baseline_layout() {
+    let mut h = Harness::new();
+    let mut account = h.account(&h.state).unwrap();
+    account.data.truncate(MixerState::BASELINE_LEN);
+    account.lamports = Rent::default().minimum_balance(MixerState::BASELINE_LEN);
+    h.set_account(h.state, account);
+    let recipient = Keypair::new().pubkey();
+    h.run(h.withdraw(field(7), &recipient)).assert_error(ProgramError::AccountDataTooSmall);
+
+    let (program_id, authority) = (h.program_id, h.authority);
+    let resize = |verifier: &Pubkey| {
+        cpi::resize_state_instruction(&program_id, &authority, &authority, &authority, verifier)
+    };
+    let program_data = resize(&h.verifier_id).accounts[5].pubkey;
+    let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    data.extend_from_slice(h.authority.as_ref());
+    h.set_account(program_data, fixture(solana_sdk_ids::bpf_loader_upgradeable::id(), data));
+
+    h.run(resize(&Pubkey::default())).assert_mixer_error(MixerError::InvalidVerifier);
+    let mut unsigned = resize(&h.verifier_id);
+    unsigned.accounts[4] = AccountMeta::new_readonly(Keypair::new().pubkey(), false);
+    h.run(unsigned).assert_error(ProgramError::MissingRequiredSignature);
+
+    h.run(resize(&h.verifier_id)).assert_ok();
+    assert_eq!(h.account(&h.state).unwrap().data.len(), MixerState::LEN);
+    let state = h.state();
+    assert_eq!(state.tree_depth, MixerState::DEFAULT_TREE_DEPTH);
+    assert_eq!((state.authority, state.verifier_program), (h.authority, h.verifier_id));
+    assert_eq!(state.outstanding_notes(), 1);
+    h.run(h.withdraw(field(7), &recipient)).assert_ok();
+}
+
+#[test]
+fn resize_state_requires_the_upgrade_authority() {
+    let mut h = Harness::new();
+    let mut account = h.account(&h.state).unwrap();
+    account.data.truncate(MixerState::BASELINE_LEN);
+    h.set_account(h.state, account);
+    let resize = cpi::resize_state_instruction(
+        &h.program_id,
+        &h.authority,
+        &h.authority,
+        &h.authority,
+        &h.verifier_id,
+    );
+    let program_data = resize.accounts[5].pubkey;
+    h.run(resize.clone()).assert_error(ProgramError::InvalidArgument);
+
+    let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    data.extend_from_slice(Keypair::new().pubkey().as_ref());
+    h.set_account(program_data, fixture(solana_sdk_ids::bpf_loader_upgradeable::id(), data));
+    h.run(resize.clone()).assert_mixer_error(MixerError::Unauthorized);
+
+    // An immutable program has no upgrade authority to resize it.
+    let data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    h.set_account(program_data, fixture(solana_sdk_ids::bpf_loader_upgradeable::id(), data));
+    h.run(resize).assert_mixer_error(MixerError::Unauthorized);
+}
+
+fn sweep_excess(h: &Harness, destination: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: h.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(h.authority, true),
+            AccountMeta::new_readonly(h.state, false),
+            AccountMeta::new(h.vault, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(solana_sdk_ids::system_program::id(), false),
+            AccountMeta::new_readonly(mixer::events::event_authority(&h.program_id).0, false),
+            AccountMeta::new_readonly(h.program_id, false),
+        ],
+        data: vec![23],
+    }
+}
+
+#[test]
+fn sweep_excess_moves_only_the_excess() {
+    let mut h = Harness::new();
+    let reserve = DENOMINATION + Rent::default().minimum_balance(0);
+    let mut vault = h.account(&h.vault).unwrap();
+    vault.lamports = reserve + 5_000;
+    h.set_account(h.vault, vault);
+    let treasury = Keypair::new().pubkey();
+    h.set_account(treasury, fixture(solana_sdk_ids::system_program::id(), vec![]));
+
+    let outcome = h.run(sweep_excess(&h, &treasury));
+    outcome.assert_ok();
+    let mut expected = vec![h.vault, treasury];
+    expected.sort();
+    assert_eq!(outcome.changed(), expected);
+    assert_eq!(h.account(&h.vault).unwrap().lamports, reserve);
+    assert_eq!(h.account(&treasury).unwrap().lamports, DENOMINATION + 5_000);
+
+    // The outstanding note is still covered.
+    h.run(h.withdraw(field(7), &Keypair::new().pubkey())).assert_ok();
+}
+
+#[test]
+fn sweep_excess_rejects_an_insolvent_vault() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.deposit_count = 2;
+    h.set_state(&state);
+    let outcome = h.run(sweep_excess(&h, &Keypair::new().pubkey()));
+    outcome.assert_mixer_error(MixerError::VaultInsolvent);
+    assert!(outcome.diffs.is_empty());
+}
+
+#[test]
+fn push_root_counts_one_leaf_per_deposit() {
+    let mut h = Harness::new();
+    let capacity = h.state().tree_capacity();
+    let reserve = 2 * DENOMINATION + Rent::default().minimum_balance(0);
+    let push_root = |h: &Harness, leaf_count| {
+        cpi::push_root_instruction(&h.program_id, &h.authority, &h.state, field(2), leaf_count)
+    };
+    // A reported full tree would otherwise rotate the pool on the next push.
+    h.run(push_root(&h, Some(capacity))).assert_mixer_error(MixerError::ParameterOutOfBounds);
+    h.run(push_root(&h, Some(1))).assert_mixer_error(MixerError::ParameterOutOfBounds);
+    h.run(push_root(&h, None)).assert_ok();
+    assert_eq!(h.state().leaf_count, 2);
+    h.run(push_root(&h, Some(3))).assert_ok();
+    let state = h.state();
+    assert_eq!((state.leaf_count, state.tree_id), (3, 0));
+    assert_eq!(state.outstanding_notes(), 3);
+    let reserve = reserve + DENOMINATION;
+
+    let mut vault = h.account(&h.vault).unwrap();
+    vault.lamports = reserve + 5_000;
+    h.set_account(h.vault, vault);
+    let treasury = Keypair::new().pubkey();
+    h.set_account(treasury, fixture(solana_sdk_ids::system_program::id(), vec![]));
+    h.run(sweep_excess(&h, &treasury)).assert_ok();
+    assert_eq!(h.account(&h.vault).unwrap().lamports, reserve);
+}
+
+#[test]
+fn sweep_excess_requires_the_authority() {
+    let mut h = Harness::new();
+    let mut instruction = sweep_excess(&h, &Keypair::new().pubkey());
+    instruction.accounts[0].pubkey = Keypair::new().pubkey();
+    h.run(instruction).assert_mixer_error(MixerError::Unauthorized);
+}
+
+#[test]
+fn append_backup_creates_and_grows_the_vault() {
+    let mut h = Harness::new();
+    let vault_id = field(0xb0);
+    let backup_vault = mixer::backup::find_backup_vault_address(&h.program_id, &vault_id).0;
+    let append = |h: &Harness, ciphertext: &[u8]| {
+        mixer::backup::append_backup_instruction(&h.program_id, &h.authority, vault_id, ciphertext)
+    };
+
+    let outcome = h.run(append(&h, &[1; 100]));
+    outcome.assert_ok();
+    let mut expected = vec![h.authority, backup_vault];
+    expected.sort();
+    assert_eq!(outcome.changed(), expected);
+    h.run(append(&h, &[2; 40])).assert_ok();
+
+    let account = h.account(&backup_vault).unwrap();
+    assert_eq!(account.owner, h.program_id);
+    assert_eq!(account.lamports, Rent::default().minimum_balance(account.data.len()));
+    assert_eq!(mixer::backup::backup_entries(&account.data), vec![&[1; 100][..], &[2; 40][..]]);
+}
+
+#[test]
+fn append_backup_rejects_oversized_ciphertexts_and_spoofed_vaults() {
+    let mut h = Harness::new();
+    let too_long = [0; mixer::backup::MAX_BACKUP_CIPHERTEXT_LEN + 1];
+    let instruction =
+        mixer::backup::append_backup_instruction(&h.program_id, &h.authority, field(0xb0), &too_long);
+    h.run(instruction).assert_mixer_error(MixerError::InvalidInstruction);
+
+    let mut instruction =
+        mixer::backup::append_backup_instruction(&h.program_id, &h.authority, field(0xb0), &[1; 8]);
+    instruction.accounts[1].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+}
+
+#[test]
+fn get_state_returns_the_pool_state_and_changes_nothing() {
+    let mut h = Harness::new();
+    h.run(h.push_root(field(2))).assert_ok();
+
+    let outcome = h.run(mixer::view::get_state_instruction(&h.program_id));
+    outcome.assert_ok();
+    assert!(outcome.diffs.is_empty());
+    let view = mixer::view::StateView::try_from_slice(&outcome.return_data).unwrap();
+    assert_eq!(view, mixer::view::StateView::from(&h.state()));
+    assert_eq!(view.denomination, DENOMINATION);
+    assert_eq!(view.latest_root, field(2));
+    assert_eq!(view.authority, h.authority.to_bytes());
+}
+
+#[test]
+fn get_state_rejects_other_accounts() {
+    let mut h = Harness::new();
+    let mut instruction = mixer::view::get_state_instruction(&h.program_id);
+    instruction.accounts[0].pubkey = Pubkey::new_unique();
+    h.run(instruction).assert_error(ProgramError::InvalidArgument);
+}
+
+#[test]
+fn close_account_returns_a_buffer_and_a_spent_counter_to_their_owner() {
+    let mut h = Harness::new();
+    let target = mixer::CloseTarget::ProofBuffer { buffer_id: 3 };
+    let buffer = mixer::ProofBuffer::find_address(&h.program_id, &h.authority, 3).0;
+    let mut data = h.authority.to_bytes().to_vec();
+    data.resize(mixer::ProofBuffer::HEADER_LEN + 64, 0);
+    h.set_account(buffer, fixture(h.program_id, data));
+    // Another signer names its own PDA, not the authority's buffer.
+    let mut stranger = cpi::close_account_instruction(&h.program_id, &Pubkey::new_unique(), target);
+    stranger.accounts[1].pubkey = buffer;
+    h.run(stranger).assert_error(ProgramError::InvalidArgument);
+    let before = h.account(&h.authority).unwrap().lamports;
+    h.run(cpi::close_account_instruction(&h.program_id, &h.authority, target)).assert_ok();
+    assert!(h.account(&buffer).is_none());
+    assert_eq!(h.account(&h.authority).unwrap().lamports, before + DENOMINATION);
+
+    let mut state = h.state();
+    state.max_deposits_per_depositor = 1;
+    h.set_state(&state);
+    let push_root =
+        cpi::push_root_with_counter_instruction(&h.program_id, &h.authority, &h.state, field(2), None);
+    h.run(push_root).assert_ok();
+    let close =
+        cpi::close_account_instruction(&h.program_id, &h.authority, mixer::CloseTarget::DepositCounter);
+    // Closing it now would let the depositor deposit again this epoch.
+    h.run(close.clone()).assert_mixer_error(MixerError::AccountInUse);
+    h.advance_epoch();
+    h.run(close.clone()).assert_ok();
+    assert!(h.account(&close.accounts[1].pubkey).is_none());
+    h.run(close).assert_error(ProgramError::UninitializedAccount);
+}
+
+#[test]
+fn close_account_keeps_verifying_keys_the_pool_uses() {
+    let mut h = Harness::new();
+    let target = mixer::CloseTarget::VerifyingKey { vk_id: 1 };
+    let vk = mixer::verifier::Groth16VerifyingKey::find_address(&h.program_id, 1).0;
+    h.set_account(vk, fixture(h.program_id, vec![0; mixer::verifier::Groth16VerifyingKey::LEN]));
+    let mut state = h.state();
+    state.verifier_backend = mixer::verifier::VerifierBackend::NativeGroth16 as u8;
+    state.verifier_program = vk;
+    h.set_state(&state);
+
+    h.run(cpi::close_account_instruction(&h.program_id, &Pubkey::new_unique(), target))
+        .assert_mixer_error(MixerError::Unauthorized);
+    let close = cpi::close_account_instruction(&h.program_id, &h.authority, target);
+    h.run(close.clone()).assert_mixer_error(MixerError::AccountInUse);
+
+    state.verifier_backend = mixer::verifier::VerifierBackend::SunspotCpi as u8;
+    state.verifier_program = h.verifier_id;
+    state.circuit_versions[0] = mixer::verifier::CircuitVersion {
+        version: 2,
+        backend: mixer::verifier::VerifierBackend::NativeGroth16 as u8,
+        verifier: vk,
+        activation_slot: 0,
+        deprecation_slot: 10,
+    };
+    h.set_state(&state);
+    h.run(close.clone()).assert_mixer_error(MixerError::AccountInUse);
+    // Past its deprecation slot the version can no longer use the key.
+    h.warp_to_slot(10);
+    h.run(close).assert_ok();
+    assert!(h.account(&vk).is_none());
+}
+
+/// `QueueAdminAction` (tag 11) of `action`, or `ExecuteAdminAction` (12)
+/// without one, for action 1.
+fn admin_action(h: &Harness, action: Option<&mixer::admin::AdminAction>) -> Instruction {
+    let pending = Pubkey::find_program_address(&[b"admin_action", &1u64.to_le_bytes()], &h.program_id).0;
+    let mut accounts = vec![
+        AccountMeta::new(h.authority, true),
+        AccountMeta::new(h.state, false),
+        AccountMeta::new(pending, false),
+    ];
+    let mut data = vec![if action.is_some() { 11 } else { 12 }];
+    data.extend_from_slice(&1u64.to_le_bytes());
+    if let Some(action) = action {
+        accounts.push(AccountMeta::new_readonly(solana_sdk_ids::system_program::id(), false));
+        data.extend_from_slice(&borsh::to_vec(action).unwrap());
+    }
+    accounts.extend([
+        AccountMeta::new_readonly(mixer::events::event_authority(&h.program_id).0, false),
+        AccountMeta::new_readonly(h.program_id, false),
+    ]);
+    Instruction { program_id: h.program_id, accounts, data }
+}
+
+#[test]
+fn verifier_actions_reject_the_zero_address() {
+    use mixer::admin::AdminAction;
+    let mut h = Harness::new();
+    let backend = h.state().verifier_backend;
+    for action in [
+        AdminAction::SetVerifier { verifier_program: [0; 32] },
+        AdminAction::SetVerifierBackend { backend, verifier: [0; 32] },
+    ] {
+        h.run(admin_action(&h, Some(&action))).assert_mixer_error(MixerError::ParameterOutOfBounds);
+    }
+}
+
+#[test]
+fn set_denomination_needs_a_paused_pool_without_unspent_notes() {
+    let mut h = Harness::new();
+    let zero = mixer::admin::AdminAction::SetDenomination { denomination: 0 };
+    h.run(admin_action(&h, Some(&zero))).assert_mixer_error(MixerError::ParameterOutOfBounds);
+    let action = mixer::admin::AdminAction::SetDenomination { denomination: 2 * DENOMINATION };
+    h.run(admin_action(&h, Some(&action))).assert_ok();
+
+    h.run(admin_action(&h, None)).assert_mixer_error(MixerError::NotPaused);
+    let mut state = h.state();
+    state.paused = true;
+    h.set_state(&state);
+    h.run(admin_action(&h, None)).assert_mixer_error(MixerError::NotesOutstanding);
+
+    state.paused = false;
+    h.set_state(&state);
+    h.run(h.withdraw(field(7), &Keypair::new().pubkey())).assert_ok();
+    let mut state = h.state();
+    state.paused = true;
+    h.set_state(&state);
+    h.run(admin_action(&h, None)).assert_ok();
+    assert_eq!(h.state().denomination, 2 * DENOMINATION);
+}
+
+#[test]
+fn migrate_pool_accepts_both_circuits_until_the_window_ends() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let (backend, verifier) = (h.state().verifier_backend, h.verifier_id.to_bytes());
+    let migrate = |window_slots| mixer::admin::AdminAction::MigratePool {
+        version: 2,
+        backend,
+        verifier,
+        window_slots,
+    };
+    h.run(admin_action(&h, Some(&migrate(0)))).assert_mixer_error(MixerError::ParameterOutOfBounds);
+    h.run(admin_action(&h, Some(&migrate(10)))).assert_ok();
+    h.warp_to_slot(5);
+    h.run(admin_action(&h, None)).assert_ok();
+    let state = h.state();
+    assert_eq!((state.migration_version, state.migration_end_slot), (2, 15));
+    assert!(state.circuit_verifier(2, 5).is_ok());
+
+    let finalize = mixer::admin::finalize_migration_instruction(&h.program_id);
+    h.run(finalize.clone()).assert_mixer_error(MixerError::AdminActionNotReady);
+    h.run(h.withdraw(field(7), &recipient)).assert_ok();
+
+    h.run(system_instruction::transfer(&h.authority, &h.vault, DENOMINATION)).assert_ok();
+    h.warp_to_slot(15);
+    // The old circuit stops at the end of the window, finalized or not.
+    h.run(h.withdraw(field(8), &recipient)).assert_mixer_error(MixerError::CircuitVersionNotActive);
+    h.run(finalize.clone()).assert_ok();
+    let state = h.state();
+    assert_eq!((state.migration_version, state.migration_end_slot), (0, 0));
+    assert_eq!(state.verifier_program, h.verifier_id);
+    h.run(h.withdraw(field(8), &recipient)).assert_ok();
+    h.run(finalize).assert_mixer_error(MixerError::InvalidAdminAction);
+}
+
+#[test]
+fn migrate_pool_deprecates_the_migration_it_replaces() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let (backend, verifier) = (h.state().verifier_backend, h.verifier_id.to_bytes());
+    let migrate = |version| mixer::admin::AdminAction::MigratePool {
+        version,
+        backend,
+        verifier,
+        window_slots: 10,
+    };
+    let under = |mut withdraw: Instruction, version: u16| {
+        withdraw.data[2 + 32 * 3..2 + 32 * 3 + 2].copy_from_slice(&version.to_le_bytes());
+        withdraw
+    };
+    h.run(admin_action(&h, Some(&migrate(2)))).assert_ok();
+    h.warp_to_slot(5);
+    h.run(admin_action(&h, None)).assert_ok();
+    h.run(admin_action(&h, Some(&migrate(3)))).assert_ok();
+    h.warp_to_slot(6);
+    h.run(admin_action(&h, None)).assert_ok();
+    let state = h.state();
+    assert_eq!((state.migration_version, state.migration_end_slot), (3, 16));
+    // Deprecated at once, the replaced version's slot went to version 3.
+    assert!(state.circuit_versions.iter().all(|e| e.version != 2));
+    h.run(under(h.withdraw(field(7), &recipient), 2)).assert_mixer_error(MixerError::UnknownCircuitVersion);
+
+    h.warp_to_slot(16);
+    h.run(mixer::admin::finalize_migration_instruction(&h.program_id)).assert_ok();
+    h.run(under(h.withdraw(field(7), &recipient), 2)).assert_mixer_error(MixerError::UnknownCircuitVersion);
+    h.run(under(h.withdraw(field(7), &recipient), 3)).assert_ok();
+}
+
+#[test]
+fn a_full_tree_is_archived_and_stays_withdrawable() {
+    let mut h = Harness::new();
+    let recipient = Keypair::new().pubkey();
+    let mut state = h.state();
+    state.leaf_count = state.tree_capacity();
+    h.set_state(&state);
+    let program_id = h.program_id;
+    let archive = mixer::CommitmentTree::find_address(&program_id, 0).0;
+    let in_tree = |mut instruction: Instruction, tree_id: u8| {
+        instruction.data[2 + 32 * 3 + 2] = tree_id;
+        instruction.accounts[10].pubkey =
+            mixer::CommitmentTree::find_address(&program_id, tree_id).0;
+        instruction
+    };
+
+    let push_root =
+        cpi::push_root_instruction(&h.program_id, &h.authority, &h.state, field(2), Some(1));
+    h.run(push_root.clone()).assert_error(ProgramError::NotEnoughAccountKeys);
+    h.run(cpi::with_tree_rotation(push_root.clone(), 1))
+        .assert_error(ProgramError::InvalidArgument);
+    h.run(cpi::with_tree_rotation(push_root, 0)).assert_ok();
+    let state = h.state();
+    assert_eq!((state.tree_id, state.leaf_count), (1, 1));
+    assert_eq!(state.total_leaves(), state.tree_capacity() + 1);
+    let tree = mixer::CommitmentTree::unpack(&h.account(&archive).unwrap().data).unwrap();
+    assert_eq!((tree.tree_id, tree.leaf_count), (0, state.tree_capacity()));
+    assert!(tree.root_time(&Harness::ROOT).is_some());
+    assert_eq!(tree.root_slot(&Harness::ROOT), Some(0));
+
+    // Data without a tree id is checked against the current tree only.
+    let mut untreed = h.withdraw(field(7), &recipient);
+    untreed.data[0] = mixer::VERSION_FLAG | 1;
+    untreed.data.remove(2 + 32 * 3 + 2);
+    untreed.accounts.truncate(10);
+    h.run(untreed).assert_mixer_error(MixerError::UnknownRoot);
+    h.run(in_tree(h.withdraw(field(7), &recipient), 2)).assert_mixer_error(MixerError::UnknownRoot);
+    h.run(h.withdraw(field(7), &recipient)).assert_ok();
+
+    let proof = mock_verifier::proof(mock_verifier::Mode::AcceptAll);
+    let current = h.withdraw_under(field(2), field(8), &recipient, 0, &proof);
+    h.run(current.clone()).assert_mixer_error(MixerError::UnknownRoot);
+    h.run(in_tree(current, 1)).assert_ok();
+}
+
+#[test]
+fn push_root_fails_once_the_last_tree_is_full() {
+    let mut h = Harness::new();
+    let mut state = h.state();
+    state.tree_id = u8::MAX;
+    state.leaf_count = state.tree_capacity();
+    h.set_state(&state);
+    let push_root = cpi::with_tree_rotation(h.push_root(field(2)), u8::MAX);
+    h.run(push_root).assert_mixer_error(MixerError::ParameterOutOfBounds);
+}
@@ -0,0 +1,93 @@
+//! Mock verifier program for tests and local development.
+//!
+//! Stands in for a Sunspot verifier behind the mixer's `SunspotCpi` backend,
+//! so a pool can be exercised without circuit artifacts or a prover. The
+//! mixer forwards a withdraw's proof bytes as this program's instruction
+//! data, and the first byte selects a [`Mode`]:
+//!
+//! - [`Mode::AcceptAll`] succeeds.
+//! - [`Mode::RejectAll`] fails with [`MockVerifierError::ProofRejected`].
+//! - [`Mode::Magic`] succeeds only if the remaining bytes start with
+//!   [`MAGIC`], to tell a deliberate proof from stray data.
+//!
+//! [`proof`] builds the bytes for each mode. Empty data and unknown modes
+//! fail with [`MockVerifierError::InvalidInstruction`]. The program reads no
+//! accounts and must never be pinned by a production pool.
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use thiserror::Error;
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+#[derive(Error, Debug, Copy, Clone)]
+pub enum MockVerifierError {
+    #[error("Invalid instruction")]
+    InvalidInstruction,
+    #[error("Proof rejected")]
+    ProofRejected,
+}
+
+impl From<MockVerifierError> for ProgramError {
+    fn from(e: MockVerifierError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+/// Bytes [`Mode::Magic`] accepts after the mode byte.
+pub const MAGIC: &[u8] = b"mock-verifier-ok";
+
+/// How the verifier answers; the first byte of the instruction data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Mode {
+    AcceptAll = 0,
+    RejectAll = 1,
+    Magic = 2,
+}
+
+impl TryFrom<u8> for Mode {
+    type Error = MockVerifierError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Mode::AcceptAll),
+            1 => Ok(Mode::RejectAll),
+            2 => Ok(Mode::Magic),
+            _ => Err(MockVerifierError::InvalidInstruction),
+        }
+    }
+}
+
+/// Proof bytes, i.e. instruction data, that `mode` answers for. A
+/// [`Mode::Magic`] proof carries [`MAGIC`] and is accepted.
+pub fn proof(mode: Mode) -> Vec<u8> {
+    let mut data = vec![mode as u8];
+    if mode == Mode::Magic {
+        data.extend_from_slice(MAGIC);
+    }
+    data
+}
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    _accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let (mode, rest) = instruction_data
+        .split_first()
+        .ok_or(MockVerifierError::InvalidInstruction)?;
+    let accepted = match Mode::try_from(*mode)? {
+        Mode::AcceptAll => true,
+        Mode::RejectAll => false,
+        Mode::Magic => rest.starts_with(MAGIC),
+    };
+    if !accepted {
+        msg!("Mock verifier rejected the proof");
+        return Err(MockVerifierError::ProofRejected.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! The verifier's answer to each mode, as a program under
+//! `solana-program-test`, the way the mixer's `SunspotCpi` backend invokes it.
+
+use mock_verifier::{proof, MockVerifierError, Mode, MAGIC};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_keypair::Keypair;
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+struct Verifier {
+    banks: BanksClient,
+    payer: Keypair,
+    program_id: Pubkey,
+}
+
+impl Verifier {
+    async fn start() -> Self {
+        let program_id = Pubkey::new_unique();
+        let mut test = ProgramTest::new(
+            "mock_verifier",
+            program_id,
+            processor!(mock_verifier::process_instruction),
+        );
+        test.prefer_bpf(false);
+        let (banks, payer, _) = test.start().await;
+        Verifier { banks, payer, program_id }
+    }
+
+    /// Send `data` as the verifier's instruction data, as the mixer forwards
+    /// a proof. No accounts are passed; the verifier reads none.
+    async fn verify(&mut self, data: Vec<u8>) -> Result<(), BanksClientError> {
+        let instruction = Instruction { program_id: self.program_id, accounts: vec![], data };
+        let blockhash = self.banks.get_latest_blockhash().await?;
+        let tx = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        );
+        self.banks.process_transaction(tx).await
+    }
+}
+
+fn assert_verifier_error(result: Result<(), BanksClientError>, expected: MockVerifierError) {
+    match result.expect_err("verification should fail").unwrap() {
+        TransactionError::InstructionError(0, InstructionError::Custom(code)) => {
+            assert_eq!(code, expected as u32, "expected {expected:?}");
+        }
+        other => panic!("expected {expected:?}, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn each_mode_answers_as_documented() {
+    let mut verifier = Verifier::start().await;
+    verifier.verify(proof(Mode::AcceptAll)).await.unwrap();
+    verifier.verify(proof(Mode::Magic)).await.unwrap();
+    assert_verifier_error(
+        verifier.verify(proof(Mode::RejectAll)).await,
+        MockVerifierError::ProofRejected,
+    );
+
+    // Magic needs its bytes right after the mode byte.
+    let mut wrong_magic = proof(Mode::Magic);
+    wrong_magic[MAGIC.len()] ^= 1;
+    assert_verifier_error(
+        verifier.verify(wrong_magic).await,
+        MockVerifierError::ProofRejected,
+    );
+    let mut extended = proof(Mode::Magic);
+    extended.extend_from_slice(&[0xab; 256]);
+    verifier.verify(extended).await.unwrap();
+}
+
+#[tokio::test]
+async fn empty_data_and_unknown_modes_are_invalid() {
+    let mut verifier = Verifier::start().await;
+    for data in [vec![], vec![3], vec![0xff, 0]] {
+        assert_verifier_error(
+            verifier.verify(data).await,
+            MockVerifierError::InvalidInstruction,
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Reference integrator: a payroll program that pays employees into the mixer.
+//!
+//! Each employer has a treasury PDA (seeds `["treasury", employer]`) that they
+//! top up with plain system transfers. To pay an employee, the employer takes
+//! the commitment the employee generated, inserts it into the off-chain tree,
+//! and calls `PayIntoMixer` with the resulting root. The treasury then
+//! deposits one denomination into the mixer through [`mixer::cpi::deposit`],
+//! signing with its PDA seeds. The employee later withdraws to any address
+//! without the payment being linkable to the employer.
+//!
+//! This program exists to show and exercise the CPI surface; it keeps no
+//! state of its own.
+
+use mixer::cpi::{deposit, DepositAccounts};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use thiserror::Error;
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
+
+#[derive(Error, Debug, Copy, Clone)]
+pub enum PayrollError {
+    #[error("Invalid instruction")]
+    InvalidInstruction,
+    #[error("Invalid mixer program")]
+    InvalidMixerProgram,
+}
+
+impl From<PayrollError> for ProgramError {
+    fn from(e: PayrollError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+pub const TREASURY_SEED: &[u8] = b"treasury";
+
+pub fn find_treasury_address(program_id: &Pubkey, employer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY_SEED, employer.as_ref()], program_id)
+}
+
+/// Instructions supported by the payroll example.
+pub enum PayrollInstruction {
+    /// Deposit one mixer denomination from the employer's treasury.
+    ///
+    /// Accounts:
+    ///   0. [signer]   Employer.
+    ///   1. [writable] Employer treasury (PDA of ["treasury", employer]).
+    ///   2. [writable] Mixer state account.
+    ///   3. [writable] Mixer vault.
+    ///   4. []         System program.
+    ///   5. []         Mixer event authority.
+    ///   6. []         Mixer program.
+    ///   7. [writable] Mixer latest root account.
+    ///   8. [writable] Mixer pool stats account.
+    ///
+    /// Data:
+    ///   - new_root: [u8; 32] (tree root including the employee's commitment)
+    PayIntoMixer { new_root: [u8; 32] },
+}
+
+impl PayrollInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, PayrollError> {
+        let (tag, rest) = input.split_first().ok_or(PayrollError::InvalidInstruction)?;
+        Ok(match tag {
+            0 => {
+                if rest.len() != 32 {
+                    return Err(PayrollError::InvalidInstruction);
+                }
+                let mut new_root = [0u8; 32];
+                new_root.copy_from_slice(rest);
+                PayrollInstruction::PayIntoMixer { new_root }
+            }
+            _ => return Err(PayrollError::InvalidInstruction),
+        })
+    }
+}
+
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    match PayrollInstruction::unpack(instruction_data)? {
+        PayrollInstruction::PayIntoMixer { new_root } => {
+            process_pay_into_mixer(program_id, accounts, new_root)
+        }
+    }
+}
+
+fn process_pay_into_mixer(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_root: [u8; 32],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let employer = next_account_info(account_info_iter)?;
+    let treasury = next_account_info(account_info_iter)?;
+    let mixer_state = next_account_info(account_info_iter)?;
+    let mixer_vault = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let event_authority = next_account_info(account_info_iter)?;
+    let mixer_program = next_account_info(account_info_iter)?;
+    let latest_root = next_account_info(account_info_iter)?;
+    let pool_stats = next_account_info(account_info_iter)?;
+
+    if !employer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let (expected_treasury, bump) = find_treasury_address(program_id, employer.key);
+    if treasury.key != &expected_treasury {
+        msg!("Invalid treasury PDA");
+        return Err(ProgramError::InvalidArgument);
+    }
+    // The mixer state is only trusted if it belongs to the mixer program.
+    if mixer_state.owner != mixer_program.key || !mixer_program.executable {
+        return Err(PayrollError::InvalidMixerProgram.into());
+    }
+
+    deposit(
+        DepositAccounts {
+            depositor: treasury,
+            mixer_state,
+            mixer_vault,
+            system_program,
+            event_authority,
+            mixer_program,
+            latest_root,
+            pool_stats,
+            deposit_counter: None,
+            commitment_tree: None,
+            receipt: None,
+        },
+        new_root,
+        None,
+        &[&[TREASURY_SEED, employer.key.as_ref(), &[bump]]],
+    )
+}
@@ -0,0 +1,151 @@
+//! `PayIntoMixer` under `solana-program-test`, with the mixer running
+//! natively next to the payroll program so the treasury's deposit CPI is
+//! exercised end to end.
+
+use mixer::{cpi, curve::Curve, MixerState};
+use payroll_example::{find_treasury_address, PayrollError};
+use solana_keypair::Keypair;
+use solana_program::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+use solana_signer::Signer;
+use solana_system_interface::instruction as system_instruction;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+const DENOMINATION: u64 = 1_000_000_000;
+
+struct Payroll {
+    banks: BanksClient,
+    employer: Keypair,
+    program_id: Pubkey,
+    mixer_id: Pubkey,
+    treasury: Pubkey,
+}
+
+impl Payroll {
+    /// A pool, and a treasury of the payer's funded for two payments.
+    async fn start() -> Self {
+        let program_id = Pubkey::new_unique();
+        let mixer_id = Pubkey::new_unique();
+        let mut test = ProgramTest::new(
+            "payroll_example",
+            program_id,
+            processor!(payroll_example::process_instruction),
+        );
+        test.add_program("mixer", mixer_id, processor!(mixer::process_instruction));
+        test.prefer_bpf(false);
+        let (banks, employer, _) = test.start().await;
+        let treasury = find_treasury_address(&program_id, &employer.pubkey()).0;
+        let mut payroll = Payroll { banks, employer, program_id, mixer_id, treasury };
+        let payer = payroll.employer.pubkey();
+        let init = cpi::initialize_instruction(
+            &mixer_id,
+            &payer,
+            DENOMINATION,
+            0,
+            0,
+            Curve::Bn254,
+            MixerState::DEFAULT_TREE_DEPTH,
+            &Pubkey::new_unique(),
+        );
+        let latest_root = cpi::create_latest_root_instruction(&mixer_id, &payer);
+        let pool_stats = cpi::create_pool_stats_instruction(&mixer_id, &payer);
+        let fund = system_instruction::transfer(&payer, &treasury, 2 * DENOMINATION);
+        payroll.send(&[init, latest_root, pool_stats, fund]).await.unwrap();
+        payroll
+    }
+
+    async fn send(&mut self, instructions: &[Instruction]) -> Result<(), BanksClientError> {
+        let blockhash = self.banks.get_latest_blockhash().await?;
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.employer.pubkey()),
+            &[&self.employer],
+            blockhash,
+        );
+        self.banks.process_transaction(tx).await
+    }
+
+    /// `PayIntoMixer` of `new_root` from `treasury`, with accounts in the
+    /// documented order.
+    fn pay_into_mixer(&self, treasury: Pubkey, new_root: [u8; 32]) -> Instruction {
+        let mixer_id = &self.mixer_id;
+        let mut data = vec![0];
+        data.extend_from_slice(&new_root);
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.employer.pubkey(), true),
+                AccountMeta::new(treasury, false),
+                AccountMeta::new(state_address(mixer_id), false),
+                AccountMeta::new(vault_address(mixer_id), false),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+                AccountMeta::new_readonly(mixer::events::event_authority(mixer_id).0, false),
+                AccountMeta::new_readonly(*mixer_id, false),
+                AccountMeta::new(mixer::LatestRoot::find_address(mixer_id).0, false),
+                AccountMeta::new(mixer::PoolStats::find_address(mixer_id).0, false),
+            ],
+            data,
+        }
+    }
+
+    async fn lamports(&mut self, address: Pubkey) -> u64 {
+        self.banks.get_balance(address).await.unwrap()
+    }
+}
+
+fn state_address(mixer_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"mixer_state"], mixer_id).0
+}
+
+fn vault_address(mixer_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"mixer_vault"], mixer_id).0
+}
+
+fn field(tag: u8) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[31] = tag;
+    out
+}
+
+#[tokio::test]
+async fn pay_into_mixer_deposits_from_the_treasury() {
+    let mut payroll = Payroll::start().await;
+    let treasury = payroll.treasury;
+    payroll.send(&[payroll.pay_into_mixer(treasury, field(1))]).await.unwrap();
+
+    assert_eq!(payroll.lamports(treasury).await, DENOMINATION);
+    let vault = vault_address(&payroll.mixer_id);
+    assert!(payroll.lamports(vault).await >= DENOMINATION);
+    let account = payroll.banks.get_account(state_address(&payroll.mixer_id)).await;
+    let state = mixer::unpack_state(&account.unwrap().unwrap().data).unwrap();
+    assert_eq!(state.roots[state.current_root_index as usize], field(1));
+    assert_eq!((state.leaf_count, state.deposit_count), (1, 1));
+}
+
+#[tokio::test]
+async fn pay_into_mixer_rejects_another_treasury_or_mixer() {
+    let mut payroll = Payroll::start().await;
+    let other = find_treasury_address(&payroll.program_id, &Pubkey::new_unique()).0;
+    let result = payroll.send(&[payroll.pay_into_mixer(other, field(1))]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidArgument)
+    );
+
+    // A state account the named program does not own is not a mixer pool.
+    let mut instruction = payroll.pay_into_mixer(payroll.treasury, field(1));
+    instruction.accounts[6].pubkey = payroll.program_id;
+    let result = payroll.send(&[instruction]).await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(PayrollError::InvalidMixerProgram as u32)
+        )
+    );
+    assert_eq!(payroll.lamports(payroll.treasury).await, 2 * DENOMINATION);
+}
@@ -0,0 +1,333 @@
+//! The benchmarked instructions, each measured against a freshly prepared
+//! pool.
+//!
+//! Every case starts from the same pool: initialized with `mock-verifier`
+//! pinned, one deposit under [`ROOT`], and an authority that pays for,
+//! signs and relays everything. A case's `setup` instructions run first and
+//! are not measured.
+
+use borsh::to_vec;
+use litesvm::LiteSVM;
+use mixer::{admin::AdminAction, build_info, cpi, curve::Curve, events, MixerState, ProofBuffer};
+use mock_verifier::Mode;
+use solana_keypair::Keypair;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+use crate::Result;
+
+pub const DENOMINATION: u64 = 1_000_000_000;
+/// Root of the deposit every pool starts with.
+const ROOT: [u8; 32] = field(1);
+const NULLIFIER_HASH: [u8; 32] = field(7);
+const BUFFER_ID: u64 = 1;
+const ACTION_ID: u64 = 1;
+
+pub struct Case {
+    pub name: &'static str,
+    setup: fn(&Pool) -> Vec<Instruction>,
+    measure: fn(&Pool) -> Instruction,
+}
+
+pub const CASES: &[Case] = &[
+    Case { name: "initialize", setup: |_| vec![], measure: Pool::initialize },
+    Case {
+        name: "push_root",
+        setup: |_| vec![],
+        measure: |pool| {
+            cpi::push_root_instruction(
+                &pool.program_id,
+                &pool.authority(),
+                &pool.state,
+                field(2),
+                Some(2),
+            )
+        },
+    },
+    Case { name: "set_paused", setup: |_| vec![], measure: |pool| pool.set_paused(true) },
+    Case {
+        name: "withdraw",
+        setup: |_| vec![],
+        measure: |pool| pool.withdraw(&mock_verifier::proof(Mode::AcceptAll)),
+    },
+    Case { name: "create_proof_buffer", setup: |_| vec![], measure: Pool::create_proof_buffer },
+    Case {
+        name: "write_proof_chunk",
+        setup: |pool| vec![pool.create_proof_buffer()],
+        measure: Pool::write_proof_chunk,
+    },
+    Case {
+        name: "withdraw_from_buffer",
+        setup: |pool| vec![pool.create_proof_buffer(), pool.write_proof_chunk()],
+        measure: Pool::withdraw_from_buffer,
+    },
+    Case { name: "queue_admin_action", setup: |_| vec![], measure: Pool::queue_admin_action },
+    Case {
+        name: "execute_admin_action",
+        setup: |pool| vec![pool.queue_admin_action()],
+        measure: |pool| pool.admin_action(12),
+    },
+    Case {
+        name: "cancel_admin_action",
+        setup: |pool| vec![pool.queue_admin_action()],
+        measure: |pool| pool.admin_action(13),
+    },
+    Case {
+        name: "set_build_info",
+        setup: |_| vec![],
+        measure: |pool| {
+            build_info::set_build_info_instruction(
+                &pool.program_id,
+                &pool.authority(),
+                [0x11; 20],
+                [0x22; 32],
+            )
+        },
+    },
+];
+
+pub struct Pool {
+    svm: LiteSVM,
+    authority: Keypair,
+    program_id: Pubkey,
+    verifier_id: Pubkey,
+    state: Pubkey,
+    vault: Pubkey,
+    recipient: Pubkey,
+}
+
+impl Pool {
+    /// A chain with the deployed `mixer` and `mock_verifier` programs, before
+    /// `Initialize`.
+    pub fn load(mixer_so: &[u8], verifier_so: &[u8]) -> Result<Self> {
+        let program_id = Pubkey::new_unique();
+        let verifier_id = Pubkey::new_unique();
+        let mut svm = LiteSVM::new();
+        svm.add_program(program_id, mixer_so)
+            .map_err(|e| format!("loading the mixer program: {e:?}"))?;
+        svm.add_program(verifier_id, verifier_so)
+            .map_err(|e| format!("loading the verifier program: {e:?}"))?;
+        let authority = Keypair::new();
+        svm.airdrop(&authority.pubkey(), 100 * DENOMINATION)
+            .map_err(|e| format!("funding the authority: {:?}", e.err))?;
+        Ok(Pool {
+            svm,
+            authority,
+            program_id,
+            verifier_id,
+            state: Pubkey::find_program_address(&[b"mixer_state"], &program_id).0,
+            vault: Pubkey::find_program_address(&[b"mixer_vault"], &program_id).0,
+            recipient: Keypair::new().pubkey(),
+        })
+    }
+
+    /// Compute units `case` consumes, starting from `self` with the pool
+    /// initialized unless the case is `Initialize` itself.
+    pub fn measure(&self, case: &Case) -> Result<u64> {
+        let mut pool = self.fork();
+        if case.name != "initialize" {
+            pool.send(pool.initialize())?;
+            pool.send(cpi::create_latest_root_instruction(&pool.program_id, &pool.authority()))?;
+            pool.send(cpi::create_pool_stats_instruction(&pool.program_id, &pool.authority()))?;
+            let push_root = cpi::push_root_instruction(
+                &pool.program_id,
+                &pool.authority(),
+                &pool.state,
+                ROOT,
+                Some(1),
+            );
+            pool.send(push_root)?;
+        }
+        for instruction in (case.setup)(&pool) {
+            pool.send(instruction).map_err(|e| format!("{} setup: {e}", case.name))?;
+        }
+        pool.send((case.measure)(&pool)).map_err(|e| format!("{}: {e}", case.name).into())
+    }
+
+    fn fork(&self) -> Self {
+        Pool {
+            svm: self.svm.clone(),
+            authority: self.authority.insecure_clone(),
+            ..*self
+        }
+    }
+
+    fn authority(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    /// Send `instruction` in its own transaction and return the compute
+    /// units it consumed.
+    fn send(&mut self, instruction: Instruction) -> Result<u64> {
+        // A fresh blockhash keeps repeated instructions from being deduplicated.
+        self.svm.expire_blockhash();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.authority()),
+            &[&self.authority],
+            self.svm.latest_blockhash(),
+        );
+        match self.svm.send_transaction(transaction) {
+            Ok(meta) => Ok(meta.compute_units_consumed),
+            Err(failed) => {
+                Err(format!("{:?}\n{}", failed.err, failed.meta.logs.join("\n")).into())
+            }
+        }
+    }
+
+    fn initialize(&self) -> Instruction {
+        cpi::initialize_instruction(
+            &self.program_id,
+            &self.authority(),
+            DENOMINATION,
+            0,
+            0,
+            Curve::Bn254,
+            MixerState::DEFAULT_TREE_DEPTH,
+            &self.verifier_id,
+        )
+    }
+
+    fn set_paused(&self, paused: bool) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.authority(), true),
+                AccountMeta::new(self.state, false),
+                AccountMeta::new_readonly(self.event_authority(), false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+            data: vec![14, paused as u8],
+        }
+    }
+
+    fn withdraw(&self, proof: &[u8]) -> Instruction {
+        let nullifier =
+            Pubkey::find_program_address(&[b"nullifier", &NULLIFIER_HASH], &self.program_id).0;
+        let mut recipient_field = self.recipient.to_bytes();
+        recipient_field[0] = 0;
+        cpi::withdraw_instruction(
+            &self.program_id,
+            &self.authority(),
+            &self.state,
+            &nullifier,
+            &self.vault,
+            &self.recipient,
+            &self.verifier_id,
+            ROOT,
+            NULLIFIER_HASH,
+            recipient_field,
+            0,
+            0,
+            0,
+            proof,
+        )
+    }
+
+    fn proof_buffer(&self) -> Pubkey {
+        let authority = self.authority();
+        let seeds: &[&[u8]] = &[ProofBuffer::SEED, authority.as_ref(), &BUFFER_ID.to_le_bytes()];
+        Pubkey::find_program_address(seeds, &self.program_id).0
+    }
+
+    fn create_proof_buffer(&self) -> Instruction {
+        let mut data = vec![3];
+        data.extend_from_slice(&BUFFER_ID.to_le_bytes());
+        data.extend_from_slice(&(buffered_proof().len() as u32).to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.authority(), true),
+                AccountMeta::new(self.proof_buffer(), false),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+            ],
+            data,
+        }
+    }
+
+    fn write_proof_chunk(&self) -> Instruction {
+        let mut data = vec![4];
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&buffered_proof());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.authority(), true),
+                AccountMeta::new(self.proof_buffer(), false),
+            ],
+            data,
+        }
+    }
+
+    fn withdraw_from_buffer(&self) -> Instruction {
+        let mut instruction = self.withdraw(&[]);
+        // The tag follows the version byte.
+        instruction.data[1] = 5;
+        instruction.accounts.push(AccountMeta::new(self.proof_buffer(), false));
+        instruction
+    }
+
+    fn queue_admin_action(&self) -> Instruction {
+        let mut data = vec![11];
+        data.extend_from_slice(&ACTION_ID.to_le_bytes());
+        data.extend_from_slice(&to_vec(&AdminAction::SetAdminDelay { delay_secs: 60 }).unwrap());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.authority(), true),
+                AccountMeta::new_readonly(self.state, false),
+                AccountMeta::new(self.pending_action(), false),
+                AccountMeta::new_readonly(solana_system_interface::program::ID, false),
+                AccountMeta::new_readonly(self.event_authority(), false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+            data,
+        }
+    }
+
+    /// `ExecuteAdminAction` (tag 12) or `CancelAdminAction` (tag 13) of the
+    /// queued action.
+    fn admin_action(&self, tag: u8) -> Instruction {
+        let mut data = vec![tag];
+        data.extend_from_slice(&ACTION_ID.to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.authority(), true),
+                if tag == 12 {
+                    AccountMeta::new(self.state, false)
+                } else {
+                    AccountMeta::new_readonly(self.state, false)
+                },
+                AccountMeta::new(self.pending_action(), false),
+                AccountMeta::new_readonly(self.event_authority(), false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+            data,
+        }
+    }
+
+    fn pending_action(&self) -> Pubkey {
+        mixer::admin::find_pending_action_address(&self.program_id, ACTION_ID).0
+    }
+
+    fn event_authority(&self) -> Pubkey {
+        events::event_authority(&self.program_id).0
+    }
+}
+
+/// The proof staged in a proof buffer; longer than `Mode::AcceptAll`'s so
+/// chunk writes copy some bytes.
+fn buffered_proof() -> Vec<u8> {
+    mock_verifier::proof(Mode::Magic)
+}
+
+const fn field(tag: u8) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[31] = tag;
+    out
+}
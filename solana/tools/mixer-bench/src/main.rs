@@ -0,0 +1,167 @@
+//! Compute-unit benchmarks for the mixer program.
+//!
+//! Loads the SBF builds of `mixer` and `mock-verifier` into LiteSVM, runs
+//! each instruction in `cases` against a freshly prepared pool, and writes
+//! the compute units each consumed to a JSON report. Native builds do not
+//! meter compute units, so the programs must first be built with
+//! `cargo build-sbf`.
+//!
+//! With a baseline, an instruction that consumes more than the allowed
+//! percentage above its baseline fails the run. `--update-baseline`
+//! replaces the baseline's numbers with this run's, keeping its threshold.
+
+mod cases;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use cases::{Pool, CASES};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Allowed regression of a baseline that does not set one, in percent.
+const DEFAULT_MAX_REGRESSION: f64 = 5.0;
+
+#[derive(Parser)]
+#[command(about = "Measure mixer compute units and check them against a baseline")]
+struct Cli {
+    /// SBF build of the mixer; defaults to the workspace's `target/deploy`.
+    #[arg(long)]
+    mixer_so: Option<PathBuf>,
+    /// SBF build of `mock-verifier`; defaults to the workspace's
+    /// `target/deploy`.
+    #[arg(long)]
+    verifier_so: Option<PathBuf>,
+    #[arg(long, default_value = "compute-units.json")]
+    baseline: PathBuf,
+    #[arg(long, default_value = "compute-units-report.json")]
+    report: PathBuf,
+    /// Allowed regression in percent, overriding the baseline's.
+    #[arg(long)]
+    max_regression: Option<f64>,
+    /// Write this run's numbers to the baseline instead of checking them.
+    #[arg(long)]
+    update_baseline: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Baseline {
+    max_regression_percent: f64,
+    compute_units: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    compute_units: u64,
+    baseline: Option<u64>,
+    change_percent: Option<f64>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let deploy = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy");
+    let mixer_so = read_program(&cli.mixer_so.unwrap_or_else(|| deploy.join("mixer.so")))?;
+    let verifier_so =
+        read_program(&cli.verifier_so.unwrap_or_else(|| deploy.join("mock_verifier.so")))?;
+    let pool = Pool::load(&mixer_so, &verifier_so)?;
+    let measured = CASES
+        .iter()
+        .map(|case| Ok((case.name.to_string(), pool.measure(case)?)))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+
+    let baseline = read_baseline(&cli.baseline)?;
+    let max_regression = cli
+        .max_regression
+        .or(baseline.as_ref().map(|b| b.max_regression_percent))
+        .unwrap_or(DEFAULT_MAX_REGRESSION);
+    if cli.update_baseline {
+        let baseline =
+            Baseline { max_regression_percent: max_regression, compute_units: measured };
+        write_json(&cli.baseline, &baseline)?;
+        println!("Wrote {} baseline(s) to {}", baseline.compute_units.len(), cli.baseline.display());
+        return Ok(());
+    }
+
+    let mut report = BTreeMap::new();
+    let mut regressions = Vec::new();
+    for (name, &compute_units) in &measured {
+        let base = baseline.as_ref().and_then(|b| b.compute_units.get(name).copied());
+        let change = base.map(|base| percent_change(base, compute_units));
+        let status = match change {
+            None => "new",
+            Some(change) if change > max_regression => {
+                regressions.push(name.as_str());
+                "REGRESSED"
+            }
+            Some(change) if change < 0.0 => "improved",
+            Some(_) => "ok",
+        };
+        match (base, change) {
+            (Some(base), Some(change)) => {
+                println!("{status:>9}  {name}: {compute_units} CU ({change:+.1}% vs {base})")
+            }
+            _ => println!("{status:>9}  {name}: {compute_units} CU"),
+        }
+        report.insert(name, Entry { compute_units, baseline: base, change_percent: change });
+    }
+    if let Some(baseline) = &baseline {
+        for name in baseline.compute_units.keys().filter(|name| !measured.contains_key(*name)) {
+            println!("  missing  {name}: in the baseline but not measured");
+        }
+    } else {
+        println!("No baseline at {}; run with --update-baseline to create one", cli.baseline.display());
+    }
+    write_json(&cli.report, &report)?;
+    println!("Wrote report to {}", cli.report.display());
+
+    if !regressions.is_empty() {
+        return Err(format!(
+            "{} regressed by more than {max_regression}%: {}",
+            regressions.len(),
+            regressions.join(", ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn read_program(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|e| {
+        format!("reading {}: {e} (build the programs with `cargo build-sbf` first)", path.display())
+            .into()
+    })
+}
+
+fn read_baseline(path: &Path) -> Result<Option<Baseline>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    Ok(Some(serde_json::from_str(&text).map_err(|e| format!("parsing {}: {e}", path.display()))?))
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(value)? + "\n")
+        .map_err(|e| format!("writing {}: {e}", path.display()).into())
+}
+
+fn percent_change(base: u64, current: u64) -> f64 {
+    if base == 0 {
+        return if current == 0 { 0.0 } else { f64::INFINITY };
+    }
+    (current as f64 - base as f64) / base as f64 * 100.0
+}
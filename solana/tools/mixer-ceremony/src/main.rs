@@ -0,0 +1,348 @@
+//! Groth16 phase-2 ceremony tooling for the withdraw circuit.
+//!
+//! - `verify`: check a sequence of transcript files (see [`transcript`]):
+//!   the hash chain, every contributor's proof of knowledge, and that each
+//!   contribution only rescaled `delta` and the `L`/`H` queries. Then derive
+//!   the final verifying key in the native backend's encoding.
+//! - `contribute`: add a contribution on top of the latest transcript file.
+//!
+//! Auditors run `verify` on the published transcript and compare the printed
+//! verifying key hash with the pool's `verifier_hash` (see `VerifyVkBinding`).
+//! The initial parameters are only checked for `delta = 1`; that they were
+//! derived correctly from phase 1 and the circuit is not checked here.
+
+mod transcript;
+
+use std::{fs, path::PathBuf, process};
+
+use ark_bn254::{Bn254, Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{pairing::Pairing, AffineRepr, CurveGroup, VariableBaseMSM};
+use ark_ff::{Field, UniformRand, Zero};
+use clap::{Parser, Subcommand};
+use mixer::verifier::{verifier_account_hash, Groth16VerifyingKey, VerifierBackend, NUM_PUBLIC_INPUTS};
+use rand::rngs::OsRng;
+use transcript::{challenge, g1_bytes, g2_bytes, Contribution, File, Params, PublicKey, Result};
+
+#[derive(Parser)]
+#[command(about = "Verify and contribute to the Groth16 phase-2 ceremony")]
+struct Cli {
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Verify transcript files, given in order starting with the initial one.
+    Verify {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Write the derived verifying key account data here.
+        #[arg(long)]
+        vk_out: Option<PathBuf>,
+        /// Fail unless the derived verifying key has this hash (hex).
+        #[arg(long)]
+        expected_vk_hash: Option<String>,
+    },
+    /// Contribute randomness on top of the latest transcript file.
+    Contribute { input: PathBuf, output: PathBuf },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Cmd::Verify { files, vk_out, expected_vk_hash } => {
+            let params = verify(&files)?;
+            let vk = verifying_key(&params)?;
+            let data = borsh::to_vec(&vk)?;
+            let vk_hash = hex(&verifier_account_hash(VerifierBackend::NativeGroth16, &data));
+            println!("Verifying key hash: {vk_hash}");
+            if let Some(path) = vk_out {
+                fs::write(&path, &data).map_err(|e| format!("writing {}: {e}", path.display()))?;
+                println!("Wrote verifying key to {}", path.display());
+            }
+            if let Some(expected) = expected_vk_hash {
+                if !expected.eq_ignore_ascii_case(&vk_hash) {
+                    return Err(format!("verifying key hash does not match {expected}").into());
+                }
+                println!("Verifying key matches the expected hash");
+            }
+            Ok(())
+        }
+        Cmd::Contribute { input, output } => contribute(&input, &output),
+    }
+}
+
+fn read(path: &PathBuf) -> Result<File> {
+    let data = fs::read(path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    File::decode(&data).map_err(|e| format!("decoding {}: {e}", path.display()).into())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `e(a.0, b.1) == e(a.1, b.0)`, i.e. `a.1 / a.0 == b.1 / b.0` in the
+/// exponent.
+fn same_ratio(a: (G1Affine, G1Affine), b: (G2Affine, G2Affine)) -> bool {
+    Bn254::pairing(a.0, b.1) == Bn254::pairing(a.1, b.0)
+}
+
+/// Random linear combination of a query, to compare two queries with one
+/// pairing check.
+fn combine(points: &[G1Affine], scalars: &[Fr]) -> G1Affine {
+    G1Projective::msm(points, scalars).unwrap().into_affine()
+}
+
+fn check_initial(params: &Params) -> Result<()> {
+    if params.delta_g1 != G1Affine::generator() || params.delta_g2 != G2Affine::generator() {
+        return Err("initial parameters must have delta = 1".into());
+    }
+    Ok(())
+}
+
+fn check_contribution(old: &Params, c: &Contribution) -> Result<()> {
+    let new = &c.params;
+    let pk = &c.public_key;
+    if pk.s_g1.is_zero() || new.delta_g1.is_zero() {
+        return Err("contribution contains the point at infinity".into());
+    }
+    let r = challenge(&c.previous_hash, &pk.s_g1, &pk.s_delta_g1);
+    if !same_ratio((pk.s_g1, pk.s_delta_g1), (r, pk.r_delta_g2)) {
+        return Err("invalid proof of knowledge".into());
+    }
+    if !same_ratio((old.delta_g1, new.delta_g1), (r, pk.r_delta_g2)) {
+        return Err("delta was not rescaled by the proven secret".into());
+    }
+    if !same_ratio((G1Affine::generator(), new.delta_g1), (G2Affine::generator(), new.delta_g2)) {
+        return Err("delta_g1 and delta_g2 disagree".into());
+    }
+    if new.alpha_g1 != old.alpha_g1
+        || new.beta_g1 != old.beta_g1
+        || new.beta_g2 != old.beta_g2
+        || new.gamma_g2 != old.gamma_g2
+        || new.ic != old.ic
+    {
+        return Err("contribution changed parameters other than delta, L and H".into());
+    }
+    if new.l.len() != old.l.len() || new.h.len() != old.h.len() {
+        return Err("contribution changed the query sizes".into());
+    }
+
+    // L and H must be divided by the same secret delta was multiplied by:
+    // e(sum(rho * new), delta_new) == e(sum(rho * old), delta_old).
+    let scalars: Vec<Fr> = (0..old.l.len() + old.h.len()).map(|_| Fr::rand(&mut OsRng)).collect();
+    let old_points = [old.l.as_slice(), old.h.as_slice()].concat();
+    let new_points = [new.l.as_slice(), new.h.as_slice()].concat();
+    let old_sum = combine(&old_points, &scalars);
+    let new_sum = combine(&new_points, &scalars);
+    if !same_ratio((new_sum, old_sum), (old.delta_g2, new.delta_g2)) {
+        return Err("L or H was not rescaled by the contribution".into());
+    }
+    Ok(())
+}
+
+/// Verify `files` in order and return the final parameters.
+fn verify(files: &[PathBuf]) -> Result<Params> {
+    check_chain(files.iter().map(|path| Ok((path.display().to_string(), read(path)?))))
+}
+
+/// Verify a sequence of transcript files, each with the name errors refer
+/// to it by, and return the final parameters. Files are read one at a time.
+fn check_chain(files: impl IntoIterator<Item = Result<(String, File)>>) -> Result<Params> {
+    let mut previous: Option<(Params, [u8; 32])> = None;
+    let mut count = 0;
+    for (i, file) in files.into_iter().enumerate() {
+        let (name, file) = file?;
+        match (&file, &previous) {
+            (File::Initial(params), None) => check_initial(params)?,
+            (File::Contribution(_), None) => {
+                return Err(format!("{name} is a contribution, not initial parameters").into());
+            }
+            (File::Contribution(c), Some((old, previous_hash))) => {
+                if &c.previous_hash != previous_hash {
+                    return Err(
+                        format!("{name} was not made on the previous file (hash chain broken)").into()
+                    );
+                }
+                check_contribution(old, c).map_err(|e| format!("contribution {i} ({name}): {e}"))?;
+            }
+            (File::Initial(_), Some(_)) => {
+                return Err(format!("{name} holds initial parameters").into());
+            }
+        }
+        let hash = file.hash();
+        match i {
+            0 => println!("  ok  initial parameters: {}", hex(&hash)),
+            _ => println!("  ok  contribution {i}: {}", hex(&hash)),
+        }
+        previous = Some((file.params().clone(), hash));
+        count = i;
+    }
+    let (params, _) = previous.ok_or("no transcript files")?;
+    println!("Verified {count} contribution(s)");
+    Ok(params)
+}
+
+fn verifying_key(params: &Params) -> Result<Groth16VerifyingKey> {
+    let ic = params.ic.iter().map(g1_bytes).collect::<Vec<_>>();
+    let ic = ic.try_into().map_err(|ic: Vec<_>| {
+        format!(
+            "parameters have {} public input bases, the mixer expects {}",
+            ic.len(),
+            NUM_PUBLIC_INPUTS + 1
+        )
+    })?;
+    Ok(Groth16VerifyingKey {
+        alpha_g1: g1_bytes(&params.alpha_g1),
+        beta_g2: g2_bytes(&params.beta_g2),
+        gamma_g2: g2_bytes(&params.gamma_g2),
+        delta_g2: g2_bytes(&params.delta_g2),
+        ic,
+    })
+}
+
+fn contribute(input: &PathBuf, output: &PathBuf) -> Result<()> {
+    let contribution = contribute_to(&read(input)?);
+    let hash = contribution.hash();
+    fs::write(output, contribution.encode())
+        .map_err(|e| format!("writing {}: {e}", output.display()))?;
+    println!("Wrote contribution to {}", output.display());
+    println!("Contribution hash: {}", hex(&hash));
+    Ok(())
+}
+
+/// A contribution with a fresh secret on top of `file`.
+fn contribute_to(file: &File) -> File {
+    let previous_hash = file.hash();
+    let old = file.params();
+
+    let secret = loop {
+        let d = Fr::rand(&mut OsRng);
+        if !d.is_zero() {
+            break d;
+        }
+    };
+    let inverse = secret.inverse().unwrap();
+    let s_g1 = (G1Affine::generator() * Fr::rand(&mut OsRng)).into_affine();
+    let s_delta_g1 = (s_g1 * secret).into_affine();
+    let r = challenge(&previous_hash, &s_g1, &s_delta_g1);
+    let public_key = PublicKey { s_g1, s_delta_g1, r_delta_g2: (r * secret).into_affine() };
+
+    let rescale = |query: &[G1Affine]| {
+        let scaled: Vec<G1Projective> = query.iter().map(|p| *p * inverse).collect();
+        G1Projective::normalize_batch(&scaled)
+    };
+    let params = Params {
+        delta_g1: (old.delta_g1 * secret).into_affine(),
+        delta_g2: (G2Projective::from(old.delta_g2) * secret).into_affine(),
+        l: rescale(&old.l),
+        h: rescale(&old.h),
+        ..old.clone()
+    };
+    File::Contribution(Box::new(Contribution { previous_hash, public_key, params }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn g1(scalar: u64) -> G1Affine {
+        (G1Affine::generator() * Fr::from(scalar)).into_affine()
+    }
+
+    /// Initial parameters with `delta = 1` and small queries.
+    fn initial() -> File {
+        File::Initial(Box::new(Params {
+            alpha_g1: g1(2),
+            beta_g1: g1(3),
+            beta_g2: (G2Affine::generator() * Fr::from(3u64)).into_affine(),
+            gamma_g2: (G2Affine::generator() * Fr::from(5u64)).into_affine(),
+            delta_g1: G1Affine::generator(),
+            delta_g2: G2Affine::generator(),
+            ic: vec![g1(7), g1(11)],
+            l: vec![g1(13), g1(17), g1(19)],
+            h: vec![g1(23), g1(29)],
+        }))
+    }
+
+    /// The initial file and two contributions, each through its encoding.
+    fn ceremony() -> Vec<File> {
+        let roundtrip = |file: File| File::decode(&file.encode()).unwrap();
+        let initial = roundtrip(initial());
+        let first = roundtrip(contribute_to(&initial));
+        let second = roundtrip(contribute_to(&first));
+        vec![initial, first, second]
+    }
+
+    fn check(files: Vec<File>) -> Result<Params> {
+        check_chain(files.into_iter().enumerate().map(|(i, file)| Ok((format!("file {i}"), file))))
+    }
+
+    fn contribution(file: &mut File) -> &mut Contribution {
+        match file {
+            File::Contribution(c) => c,
+            File::Initial(_) => panic!("not a contribution"),
+        }
+    }
+
+    #[test]
+    fn a_contribution_chain_verifies() {
+        let files = ceremony();
+        let last = files[2].params().clone();
+        assert_ne!(last.delta_g1, G1Affine::generator());
+        assert_eq!(check(files).unwrap(), last);
+    }
+
+    #[test]
+    fn a_tampered_contribution_is_rejected() {
+        let mut files = ceremony();
+        contribution(&mut files[1]).params.l[0] = g1(31);
+        let err = check(files).unwrap_err().to_string();
+        assert!(err.contains("L or H was not rescaled"), "{err}");
+
+        let mut files = ceremony();
+        contribution(&mut files[2]).params.ic[1] = g1(37);
+        let err = check(files).unwrap_err().to_string();
+        assert!(err.contains("changed parameters other than delta"), "{err}");
+
+        let mut files = ceremony();
+        let tampered = contribution(&mut files[1]);
+        tampered.params.delta_g1 = (tampered.params.delta_g1 * Fr::from(2u64)).into_affine();
+        assert!(check(files).is_err());
+    }
+
+    #[test]
+    fn reordered_contributions_are_rejected() {
+        let mut files = ceremony();
+        files.swap(1, 2);
+        let err = check(files).unwrap_err().to_string();
+        assert!(err.contains("hash chain broken"), "{err}");
+
+        let mut files = ceremony();
+        files.swap(0, 1);
+        let err = check(files).unwrap_err().to_string();
+        assert!(err.contains("not initial parameters"), "{err}");
+    }
+
+    #[test]
+    fn a_wrong_previous_hash_is_rejected() {
+        let mut files = ceremony();
+        contribution(&mut files[2]).previous_hash[0] ^= 1;
+        let err = check(files).unwrap_err().to_string();
+        assert!(err.contains("file 2 was not made on the previous file"), "{err}");
+
+        // A contribution made on another transcript does not chain on.
+        let mut files = ceremony();
+        let other = contribute_to(&contribute_to(&initial()));
+        files[2] = other;
+        let err = check(files).unwrap_err().to_string();
+        assert!(err.contains("hash chain broken"), "{err}");
+    }
+}
@@ -0,0 +1,307 @@
+//! Phase-2 transcript files.
+//!
+//! A ceremony is a sequence of files. The first holds the parameters derived
+//! from phase 1 and the circuit, with `delta = 1`. Each later file holds one
+//! contribution: the contributor's proof of knowledge of their secret
+//! `d`, and the parameters after multiplying `delta` by `d` and dividing the
+//! `L` and `H` queries by it.
+//!
+//! Points use the EIP-197 encoding of the mixer program (big-endian `x || y`
+//! for G1, `x1 || x0 || y1 || y0` for G2), and lengths are u32 little-endian.
+//!
+//! ```text
+//! initial      := MAGIC || 0u8 || params
+//! contribution := MAGIC || 1u8 || previous_hash[32] || s_g1 || s_delta_g1
+//!                 || r_delta_g2 || params
+//! params       := alpha_g1 || beta_g1 || beta_g2 || gamma_g2 || delta_g1
+//!                 || delta_g2 || u32 n || ic[n] || u32 n || l[n]
+//!                 || u32 n || h[n]
+//! ```
+//!
+//! The `A` and `B` queries of the proving key come from phase 1 and are not
+//! changed by phase 2, so they are not part of the transcript.
+
+use std::error::Error;
+
+use ark_bn254::{Fq, Fq2, G1Affine, G2Affine};
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use sha2::{Digest, Sha256};
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+pub const MAGIC: &[u8; 8] = b"HSPHASE2";
+
+const KIND_INITIAL: u8 = 0;
+const KIND_CONTRIBUTION: u8 = 1;
+
+/// Circuit-specific Groth16 parameters touched by phase 2.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Params {
+    pub alpha_g1: G1Affine,
+    pub beta_g1: G1Affine,
+    pub beta_g2: G2Affine,
+    pub gamma_g2: G2Affine,
+    pub delta_g1: G1Affine,
+    pub delta_g2: G2Affine,
+    /// Public input commitment bases (`K` in gnark, `IC` in the mixer).
+    pub ic: Vec<G1Affine>,
+    /// Private witness query, scaled by `1 / delta`.
+    pub l: Vec<G1Affine>,
+    /// Quotient polynomial query, scaled by `1 / delta`.
+    pub h: Vec<G1Affine>,
+}
+
+/// Proof that a contributor knows the `d` by which they scaled `delta`:
+/// `s_delta = d * s` and `r_delta = d * r`, where `r` is derived by hashing
+/// the previous transcript hash with `s` and `s_delta`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PublicKey {
+    pub s_g1: G1Affine,
+    pub s_delta_g1: G1Affine,
+    pub r_delta_g2: G2Affine,
+}
+
+pub struct Contribution {
+    /// Transcript hash of the file this contribution was made on.
+    pub previous_hash: [u8; 32],
+    pub public_key: PublicKey,
+    pub params: Params,
+}
+
+pub enum File {
+    Initial(Box<Params>),
+    Contribution(Box<Contribution>),
+}
+
+impl File {
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut r = Reader { data };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err("not a phase-2 transcript file".into());
+        }
+        let file = match r.take(1)?[0] {
+            KIND_INITIAL => File::Initial(Box::new(r.params()?)),
+            KIND_CONTRIBUTION => {
+                let previous_hash = r.take(32)?.try_into().unwrap();
+                let public_key = PublicKey {
+                    s_g1: r.g1()?,
+                    s_delta_g1: r.g1()?,
+                    r_delta_g2: r.g2()?,
+                };
+                File::Contribution(Box::new(Contribution {
+                    previous_hash,
+                    public_key,
+                    params: r.params()?,
+                }))
+            }
+            kind => return Err(format!("unknown transcript file kind {kind}").into()),
+        };
+        if !r.data.is_empty() {
+            return Err("trailing bytes after transcript file".into());
+        }
+        Ok(file)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        match self {
+            File::Initial(params) => {
+                out.push(KIND_INITIAL);
+                params.encode(&mut out);
+            }
+            File::Contribution(c) => {
+                out.push(KIND_CONTRIBUTION);
+                out.extend_from_slice(&c.previous_hash);
+                out.extend_from_slice(&g1_bytes(&c.public_key.s_g1));
+                out.extend_from_slice(&g1_bytes(&c.public_key.s_delta_g1));
+                out.extend_from_slice(&g2_bytes(&c.public_key.r_delta_g2));
+                c.params.encode(&mut out);
+            }
+        }
+        out
+    }
+
+    /// Transcript hash after this file. A contribution chains onto the hash
+    /// of the file it was made on, so the last hash commits to the whole
+    /// ceremony.
+    pub fn hash(&self) -> [u8; 32] {
+        match self {
+            File::Initial(params) => params.hash(),
+            File::Contribution(c) => c.public_key.transcript_hash(&c.previous_hash, &c.params),
+        }
+    }
+
+    pub fn params(&self) -> &Params {
+        match self {
+            File::Initial(params) => params,
+            File::Contribution(c) => &c.params,
+        }
+    }
+}
+
+impl Params {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&g1_bytes(&self.alpha_g1));
+        out.extend_from_slice(&g1_bytes(&self.beta_g1));
+        out.extend_from_slice(&g2_bytes(&self.beta_g2));
+        out.extend_from_slice(&g2_bytes(&self.gamma_g2));
+        out.extend_from_slice(&g1_bytes(&self.delta_g1));
+        out.extend_from_slice(&g2_bytes(&self.delta_g2));
+        for query in [&self.ic, &self.l, &self.h] {
+            out.extend_from_slice(&(query.len() as u32).to_le_bytes());
+            for point in query {
+                out.extend_from_slice(&g1_bytes(point));
+            }
+        }
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        let mut data = Vec::new();
+        self.encode(&mut data);
+        Sha256::digest(&data).into()
+    }
+}
+
+impl PublicKey {
+    /// Hash chained from the previous transcript hash and this contribution.
+    pub fn transcript_hash(&self, previous_hash: &[u8; 32], params: &Params) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(previous_hash);
+        hasher.update(g1_bytes(&self.s_g1));
+        hasher.update(g1_bytes(&self.s_delta_g1));
+        hasher.update(g2_bytes(&self.r_delta_g2));
+        hasher.update(params.hash());
+        hasher.finalize().into()
+    }
+}
+
+/// The G2 point `r` a contribution's proof of knowledge is checked against.
+/// It depends on the previous transcript and `s`, `s_delta`, so the
+/// contributor cannot choose it, and nobody knows its discrete logarithm.
+pub fn challenge(previous_hash: &[u8; 32], s_g1: &G1Affine, s_delta_g1: &G1Affine) -> G2Affine {
+    let seed: [u8; 32] = Sha256::new()
+        .chain_update(previous_hash)
+        .chain_update(g1_bytes(s_g1))
+        .chain_update(g1_bytes(s_delta_g1))
+        .finalize()
+        .into();
+    // Try-and-increment: hash to an x coordinate until it is on the curve,
+    // then clear the cofactor.
+    for counter in 0u32.. {
+        let coordinate = |part: u8| {
+            let digest = Sha256::new()
+                .chain_update(seed)
+                .chain_update(counter.to_le_bytes())
+                .chain_update([part])
+                .finalize();
+            Fq::from_be_bytes_mod_order(&digest)
+        };
+        let x = Fq2::new(coordinate(0), coordinate(1));
+        if let Some(point) = G2Affine::get_point_from_x_unchecked(x, false) {
+            let point = point.clear_cofactor();
+            if !point.is_zero() {
+                return point;
+            }
+        }
+    }
+    unreachable!()
+}
+
+pub fn g1_bytes(point: &G1Affine) -> [u8; 64] {
+    let mut out = [0u8; 64];
+    if let Some((x, y)) = point.xy() {
+        out[..32].copy_from_slice(&fq_bytes(&x));
+        out[32..].copy_from_slice(&fq_bytes(&y));
+    }
+    out
+}
+
+pub fn g2_bytes(point: &G2Affine) -> [u8; 128] {
+    let mut out = [0u8; 128];
+    if let Some((x, y)) = point.xy() {
+        out[..32].copy_from_slice(&fq_bytes(&x.c1));
+        out[32..64].copy_from_slice(&fq_bytes(&x.c0));
+        out[64..96].copy_from_slice(&fq_bytes(&y.c1));
+        out[96..].copy_from_slice(&fq_bytes(&y.c0));
+    }
+    out
+}
+
+fn fq_bytes(value: &Fq) -> [u8; 32] {
+    value.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err("unexpected end of transcript file".into());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn fq(&mut self) -> Result<Fq> {
+        let bytes = self.take(32)?;
+        let value = Fq::from_be_bytes_mod_order(bytes);
+        if fq_bytes(&value) != bytes {
+            return Err("coordinate is not a canonical field element".into());
+        }
+        Ok(value)
+    }
+
+    /// A G1 point; all-zero bytes encode the identity, as in EIP-197.
+    fn g1(&mut self) -> Result<G1Affine> {
+        let (x, y) = (self.fq()?, self.fq()?);
+        if x.is_zero() && y.is_zero() {
+            return Ok(G1Affine::zero());
+        }
+        let point = G1Affine::new_unchecked(x, y);
+        if !point.is_on_curve() {
+            return Err("G1 point is not on the curve".into());
+        }
+        Ok(point)
+    }
+
+    fn g2(&mut self) -> Result<G2Affine> {
+        let x1 = self.fq()?;
+        let x = Fq2::new(self.fq()?, x1);
+        let y1 = self.fq()?;
+        let y = Fq2::new(self.fq()?, y1);
+        if x.is_zero() && y.is_zero() {
+            return Ok(G2Affine::zero());
+        }
+        let point = G2Affine::new_unchecked(x, y);
+        if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+            return Err("G2 point is not in the prime-order subgroup".into());
+        }
+        Ok(point)
+    }
+
+    fn g1_vec(&mut self) -> Result<Vec<G1Affine>> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize;
+        if len > self.data.len() / 64 {
+            return Err("query length exceeds the file".into());
+        }
+        (0..len).map(|_| self.g1()).collect()
+    }
+
+    fn params(&mut self) -> Result<Params> {
+        Ok(Params {
+            alpha_g1: self.g1()?,
+            beta_g1: self.g1()?,
+            beta_g2: self.g2()?,
+            gamma_g2: self.g2()?,
+            delta_g1: self.g1()?,
+            delta_g2: self.g2()?,
+            ic: self.g1_vec()?,
+            l: self.g1_vec()?,
+            h: self.g1_vec()?,
+        })
+    }
+}
@@ -0,0 +1,440 @@
+//! Operator tooling for mixer deployments.
+//!
+//! Every command reads a TOML manifest (see `mixer-deploy.example.toml`):
+//!
+//! - `deploy`: deploy the verifier and mixer programs.
+//! - `init`: initialize the pool with the manifest's parameters.
+//! - `verify`: check that deployed bytecode and pool state match the manifest.
+//! - `upgrade`: upgrade a program through a buffer, or with `--dry-run` only
+//!   show how the local build differs from what is deployed.
+//! - `publish-build-info`: record the current commit and build hash in the
+//!   mixer's build info account.
+//! - `verify-build`: rebuild the mixer and compare the result with the
+//!   deployed bytecode and the recorded build info.
+//!
+//! Program deployment and upgrades are delegated to the `solana` CLI, which
+//! must be on `PATH`; everything else talks to the RPC node directly.
+
+mod manifest;
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use manifest::{Manifest, ProgramConfig};
+use mixer::build_info::{find_build_info_address, set_build_info_instruction, BuildInfo};
+use sha2::{Digest, Sha256};
+use solana_commitment_config::CommitmentConfig;
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_loader_v3_interface::{get_program_data_address, state::UpgradeableLoaderState};
+use solana_program::{borsh1::try_from_slice_unchecked, pubkey::Pubkey};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(about = "Deploy, initialize, verify and upgrade a mixer deployment")]
+struct Cli {
+    /// Deployment manifest.
+    #[arg(short, long, default_value = "mixer-deploy.toml")]
+    manifest: PathBuf,
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Deploy the verifier and mixer programs.
+    Deploy {
+        /// Only deploy the mixer, e.g. when reusing an existing verifier.
+        #[arg(long)]
+        skip_verifier: bool,
+    },
+    /// Initialize the pool.
+    Init,
+    /// Check deployed programs and pool state against the manifest.
+    Verify,
+    /// Upgrade a deployed program from its local build.
+    Upgrade {
+        #[arg(value_enum, default_value_t = Target::Mixer)]
+        program: Target,
+        /// Print the difference to the deployed program and exit.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Record the checked-out commit and the local build hash on-chain.
+    PublishBuildInfo,
+    /// Rebuild the mixer and compare it with the deployed program.
+    VerifyBuild,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    Mixer,
+    Verifier,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let manifest = Manifest::load(&cli.manifest)?;
+    match cli.command {
+        Cmd::Deploy { skip_verifier } => {
+            if !skip_verifier {
+                deploy(&manifest, &manifest.verifier)?;
+            }
+            deploy(&manifest, &manifest.mixer)
+        }
+        Cmd::Init => init(&manifest),
+        Cmd::Verify => {
+            let mismatches = verify(&manifest)?;
+            if mismatches > 0 {
+                return Err(format!("{mismatches} mismatch(es) against the manifest").into());
+            }
+            println!("Deployment matches the manifest");
+            Ok(())
+        }
+        Cmd::Upgrade { program, dry_run } => {
+            let config = match program {
+                Target::Mixer => &manifest.mixer,
+                Target::Verifier => &manifest.verifier,
+            };
+            upgrade(&manifest, config, dry_run)
+        }
+        Cmd::PublishBuildInfo => publish_build_info(&manifest),
+        Cmd::VerifyBuild => {
+            let mismatches = verify_build(&manifest)?;
+            if mismatches > 0 {
+                return Err(format!("{mismatches} mismatch(es) in build verification").into());
+            }
+            println!("Deployed mixer matches the rebuilt source");
+            Ok(())
+        }
+    }
+}
+
+fn keypair(path: &Path) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|e| format!("reading keypair {}: {e}", path.display()).into())
+}
+
+fn program_id(config: &ProgramConfig) -> Result<Pubkey> {
+    Ok(keypair(&config.keypair)?.pubkey())
+}
+
+fn rpc(manifest: &Manifest) -> RpcClient {
+    RpcClient::new_with_commitment(manifest.rpc_url().to_string(), CommitmentConfig::confirmed())
+}
+
+/// Run the `solana` CLI against the manifest's cluster and payer and return
+/// its stdout.
+fn solana_cli(manifest: &Manifest, args: &[&str]) -> Result<String> {
+    let output = Command::new("solana")
+        .args(args)
+        .arg("--url")
+        .arg(manifest.rpc_url())
+        .arg("--keypair")
+        .arg(&manifest.payer)
+        .output()
+        .map_err(|e| format!("running solana CLI: {e}"))?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    print!("{stdout}");
+    if !output.status.success() {
+        return Err(format!(
+            "solana {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    Ok(stdout)
+}
+
+fn path_arg(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| format!("non UTF-8 path: {}", path.display()).into())
+}
+
+fn deploy(manifest: &Manifest, config: &ProgramConfig) -> Result<()> {
+    println!("Deploying {} as {}", config.so.display(), program_id(config)?);
+    solana_cli(
+        manifest,
+        &[
+            "program",
+            "deploy",
+            path_arg(&config.so)?,
+            "--program-id",
+            path_arg(&config.keypair)?,
+        ],
+    )?;
+    Ok(())
+}
+
+fn init(manifest: &Manifest) -> Result<()> {
+    let rpc = rpc(manifest);
+    let payer = keypair(&manifest.payer)?;
+    let mixer_id = program_id(&manifest.mixer)?;
+    let verifier_id = program_id(&manifest.verifier)?;
+    let pool = &manifest.pool;
+
+    let mut ixs = vec![mixer::cpi::initialize_instruction(
+        &mixer_id,
+        &payer.pubkey(),
+        pool.denomination,
+        pool.flags(),
+        pool.admin_delay_secs,
+        pool.curve(),
+        pool.tree_depth,
+        &verifier_id,
+    )];
+    // Deposits need the latest root account, and deposits and withdrawals
+    // the pool stats; create whichever is missing.
+    let latest_root = mixer::LatestRoot::find_address(&mixer_id).0;
+    if rpc.get_account_with_commitment(&latest_root, rpc.commitment())?.value.is_none() {
+        ixs.push(mixer::cpi::create_latest_root_instruction(&mixer_id, &payer.pubkey()));
+    }
+    let pool_stats = mixer::PoolStats::find_address(&mixer_id).0;
+    if rpc.get_account_with_commitment(&pool_stats, rpc.commitment())?.value.is_none() {
+        ixs.push(mixer::cpi::create_pool_stats_instruction(&mixer_id, &payer.pubkey()));
+    }
+    let blockhash = rpc.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(&ixs, Some(&payer.pubkey()), &[&payer], blockhash);
+    let signature = rpc.send_and_confirm_transaction(&tx)?;
+    println!("Initialized pool: {signature}");
+    Ok(())
+}
+
+/// Print one check and return 1 if it failed.
+fn check(name: &str, expected: impl std::fmt::Display, actual: impl std::fmt::Display) -> usize {
+    let (expected, actual) = (expected.to_string(), actual.to_string());
+    if expected == actual {
+        println!("  ok       {name}: {actual}");
+        0
+    } else {
+        println!("  MISMATCH {name}: expected {expected}, found {actual}");
+        1
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+/// Deployed bytecode of an upgradeable program, without the trailing zero
+/// padding of its program data account.
+fn deployed_bytecode(rpc: &RpcClient, program_id: &Pubkey) -> Result<Vec<u8>> {
+    let program_data = rpc
+        .get_account_data(&get_program_data_address(program_id))
+        .map_err(|e| format!("fetching program data of {program_id}: {e}"))?;
+    let mut code = program_data
+        .get(UpgradeableLoaderState::size_of_programdata_metadata()..)
+        .ok_or("program data account too small")?
+        .to_vec();
+    let len = code.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    code.truncate(len);
+    Ok(code)
+}
+
+/// Local build with trailing zeroes trimmed, so it compares equal to
+/// [`deployed_bytecode`].
+fn local_bytecode(config: &ProgramConfig) -> Result<Vec<u8>> {
+    let mut code =
+        fs::read(&config.so).map_err(|e| format!("reading {}: {e}", config.so.display()))?;
+    let len = code.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    code.truncate(len);
+    Ok(code)
+}
+
+fn verify(manifest: &Manifest) -> Result<usize> {
+    let rpc = rpc(manifest);
+    let payer = keypair(&manifest.payer)?.pubkey();
+    let mixer_id = program_id(&manifest.mixer)?;
+    let verifier_id = program_id(&manifest.verifier)?;
+    let mut mismatches = 0;
+    let local_verifier = sha256_hex(&local_bytecode(&manifest.verifier)?);
+
+    for (name, config, id) in [
+        ("verifier", &manifest.verifier, verifier_id),
+        ("mixer", &manifest.mixer, mixer_id),
+    ] {
+        println!("{name} program {id}");
+        let local = sha256_hex(&local_bytecode(config)?);
+        let deployed = sha256_hex(&deployed_bytecode(&rpc, &id)?);
+        mismatches += check("bytecode sha256", local, deployed);
+    }
+
+    let (state_address, _) = Pubkey::find_program_address(&[b"mixer_state"], &mixer_id);
+    println!("pool state {state_address}");
+    let account = rpc
+        .get_account(&state_address)
+        .map_err(|e| format!("fetching pool state: {e}"))?;
+    mismatches += check("owner", mixer_id, account.owner);
+    let state = mixer::unpack_state(&account.data)?;
+    let pool = &manifest.pool;
+    mismatches += check("denomination", pool.denomination, state.denomination);
+    mismatches += check("flags", pool.flags(), state.flags);
+    mismatches += check("admin delay", pool.admin_delay_secs, state.admin_delay);
+    mismatches += check("curve", pool.curve() as u8, state.curve);
+    mismatches += check("tree depth", pool.tree_depth, state.tree_depth);
+    mismatches += check("verifier", verifier_id, state.verifier_program);
+    mismatches += check("authority", payer, state.authority);
+    mismatches += check("paused", false, state.paused);
+    if state.verifier_hash != [0u8; 32] {
+        mismatches += check(
+            "verifier hash",
+            local_verifier.as_str(),
+            hex(&state.verifier_hash),
+        );
+    }
+    Ok(mismatches)
+}
+
+fn upgrade(manifest: &Manifest, config: &ProgramConfig, dry_run: bool) -> Result<()> {
+    let rpc = rpc(manifest);
+    let id = program_id(config)?;
+    let local = local_bytecode(config)?;
+    let deployed = deployed_bytecode(&rpc, &id)?;
+    let capacity = rpc
+        .get_account_data(&get_program_data_address(&id))?
+        .len()
+        - UpgradeableLoaderState::size_of_programdata_metadata();
+
+    println!("program  {id}");
+    println!("deployed {} bytes, sha256 {}", deployed.len(), sha256_hex(&deployed));
+    println!("local    {} bytes, sha256 {}", local.len(), sha256_hex(&local));
+    if local == deployed {
+        println!("Deployed program is up to date");
+        return Ok(());
+    }
+    let changed = local
+        .iter()
+        .zip(&deployed)
+        .filter(|(a, b)| a != b)
+        .count()
+        + local.len().abs_diff(deployed.len());
+    println!("{changed} byte(s) differ");
+    let extend_by = local.len().saturating_sub(capacity);
+    if extend_by > 0 {
+        println!("program data must be extended by {extend_by} bytes");
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    if extend_by > 0 {
+        solana_cli(
+            manifest,
+            &["program", "extend", &id.to_string(), &extend_by.to_string()],
+        )?;
+    }
+    let output = solana_cli(manifest, &["program", "write-buffer", path_arg(&config.so)?])?;
+    let buffer = output
+        .lines()
+        .find_map(|line| line.strip_prefix("Buffer: "))
+        .ok_or("could not find the buffer address in the solana CLI output")?
+        .trim()
+        .to_string();
+    solana_cli(manifest, &["program", "upgrade", &buffer, &id.to_string()])?;
+    println!("Upgraded {id} from buffer {buffer}");
+    Ok(())
+}
+
+/// Commit checked out in the manifest directory.
+fn git_head(manifest: &Manifest) -> Result<[u8; 20]> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(&manifest.base)
+        .output()
+        .map_err(|e| format!("running git: {e}"))?;
+    if !output.status.success() {
+        return Err("git rev-parse HEAD failed".into());
+    }
+    let text = String::from_utf8(output.stdout)?;
+    let text = text.trim();
+    if text.len() != 40 {
+        return Err(format!("unexpected commit id: {text}").into());
+    }
+    let mut commit = [0u8; 20];
+    for (i, byte) in commit.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[2 * i..2 * i + 2], 16)?;
+    }
+    Ok(commit)
+}
+
+fn publish_build_info(manifest: &Manifest) -> Result<()> {
+    let rpc = rpc(manifest);
+    let payer = keypair(&manifest.payer)?;
+    let mixer_id = program_id(&manifest.mixer)?;
+    let local = local_bytecode(&manifest.mixer)?;
+    if local != deployed_bytecode(&rpc, &mixer_id)? {
+        return Err("local build differs from the deployed mixer; upgrade first".into());
+    }
+    let commit = git_head(manifest)?;
+    let build_hash: [u8; 32] = Sha256::digest(&local).into();
+
+    let ix = set_build_info_instruction(&mixer_id, &payer.pubkey(), commit, build_hash);
+    let blockhash = rpc.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[&payer], blockhash);
+    let signature = rpc.send_and_confirm_transaction(&tx)?;
+    println!(
+        "Recorded commit {} build {}: {signature}",
+        hex(&commit),
+        hex(&build_hash)
+    );
+    Ok(())
+}
+
+fn verify_build(manifest: &Manifest) -> Result<usize> {
+    let rpc = rpc(manifest);
+    let mixer_id = program_id(&manifest.mixer)?;
+    let build = manifest
+        .mixer
+        .build
+        .as_deref()
+        .ok_or("the manifest has no [mixer] build command")?;
+
+    let data = rpc
+        .get_account_data(&find_build_info_address(&mixer_id).0)
+        .map_err(|e| format!("fetching build info: {e}"))?;
+    let info: BuildInfo = try_from_slice_unchecked(&data)?;
+    let head = git_head(manifest)?;
+    if head != info.commit {
+        return Err(format!(
+            "build info records commit {}, but {} is checked out; check out the recorded commit first",
+            hex(&info.commit),
+            hex(&head)
+        )
+        .into());
+    }
+
+    println!("Rebuilding commit {} with `{build}`", hex(&head));
+    let status = Command::new("sh")
+        .args(["-c", build])
+        .current_dir(&manifest.base)
+        .status()
+        .map_err(|e| format!("running build command: {e}"))?;
+    if !status.success() {
+        return Err(format!("build command failed: {status}").into());
+    }
+
+    let rebuilt = sha256_hex(&local_bytecode(&manifest.mixer)?);
+    let deployed = sha256_hex(&deployed_bytecode(&rpc, &mixer_id)?);
+    println!("mixer program {mixer_id}");
+    Ok(check("rebuilt vs deployed sha256", rebuilt.as_str(), deployed)
+        + check("rebuilt vs recorded sha256", rebuilt.as_str(), hex(&info.build_hash)))
+}
@@ -0,0 +1,236 @@
+//! TOML manifest describing one mixer deployment.
+//!
+//! See `mixer-deploy.example.toml` for a complete example.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use mixer::{curve::Curve, MixerState};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// RPC URL or Solana CLI moniker.
+    pub url: String,
+    /// Fee payer, upgrade authority and pool authority.
+    pub payer: PathBuf,
+    pub verifier: ProgramConfig,
+    pub mixer: ProgramConfig,
+    pub pool: PoolConfig,
+    /// Directory containing the manifest; commands run from here.
+    #[serde(skip)]
+    pub base: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramConfig {
+    /// Built program, e.g. `target/deploy/mixer.so`.
+    pub so: PathBuf,
+    /// Keypair whose address is the program id.
+    pub keypair: PathBuf,
+    /// Shell command that rebuilds `so` reproducibly, run from the manifest
+    /// directory by `verify-build`.
+    #[serde(default)]
+    pub build: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PoolConfig {
+    pub denomination: u64,
+    #[serde(default)]
+    pub admin_delay_secs: u64,
+    #[serde(default)]
+    pub allow_off_curve_recipients: bool,
+    /// Curve of the pool's circuit; fixed once the pool is initialized.
+    #[serde(default)]
+    pub curve: CurveName,
+    /// Merkle tree depth of the pool's circuit; fixed once the pool is
+    /// initialized.
+    #[serde(default = "default_tree_depth")]
+    pub tree_depth: u8,
+}
+
+fn default_tree_depth() -> u8 {
+    MixerState::DEFAULT_TREE_DEPTH
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum CurveName {
+    #[default]
+    #[serde(rename = "bn254")]
+    Bn254,
+    #[serde(rename = "bls12-381")]
+    Bls12_381,
+}
+
+impl PoolConfig {
+    pub fn flags(&self) -> u8 {
+        if self.allow_off_curve_recipients {
+            MixerState::FLAG_ALLOW_OFF_CURVE_RECIPIENTS
+        } else {
+            0
+        }
+    }
+
+    pub fn curve(&self) -> Curve {
+        match self.curve {
+            CurveName::Bn254 => Curve::Bn254,
+            CurveName::Bls12_381 => Curve::Bls12_381,
+        }
+    }
+
+    /// Reject a pool `Initialize` would refuse, before anything is sent.
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if !(1..=MixerState::MAX_TREE_DEPTH).contains(&self.tree_depth) {
+            return Err(format!(
+                "pool.tree_depth must be between 1 and {}",
+                MixerState::MAX_TREE_DEPTH
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("reading {}: {e}", path.display()))?;
+        let base = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        Self::parse(&text, base).map_err(|e| format!("parsing {}: {e}", path.display()).into())
+    }
+
+    /// Parse and check manifest `text`, resolving relative paths against
+    /// `base`.
+    fn parse(text: &str, base: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let mut manifest: Manifest = toml::from_str(text)?;
+        manifest.pool.validate()?;
+        for p in [
+            &mut manifest.payer,
+            &mut manifest.verifier.so,
+            &mut manifest.verifier.keypair,
+            &mut manifest.mixer.so,
+            &mut manifest.mixer.keypair,
+        ] {
+            if p.is_relative() {
+                *p = base.join(&*p);
+            }
+        }
+        manifest.base = base;
+        Ok(manifest)
+    }
+
+    /// JSON-RPC endpoint for `url`, expanding CLI monikers.
+    pub fn rpc_url(&self) -> &str {
+        match self.url.as_str() {
+            "localhost" => "http://127.0.0.1:8899",
+            "devnet" => "https://api.devnet.solana.com",
+            "testnet" => "https://api.testnet.solana.com",
+            "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+            url => url,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = r#"
+        url = "localhost"
+        payer = "payer.json"
+
+        [verifier]
+        so = "verifier.so"
+        keypair = "verifier.json"
+
+        [mixer]
+        so = "/deploy/mixer.so"
+        keypair = "mixer.json"
+
+        [pool]
+        denomination = 100
+    "#;
+
+    fn parse(text: &str) -> Result<Manifest, Box<dyn Error>> {
+        Manifest::parse(text, PathBuf::from("deploy"))
+    }
+
+    #[test]
+    fn example_manifest_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("mixer-deploy.example.toml");
+        let manifest = Manifest::load(&path).unwrap();
+        assert_eq!(manifest.rpc_url(), "https://api.devnet.solana.com");
+        assert_eq!(manifest.base, Path::new(env!("CARGO_MANIFEST_DIR")));
+        assert_eq!(manifest.mixer.so, manifest.base.join("../../target/deploy/mixer.so"));
+        assert!(manifest.mixer.build.is_some() && manifest.verifier.build.is_none());
+        assert_eq!(manifest.pool.denomination, 1_000_000_000);
+        assert_eq!(manifest.pool.admin_delay_secs, 172_800);
+        assert_eq!(manifest.pool.curve(), Curve::Bn254);
+        assert_eq!(manifest.pool.tree_depth, 20);
+    }
+
+    #[test]
+    fn omitted_pool_fields_take_their_defaults() {
+        let manifest = parse(MINIMAL).unwrap();
+        let pool = &manifest.pool;
+        assert_eq!(pool.admin_delay_secs, 0);
+        assert_eq!(pool.flags(), 0);
+        assert_eq!(pool.curve, CurveName::Bn254);
+        assert_eq!(pool.tree_depth, MixerState::DEFAULT_TREE_DEPTH);
+    }
+
+    #[test]
+    fn relative_paths_resolve_against_the_manifest_directory() {
+        let manifest = parse(MINIMAL).unwrap();
+        assert_eq!(manifest.base, Path::new("deploy"));
+        assert_eq!(manifest.payer, Path::new("deploy/payer.json"));
+        assert_eq!(manifest.verifier.so, Path::new("deploy/verifier.so"));
+        assert_eq!(manifest.mixer.so, Path::new("/deploy/mixer.so"));
+    }
+
+    #[test]
+    fn pool_options_parse() {
+        let text = MINIMAL.replace(
+            "denomination = 100",
+            "denomination = 100\nallow_off_curve_recipients = true\ncurve = \"bls12-381\"\ntree_depth = 32",
+        );
+        let pool = parse(&text).unwrap().pool;
+        assert_eq!(pool.flags(), MixerState::FLAG_ALLOW_OFF_CURVE_RECIPIENTS);
+        assert_eq!(pool.curve(), Curve::Bls12_381);
+        assert_eq!(pool.tree_depth, 32);
+    }
+
+    #[test]
+    fn invalid_manifests_are_rejected() {
+        for (from, to) in [
+            ("denomination = 100", "denomination = 100\npin_verifier = true"),
+            ("denomination = 100", "denomination = 100\ncurve = \"secp256k1\""),
+            ("denomination = 100", "denomination = -1"),
+            ("denomination = 100", ""),
+            ("keypair = \"mixer.json\"", ""),
+            ("denomination = 100", "denomination = 100\ntree_depth = 0"),
+            ("denomination = 100", "denomination = 100\ntree_depth = 33"),
+        ] {
+            let text = MINIMAL.replace(from, to);
+            assert!(parse(&text).is_err(), "accepted {to:?}");
+        }
+    }
+
+    #[test]
+    fn rpc_url_expands_monikers() {
+        let mut manifest = parse(MINIMAL).unwrap();
+        assert_eq!(manifest.rpc_url(), "http://127.0.0.1:8899");
+        manifest.url = "https://rpc.example.com".into();
+        assert_eq!(manifest.rpc_url(), "https://rpc.example.com");
+    }
+}
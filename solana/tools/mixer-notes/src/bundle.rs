@@ -0,0 +1,289 @@
+//! Note files and their password-protected form.
+//!
+//! A note file holds a wallet's notes for one pool. A note is the nullifier
+//! and secret of a deposit, in the big-endian hex of `Prover.toml`, and the
+//! leaf its commitment was inserted at, optionally with the signature of the
+//! deposit transaction:
+//!
+//! ```text
+//! { "kind": "notes", "version": 1,
+//!   "pool": { "program_id": "<base58>", "state": "<base58>", "denomination": <lamports> },
+//!   "notes": [ { "nullifier": "0x…", "secret": "0x…", "leaf_index": <u64>,
+//!                "deposit_signature": "<base58>" }, … ] }
+//! ```
+//!
+//! An encrypted file holds the JSON of a note file sealed with
+//! XChaCha20-Poly1305, under a key derived from a password with Argon2id:
+//!
+//! ```text
+//! { "kind": "encrypted-notes", "version": 1,
+//!   "kdf": { "algorithm": "argon2id", "memory_kib": <u32>, "iterations": <u32>,
+//!            "parallelism": <u32>, "salt": "0x…" },
+//!   "cipher": "xchacha20-poly1305", "nonce": "0x…", "ciphertext": "0x…" }
+//! ```
+//!
+//! The KDF parameters are stored with the file, so stronger defaults later
+//! still open older files. They are also bound as associated data, so a
+//! file whose header was changed fails to decrypt like a wrong password.
+
+use std::{error::Error, str::FromStr};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use mixer_vectors::tree::from_hex;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_signature::Signature;
+use zeroize::Zeroizing;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+pub const VERSION: u32 = 1;
+
+const KDF_ALGORITHM: &str = "argon2id";
+const CIPHER: &str = "xchacha20-poly1305";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Argon2id cost of new files: 64 MiB, 3 passes, one lane.
+const DEFAULT_MEMORY_KIB: u32 = 64 * 1024;
+const DEFAULT_ITERATIONS: u32 = 3;
+const DEFAULT_PARALLELISM: u32 = 1;
+
+/// Largest costs a file may ask for, so opening a crafted file cannot
+/// exhaust memory or take hours.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 16;
+const MAX_PARALLELISM: u32 = 16;
+
+/// The pool a note file's notes were deposited into.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Pool {
+    pub program_id: String,
+    pub state: String,
+    pub denomination: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Note {
+    pub nullifier: String,
+    pub secret: String,
+    pub leaf_index: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deposit_signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NoteFile {
+    pub version: u32,
+    pub pool: Pool,
+    pub notes: Vec<Note>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Kdf {
+    pub algorithm: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EncryptedNotes {
+    pub version: u32,
+    pub kdf: Kdf,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum Bundle {
+    #[serde(rename = "notes")]
+    Plain(NoteFile),
+    #[serde(rename = "encrypted-notes")]
+    Encrypted(EncryptedNotes),
+}
+
+impl NoteFile {
+    /// Check the version, the pool addresses, and that every note is a pair
+    /// of canonical field elements with a nullifier of its own.
+    pub fn validate(&self) -> Result<()> {
+        if self.version != VERSION {
+            return Err(format!("unsupported note file version {}", self.version).into());
+        }
+        Pubkey::from_str(&self.pool.program_id).map_err(|e| format!("pool program_id: {e}"))?;
+        Pubkey::from_str(&self.pool.state).map_err(|e| format!("pool state: {e}"))?;
+        for (i, note) in self.notes.iter().enumerate() {
+            from_hex(&note.nullifier).map_err(|e| format!("note {i} nullifier: {e}"))?;
+            from_hex(&note.secret).map_err(|e| format!("note {i} secret: {e}"))?;
+            if let Some(signature) = &note.deposit_signature {
+                Signature::from_str(signature).map_err(|e| format!("note {i} deposit_signature: {e}"))?;
+            }
+            if self.notes[..i].iter().any(|n| same_field(&n.nullifier, &note.nullifier)) {
+                return Err(format!("note {i} repeats the nullifier of an earlier note").into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether two hex field elements have the same value, regardless of
+/// prefix, case and leading zeroes. Text that is not a field element equals
+/// nothing.
+pub fn same_field(a: &str, b: &str) -> bool {
+    let value = |h: &str| {
+        let digits = h.strip_prefix("0x").unwrap_or(h).trim_start_matches('0');
+        (digits.len() <= 64).then(|| from_hex(&format!("{digits:0>64}")).ok()).flatten()
+    };
+    matches!((value(a), value(b)), (Some(a), Some(b)) if a == b)
+}
+
+/// Seal `file` under `password` with fresh salt and nonce.
+pub fn encrypt(file: &NoteFile, password: &[u8]) -> Result<EncryptedNotes> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let kdf = Kdf {
+        algorithm: KDF_ALGORITHM.into(),
+        memory_kib: DEFAULT_MEMORY_KIB,
+        iterations: DEFAULT_ITERATIONS,
+        parallelism: DEFAULT_PARALLELISM,
+        salt: to_hex(&salt),
+    };
+    let key = derive_key(password, &kdf)?;
+    let plaintext = Zeroizing::new(serde_json::to_vec(file)?);
+    let aad = associated_data(VERSION, &kdf);
+    let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(&XNonce::from(nonce), Payload { msg: &plaintext, aad: &aad })
+        .map_err(|_| "encryption failed")?;
+    Ok(EncryptedNotes {
+        version: VERSION,
+        kdf,
+        cipher: CIPHER.into(),
+        nonce: to_hex(&nonce),
+        ciphertext: to_hex(&ciphertext),
+    })
+}
+
+/// Open `encrypted` with `password` and validate the note file inside.
+pub fn decrypt(encrypted: &EncryptedNotes, password: &[u8]) -> Result<NoteFile> {
+    if encrypted.version != VERSION {
+        return Err(format!("unsupported encrypted file version {}", encrypted.version).into());
+    }
+    if encrypted.cipher != CIPHER {
+        return Err(format!("unsupported cipher {}", encrypted.cipher).into());
+    }
+    let nonce: [u8; NONCE_LEN] = bytes_from_hex(&encrypted.nonce)?
+        .try_into()
+        .map_err(|_| format!("nonce must be {NONCE_LEN} bytes"))?;
+    let ciphertext = bytes_from_hex(&encrypted.ciphertext)?;
+    let key = derive_key(password, &encrypted.kdf)?;
+    let aad = associated_data(encrypted.version, &encrypted.kdf);
+    let plaintext = XChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(&XNonce::from(nonce), Payload { msg: &ciphertext, aad: &aad })
+        .map(Zeroizing::new)
+        .map_err(|_| "wrong password, or the file was modified")?;
+    let file: NoteFile = serde_json::from_slice(&plaintext)?;
+    file.validate()?;
+    Ok(file)
+}
+
+fn derive_key(password: &[u8], kdf: &Kdf) -> Result<Zeroizing<[u8; 32]>> {
+    if kdf.algorithm != KDF_ALGORITHM {
+        return Err(format!("unsupported key derivation {}", kdf.algorithm).into());
+    }
+    if kdf.memory_kib > MAX_MEMORY_KIB {
+        return Err(format!(
+            "key derivation asks for {} KiB, at most {MAX_MEMORY_KIB} are allowed",
+            kdf.memory_kib
+        )
+        .into());
+    }
+    if kdf.iterations > MAX_ITERATIONS {
+        return Err(format!(
+            "key derivation asks for {} iterations, at most {MAX_ITERATIONS} are allowed",
+            kdf.iterations
+        )
+        .into());
+    }
+    if kdf.parallelism > MAX_PARALLELISM {
+        return Err(format!(
+            "key derivation asks for {} lanes, at most {MAX_PARALLELISM} are allowed",
+            kdf.parallelism
+        )
+        .into());
+    }
+    let salt = bytes_from_hex(&kdf.salt)?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|e| format!("key derivation parameters: {e}"))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password, &salt, key.as_mut())
+        .map_err(|e| format!("key derivation: {e}"))?;
+    Ok(key)
+}
+
+fn associated_data(version: u32, kdf: &Kdf) -> Vec<u8> {
+    format!(
+        "mixer-notes/{version}/{}/{}/{}/{}/{}",
+        kdf.algorithm, kdf.memory_kib, kdf.iterations, kdf.parallelism, kdf.salt
+    )
+    .into_bytes()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.iter().map(|b| format!("{b:02x}")).collect::<String>())
+}
+
+pub fn bytes_from_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err(format!("{hex} is not hex").into());
+    }
+    (0..digits.len() / 2)
+        .map(|i| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| format!("{hex}: {e}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_field_compares_values() {
+        let one = format!("0x{:0>64}", "1");
+        assert!(same_field(&one, "0x1"));
+        assert!(same_field("0x01", "1"));
+        assert!(same_field(&"ab".repeat(16), &format!("0x{}", "AB".repeat(16))));
+        assert!(!same_field("0x1", "0x2"));
+        assert!(!same_field("0xzz", "0xzz"));
+        assert!(!same_field(&format!("0x{}", "f".repeat(64)), &format!("0x{}", "f".repeat(64))));
+    }
+
+    #[test]
+    fn derive_key_rejects_excessive_costs() {
+        let kdf = Kdf {
+            algorithm: KDF_ALGORITHM.into(),
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+            salt: to_hex(&[0; SALT_LEN]),
+        };
+        derive_key(b"password", &kdf).unwrap();
+        for kdf in [
+            Kdf { memory_kib: MAX_MEMORY_KIB + 1, ..kdf.clone() },
+            Kdf { iterations: MAX_ITERATIONS + 1, ..kdf.clone() },
+            Kdf { parallelism: MAX_PARALLELISM + 1, ..kdf.clone() },
+        ] {
+            assert!(derive_key(b"password", &kdf).is_err());
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Portable note files, so a wallet's notes can move between machines.
+//!
+//! - `export`: write some or all notes of a note file (see [`bundle`]) to a
+//!   new file, encrypted under a password with `--encrypt`.
+//! - `import`: read an exported file, encrypted or not, and merge its notes
+//!   into a note file, creating it if needed.
+//! - `to-mnemonic` / `from-mnemonic`: write a note as words for writing
+//!   down, and add a note typed back in (see [`mnemonic`]).
+//! - `report`: list the deposits and withdrawals of a note file's notes as
+//!   CSV or JSON, for tax reporting or voluntary disclosure (see [`report`]).
+//!
+//! A note's nullifier and secret are all it takes to withdraw its deposit,
+//! so an unencrypted file is as sensitive as the funds. Files are created
+//! readable by their owner only. The password is taken from
+//! `MIXER_NOTES_PASSWORD` if set, and prompted for otherwise.
+
+mod bundle;
+mod mnemonic;
+mod report;
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use bip39::Language;
+use bundle::{Bundle, Note, NoteFile, Pool, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use solana_commitment_config::CommitmentConfig;
+use solana_rpc_client::rpc_client::RpcClient;
+use zeroize::Zeroizing;
+
+const PASSWORD_ENV: &str = "MIXER_NOTES_PASSWORD";
+
+#[derive(Parser)]
+#[command(about = "Export and import password-protected note files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Write notes from a note file to a new file.
+    Export {
+        notes: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+        /// Only export the note at this leaf index; repeat for several.
+        #[arg(long = "leaf")]
+        leaves: Vec<u64>,
+        /// Encrypt the file under a password.
+        #[arg(long)]
+        encrypt: bool,
+    },
+    /// Merge the notes of an exported file into a note file.
+    Import {
+        file: PathBuf,
+        #[arg(long)]
+        into: PathBuf,
+    },
+    /// Print the note at a leaf as a word mnemonic.
+    ToMnemonic {
+        notes: PathBuf,
+        #[arg(long)]
+        leaf: u64,
+        #[arg(long, value_enum, default_value_t = WordList::English)]
+        language: WordList,
+    },
+    /// Add a note, read from standard input as a word mnemonic, to a note file.
+    FromMnemonic {
+        #[arg(long)]
+        into: PathBuf,
+        /// Leaf the note's commitment was inserted at.
+        #[arg(long)]
+        leaf: u64,
+        #[arg(long, value_enum, default_value_t = WordList::English)]
+        language: WordList,
+        /// Pool of a new note file; required if `--into` does not exist.
+        #[arg(long, requires_all = ["state", "denomination"])]
+        program_id: Option<String>,
+        #[arg(long, requires_all = ["program_id", "denomination"])]
+        state: Option<String>,
+        #[arg(long, requires_all = ["program_id", "state"])]
+        denomination: Option<u64>,
+    },
+    /// Report the deposits and withdrawals of a note file's notes.
+    Report {
+        notes: PathBuf,
+        /// RPC endpoint of the pool's cluster.
+        #[arg(long)]
+        url: String,
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+        /// Write the report here instead of to standard output.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// BIP39 word lists.
+#[derive(Clone, Copy, ValueEnum)]
+enum WordList {
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl From<WordList> for Language {
+    fn from(list: WordList) -> Self {
+        match list {
+            WordList::English => Language::English,
+            WordList::ChineseSimplified => Language::SimplifiedChinese,
+            WordList::ChineseTraditional => Language::TraditionalChinese,
+            WordList::Czech => Language::Czech,
+            WordList::French => Language::French,
+            WordList::Italian => Language::Italian,
+            WordList::Japanese => Language::Japanese,
+            WordList::Korean => Language::Korean,
+            WordList::Portuguese => Language::Portuguese,
+            WordList::Spanish => Language::Spanish,
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Cmd::Export { notes, out, leaves, encrypt } => {
+            let mut file = match read(&notes)? {
+                Bundle::Plain(file) => file,
+                Bundle::Encrypted(_) => {
+                    return Err(format!("{} is encrypted; import it first", notes.display()).into())
+                }
+            };
+            file.validate().map_err(|e| format!("{}: {e}", notes.display()))?;
+            if !leaves.is_empty() {
+                let has_leaf = |leaf: &&u64| file.notes.iter().any(|n| n.leaf_index == **leaf);
+                if let Some(missing) = leaves.iter().find(|l| !has_leaf(l)) {
+                    return Err(format!("{} has no note at leaf {missing}", notes.display()).into());
+                }
+                file.notes.retain(|n| leaves.contains(&n.leaf_index));
+            }
+            let count = file.notes.len();
+            let bundle = if encrypt {
+                Bundle::Encrypted(bundle::encrypt(&file, &password(true)?)?)
+            } else {
+                Bundle::Plain(file)
+            };
+            let mut handle = create_private(&out)?;
+            handle.write_all(&to_json(&bundle)?)?;
+            handle.sync_all()?;
+            println!("Exported {count} note(s) to {}", out.display());
+            if !encrypt {
+                eprintln!("warning: the notes are unencrypted; anyone who reads the file can withdraw them");
+            }
+            Ok(())
+        }
+        Cmd::Import { file, into } => {
+            let imported = match read(&file)? {
+                Bundle::Plain(notes) => notes,
+                Bundle::Encrypted(encrypted) => bundle::decrypt(&encrypted, &password(false)?)
+                    .map_err(|e| format!("{}: {e}", file.display()))?,
+            };
+            imported.validate().map_err(|e| format!("{}: {e}", file.display()))?;
+            let (merged, added) = match read_if_exists(&into)? {
+                None => {
+                    let added = imported.notes.len();
+                    (imported, added)
+                }
+                Some(Bundle::Plain(existing)) => {
+                    existing.validate().map_err(|e| format!("{}: {e}", into.display()))?;
+                    merge(existing, imported)?
+                }
+                Some(Bundle::Encrypted(_)) => {
+                    let e = format!("{} is encrypted; import into a plain note file", into.display());
+                    return Err(e.into());
+                }
+            };
+            let total = merged.notes.len();
+            replace_private(&into, &to_json(&Bundle::Plain(merged))?)?;
+            println!("Imported {added} note(s) into {} ({total} in total)", into.display());
+            Ok(())
+        }
+        Cmd::ToMnemonic { notes, leaf, language } => {
+            let file = match read(&notes)? {
+                Bundle::Plain(file) => file,
+                Bundle::Encrypted(_) => {
+                    return Err(format!("{} is encrypted; import it first", notes.display()).into())
+                }
+            };
+            file.validate().map_err(|e| format!("{}: {e}", notes.display()))?;
+            let note = file
+                .notes
+                .iter()
+                .find(|n| n.leaf_index == leaf)
+                .ok_or_else(|| format!("{} has no note at leaf {leaf}", notes.display()))?;
+            let nullifier = field_bytes(&note.nullifier)?;
+            let secret = field_bytes(&note.secret)?;
+            println!("{}", Zeroizing::new(mnemonic::encode(&nullifier, &secret, language.into())).as_str());
+            eprintln!("Anyone who reads these {} words can withdraw the note at leaf {leaf}", mnemonic::WORDS);
+            Ok(())
+        }
+        Cmd::FromMnemonic { into, leaf, language, program_id, state, denomination } => {
+            if io::stdin().is_terminal() {
+                eprintln!("Enter the {} words, then an empty line or end of input:", mnemonic::WORDS);
+            }
+            let phrase = read_phrase()?;
+            let (nullifier, secret) = mnemonic::decode(&phrase, language.into())?;
+            let note = Note {
+                nullifier: bundle::to_hex(&nullifier),
+                secret: bundle::to_hex(&secret),
+                leaf_index: leaf,
+                deposit_signature: None,
+            };
+            let existing = match read_if_exists(&into)? {
+                Some(Bundle::Plain(existing)) => existing,
+                Some(Bundle::Encrypted(_)) => {
+                    let e = format!("{} is encrypted; import into a plain note file", into.display());
+                    return Err(e.into());
+                }
+                None => {
+                    let (Some(program_id), Some(state), Some(denomination)) = (program_id, state, denomination)
+                    else {
+                        return Err(format!(
+                            "{} does not exist; give --program-id, --state and --denomination to create it",
+                            into.display()
+                        )
+                        .into());
+                    };
+                    NoteFile {
+                        version: bundle::VERSION,
+                        pool: Pool { program_id, state, denomination },
+                        notes: Vec::new(),
+                    }
+                }
+            };
+            let imported = NoteFile { version: bundle::VERSION, pool: existing.pool.clone(), notes: vec![note] };
+            imported.validate()?;
+            existing.validate().map_err(|e| format!("{}: {e}", into.display()))?;
+            let (merged, added) = merge(existing, imported)?;
+            replace_private(&into, &to_json(&Bundle::Plain(merged))?)?;
+            match added {
+                0 => println!("{} already has this note", into.display()),
+                _ => println!("Added the note at leaf {leaf} to {}", into.display()),
+            }
+            Ok(())
+        }
+        Cmd::Report { notes, url, format, out } => {
+            let file = match read(&notes)? {
+                Bundle::Plain(file) => file,
+                Bundle::Encrypted(_) => {
+                    return Err(format!("{} is encrypted; import it first", notes.display()).into())
+                }
+            };
+            file.validate().map_err(|e| format!("{}: {e}", notes.display()))?;
+            let rpc = RpcClient::new_with_commitment(url, CommitmentConfig::confirmed());
+            let rows = report::report(&rpc, &file)?;
+            let text = match format {
+                Format::Csv => {
+                    let lines = rows.iter().map(report::Row::csv);
+                    std::iter::once(report::CSV_HEADER.to_string()).chain(lines).collect::<Vec<_>>().join("\n") + "\n"
+                }
+                Format::Json => serde_json::to_string_pretty(&rows)? + "\n",
+            };
+            match out {
+                Some(path) => {
+                    fs::write(&path, text).map_err(|e| format!("writing {}: {e}", path.display()))?;
+                    eprintln!("Wrote {} row(s) to {}", rows.len(), path.display());
+                }
+                None => print!("{text}"),
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Standard input up to its end or an empty line.
+fn read_phrase() -> Result<Zeroizing<String>> {
+    let mut phrase = Zeroizing::new(String::new());
+    if io::stdin().is_terminal() {
+        for line in io::stdin().lines() {
+            let line = Zeroizing::new(line?);
+            if line.trim().is_empty() {
+                break;
+            }
+            phrase.push_str(&line);
+            phrase.push('\n');
+        }
+    } else {
+        io::stdin().read_to_string(&mut phrase)?;
+    }
+    Ok(phrase)
+}
+
+fn field_bytes(hex: &str) -> Result<[u8; 32]> {
+    bundle::bytes_from_hex(hex)?
+        .try_into()
+        .map_err(|_| format!("{hex} is not 32 bytes").into())
+}
+
+/// Add the notes of `imported` that `existing` lacks. Fails if the files
+/// are for different pools or disagree on a note.
+fn merge(mut existing: NoteFile, imported: NoteFile) -> Result<(NoteFile, usize)> {
+    if existing.pool != imported.pool {
+        return Err(format!(
+            "the imported notes are for pool {} ({} lamports), the note file for pool {} ({} lamports)",
+            imported.pool.state,
+            imported.pool.denomination,
+            existing.pool.state,
+            existing.pool.denomination
+        )
+        .into());
+    }
+    let mut added = 0;
+    for note in imported.notes {
+        let known = existing.notes.iter_mut().find(|n| bundle::same_field(&n.nullifier, &note.nullifier));
+        match known {
+            Some(n) if bundle::same_field(&n.secret, &note.secret) && n.leaf_index == note.leaf_index => {
+                if n.deposit_signature.is_none() {
+                    n.deposit_signature = note.deposit_signature;
+                }
+            }
+            Some(n) => {
+                return Err(format!(
+                    "the imported note at leaf {} has the nullifier of the note at leaf {} but differs from it",
+                    note.leaf_index, n.leaf_index
+                )
+                .into())
+            }
+            None => {
+                existing.notes.push(note);
+                added += 1;
+            }
+        }
+    }
+    existing.notes.sort_by_key(|n| n.leaf_index);
+    Ok((existing, added))
+}
+
+fn password(confirm: bool) -> Result<Zeroizing<Vec<u8>>> {
+    let password = match env::var_os(PASSWORD_ENV) {
+        Some(password) => Zeroizing::new(password.into_encoded_bytes()),
+        None => {
+            let password = Zeroizing::new(rpassword::prompt_password("Password: ")?);
+            if confirm {
+                let again = Zeroizing::new(rpassword::prompt_password("Repeat password: ")?);
+                if *again != *password {
+                    return Err("passwords do not match".into());
+                }
+            }
+            Zeroizing::new(password.as_bytes().to_vec())
+        }
+    };
+    if password.is_empty() {
+        return Err("the password must not be empty".into());
+    }
+    Ok(password)
+}
+
+fn read(path: &Path) -> Result<Bundle> {
+    let text = fs::read_to_string(path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    let text = Zeroizing::new(text);
+    serde_json::from_str(&text).map_err(|e| format!("decoding {}: {e}", path.display()).into())
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<Bundle>> {
+    match fs::metadata(path) {
+        Ok(_) => read(path).map(Some),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("reading {}: {e}", path.display()).into()),
+    }
+}
+
+fn to_json(bundle: &Bundle) -> Result<Zeroizing<Vec<u8>>> {
+    let mut json = Zeroizing::new(serde_json::to_vec_pretty(bundle)?);
+    json.push(b'\n');
+    Ok(json)
+}
+
+/// Create `path`, which must not exist yet, readable by its owner only.
+fn create_private(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).map_err(|e| format!("creating {}: {e}", path.display()).into())
+}
+
+/// Write `data` to `path` through a temporary file, so an interrupted
+/// import leaves the old note file intact.
+fn replace_private(path: &Path, data: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut handle = create_private(&tmp)?;
+    handle.write_all(data)?;
+    handle.sync_all()?;
+    fs::rename(&tmp, path).map_err(|e| format!("replacing {}: {e}", path.display()).into())
+}
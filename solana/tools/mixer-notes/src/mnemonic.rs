@@ -0,0 +1,182 @@
+//! Notes as word mnemonics.
+//!
+//! A note's nullifier and secret, 64 bytes, are written as 48 words of a
+//! BIP39 word list, with BIP39's encoding carried to the larger size: the
+//! bytes are followed by the first 16 bits of their SHA-256 as a checksum,
+//! and the 528 bits are split into 11-bit word indices, most significant bit
+//! first. BIP39 stops at 32 bytes of entropy, so a note mnemonic is not a
+//! seed phrase and wallets must not import it as one.
+//!
+//! The checksum misses about one in 65536 mistyped, missing or reordered
+//! words. Words are matched after NFKD normalization and lowercasing, so
+//! accents typed either way are accepted.
+
+use std::{error::Error, fmt};
+
+use bip39::Language;
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+
+/// Words in a note mnemonic.
+pub const WORDS: usize = 48;
+
+const NOTE_LEN: usize = 64;
+const CHECKSUM_LEN: usize = 2;
+const BITS_PER_WORD: usize = 11;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MnemonicError {
+    WordCount(usize),
+    /// The word at this position, counted from 1, is not in the word list.
+    UnknownWord(usize, String),
+    Checksum,
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MnemonicError::WordCount(n) => write!(f, "a note mnemonic has {WORDS} words, not {n}"),
+            MnemonicError::UnknownWord(i, word) => write!(f, "word {i} ({word}) is not in the word list"),
+            MnemonicError::Checksum => write!(f, "checksum mismatch; a word is wrong or out of order"),
+        }
+    }
+}
+
+impl Error for MnemonicError {}
+
+/// The mnemonic of the note with `nullifier` and `secret`.
+pub fn encode(nullifier: &[u8; 32], secret: &[u8; 32], language: Language) -> String {
+    let mut bytes = [nullifier.as_slice(), secret.as_slice()].concat();
+    bytes.extend_from_slice(&Sha256::digest(&bytes)[..CHECKSUM_LEN]);
+    let words = language.word_list();
+    let separator = if language == Language::Japanese { "\u{3000}" } else { " " };
+    (0..WORDS)
+        .map(|i| words[read_bits(&bytes, i * BITS_PER_WORD)])
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// The nullifier and secret in `phrase`.
+pub fn decode(phrase: &str, language: Language) -> Result<([u8; 32], [u8; 32]), MnemonicError> {
+    let normalized: String = phrase.nfkd().collect::<String>().to_lowercase();
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if words.len() != WORDS {
+        return Err(MnemonicError::WordCount(words.len()));
+    }
+    let mut bytes = [0u8; NOTE_LEN + CHECKSUM_LEN];
+    for (i, word) in words.iter().enumerate() {
+        let index = language
+            .find_word(word)
+            .ok_or_else(|| MnemonicError::UnknownWord(i + 1, word.to_string()))?;
+        write_bits(&mut bytes, i * BITS_PER_WORD, index as usize);
+    }
+    let (note, checksum) = bytes.split_at(NOTE_LEN);
+    if Sha256::digest(note)[..CHECKSUM_LEN] != *checksum {
+        return Err(MnemonicError::Checksum);
+    }
+    Ok((note[..32].try_into().unwrap(), note[32..].try_into().unwrap()))
+}
+
+/// The 11 bits of `bytes` starting at bit `offset`.
+fn read_bits(bytes: &[u8], offset: usize) -> usize {
+    (0..BITS_PER_WORD).fold(0, |acc, i| {
+        let bit = offset + i;
+        (acc << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1) as usize
+    })
+}
+
+fn write_bits(bytes: &mut [u8], offset: usize, value: usize) {
+    for i in 0..BITS_PER_WORD {
+        let bit = offset + i;
+        if (value >> (BITS_PER_WORD - 1 - i)) & 1 == 1 {
+            bytes[bit / 8] |= 1 << (7 - bit % 8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(seed: u8) -> ([u8; 32], [u8; 32]) {
+        let nullifier: [u8; 32] = core::array::from_fn(|i| seed.wrapping_mul(31).wrapping_add(i as u8));
+        let secret: [u8; 32] = core::array::from_fn(|i| seed.wrapping_add(7).wrapping_mul(i as u8 + 3));
+        (nullifier, secret)
+    }
+
+    #[test]
+    fn round_trips_in_every_language() {
+        for &language in Language::ALL {
+            for seed in [0, 1, 0x7f, 0xff] {
+                let (nullifier, secret) = note(seed);
+                let phrase = encode(&nullifier, &secret, language);
+                assert_eq!(phrase.split_whitespace().count(), WORDS, "{language:?}");
+                assert_eq!(decode(&phrase, language), Ok((nullifier, secret)), "{language:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_bip39_on_the_first_bits() {
+        // All-zero bytes encode as the first word until the checksum starts,
+        // as in BIP39's test vectors.
+        let phrase = encode(&[0; 32], &[0; 32], Language::English);
+        let words: Vec<&str> = phrase.split(' ').collect();
+        assert!(words[..46].iter().all(|w| *w == "abandon"));
+        assert_eq!(decode(&phrase, Language::English), Ok(([0; 32], [0; 32])));
+    }
+
+    #[test]
+    fn accepts_case_and_spacing_differences() {
+        let (nullifier, secret) = note(3);
+        let phrase = encode(&nullifier, &secret, Language::English);
+        let messy = format!("  {}\n", phrase.to_uppercase().replace(' ', "  \t"));
+        assert_eq!(decode(&messy, Language::English), Ok((nullifier, secret)));
+    }
+
+    #[test]
+    fn detects_single_word_substitutions() {
+        let (nullifier, secret) = note(9);
+        let phrase = encode(&nullifier, &secret, Language::English);
+        let words = Language::English.word_list();
+        for position in 0..WORDS {
+            let mut changed: Vec<&str> = phrase.split(' ').collect();
+            let index = Language::English.find_word(changed[position]).unwrap() as usize;
+            changed[position] = words[(index + 1) % words.len()];
+            assert_eq!(
+                decode(&changed.join(" "), Language::English),
+                Err(MnemonicError::Checksum),
+                "word {position}"
+            );
+        }
+    }
+
+    #[test]
+    fn detects_swapped_words() {
+        let (nullifier, secret) = note(5);
+        let phrase = encode(&nullifier, &secret, Language::English);
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+        let j = (1..WORDS).find(|&j| words[j] != words[0]).unwrap();
+        words.swap(0, j);
+        assert_eq!(decode(&words.join(" "), Language::English), Err(MnemonicError::Checksum));
+    }
+
+    #[test]
+    fn rejects_wrong_lengths_unknown_words_and_other_languages() {
+        let (nullifier, secret) = note(1);
+        let phrase = encode(&nullifier, &secret, Language::English);
+        let words: Vec<&str> = phrase.split(' ').collect();
+        assert_eq!(
+            decode(&words[..WORDS - 1].join(" "), Language::English),
+            Err(MnemonicError::WordCount(WORDS - 1))
+        );
+        let mut typo = words.clone();
+        typo[4] = "abandonn";
+        assert_eq!(
+            decode(&typo.join(" "), Language::English),
+            Err(MnemonicError::UnknownWord(5, "abandonn".into()))
+        );
+        let french = encode(&nullifier, &secret, Language::French);
+        assert!(matches!(decode(&french, Language::English), Err(MnemonicError::UnknownWord(..))));
+    }
+}
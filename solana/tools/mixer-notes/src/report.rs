@@ -0,0 +1,244 @@
+//! Activity reports over a note file.
+//!
+//! Each note gives a deposit row and, once its nullifier is spent, a
+//! withdraw row. Deposits are dated from the note's `deposit_signature`;
+//! notes without one keep their deposit row with the time and signature
+//! left empty. Withdrawals are found through the note's nullifier PDA: the
+//! transaction that created it carries the `Withdraw` event with the amount
+//! paid and the recipient.
+//!
+//! Only the note file's own nullifier PDAs and deposit signatures are looked
+//! up, so the report holds nothing about other users. The RPC node still
+//! sees every lookup, which links the reported notes to each other for its
+//! operator; use a node you trust.
+
+use std::str::FromStr;
+
+use borsh::BorshDeserialize;
+use mixer::events::{self, Event, EVENT_IX_TAG_LE};
+use mixer_vectors::tree::{self, from_hex};
+use serde::Serialize;
+use solana_commitment_config::CommitmentConfig;
+use solana_program::pubkey::Pubkey;
+use solana_rpc_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_rpc_client_api::config::RpcTransactionConfig;
+use solana_signature::Signature;
+use solana_transaction_status_client_types::{
+    option_serializer::OptionSerializer, EncodedConfirmedTransactionWithStatusMeta, UiInstruction,
+    UiTransactionEncoding,
+};
+
+use crate::bundle::{self, NoteFile, Result};
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Row {
+    /// `deposit` or `withdraw`.
+    pub kind: &'static str,
+    /// The pool's state account.
+    pub pool: String,
+    pub leaf_index: u64,
+    /// Lamports deposited, or paid to the recipient.
+    pub amount_lamports: u64,
+    /// Block time, as UTC RFC 3339.
+    pub time: Option<String>,
+    pub signature: Option<String>,
+    /// Withdrawals only.
+    pub recipient: Option<String>,
+}
+
+pub const CSV_HEADER: &str = "kind,pool,leaf_index,amount_lamports,time,signature,recipient";
+
+impl Row {
+    pub fn csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{}",
+            self.kind,
+            self.pool,
+            self.leaf_index,
+            self.amount_lamports,
+            self.time.as_deref().unwrap_or(""),
+            self.signature.as_deref().unwrap_or(""),
+            self.recipient.as_deref().unwrap_or("")
+        )
+    }
+}
+
+/// Deposit and withdraw rows for every note in `file`, in leaf order.
+pub fn report(rpc: &RpcClient, file: &NoteFile) -> Result<Vec<Row>> {
+    let program_id = Pubkey::from_str(&file.pool.program_id)?;
+    let mut notes: Vec<_> = file.notes.iter().collect();
+    notes.sort_by_key(|n| n.leaf_index);
+    let mut rows = Vec::new();
+    for note in notes {
+        let mut deposit = Row {
+            kind: "deposit",
+            pool: file.pool.state.clone(),
+            leaf_index: note.leaf_index,
+            amount_lamports: file.pool.denomination,
+            time: None,
+            signature: None,
+            recipient: None,
+        };
+        if let Some(signature) = &note.deposit_signature {
+            let tx = transaction(rpc, &Signature::from_str(signature)?)?;
+            let pushed_root = event_data(&tx, &program_id)
+                .iter()
+                .any(|e| e.starts_with(&events::RootPushed::DISCRIMINATOR));
+            if !succeeded(&tx) || !pushed_root {
+                return Err(format!(
+                    "note at leaf {}: {signature} is not a successful deposit into the pool",
+                    note.leaf_index
+                )
+                .into());
+            }
+            deposit.time = tx.block_time.map(utc);
+            deposit.signature = Some(signature.clone());
+        }
+        rows.push(deposit);
+
+        let nullifier_hash = nullifier_hash(&note.nullifier)?;
+        if let Some(row) = withdrawal(rpc, file, &program_id, note.leaf_index, &nullifier_hash)? {
+            rows.push(row);
+        }
+    }
+    Ok(rows)
+}
+
+/// The nullifier hash of a note, as the bytes of its nullifier PDA seed.
+fn nullifier_hash(nullifier: &str) -> Result<[u8; 32]> {
+    let hash = tree::to_hex(&tree::nullifier_hash(from_hex(nullifier)?));
+    Ok(bundle::bytes_from_hex(&hash)?.try_into().unwrap())
+}
+
+/// The withdraw that spent `nullifier_hash`, if any: the oldest successful
+/// transaction on its nullifier PDA that emitted a matching `Withdraw`.
+fn withdrawal(
+    rpc: &RpcClient,
+    file: &NoteFile,
+    program_id: &Pubkey,
+    leaf_index: u64,
+    nullifier_hash: &[u8; 32],
+) -> Result<Option<Row>> {
+    let (nullifier_pda, _) = Pubkey::find_program_address(&[b"nullifier", nullifier_hash], program_id);
+    let mut signatures = Vec::new();
+    let mut before = None;
+    loop {
+        let page = rpc.get_signatures_for_address_with_config(
+            &nullifier_pda,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: None,
+                commitment: Some(CommitmentConfig::confirmed()),
+            },
+        )?;
+        let Some(last) = page.last() else { break };
+        before = Some(Signature::from_str(&last.signature)?);
+        signatures.extend(page.into_iter().filter(|s| s.err.is_none()));
+    }
+    // Newest first; the withdraw created the PDA, so start from the oldest.
+    for status in signatures.iter().rev() {
+        let signature = Signature::from_str(&status.signature)?;
+        let tx = transaction(rpc, &signature)?;
+        for data in event_data(&tx, program_id) {
+            let Some(body) = data.strip_prefix(&events::Withdraw::DISCRIMINATOR) else {
+                continue;
+            };
+            let Ok(event) = events::Withdraw::try_from_slice(body) else {
+                continue;
+            };
+            if &event.nullifier_hash == nullifier_hash {
+                return Ok(Some(Row {
+                    kind: "withdraw",
+                    pool: file.pool.state.clone(),
+                    leaf_index,
+                    amount_lamports: event.amount,
+                    time: tx.block_time.map(utc),
+                    signature: Some(status.signature.clone()),
+                    recipient: Some(Pubkey::new_from_array(event.recipient).to_string()),
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn transaction(rpc: &RpcClient, signature: &Signature) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+    rpc.get_transaction_with_config(
+        signature,
+        RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        },
+    )
+    .map_err(|e| format!("fetching {signature}: {e}").into())
+}
+
+fn succeeded(tx: &EncodedConfirmedTransactionWithStatusMeta) -> bool {
+    tx.transaction.meta.as_ref().is_some_and(|meta| meta.err.is_none())
+}
+
+/// Payloads, discriminator first, of the events `program_id` emitted in `tx`.
+fn event_data(tx: &EncodedConfirmedTransactionWithStatusMeta, program_id: &Pubkey) -> Vec<Vec<u8>> {
+    let Some(meta) = &tx.transaction.meta else {
+        return Vec::new();
+    };
+    let Some(transaction) = tx.transaction.transaction.decode() else {
+        return Vec::new();
+    };
+    let mut keys: Vec<Pubkey> = transaction.message.static_account_keys().to_vec();
+    if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.extend(Pubkey::from_str(key).ok());
+        }
+    }
+    let OptionSerializer::Some(inner) = &meta.inner_instructions else {
+        return Vec::new();
+    };
+    inner
+        .iter()
+        .flat_map(|group| &group.instructions)
+        .filter_map(|instruction| match instruction {
+            UiInstruction::Compiled(ix) if keys.get(ix.program_id_index as usize) == Some(program_id) => {
+                bs58::decode(&ix.data).into_vec().ok()
+            }
+            _ => None,
+        })
+        .filter_map(|data| data.strip_prefix(&EVENT_IX_TAG_LE).map(<[u8]>::to_vec))
+        .collect()
+}
+
+/// `secs` since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+fn utc(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utc_formats_block_times() {
+        assert_eq!(utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(utc(-1), "1969-12-31T23:59:59Z");
+    }
+}
@@ -0,0 +1,54 @@
+//! Poseidon hashing, the Merkle tree and note decoding, as done for every
+//! deposit and withdraw by clients and by `mixer-vectors`.
+
+use std::hint::black_box;
+
+use ark_bn254::Fr;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mixer_vectors::tree::{self, from_hex, to_hex, Tree, DEFAULT_LEVELS};
+
+fn leaves(count: usize) -> Vec<Fr> {
+    (0..count as u64).map(|i| tree::commitment(Fr::from(i + 1), Fr::from(i + 2))).collect()
+}
+
+fn hashing(c: &mut Criterion) {
+    let (left, right) = (Fr::from(1u64), Fr::from(2u64));
+    c.bench_function("hash_2", |b| b.iter(|| tree::hash_2(black_box(left), black_box(right))));
+    c.bench_function("nullifier_hash", |b| b.iter(|| tree::nullifier_hash(black_box(left))));
+}
+
+fn merkle(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tree_new");
+    for count in [1, 64, 1024] {
+        let leaves = leaves(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &leaves, |b, leaves| {
+            b.iter(|| Tree::new(DEFAULT_LEVELS, black_box(leaves)))
+        });
+    }
+    group.finish();
+
+    let leaves = leaves(1024);
+    let tree = Tree::new(DEFAULT_LEVELS, &leaves);
+    c.bench_function("path", |b| b.iter(|| tree.path(black_box(517))));
+    let path = tree.path(517);
+    c.bench_function("root_from_path", |b| {
+        b.iter(|| tree::root_from_path(black_box(leaves[517]), black_box(&path)))
+    });
+}
+
+/// Decoding a note's hex fields as stored in a fixture, and deriving its
+/// commitment.
+fn notes(c: &mut Criterion) {
+    let nullifier = to_hex(&Fr::from(7u64));
+    let secret = to_hex(&Fr::from(11u64));
+    c.bench_function("note_from_hex", |b| {
+        b.iter(|| {
+            let nullifier = from_hex(black_box(&nullifier)).unwrap();
+            let secret = from_hex(black_box(&secret)).unwrap();
+            tree::commitment(nullifier, secret)
+        })
+    });
+}
+
+criterion_group!(benches, hashing, merkle, notes);
+criterion_main!(benches);
@@ -0,0 +1,4 @@
+//! The circuit's hashing and Merkle tree in Rust, shared by the
+//! `mixer-vectors` binary and its benchmarks.
+
+pub mod tree;
@@ -0,0 +1,253 @@
+//! Circuit/program consistency test vectors.
+//!
+//! - `generate`: derive notes deterministically from a seed, insert their
+//!   commitments into a tree at indices `0..count`, and write one fixture per
+//!   note: the private inputs (nullifier, secret, Merkle path) with the
+//!   commitment, nullifier hash and root computed in Rust.
+//! - `check`: recompute every fixture in Rust, then run `nargo execute` on
+//!   the circuit with the same inputs. The circuit asserts its own hashes
+//!   equal the fixture's root and nullifier hash. Each note is also run with
+//!   a perturbed root, which must fail, so a harness that silently passes is
+//!   caught too.
+//!
+//! Fixtures use the circuit's default `TREE_DEPTH` of 20; `generate --depth`
+//! targets a circuit compiled with another depth. The depth is recorded in
+//! the vectors file, so `check` needs no flag.
+//!
+//! A mismatch means the Rust hashing, the circuit and the clients disagree on
+//! hash parameters or byte order, which would strand deposits in a deployed
+//! pool.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+
+use ark_bn254::Fr;
+use ark_ff::{One, PrimeField};
+use clap::{Parser, Subcommand};
+use mixer_vectors::tree::{self, from_hex, to_hex, Tree, DEFAULT_LEVELS};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Name of the inputs file `check` writes next to the circuit's `Nargo.toml`.
+const PROVER_NAME: &str = "TestVectors";
+
+#[derive(Parser)]
+#[command(about = "Generate and check circuit/program consistency test vectors")]
+struct Cli {
+    #[command(subcommand)]
+    command: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Write fixtures for `count` notes derived from `seed`.
+    Generate {
+        #[arg(long, default_value_t = 4)]
+        count: usize,
+        #[arg(long, default_value = "mixer-vectors")]
+        seed: String,
+        /// Merkle tree depth of the circuit, as stored in the pool.
+        #[arg(long, default_value_t = DEFAULT_LEVELS)]
+        depth: usize,
+        #[arg(long, default_value = "test-vectors.json")]
+        out: PathBuf,
+    },
+    /// Check fixtures against the Rust hashing and the Noir circuit.
+    Check {
+        #[arg(default_value = "test-vectors.json")]
+        vectors: PathBuf,
+        /// Noir project to execute; defaults to the repository's `circuits/`.
+        #[arg(long)]
+        circuit_dir: Option<PathBuf>,
+        /// Only compare against the Rust hashing, without running nargo.
+        #[arg(long)]
+        skip_circuit: bool,
+    },
+}
+
+/// One note, with inputs in the big-endian hex of `Prover.toml`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct Fixture {
+    leaf_index: usize,
+    nullifier: String,
+    secret: String,
+    recipient: String,
+    commitment: String,
+    nullifier_hash: String,
+    root: String,
+    merkle_proof: Vec<String>,
+    is_even: Vec<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Vectors {
+    seed: String,
+    #[serde(default = "default_depth")]
+    depth: usize,
+    fixtures: Vec<Fixture>,
+}
+
+fn default_depth() -> usize {
+    DEFAULT_LEVELS
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli) {
+        eprintln!("error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Cmd::Generate { count, seed, depth, out } => {
+            if !(1..=32).contains(&depth) {
+                return Err("depth must be between 1 and 32".into());
+            }
+            if count == 0 || count as u64 > 1 << depth {
+                return Err(format!("count must be between 1 and {}", 1u64 << depth).into());
+            }
+            let vectors = Vectors { fixtures: generate(&seed, depth, count), seed, depth };
+            fs::write(&out, serde_json::to_string_pretty(&vectors)? + "\n")
+                .map_err(|e| format!("writing {}: {e}", out.display()))?;
+            println!("Wrote {count} fixture(s) to {}", out.display());
+            Ok(())
+        }
+        Cmd::Check { vectors, circuit_dir, skip_circuit } => {
+            let text = fs::read_to_string(&vectors)
+                .map_err(|e| format!("reading {}: {e}", vectors.display()))?;
+            let vectors: Vectors = serde_json::from_str(&text)?;
+            check_rust(&vectors)?;
+            println!("  ok  Rust hashing matches {} fixture(s)", vectors.fixtures.len());
+            if !skip_circuit {
+                let dir = circuit_dir.unwrap_or_else(|| {
+                    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../circuits")
+                });
+                check_circuit(&dir, &vectors.fixtures)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Field element derived from `seed`, so fixtures are reproducible.
+fn derive(seed: &str, label: &str, index: usize) -> Fr {
+    let digest = Sha256::new()
+        .chain_update(seed)
+        .chain_update(label)
+        .chain_update((index as u64).to_le_bytes())
+        .finalize();
+    Fr::from_be_bytes_mod_order(&digest)
+}
+
+fn generate(seed: &str, depth: usize, count: usize) -> Vec<Fixture> {
+    let notes: Vec<(Fr, Fr)> = (0..count)
+        .map(|i| (derive(seed, "nullifier", i), derive(seed, "secret", i)))
+        .collect();
+    let leaves: Vec<Fr> = notes.iter().map(|(n, s)| tree::commitment(*n, *s)).collect();
+    let tree = Tree::new(depth, &leaves);
+    notes
+        .iter()
+        .enumerate()
+        .map(|(i, (nullifier, secret))| {
+            let path = tree.path(i);
+            Fixture {
+                leaf_index: i,
+                nullifier: to_hex(nullifier),
+                secret: to_hex(secret),
+                recipient: to_hex(&derive(seed, "recipient", i)),
+                commitment: to_hex(&leaves[i]),
+                nullifier_hash: to_hex(&tree::nullifier_hash(*nullifier)),
+                root: to_hex(&tree.root()),
+                merkle_proof: path.siblings.iter().map(to_hex).collect(),
+                is_even: path.is_even,
+            }
+        })
+        .collect()
+}
+
+/// Regenerate the fixtures and compare, then check each path independently
+/// of the tree construction.
+fn check_rust(vectors: &Vectors) -> Result<()> {
+    let expected = generate(&vectors.seed, vectors.depth, vectors.fixtures.len());
+    for (fixture, expected) in vectors.fixtures.iter().zip(&expected) {
+        if fixture != expected {
+            return Err(format!(
+                "fixture {} differs from the Rust computation; regenerate or investigate hash drift",
+                fixture.leaf_index
+            )
+            .into());
+        }
+        let path = tree::Path {
+            siblings: fixture.merkle_proof.iter().map(|h| from_hex(h)).collect::<std::result::Result<_, _>>()?,
+            is_even: fixture.is_even.clone(),
+        };
+        if to_hex(&tree::root_from_path(from_hex(&fixture.commitment)?, &path)) != fixture.root {
+            return Err(format!("fixture {}: path does not lead to the root", fixture.leaf_index).into());
+        }
+    }
+    Ok(())
+}
+
+fn prover_toml(fixture: &Fixture, root: &str) -> String {
+    let quoted = |values: &[String]| {
+        values.iter().map(|v| format!("  \"{v}\"")).collect::<Vec<_>>().join(",\n")
+    };
+    let bools = fixture.is_even.iter().map(|b| format!("  {b}")).collect::<Vec<_>>().join(",\n");
+    format!(
+        "root = \"{root}\"\nnullifier_hash = \"{}\"\nrecipient = \"{}\"\nnullifier = \"{}\"\nsecret = \"{}\"\nmerkle_proof = [\n{}\n]\nis_even = [\n{bools}\n]\n",
+        fixture.nullifier_hash,
+        fixture.recipient,
+        fixture.nullifier,
+        fixture.secret,
+        quoted(&fixture.merkle_proof),
+    )
+}
+
+/// Whether `nargo execute` accepts the inputs in `PROVER_NAME.toml`.
+fn nargo_accepts(dir: &Path) -> Result<bool> {
+    let nargo = env::var("NARGO").unwrap_or_else(|_| "nargo".into());
+    let status = Command::new(&nargo)
+        .args(["execute", "--prover-name", PROVER_NAME])
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("running `{nargo} execute`: {e}"))?;
+    Ok(status.success())
+}
+
+fn check_circuit(dir: &Path, fixtures: &[Fixture]) -> Result<()> {
+    let inputs = dir.join(format!("{PROVER_NAME}.toml"));
+    let result = (|| -> Result<()> {
+        for fixture in fixtures {
+            fs::write(&inputs, prover_toml(fixture, &fixture.root))?;
+            if !nargo_accepts(dir)? {
+                return Err(format!(
+                    "fixture {}: the circuit rejects the Rust commitment, nullifier hash or root",
+                    fixture.leaf_index
+                )
+                .into());
+            }
+            let wrong_root = to_hex(&(from_hex(&fixture.root)? + Fr::one()));
+            fs::write(&inputs, prover_toml(fixture, &wrong_root))?;
+            if nargo_accepts(dir)? {
+                return Err(format!(
+                    "fixture {}: the circuit accepts a wrong root; the check is not effective",
+                    fixture.leaf_index
+                )
+                .into());
+            }
+            println!("  ok  circuit agrees on fixture {}", fixture.leaf_index);
+        }
+        Ok(())
+    })();
+    let _ = fs::remove_file(&inputs);
+    result
+}
@@ -0,0 +1,152 @@
+//! Rust reimplementation of the circuit's hashing and Merkle tree.
+//!
+//! The circuit uses `poseidon::bn254::hash_2` from noir-lang/poseidon, the
+//! circom-compatible Poseidon that `circomlibjs` (and the TypeScript
+//! `PoseidonTree`) also implement:
+//!
+//! - `commitment = H(nullifier, secret)`
+//! - `nullifier_hash = H(nullifier, 0)`
+//! - each tree level hashes `H(left, right)`
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use light_poseidon::{Poseidon, PoseidonHasher};
+
+/// Default depth of the circuit's Merkle tree (`TREE_DEPTH` in
+/// `circuits/src/merkle_tree.nr`).
+pub const DEFAULT_LEVELS: usize = 20;
+
+/// Value of an empty subtree at each of the first levels, as in
+/// `ZERO_VALUES` of `ts-client/src/merkle-tree.ts`, so roots match the
+/// client's tree. Deeper levels continue with `H(zero, zero)`.
+const ZERO_VALUES: [&str; DEFAULT_LEVELS] = [
+    "0d823319708ab99ec915efd4f7e03d11ca1790918e8f04cd14100aceca2aa9ff",
+    "170a9598425eb05eb8dc06986c6afc717811e874326a79576c02d338bdf14f13",
+    "273b1a40397b618dac2fc66ceb71399a3e1a60341e546e053cbfa5995e824caf",
+    "16bf9b1fb2dfa9d88cfb1752d6937a1594d257c2053dff3cb971016bfcffe2a1",
+    "1288271e1f93a29fa6e748b7468a77a9b8fc3db6b216ce5fc2601fc3e9bd6b36",
+    "1d47548adec1068354d163be4ffa348ca89f079b039c9191378584abd79edeca",
+    "0b98a89e6827ef697b8fb2e280a2342d61db1eb5efc229f5f4a77fb333b80bef",
+    "231555e37e6b206f43fdcd4d660c47442d76aab1ef552aef6db45f3f9cf2e955",
+    "03d0dc8c92e2844abcc5fdefe8cb67d93034de0862943990b09c6b8e3fa27a86",
+    "1d51ac275f47f10e592b8e690fd3b28a76106893ac3e60cd7b2a3a443f4e8355",
+    "16b671eb844a8e4e463e820e26560357edee4ecfdbf5d7b0a28799911505088d",
+    "115ea0c2f132c5914d5bb737af6eed04115a3896f0d65e12e761ca560083da15",
+    "139a5b42099806c76efb52da0ec1dde06a836bf6f87ef7ab4bac7d00637e28f0",
+    "0804853482335a6533eb6a4ddfc215a08026db413d247a7695e807e38debea8e",
+    "2f0b264ab5f5630b591af93d93ec2dfed28eef017b251e40905cdf7983689803",
+    "170fc161bf1b9610bf196c173bdae82c4adfd93888dc317f5010822a3ba9ebee",
+    "0b2e7665b17622cc0243b6fa35110aa7dd0ee3cc9409650172aa786ca5971439",
+    "12d5a033cbeff854c5ba0c5628ac4628104be6ab370699a1b2b4209e518b0ac5",
+    "1bc59846eb7eafafc85ba9a99a89562763735322e4255b7c1788a8fe8b90bf5d",
+    "1b9421fbd79f6972a348a3dd4721781ec25a5d8d27342942ae00aba80a3904d4",
+];
+
+pub fn hash_2(left: Fr, right: Fr) -> Fr {
+    Poseidon::<Fr>::new_circom(2).unwrap().hash(&[left, right]).unwrap()
+}
+
+pub fn commitment(nullifier: Fr, secret: Fr) -> Fr {
+    hash_2(nullifier, secret)
+}
+
+pub fn nullifier_hash(nullifier: Fr) -> Fr {
+    hash_2(nullifier, Fr::zero())
+}
+
+/// Big-endian `0x`-prefixed hex, the encoding of `Prover.toml` and of the
+/// 32-byte values the program receives.
+pub fn to_hex(value: &Fr) -> String {
+    let bytes = value.into_bigint().to_bytes_be();
+    format!("0x{}", bytes.iter().map(|b| format!("{b:02x}")).collect::<String>())
+}
+
+pub fn from_hex(hex: &str) -> Result<Fr, String> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if digits.len() != 64 {
+        return Err(format!("{hex} is not 32 bytes of hex"));
+    }
+    let bytes = (0..32)
+        .map(|i| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{hex}: {e}"))?;
+    let value = Fr::from_be_bytes_mod_order(&bytes);
+    if value.into_bigint().to_bytes_be() != bytes {
+        return Err(format!("{hex} is not a canonical field element"));
+    }
+    Ok(value)
+}
+
+/// Merkle path of one leaf: siblings bottom-up, and whether the running hash
+/// is the left input at each level (the circuit's `is_even`).
+pub struct Path {
+    pub siblings: Vec<Fr>,
+    pub is_even: Vec<bool>,
+}
+
+/// Empty-subtree values for the first `depth` levels.
+pub fn zeros(depth: usize) -> Vec<Fr> {
+    let mut zeros: Vec<Fr> =
+        ZERO_VALUES.iter().take(depth).map(|z| from_hex(z).unwrap()).collect();
+    while zeros.len() < depth {
+        let last = *zeros.last().unwrap();
+        zeros.push(hash_2(last, last));
+    }
+    zeros
+}
+
+/// Tree of `depth` levels over `leaves` at indices `0..leaves.len()`. There
+/// must be at least one leaf.
+pub struct Tree {
+    /// `levels[0]` are the leaves; `levels[depth]` holds the root.
+    levels: Vec<Vec<Fr>>,
+    zeros: Vec<Fr>,
+}
+
+impl Tree {
+    pub fn new(depth: usize, leaves: &[Fr]) -> Self {
+        let zeros = zeros(depth);
+        let mut levels = vec![leaves.to_vec()];
+        for level in 0..depth {
+            let nodes = &levels[level];
+            let parents = nodes
+                .chunks(2)
+                .map(|pair| hash_2(pair[0], pair.get(1).copied().unwrap_or(zeros[level])))
+                .collect();
+            levels.push(parents);
+        }
+        Tree { levels, zeros }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.zeros.len()
+    }
+
+    pub fn root(&self) -> Fr {
+        self.levels[self.depth()][0]
+    }
+
+    pub fn path(&self, index: usize) -> Path {
+        let mut siblings = Vec::with_capacity(self.depth());
+        let mut is_even = Vec::with_capacity(self.depth());
+        let mut i = index;
+        for level in 0..self.depth() {
+            let sibling = self.levels[level].get(i ^ 1).copied().unwrap_or(self.zeros[level]);
+            siblings.push(sibling);
+            is_even.push(i & 1 == 0);
+            i /= 2;
+        }
+        Path { siblings, is_even }
+    }
+}
+
+/// Root obtained by hashing `leaf` up along `path`, as the circuit does.
+pub fn root_from_path(leaf: Fr, path: &Path) -> Fr {
+    path.siblings.iter().zip(&path.is_even).fold(leaf, |hash, (sibling, is_even)| {
+        if *is_even {
+            hash_2(hash, *sibling)
+        } else {
+            hash_2(*sibling, hash)
+        }
+    })
+}
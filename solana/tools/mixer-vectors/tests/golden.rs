@@ -0,0 +1,69 @@
+//! The Rust hashing against the golden vectors in `golden-vectors.json`,
+//! which `circuits/src/golden.nr` checks against the circuit's hash under
+//! `nargo test`. `H(1, 2)` is also circomlibjs's published Poseidon test
+//! vector.
+
+use std::{fs, path::Path};
+
+use mixer_vectors::tree::{self, from_hex};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Golden {
+    hash_2: Vec<Hash2>,
+    notes: Vec<Note>,
+}
+
+#[derive(Deserialize)]
+struct Hash2 {
+    left: String,
+    right: String,
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct Note {
+    nullifier: String,
+    secret: String,
+    commitment: String,
+    nullifier_hash: String,
+}
+
+fn golden() -> Golden {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("golden-vectors.json");
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn hash_2_matches_the_golden_vectors() {
+    for vector in golden().hash_2 {
+        let output = tree::hash_2(from_hex(&vector.left).unwrap(), from_hex(&vector.right).unwrap());
+        assert_eq!(output, from_hex(&vector.output).unwrap(), "H({}, {})", vector.left, vector.right);
+    }
+}
+
+#[test]
+fn notes_match_the_golden_vectors() {
+    for note in golden().notes {
+        let nullifier = from_hex(&note.nullifier).unwrap();
+        let secret = from_hex(&note.secret).unwrap();
+        assert_eq!(tree::commitment(nullifier, secret), from_hex(&note.commitment).unwrap());
+        assert_eq!(tree::nullifier_hash(nullifier), from_hex(&note.nullifier_hash).unwrap());
+    }
+}
+
+/// The circuit's copy of the vectors must not drift from the JSON.
+#[test]
+fn noir_tests_check_the_same_vectors() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../circuits/src/golden.nr");
+    let noir = fs::read_to_string(path).unwrap();
+    let golden = golden();
+    let outputs = golden.hash_2.iter().map(|v| &v.output);
+    let notes = golden
+        .notes
+        .iter()
+        .flat_map(|n| [&n.nullifier, &n.secret, &n.commitment, &n.nullifier_hash]);
+    for value in outputs.chain(notes) {
+        assert!(noir.contains(value.as_str()), "{value} is missing from golden.nr");
+    }
+}
@@ -0,0 +1,850 @@
+// Timelocked pool administration (programs/mixer/src/admin.rs)
+//
+// The pool authority queues an action, waits out the pool's admin delay, and
+// then executes it. Monitoring tools should watch for `AdminActionQueued`
+// events (see `decodeAdminEvent`) to warn users before a change applies.
+import {
+  getAddressDecoder,
+  getAddressEncoder,
+  getProgramDerivedAddress,
+  type Address,
+  type ProgramDerivedAddressBump,
+} from "@solana/kit";
+import { SYSTEM_PROGRAM_ADDRESS } from "@solana-program/system";
+import { EVENT_IX_TAG_LE, VERIFIER_BACKEND, type MixerAddresses } from "./mixer-client.js";
+
+const textEncoder = new TextEncoder();
+
+/** Mirrors the borsh-encoded `AdminAction` enum; variant order matters. */
+export type AdminAction =
+  | { kind: "SetVerifier"; verifierProgram: Address }
+  | { kind: "SetAuthority"; authority: Address }
+  | { kind: "SetAdminDelay"; delaySecs: bigint }
+  /** Pass null to remove the guardian. */
+  | { kind: "SetGuardian"; guardian: Address | null }
+  | { kind: "SetProtocolFee"; feeBps: number }
+  | { kind: "SetRelayerFeeCap"; capBps: number }
+  | { kind: "SetMinWithdrawDelay"; delaySecs: bigint }
+  /**
+   * `backend` is one of `VERIFIER_BACKEND`; `verifier` is the verifier
+   * program, or the verifying key account for the native backend.
+   */
+  | { kind: "SetVerifierBackend"; backend: number; verifier: Address }
+  /** Register or replace a circuit version; pass `2n ** 64n - 1n` for no deprecation. */
+  | {
+      kind: "SetCircuitVersion";
+      version: number;
+      backend: number;
+      verifier: Address;
+      activationSlot: bigint;
+      deprecationSlot: bigint;
+    }
+  | { kind: "DeprecateCircuitVersion"; version: number; deprecationSlot: bigint }
+  /** Pass null to remove the binding. */
+  | { kind: "SetVerifierHash"; hash: Uint8Array | null }
+  /** Deposits per epoch, in total and per depositor; 0 for no limit. */
+  | { kind: "SetDepositLimits"; perEpoch: number; perDepositor: number }
+  /** Most lamports the vault may hold after a deposit; 0n for no cap. */
+  | { kind: "SetVaultCap"; maxVaultLamports: bigint }
+  /** Bubblegum tree for deposit receipts; pass null to disable receipts. */
+  | { kind: "SetReceiptTree"; tree: Address | null }
+  /** Executes only while the pool is paused and holds no unspent notes. */
+  | { kind: "SetDenomination"; denomination: bigint }
+  /**
+   * Register `version` for the new verifier and accept default (version 0)
+   * proofs for `windowSlots` more slots; see
+   * `buildFinalizeMigrationInstruction`.
+   */
+  | { kind: "MigratePool"; version: number; backend: number; verifier: Address; windowSlots: bigint };
+
+/** `deprecationSlot` of a circuit version that is never deprecated. */
+export const CIRCUIT_VERSION_NEVER_DEPRECATED = 2n ** 64n - 1n;
+
+/** Hard-coded upper bounds enforced by the program when an action is queued. */
+export const MAX_PROTOCOL_FEE_BPS = 200;
+export const MAX_RELAYER_FEE_CAP_BPS = 1_000;
+export const MAX_MIN_WITHDRAW_DELAY_SECS = 7n * 24n * 60n * 60n;
+
+const ADMIN_ACTION_VARIANTS = [
+  "SetVerifier",
+  "SetAuthority",
+  "SetAdminDelay",
+  "SetGuardian",
+  "SetProtocolFee",
+  "SetRelayerFeeCap",
+  "SetMinWithdrawDelay",
+  "SetVerifierBackend",
+  "SetCircuitVersion",
+  "DeprecateCircuitVersion",
+  "SetVerifierHash",
+  "SetDepositLimits",
+  "SetVaultCap",
+  "SetReceiptTree",
+  "SetDenomination",
+  "MigratePool",
+] as const;
+
+export function encodeAdminAction(action: AdminAction): Uint8Array {
+  const variant = ADMIN_ACTION_VARIANTS.indexOf(action.kind);
+  switch (action.kind) {
+    case "SetVerifier":
+    case "SetAuthority": {
+      const key = action.kind === "SetVerifier" ? action.verifierProgram : action.authority;
+      const out = new Uint8Array(33);
+      out[0] = variant;
+      out.set(getAddressEncoder().encode(key), 1);
+      return out;
+    }
+    case "SetAdminDelay":
+    case "SetMinWithdrawDelay":
+    case "SetVaultCap":
+    case "SetDenomination": {
+      const out = new Uint8Array(9);
+      out[0] = variant;
+      const value =
+        action.kind === "SetVaultCap"
+          ? action.maxVaultLamports
+          : action.kind === "SetDenomination"
+            ? action.denomination
+            : action.delaySecs;
+      new DataView(out.buffer).setBigUint64(1, value, true);
+      return out;
+    }
+    case "SetProtocolFee":
+    case "SetRelayerFeeCap": {
+      const out = new Uint8Array(3);
+      out[0] = variant;
+      const bps = action.kind === "SetProtocolFee" ? action.feeBps : action.capBps;
+      new DataView(out.buffer).setUint16(1, bps, true);
+      return out;
+    }
+    case "SetGuardian":
+    case "SetReceiptTree": {
+      const out = new Uint8Array(33);
+      out[0] = variant;
+      const key = action.kind === "SetGuardian" ? action.guardian : action.tree;
+      if (key) {
+        out.set(getAddressEncoder().encode(key), 1);
+      }
+      return out;
+    }
+    case "SetVerifierBackend": {
+      const out = new Uint8Array(34);
+      out[0] = variant;
+      out[1] = action.backend;
+      out.set(getAddressEncoder().encode(action.verifier), 2);
+      return out;
+    }
+    case "SetCircuitVersion": {
+      const out = new Uint8Array(52);
+      const view = new DataView(out.buffer);
+      out[0] = variant;
+      view.setUint16(1, action.version, true);
+      out[3] = action.backend;
+      out.set(getAddressEncoder().encode(action.verifier), 4);
+      view.setBigUint64(36, action.activationSlot, true);
+      view.setBigUint64(44, action.deprecationSlot, true);
+      return out;
+    }
+    case "DeprecateCircuitVersion": {
+      const out = new Uint8Array(11);
+      const view = new DataView(out.buffer);
+      out[0] = variant;
+      view.setUint16(1, action.version, true);
+      view.setBigUint64(3, action.deprecationSlot, true);
+      return out;
+    }
+    case "SetVerifierHash": {
+      const out = new Uint8Array(33);
+      out[0] = variant;
+      if (action.hash) {
+        if (action.hash.length !== 32) {
+          throw new Error("verifier hash must be 32 bytes");
+        }
+        out.set(action.hash, 1);
+      }
+      return out;
+    }
+    case "SetDepositLimits": {
+      const out = new Uint8Array(9);
+      const view = new DataView(out.buffer);
+      out[0] = variant;
+      view.setUint32(1, action.perEpoch, true);
+      view.setUint32(5, action.perDepositor, true);
+      return out;
+    }
+    case "MigratePool": {
+      const out = new Uint8Array(44);
+      const view = new DataView(out.buffer);
+      out[0] = variant;
+      view.setUint16(1, action.version, true);
+      out[3] = action.backend;
+      out.set(getAddressEncoder().encode(action.verifier), 4);
+      view.setBigUint64(36, action.windowSlots, true);
+      return out;
+    }
+  }
+}
+
+/** Decode an `AdminAction` and return it with the number of bytes consumed. */
+export function decodeAdminAction(data: Uint8Array): { action: AdminAction; length: number } {
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  const key = () => getAddressDecoder().decode(data.subarray(1, 33));
+  switch (ADMIN_ACTION_VARIANTS[data[0]]) {
+    case "SetVerifier":
+      return { action: { kind: "SetVerifier", verifierProgram: key() }, length: 33 };
+    case "SetAuthority":
+      return { action: { kind: "SetAuthority", authority: key() }, length: 33 };
+    case "SetAdminDelay":
+      return { action: { kind: "SetAdminDelay", delaySecs: view.getBigUint64(1, true) }, length: 9 };
+    case "SetGuardian": {
+      const unset = data.subarray(1, 33).every((b) => b === 0);
+      return { action: { kind: "SetGuardian", guardian: unset ? null : key() }, length: 33 };
+    }
+    case "SetProtocolFee":
+      return { action: { kind: "SetProtocolFee", feeBps: view.getUint16(1, true) }, length: 3 };
+    case "SetRelayerFeeCap":
+      return { action: { kind: "SetRelayerFeeCap", capBps: view.getUint16(1, true) }, length: 3 };
+    case "SetMinWithdrawDelay":
+      return {
+        action: { kind: "SetMinWithdrawDelay", delaySecs: view.getBigUint64(1, true) },
+        length: 9,
+      };
+    case "SetVerifierBackend":
+      return {
+        action: {
+          kind: "SetVerifierBackend",
+          backend: data[1],
+          verifier: getAddressDecoder().decode(data.subarray(2, 34)),
+        },
+        length: 34,
+      };
+    case "SetCircuitVersion":
+      return {
+        action: {
+          kind: "SetCircuitVersion",
+          version: view.getUint16(1, true),
+          backend: data[3],
+          verifier: getAddressDecoder().decode(data.subarray(4, 36)),
+          activationSlot: view.getBigUint64(36, true),
+          deprecationSlot: view.getBigUint64(44, true),
+        },
+        length: 52,
+      };
+    case "DeprecateCircuitVersion":
+      return {
+        action: {
+          kind: "DeprecateCircuitVersion",
+          version: view.getUint16(1, true),
+          deprecationSlot: view.getBigUint64(3, true),
+        },
+        length: 11,
+      };
+    case "SetVerifierHash": {
+      const unset = data.subarray(1, 33).every((b) => b === 0);
+      return {
+        action: { kind: "SetVerifierHash", hash: unset ? null : data.slice(1, 33) },
+        length: 33,
+      };
+    }
+    case "SetDepositLimits":
+      return {
+        action: {
+          kind: "SetDepositLimits",
+          perEpoch: view.getUint32(1, true),
+          perDepositor: view.getUint32(5, true),
+        },
+        length: 9,
+      };
+    case "SetVaultCap":
+      return {
+        action: { kind: "SetVaultCap", maxVaultLamports: view.getBigUint64(1, true) },
+        length: 9,
+      };
+    case "SetReceiptTree": {
+      const unset = data.subarray(1, 33).every((b) => b === 0);
+      return { action: { kind: "SetReceiptTree", tree: unset ? null : key() }, length: 33 };
+    }
+    case "SetDenomination":
+      return {
+        action: { kind: "SetDenomination", denomination: view.getBigUint64(1, true) },
+        length: 9,
+      };
+    case "MigratePool":
+      return {
+        action: {
+          kind: "MigratePool",
+          version: view.getUint16(1, true),
+          backend: data[3],
+          verifier: getAddressDecoder().decode(data.subarray(4, 36)),
+          windowSlots: view.getBigUint64(36, true),
+        },
+        length: 44,
+      };
+    default:
+      throw new Error(`unknown admin action variant: ${data[0]}`);
+  }
+}
+
+export async function getAdminActionPda(
+  mixerProgramId: Address,
+  actionId: bigint
+): Promise<ProgramDerivedAddressBump> {
+  const actionIdBytes = new Uint8Array(8);
+  new DataView(actionIdBytes.buffer).setBigUint64(0, actionId, true);
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("admin_action"), actionIdBytes],
+  });
+  return [pda, bump];
+}
+
+function actionIdData(tag: number, actionId: bigint, extra: Uint8Array = new Uint8Array(0)) {
+  const data = new Uint8Array(9 + extra.length);
+  data[0] = tag;
+  new DataView(data.buffer).setBigUint64(1, actionId, true);
+  data.set(extra, 9);
+  return data;
+}
+
+export async function buildQueueAdminActionInstruction(
+  addresses: MixerAddresses,
+  actionId: bigint,
+  action: AdminAction,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [pendingAction] = await getAdminActionPda(addresses.mixerProgramId, actionId);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: pendingAction, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: actionIdData(11, actionId, encodeAdminAction(action)), // QueueAdminAction
+  };
+}
+
+export async function buildExecuteAdminActionInstruction(
+  addresses: MixerAddresses,
+  actionId: bigint,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [pendingAction] = await getAdminActionPda(addresses.mixerProgramId, actionId);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "writable" },
+      { address: pendingAction, role: "writable" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: actionIdData(12, actionId), // ExecuteAdminAction
+  };
+}
+
+export async function buildCancelAdminActionInstruction(
+  addresses: MixerAddresses,
+  actionId: bigint,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [pendingAction] = await getAdminActionPda(addresses.mixerProgramId, actionId);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: pendingAction, role: "writable" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: actionIdData(13, actionId), // CancelAdminAction
+  };
+}
+
+/**
+ * Pause or unpause the pool immediately. `signer` is the authority, or the
+ * guardian when pausing.
+ */
+export function buildSetPausedInstruction(
+  addresses: MixerAddresses,
+  paused: boolean,
+  signer: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: signer, role: "signer" },
+      { address: addresses.mixerState, role: "writable" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([14, paused ? 1 : 0]), // SetPaused
+  };
+}
+
+/** Pay the accrued protocol fees out of the vault to `destination`. */
+export function buildCollectProtocolFeesInstruction(
+  addresses: MixerAddresses,
+  destination: Address,
+  authority: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "writable" },
+      { address: addresses.mixerVault, role: "writable" },
+      { address: destination, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([18]), // CollectProtocolFees
+  };
+}
+
+/**
+ * Pay whatever the vault holds beyond the pool's liabilities and its
+ * rent-exempt reserve to `destination`.
+ */
+export function buildSweepExcessInstruction(
+  addresses: MixerAddresses,
+  destination: Address,
+  authority: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: addresses.mixerVault, role: "writable" },
+      { address: destination, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([23]), // SweepExcess
+  };
+}
+
+/**
+ * Make a pool migration's circuit version the default verifier once its
+ * window has passed; anyone may send this.
+ */
+export function buildFinalizeMigrationInstruction(addresses: MixerAddresses): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: addresses.mixerState, role: "writable" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([30]), // FinalizeMigration
+  };
+}
+
+// ============================================================================
+// Verifier binding
+// ============================================================================
+
+const BPF_LOADER_UPGRADEABLE_ADDRESS =
+  "BPFLoaderUpgradeab1e11111111111111111111111" as Address;
+
+/**
+ * Check the pool's default verifier against its `verifierHash`; anyone may
+ * send this, and the pool is paused if the hashes differ. `verifierBackend`
+ * and `verifier` come from the pool state.
+ */
+export async function buildVerifyVkBindingInstruction(
+  addresses: MixerAddresses,
+  verifierBackend: number,
+  verifier: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  // The native backend hashes the verifying key; CPI backends hash the
+  // verifier's program data account.
+  let hashed = verifier;
+  if (verifierBackend !== VERIFIER_BACKEND.nativeGroth16) {
+    [hashed] = await getProgramDerivedAddress({
+      programAddress: BPF_LOADER_UPGRADEABLE_ADDRESS,
+      seeds: [getAddressEncoder().encode(verifier)],
+    });
+  }
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: addresses.mixerState, role: "writable" },
+      { address: hashed, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([22]), // VerifyVkBinding
+  };
+}
+
+// ============================================================================
+// Native verifier keys
+// ============================================================================
+
+/**
+ * Groth16 verifying key for the native backend, points in the big-endian
+ * EIP-197 encoding (G1 `x || y`, G2 `x1 || x0 || y1 || y0`).
+ */
+export interface Groth16VerifyingKey {
+  alphaG1: Uint8Array; // 64 bytes
+  betaG2: Uint8Array; // 128 bytes
+  gammaG2: Uint8Array; // 128 bytes
+  deltaG2: Uint8Array; // 128 bytes
+  /** One G1 point per public input plus one: 4 x 64 bytes. */
+  ic: Uint8Array[];
+}
+
+export const GROTH16_VERIFYING_KEY_LEN = 64 + 128 * 3 + 64 * 4;
+
+export async function getVerifyingKeyPda(
+  mixerProgramId: Address,
+  vkId: bigint
+): Promise<ProgramDerivedAddressBump> {
+  const id = new Uint8Array(8);
+  new DataView(id.buffer).setBigUint64(0, vkId, true);
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("verifying_key"), id],
+  });
+  return [pda, bump];
+}
+
+/**
+ * Store a verifying key under `vkId`. Point the pool at it afterwards with a
+ * queued `SetVerifierBackend` action.
+ */
+export async function buildCreateVerifyingKeyInstruction(
+  addresses: MixerAddresses,
+  vkId: bigint,
+  vk: Groth16VerifyingKey,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const parts = [vk.alphaG1, vk.betaG2, vk.gammaG2, vk.deltaG2, ...vk.ic];
+  const lengths = [64, 128, 128, 128, 64, 64, 64, 64];
+  if (parts.length !== lengths.length || parts.some((p, i) => p.length !== lengths[i])) {
+    throw new Error("malformed Groth16 verifying key");
+  }
+  const data = new Uint8Array(1 + 8 + GROTH16_VERIFYING_KEY_LEN);
+  data[0] = 20; // CreateVerifyingKey
+  new DataView(data.buffer).setBigUint64(1, vkId, true);
+  let offset = 9;
+  for (const part of parts) {
+    data.set(part, offset);
+    offset += part.length;
+  }
+  const [verifyingKey] = await getVerifyingKeyPda(addresses.mixerProgramId, vkId);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: verifyingKey, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data,
+  };
+}
+
+// ============================================================================
+// Emergency drain
+// ============================================================================
+
+/** Mirrors `admin::EMERGENCY_DRAIN_MIN_DELAY`. */
+export const EMERGENCY_DRAIN_MIN_DELAY_SECS = 14n * 24n * 60n * 60n;
+
+export async function getEmergencyDrainPda(
+  mixerProgramId: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("emergency_drain")],
+  });
+  return [pda, bump];
+}
+
+/** Queue draining the whole vault to `recovery`. The pool must be paused. */
+export async function buildQueueEmergencyDrainInstruction(
+  addresses: MixerAddresses,
+  recovery: Address,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [pendingDrain] = await getEmergencyDrainPda(addresses.mixerProgramId);
+  const data = new Uint8Array(33);
+  data[0] = 15; // QueueEmergencyDrain
+  data.set(getAddressEncoder().encode(recovery), 1);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: pendingDrain, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data,
+  };
+}
+
+export async function buildExecuteEmergencyDrainInstruction(
+  addresses: MixerAddresses,
+  recovery: Address,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [pendingDrain] = await getEmergencyDrainPda(addresses.mixerProgramId);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: pendingDrain, role: "writable" },
+      { address: addresses.mixerVault, role: "writable" },
+      { address: recovery, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([16]), // ExecuteEmergencyDrain
+  };
+}
+
+export async function buildCancelEmergencyDrainInstruction(
+  addresses: MixerAddresses,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [pendingDrain] = await getEmergencyDrainPda(addresses.mixerProgramId);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: pendingDrain, role: "writable" },
+      { address: addresses.eventAuthority, role: "readonly" },
+      { address: addresses.mixerProgramId, role: "readonly" },
+    ],
+    data: new Uint8Array([17]), // CancelEmergencyDrain
+  };
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+const ADMIN_EVENT_DISCRIMINATORS = {
+  // sha256("event:AdminActionQueued")[..8]
+  AdminActionQueued: new Uint8Array([0xb8, 0x69, 0x9d, 0xc7, 0x04, 0x45, 0x1c, 0x82]),
+  // sha256("event:AdminActionExecuted")[..8]
+  AdminActionExecuted: new Uint8Array([0xa6, 0x1e, 0x3b, 0x4f, 0xc6, 0x99, 0x40, 0x4e]),
+  // sha256("event:AdminActionCancelled")[..8]
+  AdminActionCancelled: new Uint8Array([0x6f, 0x0a, 0xa4, 0x95, 0x65, 0x4d, 0x65, 0x8b]),
+  // sha256("event:PauseChanged")[..8]
+  PauseChanged: new Uint8Array([0xee, 0xbc, 0xd5, 0x4e, 0x86, 0xd1, 0xb2, 0xda]),
+  // sha256("event:ParametersChanged")[..8]
+  ParametersChanged: new Uint8Array([0xc7, 0x50, 0xd6, 0x62, 0x0f, 0x46, 0xb4, 0x76]),
+  // sha256("event:ProtocolFeesCollected")[..8]
+  ProtocolFeesCollected: new Uint8Array([0xa5, 0x22, 0x7d, 0x9b, 0x0f, 0x56, 0x63, 0xbf]),
+  // sha256("event:EmergencyDrainQueued")[..8]
+  EmergencyDrainQueued: new Uint8Array([0x43, 0x11, 0xd1, 0x89, 0xb5, 0x89, 0x6e, 0x4c]),
+  // sha256("event:EmergencyDrainExecuted")[..8]
+  EmergencyDrainExecuted: new Uint8Array([0x8c, 0x89, 0x7e, 0xe9, 0x19, 0x77, 0xcc, 0x0d]),
+  // sha256("event:EmergencyDrainCancelled")[..8]
+  EmergencyDrainCancelled: new Uint8Array([0x50, 0x1c, 0xd8, 0xca, 0xb2, 0xef, 0xa7, 0x1c]),
+  // sha256("event:VerifyingKeyCreated")[..8]
+  VerifyingKeyCreated: new Uint8Array([0x07, 0xf1, 0xdc, 0x8c, 0x54, 0xe8, 0x09, 0xb9]),
+  // sha256("event:VerifierBindingChecked")[..8]
+  VerifierBindingChecked: new Uint8Array([0xea, 0xe7, 0xfe, 0xaf, 0xc9, 0xeb, 0x63, 0xb0]),
+  // sha256("event:ExcessSwept")[..8]
+  ExcessSwept: new Uint8Array([0xe7, 0xb0, 0xaf, 0x41, 0x92, 0x02, 0xd1, 0x9d]),
+  // sha256("event:DenominationChanged")[..8]
+  DenominationChanged: new Uint8Array([0xc2, 0x4b, 0xa4, 0x00, 0x51, 0xfd, 0x43, 0x03]),
+  // sha256("event:PoolMigrated")[..8]
+  PoolMigrated: new Uint8Array([0xfa, 0xcc, 0x18, 0xc3, 0x25, 0xfd, 0x98, 0x06]),
+} as const;
+
+export type AdminEvent =
+  | { name: "AdminActionQueued"; actionId: bigint; eta: bigint; action: AdminAction }
+  | { name: "AdminActionExecuted"; actionId: bigint; action: AdminAction }
+  | { name: "AdminActionCancelled"; actionId: bigint }
+  | { name: "PauseChanged"; paused: boolean; by: Address }
+  | {
+      name: "ParametersChanged";
+      protocolFeeBps: number;
+      relayerFeeCapBps: number;
+      minWithdrawDelaySecs: bigint;
+    }
+  | { name: "ProtocolFeesCollected"; destination: Address; amount: bigint }
+  | { name: "ExcessSwept"; destination: Address; amount: bigint }
+  | { name: "DenominationChanged"; previous: bigint; denomination: bigint }
+  | { name: "PoolMigrated"; version: number; backend: number; verifier: Address }
+  | { name: "EmergencyDrainQueued"; recovery: Address; eta: bigint }
+  | { name: "EmergencyDrainExecuted"; recovery: Address; amount: bigint }
+  | { name: "EmergencyDrainCancelled"; recovery: Address }
+  | { name: "VerifyingKeyCreated"; vkId: bigint; address: Address }
+  | {
+      name: "VerifierBindingChecked";
+      verifier: Address;
+      expectedHash: Uint8Array;
+      actualHash: Uint8Array;
+      /** True if the hashes differed and the pool was paused. */
+      paused: boolean;
+    };
+
+function bytesEqual(a: Uint8Array, b: Uint8Array): boolean {
+  return a.length === b.length && a.every((v, i) => v === b[i]);
+}
+
+/**
+ * Decode the data of a mixer inner instruction into an admin event. Returns
+ * null for anything else; pool events are handled by `decodeMixerEvent`.
+ */
+export function decodeAdminEvent(data: Uint8Array): AdminEvent | null {
+  if (data.length < 16 || !bytesEqual(data.subarray(0, 8), EVENT_IX_TAG_LE)) {
+    return null;
+  }
+  const discriminator = data.subarray(8, 16);
+  const body = data.subarray(16);
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.PauseChanged) && body.length === 33) {
+    return {
+      name: "PauseChanged",
+      paused: body[0] !== 0,
+      by: getAddressDecoder().decode(body.subarray(1, 33)),
+    };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.ParametersChanged) && body.length === 12) {
+    const view = new DataView(body.buffer, body.byteOffset, body.byteLength);
+    return {
+      name: "ParametersChanged",
+      protocolFeeBps: view.getUint16(0, true),
+      relayerFeeCapBps: view.getUint16(2, true),
+      minWithdrawDelaySecs: view.getBigUint64(4, true),
+    };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.VerifierBindingChecked) && body.length === 97) {
+    return {
+      name: "VerifierBindingChecked",
+      verifier: getAddressDecoder().decode(body.subarray(0, 32)),
+      expectedHash: body.slice(32, 64),
+      actualHash: body.slice(64, 96),
+      paused: body[96] !== 0,
+    };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.DenominationChanged) && body.length === 16) {
+    const view = new DataView(body.buffer, body.byteOffset, body.byteLength);
+    return {
+      name: "DenominationChanged",
+      previous: view.getBigUint64(0, true),
+      denomination: view.getBigUint64(8, true),
+    };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.PoolMigrated) && body.length === 35) {
+    return {
+      name: "PoolMigrated",
+      version: new DataView(body.buffer, body.byteOffset, 2).getUint16(0, true),
+      backend: body[2],
+      verifier: getAddressDecoder().decode(body.subarray(3, 35)),
+    };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.VerifyingKeyCreated) && body.length === 40) {
+    return {
+      name: "VerifyingKeyCreated",
+      vkId: new DataView(body.buffer, body.byteOffset, 8).getBigUint64(0, true),
+      address: getAddressDecoder().decode(body.subarray(8, 40)),
+    };
+  }
+  if (body.length === 32 || body.length === 40) {
+    const key = getAddressDecoder().decode(body.subarray(0, 32));
+    if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.EmergencyDrainCancelled)) {
+      return { name: "EmergencyDrainCancelled", recovery: key };
+    }
+    if (body.length === 40) {
+      const tail = new DataView(body.buffer, body.byteOffset + 32, 8);
+      if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.EmergencyDrainQueued)) {
+        return { name: "EmergencyDrainQueued", recovery: key, eta: tail.getBigInt64(0, true) };
+      }
+      if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.EmergencyDrainExecuted)) {
+        return { name: "EmergencyDrainExecuted", recovery: key, amount: tail.getBigUint64(0, true) };
+      }
+      if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.ProtocolFeesCollected)) {
+        return { name: "ProtocolFeesCollected", destination: key, amount: tail.getBigUint64(0, true) };
+      }
+      if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.ExcessSwept)) {
+        return { name: "ExcessSwept", destination: key, amount: tail.getBigUint64(0, true) };
+      }
+    }
+  }
+  if (body.length < 8) {
+    return null;
+  }
+  const view = new DataView(body.buffer, body.byteOffset, body.byteLength);
+  const actionId = view.getBigUint64(0, true);
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.AdminActionQueued)) {
+    const eta = view.getBigInt64(8, true);
+    return { name: "AdminActionQueued", actionId, eta, action: decodeAdminAction(body.subarray(16)).action };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.AdminActionExecuted)) {
+    return { name: "AdminActionExecuted", actionId, action: decodeAdminAction(body.subarray(8)).action };
+  }
+  if (bytesEqual(discriminator, ADMIN_EVENT_DISCRIMINATORS.AdminActionCancelled)) {
+    return { name: "AdminActionCancelled", actionId };
+  }
+  return null;
+}
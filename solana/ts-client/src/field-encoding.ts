@@ -0,0 +1,66 @@
+// Field element <-> byte encodings shared with the EVM Mixer.sol / circom stack
+//
+// The mixer program treats roots, nullifier hashes and recipient fields as
+// opaque 32-byte values, so the encoding is purely a client convention. The
+// EVM deployment passes every field element as a big-endian `bytes32` /
+// `uint256`, which is also what `PoseidonTree` produces (0x-prefixed hex).
+// Using "evm" byte order for all values means the same note, root and
+// nullifier hash are byte-identical on both deployments; "le" remains for
+// values already written by older clients.
+import { BN254_FIELD_MODULUS } from "./mixer-client.js";
+
+export type FieldByteOrder = "evm" | "le";
+
+export function assertIsFieldElement(value: bigint): void {
+  if (value < 0n || value >= BN254_FIELD_MODULUS) {
+    throw new Error(`value is not a BN254 field element: ${value}`);
+  }
+}
+
+/** 32-byte encoding of a field element. */
+export function fieldToBytes(value: bigint, order: FieldByteOrder = "evm"): Uint8Array {
+  assertIsFieldElement(value);
+  const out = new Uint8Array(32);
+  for (let i = 0; i < 32; i++) {
+    const byte = Number((value >> BigInt(8 * i)) & 0xffn);
+    out[order === "evm" ? 31 - i : i] = byte;
+  }
+  return out;
+}
+
+export function bytesToField(bytes: Uint8Array, order: FieldByteOrder = "evm"): bigint {
+  if (bytes.length !== 32) {
+    throw new Error(`field element must be 32 bytes, got ${bytes.length}`);
+  }
+  let value = 0n;
+  for (let i = 0; i < 32; i++) {
+    value = (value << 8n) | BigInt(bytes[order === "evm" ? i : 31 - i]);
+  }
+  assertIsFieldElement(value);
+  return value;
+}
+
+/** `bytes32` hex as used by Mixer.sol events and calldata. */
+export function fieldToEvmBytes32(value: bigint): string {
+  assertIsFieldElement(value);
+  return "0x" + value.toString(16).padStart(64, "0");
+}
+
+export function evmBytes32ToField(hex: string): bigint {
+  const cleaned = hex.startsWith("0x") ? hex.slice(2) : hex;
+  if (!/^[0-9a-fA-F]{1,64}$/.test(cleaned)) {
+    throw new Error(`invalid bytes32 hex: ${hex}`);
+  }
+  const value = BigInt("0x" + cleaned);
+  assertIsFieldElement(value);
+  return value;
+}
+
+/** Re-encode 32 bytes written in one byte order into the other. */
+export function convertFieldBytes(
+  bytes: Uint8Array,
+  from: FieldByteOrder,
+  to: FieldByteOrder
+): Uint8Array {
+  return fieldToBytes(bytesToField(bytes, from), to);
+}
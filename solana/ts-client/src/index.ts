@@ -15,6 +15,8 @@ import { Connection, PublicKey, SystemProgram, TransactionInstruction } from "@s
  *      - root
  *      - nullifier_hash
  *      - recipient_field
+ *      - circuit_version
+ *      - relayer_fee
  *      - proof
  * 4. Submit a transaction containing the mixer `Withdraw` ix and any
  *    required funding/transfers.
@@ -25,6 +27,8 @@ export interface MixerAddresses {
   verifierProgramId: PublicKey;
   mixerState: PublicKey;
   mixerVault: PublicKey;
+  eventAuthority: PublicKey;
+  poolStats: PublicKey;
 }
 
 export function buildWithdrawInstruction(opts: {
@@ -36,29 +40,44 @@ export function buildWithdrawInstruction(opts: {
   nullifierAccount: PublicKey;
   recipient: PublicKey;
   relayer: PublicKey;
+  /** Defaults to 0, the pool's default verifier. */
+  circuitVersion?: number;
+  /**
+   * Lamports paid to the relayer, at most the pool's relayer fee cap. Defaults
+   * to 0. A nonzero fee needs the recipient's signature.
+   */
+  relayerFee?: bigint;
 }): TransactionInstruction {
   const { addresses, root, nullifierHash, recipientField, proof, nullifierAccount, recipient, relayer } = opts;
+  const circuitVersion = opts.circuitVersion ?? 0;
+  const relayerFee = opts.relayerFee ?? 0n;
 
   if (root.length !== 32 || nullifierHash.length !== 32 || recipientField.length !== 32) {
     throw new Error("root/nullifierHash/recipientField must be 32 bytes each");
   }
 
   // MixerInstruction::Withdraw tag = 2
-  const data = new Uint8Array(1 + 32 + 32 + 32 + proof.length);
+  const data = new Uint8Array(1 + 32 + 32 + 32 + 2 + 8 + proof.length);
   data[0] = 2;
   data.set(root, 1);
   data.set(nullifierHash, 1 + 32);
   data.set(recipientField, 1 + 64);
-  data.set(proof, 1 + 96);
+  const view = new DataView(data.buffer);
+  view.setUint16(1 + 96, circuitVersion, true);
+  view.setBigUint64(1 + 98, relayerFee, true);
+  data.set(proof, 1 + 106);
 
   const keys = [
-    { pubkey: relayer, isSigner: true, isWritable: false },
+    { pubkey: relayer, isSigner: true, isWritable: true },
     { pubkey: addresses.mixerState, isSigner: false, isWritable: true },
     { pubkey: nullifierAccount, isSigner: false, isWritable: true },
     { pubkey: addresses.mixerVault, isSigner: false, isWritable: true },
-    { pubkey: recipient, isSigner: false, isWritable: true },
+    { pubkey: recipient, isSigner: relayerFee > 0n, isWritable: true },
     { pubkey: addresses.verifierProgramId, isSigner: false, isWritable: false },
     { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: addresses.eventAuthority, isSigner: false, isWritable: false },
+    { pubkey: addresses.mixerProgramId, isSigner: false, isWritable: false },
+    { pubkey: addresses.poolStats, isSigner: false, isWritable: true },
   ];
 
   return new TransactionInstruction({
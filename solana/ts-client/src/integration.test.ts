@@ -22,10 +22,6 @@ import {
   generateKeyPairSigner,
   createSolanaRpc,
   createSolanaRpcSubscriptions,
-  createTransactionMessage,
-  appendTransactionMessageInstructions,
-  setTransactionMessageFeePayerSigner,
-  setTransactionMessageLifetimeUsingBlockhash,
   signTransactionMessageWithSigners,
   assertIsSendableTransaction,
   assertIsTransactionWithBlockhashLifetime,
@@ -39,10 +35,7 @@ import {
   type ProgramDerivedAddressBump,
 } from "@solana/kit";
 import { getSetComputeUnitLimitInstruction } from "@solana-program/compute-budget";
-import {
-  getTransferSolInstruction,
-  SYSTEM_PROGRAM_ADDRESS,
-} from "@solana-program/system";
+import { SYSTEM_PROGRAM_ADDRESS } from "@solana-program/system";
 import fs from "fs";
 import path from "path";
 import { buildPoseidon, type Poseidon } from "circomlibjs";
@@ -57,6 +50,12 @@ import {
   buildPushRootInstruction,
   buildWithdrawInstruction,
 } from "./mixer-client.js";
+import {
+  buildMixerTransactionMessage,
+  buildMixerTransactionMessageWithComputeBudget,
+  assertTransactionMessageFitsSize,
+  detectTransactionVersion,
+} from "./transaction.js";
 
 // ============================================================================
 // Configuration
@@ -133,6 +132,27 @@ async function getMixerVaultPda(): Promise<PdaResult> {
   });
 }
 
+async function getEventAuthorityPda(): Promise<PdaResult> {
+  return getProgramDerivedAddress({
+    programAddress: MIXER_PROGRAM_ID,
+    seeds: [textEncoder.encode("__event_authority")],
+  });
+}
+
+async function getLatestRootPda(): Promise<PdaResult> {
+  return getProgramDerivedAddress({
+    programAddress: MIXER_PROGRAM_ID,
+    seeds: [textEncoder.encode("latest_root")],
+  });
+}
+
+async function getPoolStatsPda(): Promise<PdaResult> {
+  return getProgramDerivedAddress({
+    programAddress: MIXER_PROGRAM_ID,
+    seeds: [textEncoder.encode("pool_stats")],
+  });
+}
+
 async function getNullifierPda(nullifierHash: Uint8Array): Promise<PdaResult> {
   return getProgramDerivedAddress({
     programAddress: MIXER_PROGRAM_ID,
@@ -395,6 +415,8 @@ async function main() {
   console.log(`Mixer: ${MIXER_PROGRAM_ID}\n`);
 
   const ctx = createRpcContext(RPC_URL);
+  const txVersion = await detectTransactionVersion(ctx.rpc);
+  console.log(`Transaction version: ${txVersion}\n`);
 
   // Create test keypairs
   const payer = await loadKeypair(
@@ -426,6 +448,9 @@ async function main() {
   // Get PDAs
   const [mixerState] = await getMixerStatePda();
   const [mixerVault] = await getMixerVaultPda();
+  const [eventAuthority] = await getEventAuthorityPda();
+  const [latestRoot] = await getLatestRootPda();
+  const [poolStats] = await getPoolStatsPda();
 
   console.log(`\nMixer State PDA: ${mixerState}`);
   console.log(`Mixer Vault PDA: ${mixerVault}\n`);
@@ -452,23 +477,17 @@ async function main() {
       { address: payer.address, role: 3 }, // signer + writable (payer funds the account)
       { address: mixerState, role: 1 }, // writable (PDA being created)
       { address: SYSTEM_PROGRAM_ADDRESS, role: 0 }, // readonly
+      { address: VERIFIER_PROGRAM_ID, role: 0 }, // readonly, pinned as the pool's verifier
     ],
     data: initData,
   };
 
-  const initMessage = createTransactionMessage({ version: 0 });
-  const initMessageWithPayer = setTransactionMessageFeePayerSigner(
-    payer,
-    initMessage
-  );
-  const initMessageWithLifetime = setTransactionMessageLifetimeUsingBlockhash(
+  const initTransactionMessage = buildMixerTransactionMessage({
+    feePayer: payer,
     latestBlockhash,
-    initMessageWithPayer
-  );
-  const initTransactionMessage = appendTransactionMessageInstructions(
-    [getSetComputeUnitLimitInstruction({ units: 200_000 }), initIx],
-    initMessageWithLifetime
-  );
+    version: txVersion,
+    instructions: [getSetComputeUnitLimitInstruction({ units: 200_000 }), initIx],
+  });
 
   try {
     const signedInit = await signTransactionMessageWithSigners(
@@ -488,7 +507,11 @@ async function main() {
       console.log(`  ℹ️  sendAndConfirm returned different signature: ${initSig}`);
     }
   } catch (err: any) {
-    if (err.message?.includes("already in use") || err.message?.includes("AccountInUse")) {
+    if (
+      err.message?.includes("already in use") ||
+      err.message?.includes("AccountInUse") ||
+      err.message?.includes("already initialized")
+    ) {
       console.log("  ℹ️  Mixer already initialized");
     } else {
       console.error("  ❌ Initialization error:", err.message || err);
@@ -532,30 +555,57 @@ async function main() {
       // Account roles: 3 = signer + writable, 1 = writable, 0 = readonly
       { address: payer.address, role: 3 }, // signer + writable
       { address: mixerState, role: 1 }, // writable
+      { address: eventAuthority, role: 0 },
+      { address: MIXER_PROGRAM_ID, role: 0 },
+      { address: mixerVault, role: 1 }, // writable, receives the deposit
+      { address: latestRoot, role: 1 }, // writable
+      { address: poolStats, role: 1 }, // writable
+      { address: SYSTEM_PROGRAM_ADDRESS, role: 0 },
     ],
     data: pushRootData,
   };
 
-  // Also transfer funds to vault
-  const transferIx = getTransferSolInstruction({
-    source: payer.address,
-    destination: mixerVault,
-    amount: DENOMINATION,
-  });
+  // Pools need their latest root account before the first push
+  const { value: latestRootAccount } = await ctx.rpc
+    .getAccountInfo(latestRoot, { encoding: "base64" })
+    .send();
+  const createLatestRootIx = {
+    programAddress: MIXER_PROGRAM_ID,
+    accounts: [
+      { address: payer.address, role: 3 }, // signer + writable (pays the rent)
+      { address: mixerState, role: 0 },
+      { address: latestRoot, role: 1 },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: 0 },
+    ],
+    data: new Uint8Array([26]), // CreateLatestRoot instruction
+  };
 
-  const pushRootMessage = createTransactionMessage({ version: 0 });
-  const pushRootMessageWithPayer = setTransactionMessageFeePayerSigner(
-    payer,
-    pushRootMessage
-  );
-  const pushRootMessageWithLifetime = setTransactionMessageLifetimeUsingBlockhash(
-    latestBlockhash2,
-    pushRootMessageWithPayer
-  );
-  const pushRootTransactionMessage = appendTransactionMessageInstructions(
-    [getSetComputeUnitLimitInstruction({ units: 200_000 }), pushRootIx, transferIx],
-    pushRootMessageWithLifetime
-  );
+  // ...and their pool stats account
+  const { value: poolStatsAccount } = await ctx.rpc
+    .getAccountInfo(poolStats, { encoding: "base64" })
+    .send();
+  const createPoolStatsIx = {
+    programAddress: MIXER_PROGRAM_ID,
+    accounts: [
+      { address: payer.address, role: 3 }, // signer + writable (pays the rent)
+      { address: mixerState, role: 0 },
+      { address: poolStats, role: 1 },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: 0 },
+    ],
+    data: new Uint8Array([27]), // CreatePoolStats instruction
+  };
+
+  const pushRootTransactionMessage = buildMixerTransactionMessage({
+    feePayer: payer,
+    latestBlockhash: latestBlockhash2,
+    version: txVersion,
+    instructions: [
+      getSetComputeUnitLimitInstruction({ units: 200_000 }),
+      ...(latestRootAccount === null ? [createLatestRootIx] : []),
+      ...(poolStatsAccount === null ? [createPoolStatsIx] : []),
+      pushRootIx,
+    ],
+  });
 
   const signedPushRoot = await signTransactionMessageWithSigners(
     pushRootTransactionMessage
@@ -659,23 +709,20 @@ async function main() {
       { address: recipient.address, role: 1 },
       { address: VERIFIER_PROGRAM_ID, role: 0 },
       { address: SYSTEM_PROGRAM_ADDRESS, role: 0 },
+      { address: eventAuthority, role: 0 },
+      { address: MIXER_PROGRAM_ID, role: 0 },
+      { address: poolStats, role: 1 },
     ],
     data: withdrawData,
   };
 
-  const withdrawMessage = createTransactionMessage({ version: 0 });
-  const withdrawMessageWithPayer = setTransactionMessageFeePayerSigner(
-    payer,
-    withdrawMessage
-  );
-  const withdrawMessageWithLifetime = setTransactionMessageLifetimeUsingBlockhash(
-    latestBlockhash3,
-    withdrawMessageWithPayer
-  );
-  const withdrawTransactionMessage = appendTransactionMessageInstructions(
-    [getSetComputeUnitLimitInstruction({ units: 1_000_000 }), withdrawIx],
-    withdrawMessageWithLifetime
-  );
+  // Size the compute limit from simulation instead of a fixed guess.
+  const withdrawTransactionMessage = await buildMixerTransactionMessageWithComputeBudget(ctx.rpc, {
+    feePayer: payer,
+    latestBlockhash: latestBlockhash3,
+    version: txVersion,
+    instructions: [withdrawIx],
+  });
 
   const recipientBalanceBefore = await getBalance(ctx, recipient.address);
 
@@ -727,23 +774,20 @@ async function main() {
       { address: recipient.address, role: 1 },
       { address: VERIFIER_PROGRAM_ID, role: 0 },
       { address: SYSTEM_PROGRAM_ADDRESS, role: 0 },
+      { address: eventAuthority, role: 0 },
+      { address: MIXER_PROGRAM_ID, role: 0 },
+      { address: poolStats, role: 1 },
     ],
     data: withdrawData,
   };
 
-  const withdrawMessage2 = createTransactionMessage({ version: 0 });
-  const withdrawMessageWithPayer2 = setTransactionMessageFeePayerSigner(
-    payer,
-    withdrawMessage2
-  );
-  const withdrawMessageWithLifetime2 = setTransactionMessageLifetimeUsingBlockhash(
-    latestBlockhash4,
-    withdrawMessageWithPayer2
-  );
-  const withdrawTransactionMessage2 = appendTransactionMessageInstructions(
-    [getSetComputeUnitLimitInstruction({ units: 1_000_000 }), withdrawIx2],
-    withdrawMessageWithLifetime2
-  );
+  const withdrawTransactionMessage2 = buildMixerTransactionMessage({
+    feePayer: payer,
+    latestBlockhash: latestBlockhash4,
+    version: txVersion,
+    instructions: [getSetComputeUnitLimitInstruction({ units: 1_000_000 }), withdrawIx2],
+  });
+  assertTransactionMessageFitsSize(withdrawTransactionMessage2);
 
   try {
     const signedWithdraw2 = await signTransactionMessageWithSigners(
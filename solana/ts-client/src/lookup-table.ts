@@ -0,0 +1,190 @@
+// Address lookup table helpers for mixer transactions
+//
+// Withdrawals reference the same static accounts every time (mixer program,
+// verifier, state, vault, event authority, system program, pool stats). Putting those in a
+// lookup table lets v0 transactions reference each with a 1-byte index instead
+// of a 32-byte key, which leaves room for larger proof payloads.
+//
+// Typical relayer setup:
+//   1. `buildCreateMixerLookupTableInstructions` once, send, wait one slot.
+//   2. `fetchAddressesByLookupTable` at startup.
+//   3. Pass the result to `compressTransactionMessageUsingAddressLookupTables`
+//      from @solana/kit when building each v0 withdraw message.
+import {
+  address,
+  AccountRole,
+  getAddressDecoder,
+  getAddressEncoder,
+  getBase64Encoder,
+  getProgramDerivedAddress,
+  type Address,
+  type AddressesByLookupTableAddress,
+  type GetMultipleAccountsApi,
+  type Instruction,
+  type ProgramDerivedAddressBump,
+  type Rpc,
+} from "@solana/kit";
+import { SYSTEM_PROGRAM_ADDRESS } from "@solana-program/system";
+import type { MixerAddresses } from "./mixer-client.js";
+
+export const ADDRESS_LOOKUP_TABLE_PROGRAM_ADDRESS = address(
+  "AddressLookupTab1e1111111111111111111111111"
+);
+
+// Size of the lookup table metadata header preceding the address list.
+const LOOKUP_TABLE_META_SIZE = 56;
+
+/** Accounts shared by every mixer withdraw, in a stable order. */
+export function getMixerLookupTableAddresses(addresses: MixerAddresses): Address[] {
+  return [
+    addresses.mixerProgramId,
+    addresses.verifierProgramId,
+    addresses.mixerState,
+    addresses.mixerVault,
+    addresses.eventAuthority,
+    SYSTEM_PROGRAM_ADDRESS,
+    addresses.poolStats,
+  ];
+}
+
+export async function findLookupTableAddress(
+  authority: Address,
+  recentSlot: bigint
+): Promise<ProgramDerivedAddressBump> {
+  const slotBytes = new Uint8Array(8);
+  new DataView(slotBytes.buffer).setBigUint64(0, recentSlot, true);
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: ADDRESS_LOOKUP_TABLE_PROGRAM_ADDRESS,
+    seeds: [getAddressEncoder().encode(authority), slotBytes],
+  });
+  return [pda, bump];
+}
+
+export function buildCreateLookupTableInstruction(
+  lookupTable: Address,
+  authority: Address,
+  payer: Address,
+  recentSlot: bigint,
+  bump: number
+): Instruction {
+  // bincode: u32 variant (0 = CreateLookupTable) || recent_slot: u64 || bump_seed: u8
+  const data = new Uint8Array(4 + 8 + 1);
+  const view = new DataView(data.buffer);
+  view.setUint32(0, 0, true);
+  view.setBigUint64(4, recentSlot, true);
+  data[12] = bump;
+
+  return {
+    programAddress: ADDRESS_LOOKUP_TABLE_PROGRAM_ADDRESS,
+    accounts: [
+      { address: lookupTable, role: AccountRole.WRITABLE },
+      { address: authority, role: AccountRole.READONLY_SIGNER },
+      { address: payer, role: AccountRole.WRITABLE_SIGNER },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+export function buildExtendLookupTableInstruction(
+  lookupTable: Address,
+  authority: Address,
+  payer: Address,
+  newAddresses: Address[]
+): Instruction {
+  // bincode: u32 variant (2 = ExtendLookupTable) || u64 length || addresses
+  const data = new Uint8Array(4 + 8 + 32 * newAddresses.length);
+  const view = new DataView(data.buffer);
+  view.setUint32(0, 2, true);
+  view.setBigUint64(4, BigInt(newAddresses.length), true);
+  const encoder = getAddressEncoder();
+  newAddresses.forEach((addr, i) => data.set(encoder.encode(addr), 12 + 32 * i));
+
+  return {
+    programAddress: ADDRESS_LOOKUP_TABLE_PROGRAM_ADDRESS,
+    accounts: [
+      { address: lookupTable, role: AccountRole.WRITABLE },
+      { address: authority, role: AccountRole.READONLY_SIGNER },
+      { address: payer, role: AccountRole.WRITABLE_SIGNER },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+/**
+ * Instructions creating a lookup table that holds the static mixer accounts.
+ * `recentSlot` must be a recent slot (e.g. from `getSlot` at "finalized"), and
+ * the table is only usable from the slot after the extend lands.
+ */
+export async function buildCreateMixerLookupTableInstructions(
+  addresses: MixerAddresses,
+  authority: Address,
+  payer: Address,
+  recentSlot: bigint
+): Promise<{ lookupTable: Address; instructions: Instruction[] }> {
+  const [lookupTable, bump] = await findLookupTableAddress(authority, recentSlot);
+  return {
+    lookupTable,
+    instructions: [
+      buildCreateLookupTableInstruction(lookupTable, authority, payer, recentSlot, bump),
+      buildExtendLookupTableInstruction(
+        lookupTable,
+        authority,
+        payer,
+        getMixerLookupTableAddresses(addresses)
+      ),
+    ],
+  };
+}
+
+/**
+ * Instruction adding any of `extra` not already present in the table, or null
+ * if there is nothing to add. Use when new static accounts (fee receivers,
+ * treasuries) are introduced.
+ */
+export function buildExtendMixerLookupTableInstruction(
+  lookupTable: Address,
+  existing: Address[],
+  extra: Address[],
+  authority: Address,
+  payer: Address
+): Instruction | null {
+  const known = new Set<string>(existing);
+  const missing = extra.filter((addr) => !known.has(addr));
+  if (missing.length === 0) {
+    return null;
+  }
+  return buildExtendLookupTableInstruction(lookupTable, authority, payer, missing);
+}
+
+export function decodeLookupTableAddresses(data: Uint8Array): Address[] {
+  if (data.length < LOOKUP_TABLE_META_SIZE || (data.length - LOOKUP_TABLE_META_SIZE) % 32 !== 0) {
+    throw new Error(`invalid lookup table account data length: ${data.length}`);
+  }
+  const decoder = getAddressDecoder();
+  const addresses: Address[] = [];
+  for (let start = LOOKUP_TABLE_META_SIZE; start < data.length; start += 32) {
+    addresses.push(decoder.decode(data.subarray(start, start + 32)));
+  }
+  return addresses;
+}
+
+/** Fetch lookup tables in the shape expected by `compressTransactionMessageUsingAddressLookupTables`. */
+export async function fetchAddressesByLookupTable(
+  rpc: Rpc<GetMultipleAccountsApi>,
+  lookupTables: Address[]
+): Promise<AddressesByLookupTableAddress> {
+  const { value } = await rpc
+    .getMultipleAccounts(lookupTables, { encoding: "base64" })
+    .send();
+  const result: AddressesByLookupTableAddress = {};
+  value.forEach((account, i) => {
+    if (!account) {
+      throw new Error(`lookup table not found: ${lookupTables[i]}`);
+    }
+    const data = new Uint8Array(getBase64Encoder().encode(account.data[0]));
+    result[lookupTables[i]] = decodeLookupTableAddresses(data);
+  });
+  return result;
+}
@@ -168,6 +168,28 @@ export class PoseidonTree {
   }
 }
 
+/**
+ * Empty-subtree values for `levels` levels: `ZERO_VALUES`, continued with
+ * `H(zero, zero)` for trees deeper than 20 (as in `tools/mixer-vectors`).
+ */
+function zeroValues(levels: number): string[] {
+  const zeros = ZERO_VALUES.slice(0, levels);
+  while (zeros.length < levels) {
+    const last = hexToBigint(zeros[zeros.length - 1]);
+    zeros.push(bigintToHex(poseidonHash2(last, last)));
+  }
+  return zeros;
+}
+
+/** Poseidon must be initialized first for trees deeper than 20. */
 export function createPoseidonTree(levels: number = 20): PoseidonTree {
-  return new PoseidonTree(levels, ZERO_VALUES);
+  return new PoseidonTree(levels, zeroValues(levels));
+}
+
+/**
+ * Tree matching a pool's circuit, with the depth read from its state
+ * (`decodeMixerState(...).treeDepth`).
+ */
+export function createPoseidonTreeForPool(state: { treeDepth: number }): PoseidonTree {
+  return createPoseidonTree(state.treeDepth);
 }
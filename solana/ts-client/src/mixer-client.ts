@@ -1,19 +1,123 @@
 // Mixer program client utilities
 import {
   address,
+  AccountRole,
+  getBase64Decoder,
+  getAddressDecoder,
+  getAddressEncoder,
+  getBase64Encoder,
   getProgramDerivedAddress,
   type Address,
+  type Base64EncodedBytes,
+  type GetMultipleAccountsApi,
+  type GetProgramAccountsApi,
+  type GetProgramAccountsDatasizeFilter,
+  type GetProgramAccountsMemcmpFilter,
+  type Instruction,
   type ProgramDerivedAddressBump,
+  type Rpc,
 } from "@solana/kit";
 import { SYSTEM_PROGRAM_ADDRESS } from "@solana-program/system";
 
 const textEncoder = new TextEncoder();
 
+// Mixer state account layout (must match `MixerState` in programs/mixer/src/lib.rs):
+//   denomination: u64 LE | roots: [[u8; 32]; 30] | current_root_index: u8 | flags: u8
+//   | authority: [u8; 32] | verifier_program: [u8; 32] | admin_delay: u64 LE
+//   | guardian: [u8; 32] | paused: u8 | protocol_fee_bps: u16 LE
+//   | relayer_fee_cap_bps: u16 LE | min_withdraw_delay: u64 LE
+//   | protocol_fees: u64 LE | root_times: [i64 LE; 30] | verifier_backend: u8
+//   | circuit_versions: [CircuitVersion; 4] | verifier_hash: [u8; 32] | curve: u8
+//   | tree_depth: u8 | leaf_count: u64 LE | max_deposits_per_epoch: u32 LE
+//   | max_deposits_per_depositor: u32 LE | deposit_epoch: u64 LE
+//   | epoch_deposits: u32 LE | max_vault_lamports: u64 LE | deposit_count: u64 LE
+//   | withdraw_count: u64 LE | receipt_tree: [u8; 32] | migration_version: u16 LE
+//   | migration_end_slot: u64 LE | tree_id: u8 | root_slots: [u64 LE; 30]
+// where CircuitVersion is version: u16 LE | backend: u8 | verifier: [u8; 32]
+//   | activation_slot: u64 LE | deprecation_slot: u64 LE
+export const MIXER_ROOT_HISTORY_SIZE = 30;
+export const MIXER_STATE_OFFSETS = {
+  denomination: 0,
+  roots: 8,
+  currentRootIndex: 8 + 32 * MIXER_ROOT_HISTORY_SIZE,
+  flags: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 1,
+  authority: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 2,
+  verifierProgram: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 34,
+  adminDelay: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 66,
+  guardian: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 74,
+  paused: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 106,
+  protocolFeeBps: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 107,
+  relayerFeeCapBps: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 109,
+  minWithdrawDelay: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 111,
+  protocolFees: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 119,
+  rootTimes: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 127,
+  verifierBackend: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 127 + 8 * MIXER_ROOT_HISTORY_SIZE,
+  circuitVersions: 8 + 32 * MIXER_ROOT_HISTORY_SIZE + 128 + 8 * MIXER_ROOT_HISTORY_SIZE,
+} as const;
+export const MAX_CIRCUIT_VERSIONS = 4;
+const CIRCUIT_VERSION_LEN = 51;
+const VERIFIER_HASH_OFFSET =
+  MIXER_STATE_OFFSETS.circuitVersions + CIRCUIT_VERSION_LEN * MAX_CIRCUIT_VERSIONS;
+const CURVE_OFFSET = VERIFIER_HASH_OFFSET + 32;
+const TREE_DEPTH_OFFSET = CURVE_OFFSET + 1;
+const LEAF_COUNT_OFFSET = TREE_DEPTH_OFFSET + 1;
+const MAX_DEPOSITS_PER_EPOCH_OFFSET = LEAF_COUNT_OFFSET + 8;
+const MAX_DEPOSITS_PER_DEPOSITOR_OFFSET = MAX_DEPOSITS_PER_EPOCH_OFFSET + 4;
+const DEPOSIT_EPOCH_OFFSET = MAX_DEPOSITS_PER_DEPOSITOR_OFFSET + 4;
+const EPOCH_DEPOSITS_OFFSET = DEPOSIT_EPOCH_OFFSET + 8;
+const MAX_VAULT_LAMPORTS_OFFSET = EPOCH_DEPOSITS_OFFSET + 4;
+const DEPOSIT_COUNT_OFFSET = MAX_VAULT_LAMPORTS_OFFSET + 8;
+const WITHDRAW_COUNT_OFFSET = DEPOSIT_COUNT_OFFSET + 8;
+const RECEIPT_TREE_OFFSET = WITHDRAW_COUNT_OFFSET + 8;
+const MIGRATION_VERSION_OFFSET = RECEIPT_TREE_OFFSET + 32;
+const MIGRATION_END_SLOT_OFFSET = MIGRATION_VERSION_OFFSET + 2;
+const TREE_ID_OFFSET = MIGRATION_END_SLOT_OFFSET + 8;
+const ROOT_SLOTS_OFFSET = TREE_ID_OFFSET + 1;
+export const MIXER_STATE_LEN = ROOT_SLOTS_OFFSET + 8 * MIXER_ROOT_HISTORY_SIZE;
+/** State length of pools created before the state grew; see `buildResizeStateInstruction`. */
+export const MIXER_STATE_BASELINE_LEN = MIXER_STATE_OFFSETS.currentRootIndex + 1;
+
+/** Tree depth of pools initialized without one (`MixerState::DEFAULT_TREE_DEPTH`). */
+export const DEFAULT_TREE_DEPTH = 20;
+/** Deepest tree a pool can be initialized with (`MixerState::MAX_TREE_DEPTH`). */
+export const MAX_TREE_DEPTH = 32;
+
+/** Proof verification backends (`VerifierBackend` in programs/mixer/src/verifier.rs). */
+export const VERIFIER_BACKEND = {
+  /** CPI into a Sunspot-generated verifier program. */
+  sunspotCpi: 0,
+  /** In-program Groth16 check against a stored verifying key. */
+  nativeGroth16: 1,
+  /**
+   * CPI into an UltraHonk verifier program. Proofs must be withdrawn from a
+   * proof buffer, and verification needs close to the full compute budget.
+   */
+  ultraHonkCpi: 2,
+} as const;
+
+/**
+ * Curves a pool's circuit can use (`Curve` in programs/mixer/src/curve.rs),
+ * fixed at initialization. The native Groth16 backend is BN254-only.
+ */
+export const MIXER_CURVE = {
+  bn254: 0,
+  bls12_381: 1,
+} as const;
+
+/** Pool flags set at initialization (`MixerState::FLAG_*`). */
+export const MIXER_FLAGS = {
+  /** Permit withdrawals to off-curve (PDA) recipients. */
+  allowOffCurveRecipients: 1 << 0,
+} as const;
+
 export interface MixerAddresses {
   mixerProgramId: Address;
   verifierProgramId: Address;
   mixerState: Address;
   mixerVault: Address;
+  eventAuthority: Address;
+  latestRoot: Address;
+  poolStats: Address;
 }
 
 export async function getMixerStatePda(
@@ -47,69 +151,588 @@ export async function getNullifierPda(
   return [pda, bump];
 }
 
+export async function getProofBufferPda(
+  mixerProgramId: Address,
+  authority: Address,
+  bufferId: bigint
+): Promise<ProgramDerivedAddressBump> {
+  const bufferIdBytes = new Uint8Array(8);
+  new DataView(bufferIdBytes.buffer).setBigUint64(0, bufferId, true);
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [
+      textEncoder.encode("proof_buffer"),
+      getAddressEncoder().encode(authority),
+      bufferIdBytes,
+    ],
+  });
+  return [pda, bump];
+}
+
+export async function getScheduledWithdrawPda(
+  mixerProgramId: Address,
+  proofBuffer: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("scheduled_withdraw"), getAddressEncoder().encode(proofBuffer)],
+  });
+  return [pda, bump];
+}
+
+export async function getStakeAccountPda(
+  mixerProgramId: Address,
+  nullifierHash: Uint8Array
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("stake_account"), nullifierHash],
+  });
+  return [pda, bump];
+}
+
+export async function getStakeAuthorityPda(
+  mixerProgramId: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("stake_authority")],
+  });
+  return [pda, bump];
+}
+
+/** Per-depositor deposit count, needed by `PushRoot` when the pool limits deposits per depositor. */
+export async function getDepositCounterPda(
+  mixerProgramId: Address,
+  depositor: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("deposit_counter"), getAddressEncoder().encode(depositor)],
+  });
+  return [pda, bump];
+}
+
+export async function getEventAuthorityPda(
+  mixerProgramId: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("__event_authority")],
+  });
+  return [pda, bump];
+}
+
+export async function getLatestRootPda(
+  mixerProgramId: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("latest_root")],
+  });
+  return [pda, bump];
+}
+
+export async function getPoolStatsPda(
+  mixerProgramId: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("pool_stats")],
+  });
+  return [pda, bump];
+}
+
+/** Archive of the pool's tree `treeId`, once deposits have moved past it. */
+export async function getCommitmentTreePda(
+  mixerProgramId: Address,
+  treeId: number
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("commitment_tree"), new Uint8Array([treeId])],
+  });
+  return [pda, bump];
+}
+
+/** Withdraw receipt of the note with `nullifierHash`; see `withWithdrawReceipt`. */
+export async function getWithdrawReceiptPda(
+  mixerProgramId: Address,
+  nullifierHash: Uint8Array
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("withdraw_receipt"), nullifierHash],
+  });
+  return [pda, bump];
+}
+
 export async function getMixerAddresses(
   mixerProgramId: Address,
   verifierProgramId: Address
 ): Promise<MixerAddresses> {
   const [mixerState] = await getMixerStatePda(mixerProgramId);
   const [mixerVault] = await getMixerVaultPda(mixerProgramId);
+  const [eventAuthority] = await getEventAuthorityPda(mixerProgramId);
+  const [latestRoot] = await getLatestRootPda(mixerProgramId);
+  const [poolStats] = await getPoolStatsPda(mixerProgramId);
   return {
     mixerProgramId,
     verifierProgramId,
     mixerState,
     mixerVault,
+    eventAuthority,
+    latestRoot,
+    poolStats,
   };
 }
 
+/** BN254 scalar field modulus; public circuit inputs must be below it. */
+export const BN254_FIELD_MODULUS =
+  21888242871839275222246405745257275088548364400416034343698204186575808495617n;
+
+/** BLS12-381 scalar field modulus, for pools over that curve. */
+export const BLS12_381_FIELD_MODULUS =
+  52435875175126190479447740508185965837690552500527637822603658699938581184513n;
+
+/** Scalar field modulus of a `MIXER_CURVE`; public inputs must be below it. */
+export function scalarFieldModulus(curve: number): bigint {
+  switch (curve) {
+    case MIXER_CURVE.bn254:
+      return BN254_FIELD_MODULUS;
+    case MIXER_CURVE.bls12_381:
+      return BLS12_381_FIELD_MODULUS;
+    default:
+      throw new Error(`unknown curve: ${curve}`);
+  }
+}
+
+/** BN254 base field modulus; proof point coordinates are below it. */
+export const BN254_BASE_FIELD_MODULUS =
+  21888242871839275222246405745257275088696311157297823662689037894645226208583n;
+
+export const GROTH16_PROOF_LEN = 256;
+export const GROTH16_COMPRESSED_PROOF_LEN = 128;
+
+function readBigUint(bytes: Uint8Array): bigint {
+  let value = 0n;
+  for (const byte of bytes) value = (value << 8n) | BigInt(byte);
+  return value;
+}
+
+/** Whether `y` is the larger of `y` and `-y`, the sign the compressed encoding records. */
+function isLargerRoot(y: bigint): boolean {
+  return y > BN254_BASE_FIELD_MODULUS - y;
+}
+
+/**
+ * Compress an uncompressed native Groth16 proof (`a || b || c`, EIP-197
+ * encoding) to the 128 bytes the native backend also accepts. Each point
+ * keeps its x coordinate; bit 7 of the first byte marks the larger y root,
+ * as the alt_bn128 decompression syscalls expect. G2 roots are compared by
+ * `y1` unless it is zero.
+ */
+export function compressGroth16Proof(proof: Uint8Array): Uint8Array {
+  if (proof.length !== GROTH16_PROOF_LEN) {
+    throw new Error(`Groth16 proof must be ${GROTH16_PROOF_LEN} bytes`);
+  }
+  const out = new Uint8Array(GROTH16_COMPRESSED_PROOF_LEN);
+  const g1 = (point: Uint8Array, at: number) => {
+    out.set(point.subarray(0, 32), at);
+    if (isLargerRoot(readBigUint(point.subarray(32, 64)))) out[at] |= 0x80;
+  };
+  g1(proof.subarray(0, 64), 0);
+  const b = proof.subarray(64, 192);
+  out.set(b.subarray(0, 64), 32);
+  const y1 = readBigUint(b.subarray(64, 96));
+  const y0 = readBigUint(b.subarray(96, 128));
+  if (y1 !== 0n ? isLargerRoot(y1) : isLargerRoot(y0)) out[32] |= 0x80;
+  g1(proof.subarray(192, 256), 96);
+  return out;
+}
+
+/**
+ * Encode a recipient address as the circuit's `recipient` public input
+ * (32 bytes, little-endian). The key bytes are read as a little-endian
+ * integer and reduced into the field, so on-curve wallets and off-curve PDAs
+ * encode the same way; whether an off-curve recipient is accepted is decided
+ * by the pool's `allowOffCurveRecipients` flag on-chain.
+ */
+export function encodeRecipientField(recipient: Address): Uint8Array {
+  const bytes = getAddressEncoder().encode(recipient);
+  let value = 0n;
+  for (let i = 31; i >= 0; i--) {
+    value = (value << 8n) | BigInt(bytes[i]);
+  }
+  value %= BN254_FIELD_MODULUS;
+  const out = new Uint8Array(32);
+  for (let i = 0; i < 32; i++) {
+    out[i] = Number((value >> BigInt(8 * i)) & 0xffn);
+  }
+  return out;
+}
+
 export function buildInitializeInstruction(
   addresses: MixerAddresses,
   denomination: bigint,
-  payer: Address
+  payer: Address,
+  flags: number = 0,
+  adminDelaySecs: bigint = 0n,
+  curve: number = MIXER_CURVE.bn254,
+  treeDepth: number = DEFAULT_TREE_DEPTH
 ): {
   programAddress: Address;
   accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
   data: Uint8Array;
 } {
-  const data = new Uint8Array(9);
+  if (!Number.isInteger(treeDepth) || treeDepth < 1 || treeDepth > MAX_TREE_DEPTH) {
+    throw new Error(`tree depth must be between 1 and ${MAX_TREE_DEPTH}: ${treeDepth}`);
+  }
+  const data = new Uint8Array(20);
   data[0] = 0; // Initialize instruction
-  const denominationBytes = new Uint8Array(8);
-  new DataView(denominationBytes.buffer).setBigUint64(0, denomination, true);
-  data.set(denominationBytes, 1);
+  const view = new DataView(data.buffer);
+  view.setBigUint64(1, denomination, true);
+  data[9] = flags;
+  view.setBigUint64(10, adminDelaySecs, true);
+  data[18] = curve;
+  data[19] = treeDepth;
 
+  // The payer becomes the pool authority and the verifier is pinned.
   return {
     programAddress: addresses.mixerProgramId,
     accounts: [
       { address: payer, role: "signer" },
       { address: addresses.mixerState, role: "writable" },
       { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+      { address: addresses.verifierProgramId, role: "readonly" },
     ],
     data,
   };
 }
 
+export const BUBBLEGUM_PROGRAM_ADDRESS = address("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+export const NOOP_PROGRAM_ADDRESS = address("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+export const ACCOUNT_COMPRESSION_PROGRAM_ADDRESS = address(
+  "cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK"
+);
+
+/**
+ * A compressed NFT minted by `PushRoot` to `owner` as the receipt of a
+ * deposit, holding the pool and leaf index. The receipt links the deposit
+ * to `owner` in public; only ask for one when that is the point.
+ */
+export interface DepositReceipt {
+  owner: Address;
+  /** The pool's `receiptTree`. */
+  tree: Address;
+  treeConfig: Address;
+  receiptAuthority: Address;
+}
+
+export async function getReceiptAuthorityPda(
+  mixerProgramId: Address
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("receipt_authority")],
+  });
+  return [pda, bump];
+}
+
+export async function getDepositReceipt(
+  addresses: MixerAddresses,
+  tree: Address,
+  owner: Address
+): Promise<DepositReceipt> {
+  const [treeConfig] = await getProgramDerivedAddress({
+    programAddress: BUBBLEGUM_PROGRAM_ADDRESS,
+    seeds: [getAddressEncoder().encode(tree)],
+  });
+  const [receiptAuthority] = await getReceiptAuthorityPda(addresses.mixerProgramId);
+  return { owner, tree, treeConfig, receiptAuthority };
+}
+
+/** `PushRoot` accounts after the deposit counter, with whether each is writable. */
+function receiptAccounts(receipt: DepositReceipt): Array<[Address, boolean]> {
+  return [
+    [receipt.owner, false],
+    [receipt.receiptAuthority, false],
+    [receipt.treeConfig, true],
+    [receipt.tree, true],
+    [NOOP_PROGRAM_ADDRESS, false],
+    [ACCOUNT_COMPRESSION_PROGRAM_ADDRESS, false],
+    [BUBBLEGUM_PROGRAM_ADDRESS, false],
+    [SYSTEM_PROGRAM_ADDRESS, false],
+  ];
+}
+
+/**
+ * `PushRoot` data, with the number of leaves under `root` if known and the
+ * receipt flag, which needs it.
+ */
+function encodePushRoot(root: Uint8Array, leafCount?: bigint, receipt = false): Uint8Array {
+  if (receipt && leafCount === undefined) {
+    throw new Error("a deposit receipt needs the leaf count");
+  }
+  const data = new Uint8Array(leafCount === undefined ? 33 : receipt ? 42 : 41);
+  data[0] = 1; // PushRoot instruction
+  data.set(root, 1);
+  if (leafCount !== undefined) {
+    new DataView(data.buffer).setBigUint64(33, leafCount, true);
+  }
+  if (receipt) {
+    data[41] = 1;
+  }
+  return data;
+}
+
+/**
+ * `PushRoot`, which moves one denomination from `authority` into the vault and
+ * records `root`.
+ *
+ * `leafCount` is the number of leaves in the tree under `root`. The program
+ * counts one leaf per push, and rejects a `leafCount` that disagrees.
+ *
+ * Pools with `maxDepositsPerDepositor` set also need `depositCounter`, the
+ * authority's `getDepositCounterPda`; the authority then pays its rent on
+ * its first deposit.
+ *
+ * `receipt`, from `getDepositReceipt`, mints a deposit receipt into the
+ * pool's receipt tree, paid by the authority; it needs `leafCount`.
+ *
+ * When the pool's current tree is full, `rotation` is its
+ * `getCommitmentTreePda`: the push archives the tree there, paid by the
+ * authority, and starts the next one, so `leafCount` counts from 0 again.
+ */
 export function buildPushRootInstruction(
   addresses: MixerAddresses,
   root: Uint8Array,
-  authority: Address
+  authority: Address,
+  leafCount?: bigint,
+  depositCounter?: Address,
+  receipt?: DepositReceipt,
+  rotation?: Address
 ): {
   programAddress: Address;
   accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
   data: Uint8Array;
 } {
-  const data = new Uint8Array(33);
-  data[0] = 1; // PushRoot instruction
-  data.set(root, 1);
+  const data = encodePushRoot(root, leafCount, receipt !== undefined);
+  const accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }> = [
+    { address: authority, role: "signer" },
+    { address: addresses.mixerState, role: "writable" },
+    { address: addresses.eventAuthority, role: "readonly" },
+    { address: addresses.mixerProgramId, role: "readonly" },
+    { address: addresses.mixerVault, role: "writable" },
+    { address: addresses.latestRoot, role: "writable" },
+    { address: addresses.poolStats, role: "writable" },
+    { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+  ];
+  if (depositCounter !== undefined) {
+    accounts.push(
+      { address: depositCounter, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" }
+    );
+  }
+  if (rotation !== undefined) {
+    accounts.push(
+      { address: rotation, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" }
+    );
+  }
+  if (receipt !== undefined) {
+    for (const [key, writable] of receiptAccounts(receipt)) {
+      accounts.push({ address: key, role: writable ? "writable" : "readonly" });
+    }
+  }
+
+  return { programAddress: addresses.mixerProgramId, accounts, data };
+}
 
+/**
+ * `CreateLatestRoot`, needed once per pool before its first `PushRoot`.
+ * Anyone may send it; `payer` pays the account's rent.
+ */
+export function buildCreateLatestRootInstruction(
+  addresses: MixerAddresses,
+  payer: Address
+): Instruction {
   return {
     programAddress: addresses.mixerProgramId,
     accounts: [
-      { address: authority, role: "signer" },
-      { address: addresses.mixerState, role: "writable" },
+      { address: payer, role: AccountRole.WRITABLE_SIGNER },
+      { address: addresses.mixerState, role: AccountRole.READONLY },
+      { address: addresses.latestRoot, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+    ],
+    data: new Uint8Array([26]), // CreateLatestRoot instruction
+  };
+}
+
+/**
+ * `CreatePoolStats`, needed once per pool before its first `PushRoot` or
+ * withdrawal. Anyone may send it; `payer` pays the account's rent.
+ */
+export function buildCreatePoolStatsInstruction(
+  addresses: MixerAddresses,
+  payer: Address
+): Instruction {
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: payer, role: AccountRole.WRITABLE_SIGNER },
+      { address: addresses.mixerState, role: AccountRole.READONLY },
+      { address: addresses.poolStats, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+    ],
+    data: new Uint8Array([27]), // CreatePoolStats instruction
+  };
+}
+
+/**
+ * `ResizeState`, which grows the state of a pool created before the state
+ * reached `MIXER_STATE_LEN` so the program accepts it again. Only the mixer
+ * program's `upgradeAuthority` may send it; `payer` tops up the rent. A pool
+ * that predates them takes `authority` and `verifierProgram`.
+ *
+ * `programData` is the mixer's program data account, the PDA of
+ * `[mixerProgramId]` under the upgradeable BPF loader.
+ */
+export function buildResizeStateInstruction(
+  addresses: MixerAddresses,
+  payer: Address,
+  upgradeAuthority: Address,
+  programData: Address,
+  authority: Address,
+  verifierProgram: Address
+): Instruction {
+  const encoder = getAddressEncoder();
+  const data = new Uint8Array(65);
+  data[0] = 31; // ResizeState instruction
+  data.set(encoder.encode(authority), 1);
+  data.set(encoder.encode(verifierProgram), 33);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: payer, role: AccountRole.WRITABLE_SIGNER },
+      { address: addresses.mixerState, role: AccountRole.WRITABLE },
+      { address: addresses.mixerVault, role: AccountRole.READONLY },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+      { address: upgradeAuthority, role: AccountRole.READONLY_SIGNER },
+      { address: programData, role: AccountRole.READONLY },
     ],
     data,
   };
 }
 
+/**
+ * The instructions of a deposit: `PushRoot`, which moves one denomination
+ * into the vault itself and fails if the vault ends up over the pool's cap.
+ *
+ * `depositor` is a plain address rather than a signer so the deposit can be
+ * wrapped in a multisig (e.g. Squads vault) transaction: the multisig program
+ * executes it via CPI with the vault PDA signing, which the mixer accepts
+ * like any other signer. The note secret never touches the depositor, so
+ * whoever generated the commitment keeps the note and its backup.
+ */
+export function buildDepositInstructions(
+  addresses: MixerAddresses,
+  newRoot: Uint8Array,
+  depositor: Address,
+  leafCount?: bigint,
+  /** The depositor's `getDepositCounterPda`, for pools limiting deposits per depositor. */
+  depositCounter?: Address,
+  /** A receipt from `getDepositReceipt`; needs `leafCount`. */
+  receipt?: DepositReceipt,
+  /** The full current tree's `getCommitmentTreePda`; see `buildPushRootInstruction`. */
+  rotation?: Address
+): Instruction[] {
+  const pushRootData = encodePushRoot(newRoot, leafCount, receipt !== undefined);
+
+  return [
+    {
+      programAddress: addresses.mixerProgramId,
+      accounts: [
+        { address: depositor, role: AccountRole.WRITABLE_SIGNER },
+        { address: addresses.mixerState, role: AccountRole.WRITABLE },
+        { address: addresses.eventAuthority, role: AccountRole.READONLY },
+        { address: addresses.mixerProgramId, role: AccountRole.READONLY },
+        { address: addresses.mixerVault, role: AccountRole.WRITABLE },
+        { address: addresses.latestRoot, role: AccountRole.WRITABLE },
+        { address: addresses.poolStats, role: AccountRole.WRITABLE },
+        { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+        ...(depositCounter === undefined
+          ? []
+          : [
+              { address: depositCounter, role: AccountRole.WRITABLE },
+              { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+            ]),
+        ...(rotation === undefined
+          ? []
+          : [
+              { address: rotation, role: AccountRole.WRITABLE },
+              { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+            ]),
+        ...(receipt === undefined
+          ? []
+          : receiptAccounts(receipt).map(([key, writable]) => ({
+              address: key,
+              role: writable ? AccountRole.WRITABLE : AccountRole.READONLY,
+            }))),
+      ],
+      data: pushRootData,
+    },
+  ];
+}
+
+/**
+ * The pool tree a withdraw's root is from, with its `getCommitmentTreePda`.
+ * Withdraw variants without one are checked against the pool's current tree
+ * only, so a root of an archived tree needs it.
+ */
+export interface CommitmentTreeRef {
+  treeId: number;
+  address: Address;
+}
+
+// Accounts every withdraw variant starts with; a commitment tree follows.
+const WITHDRAW_ACCOUNTS = 10;
+
+/**
+ * Unversioned data of a withdraw variant as version 2, which carries the
+ * tree id after circuit_version: `VERSION_FLAG | 2 || tag || ...`.
+ */
+function withTreeId(data: Uint8Array, treeId: number): Uint8Array {
+  const treeIdAt = 1 + 32 * 3 + 2;
+  const versioned = new Uint8Array(data.length + 2);
+  versioned[0] = 0x82;
+  versioned.set(data.subarray(0, treeIdAt), 1);
+  versioned[1 + treeIdAt] = treeId;
+  versioned.set(data.subarray(treeIdAt), 2 + treeIdAt);
+  return versioned;
+}
+
+/** `withdraw`, an unversioned withdraw variant, naming `tree` after its shared accounts. */
+function inTree<
+  T extends {
+    accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+    data: Uint8Array;
+  },
+>(withdraw: T, tree: CommitmentTreeRef | undefined): T {
+  if (tree === undefined) {
+    return withdraw;
+  }
+  return {
+    ...withdraw,
+    accounts: [
+      ...withdraw.accounts.slice(0, WITHDRAW_ACCOUNTS),
+      { address: tree.address, role: "readonly" },
+      ...withdraw.accounts.slice(WITHDRAW_ACCOUNTS),
+    ],
+    data: withTreeId(withdraw.data, tree.treeId),
+  };
+}
+
 export function buildWithdrawInstruction(
   addresses: MixerAddresses,
   root: Uint8Array,
@@ -118,7 +741,10 @@ export function buildWithdrawInstruction(
   proofWithWitness: Uint8Array,
   nullifierPda: Address,
   recipient: Address,
-  relayer: Address
+  relayer: Address,
+  circuitVersion: number = 0,
+  relayerFee: bigint = 0n,
+  tree?: CommitmentTreeRef
 ): {
   programAddress: Address;
   accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
@@ -128,25 +754,1157 @@ export function buildWithdrawInstruction(
     throw new Error("root/nullifierHash/recipientField must be 32 bytes each");
   }
 
-  const data = new Uint8Array(1 + 32 + 32 + 32 + proofWithWitness.length);
+  const data = new Uint8Array(1 + 32 + 32 + 32 + 2 + 8 + proofWithWitness.length);
   data[0] = 2; // Withdraw instruction
   data.set(root, 1);
   data.set(nullifierHash, 33);
   data.set(recipientField, 65);
-  data.set(proofWithWitness, 97);
+  const view = new DataView(data.buffer);
+  view.setUint16(97, circuitVersion, true);
+  view.setBigUint64(99, relayerFee, true);
+  data.set(proofWithWitness, 107);
+
+  return inTree(
+    {
+      programAddress: addresses.mixerProgramId,
+      accounts: [
+        { address: relayer, role: "signer" },
+        { address: addresses.mixerState, role: "writable" },
+        { address: nullifierPda, role: "writable" },
+        { address: addresses.mixerVault, role: "writable" },
+        // The proof doesn't bind the fee, so the recipient agrees to it by signing.
+        { address: recipient, role: relayerFee > 0n ? "signer" : "writable" },
+        { address: addresses.verifierProgramId, role: "readonly" },
+        { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+        { address: addresses.eventAuthority, role: "readonly" },
+        { address: addresses.mixerProgramId, role: "readonly" },
+        { address: addresses.poolStats, role: "writable" },
+      ],
+      data,
+    },
+    tree
+  );
+}
+
+/**
+ * Withdraw, then CPI into `targetProgram` with the recipient as its first
+ * account followed by `extraAccounts`. The call data is not bound by the
+ * proof: if the call spends the recipient's funds, the recipient must also
+ * sign the transaction.
+ */
+export function buildWithdrawAndCallInstruction(
+  addresses: MixerAddresses,
+  root: Uint8Array,
+  nullifierHash: Uint8Array,
+  recipientField: Uint8Array,
+  proofWithWitness: Uint8Array,
+  nullifierPda: Address,
+  recipient: Address,
+  relayer: Address,
+  targetProgram: Address,
+  callData: Uint8Array,
+  extraAccounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }> = [],
+  circuitVersion: number = 0,
+  relayerFee: bigint = 0n,
+  tree?: CommitmentTreeRef
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  const withdraw = buildWithdrawInstruction(
+    addresses,
+    root,
+    nullifierHash,
+    recipientField,
+    new Uint8Array(0),
+    nullifierPda,
+    recipient,
+    relayer,
+    circuitVersion,
+    relayerFee
+  );
+
+  const data = new Uint8Array(1 + 106 + 4 + proofWithWitness.length + callData.length);
+  data[0] = 6; // WithdrawAndCall instruction
+  data.set(withdraw.data.subarray(1, 107), 1);
+  new DataView(data.buffer).setUint32(107, proofWithWitness.length, true);
+  data.set(proofWithWitness, 111);
+  data.set(callData, 111 + proofWithWitness.length);
+
+  return inTree(
+    {
+      programAddress: addresses.mixerProgramId,
+      accounts: [
+        ...withdraw.accounts,
+        { address: targetProgram, role: "readonly" },
+        ...extraAccounts,
+      ],
+      data,
+    },
+    tree
+  );
+}
+
+export const STAKE_PROGRAM_ADDRESS = address("Stake11111111111111111111111111111111111111");
+export const STAKE_CONFIG_ADDRESS = address("StakeConfig11111111111111111111111111111111");
+export const SYSVAR_RENT_ADDRESS = address("SysvarRent111111111111111111111111111111111");
+export const SYSVAR_CLOCK_ADDRESS = address("SysvarC1ock11111111111111111111111111111111");
+export const SYSVAR_STAKE_HISTORY_ADDRESS = address("SysvarStakeHistory1111111111111111111111111");
+
+/**
+ * Withdraw into a new stake account delegated to `voteAccount`, staked and
+ * withdrawable by `recipient`. The stake account address is derived from the
+ * nullifier hash (see `getStakeAccountPda`).
+ */
+export async function buildWithdrawToStakeInstruction(
+  addresses: MixerAddresses,
+  root: Uint8Array,
+  nullifierHash: Uint8Array,
+  recipientField: Uint8Array,
+  proofWithWitness: Uint8Array,
+  nullifierPda: Address,
+  recipient: Address,
+  relayer: Address,
+  voteAccount: Address,
+  circuitVersion: number = 0,
+  relayerFee: bigint = 0n,
+  tree?: CommitmentTreeRef
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const withdraw = buildWithdrawInstruction(
+    addresses,
+    root,
+    nullifierHash,
+    recipientField,
+    proofWithWitness,
+    nullifierPda,
+    recipient,
+    relayer,
+    circuitVersion,
+    relayerFee
+  );
+  const data = withdraw.data;
+  data[0] = 7; // WithdrawToStake instruction
+
+  const [stakeAccount] = await getStakeAccountPda(addresses.mixerProgramId, nullifierHash);
+  const [stakeAuthority] = await getStakeAuthorityPda(addresses.mixerProgramId);
+
+  return inTree(
+    {
+      programAddress: addresses.mixerProgramId,
+      accounts: [
+        ...withdraw.accounts,
+        { address: stakeAccount, role: "writable" },
+        { address: voteAccount, role: "readonly" },
+        { address: stakeAuthority, role: "readonly" },
+        { address: STAKE_PROGRAM_ADDRESS, role: "readonly" },
+        { address: SYSVAR_RENT_ADDRESS, role: "readonly" },
+        { address: SYSVAR_CLOCK_ADDRESS, role: "readonly" },
+        { address: SYSVAR_STAKE_HISTORY_ADDRESS, role: "readonly" },
+        { address: STAKE_CONFIG_ADDRESS, role: "readonly" },
+      ],
+      data,
+    },
+    tree
+  );
+}
+
+// ============================================================================
+// Proof buffers
+// ============================================================================
+
+/** Payload bytes per WriteProofChunk that keep the write transaction well under 1232 bytes. */
+export const DEFAULT_PROOF_CHUNK_SIZE = 900;
+
+/** Largest payload `CreateProofBuffer` accepts (`ProofBuffer::MAX_PAYLOAD_LEN`). */
+export const MAX_PROOF_BUFFER_CREATE_LEN = 10_240 - 32;
+/** Largest payload a buffer can be extended to (`ProofBuffer::MAX_EXTENDED_PAYLOAD_LEN`). */
+export const MAX_PROOF_BUFFER_PAYLOAD_LEN = 32 * 1024;
+/** Most bytes one `ExtendProofBuffer` may add. */
+export const MAX_PROOF_BUFFER_EXTEND_LEN = 10_240;
+
+export function buildCreateProofBufferInstruction(
+  addresses: MixerAddresses,
+  proofBuffer: Address,
+  bufferId: bigint,
+  payloadLen: number,
+  authority: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  const data = new Uint8Array(1 + 8 + 4);
+  const view = new DataView(data.buffer);
+  data[0] = 3; // CreateProofBuffer instruction
+  view.setBigUint64(1, bufferId, true);
+  view.setUint32(9, payloadLen, true);
 
   return {
     programAddress: addresses.mixerProgramId,
     accounts: [
-      { address: relayer, role: "signer" },
-      { address: addresses.mixerState, role: "writable" },
-      { address: nullifierPda, role: "writable" },
-      { address: addresses.mixerVault, role: "writable" },
-      { address: recipient, role: "writable" },
-      { address: addresses.verifierProgramId, role: "readonly" },
+      { address: authority, role: "signer" },
+      { address: proofBuffer, role: "writable" },
       { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
     ],
     data,
   };
 }
 
+export function buildWriteProofChunkInstruction(
+  addresses: MixerAddresses,
+  proofBuffer: Address,
+  offset: number,
+  chunk: Uint8Array,
+  authority: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  const data = new Uint8Array(1 + 4 + chunk.length);
+  data[0] = 4; // WriteProofChunk instruction
+  new DataView(data.buffer).setUint32(1, offset, true);
+  data.set(chunk, 5);
+
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: proofBuffer, role: "writable" },
+    ],
+    data,
+  };
+}
+
+export function buildExtendProofBufferInstruction(
+  addresses: MixerAddresses,
+  proofBuffer: Address,
+  additionalLen: number,
+  authority: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  const data = new Uint8Array(1 + 4);
+  data[0] = 21; // ExtendProofBuffer instruction
+  new DataView(data.buffer).setUint32(1, additionalLen, true);
+
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: proofBuffer, role: "writable" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+    ],
+    data,
+  };
+}
+
+/**
+ * Build CreateProofBuffer followed by as many ExtendProofBuffer instructions
+ * as a `payloadLen` payload needs, one per transaction.
+ */
+export function buildAllocateProofBufferInstructions(
+  addresses: MixerAddresses,
+  proofBuffer: Address,
+  bufferId: bigint,
+  payloadLen: number,
+  authority: Address
+): ReturnType<typeof buildCreateProofBufferInstruction>[] {
+  if (payloadLen > MAX_PROOF_BUFFER_PAYLOAD_LEN) {
+    throw new Error(
+      `proof payload is ${payloadLen} bytes, at most ${MAX_PROOF_BUFFER_PAYLOAD_LEN} fit in a buffer`
+    );
+  }
+  const initial = Math.min(payloadLen, MAX_PROOF_BUFFER_CREATE_LEN);
+  const instructions = [
+    buildCreateProofBufferInstruction(addresses, proofBuffer, bufferId, initial, authority),
+  ];
+  for (let len = initial; len < payloadLen; len += MAX_PROOF_BUFFER_EXTEND_LEN) {
+    const step = Math.min(payloadLen - len, MAX_PROOF_BUFFER_EXTEND_LEN);
+    instructions.push(buildExtendProofBufferInstruction(addresses, proofBuffer, step, authority));
+  }
+  return instructions;
+}
+
+/**
+ * Split a proof payload into WriteProofChunk instructions, one per
+ * transaction. Send them after CreateProofBuffer and before
+ * WithdrawFromBuffer, in any order.
+ */
+export function buildWriteProofChunkInstructions(
+  addresses: MixerAddresses,
+  proofBuffer: Address,
+  payload: Uint8Array,
+  authority: Address,
+  chunkSize: number = DEFAULT_PROOF_CHUNK_SIZE
+): ReturnType<typeof buildWriteProofChunkInstruction>[] {
+  const instructions: ReturnType<typeof buildWriteProofChunkInstruction>[] = [];
+  for (let offset = 0; offset < payload.length; offset += chunkSize) {
+    instructions.push(
+      buildWriteProofChunkInstruction(
+        addresses,
+        proofBuffer,
+        offset,
+        payload.subarray(offset, offset + chunkSize),
+        authority
+      )
+    );
+  }
+  return instructions;
+}
+
+export function buildWithdrawFromBufferInstruction(
+  addresses: MixerAddresses,
+  root: Uint8Array,
+  nullifierHash: Uint8Array,
+  recipientField: Uint8Array,
+  proofBuffer: Address,
+  nullifierPda: Address,
+  recipient: Address,
+  relayer: Address,
+  circuitVersion: number = 0,
+  relayerFee: bigint = 0n,
+  tree?: CommitmentTreeRef
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  const withdraw = buildWithdrawInstruction(
+    addresses,
+    root,
+    nullifierHash,
+    recipientField,
+    new Uint8Array(0),
+    nullifierPda,
+    recipient,
+    relayer,
+    circuitVersion,
+    relayerFee
+  );
+  const data = withdraw.data;
+  data[0] = 5; // WithdrawFromBuffer instruction
+
+  return inTree(
+    {
+      programAddress: addresses.mixerProgramId,
+      accounts: [...withdraw.accounts, { address: proofBuffer, role: "writable" }],
+      data,
+    },
+    tree
+  );
+}
+
+/**
+ * Ask `withdraw`, from `buildWithdrawInstruction` or
+ * `buildWithdrawFromBufferInstruction`, to leave the recipient a withdraw
+ * receipt recording the payout and slot, as proof of payment. `receipt` is
+ * the note's `getWithdrawReceiptPda`; the relayer pays its rent, which the
+ * recipient gets back by closing it with `buildCloseWithdrawReceiptInstruction`.
+ */
+export function withWithdrawReceipt(
+  withdraw: {
+    programAddress: Address;
+    accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+    data: Uint8Array;
+  },
+  receipt: Address
+): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  return {
+    programAddress: withdraw.programAddress,
+    accounts: [...withdraw.accounts, { address: receipt, role: "writable" }],
+    data: withdraw.data,
+  };
+}
+
+/** `CloseWithdrawReceipt`, signed by the recipient the receipt names. */
+export async function buildCloseWithdrawReceiptInstruction(
+  addresses: MixerAddresses,
+  nullifierHash: Uint8Array,
+  recipient: Address
+): Promise<Instruction> {
+  if (nullifierHash.length !== 32) {
+    throw new Error("nullifierHash must be 32 bytes");
+  }
+  const [receipt] = await getWithdrawReceiptPda(addresses.mixerProgramId, nullifierHash);
+  const data = new Uint8Array(1 + 32);
+  data[0] = 28; // CloseWithdrawReceipt instruction
+  data.set(nullifierHash, 1);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: recipient, role: AccountRole.WRITABLE_SIGNER },
+      { address: receipt, role: AccountRole.WRITABLE },
+    ],
+    data,
+  };
+}
+
+/** An auxiliary account `buildCloseAccountInstruction` can reclaim. */
+export type CloseTarget =
+  | { kind: "proofBuffer"; bufferId: bigint }
+  | { kind: "depositCounter" }
+  | { kind: "verifyingKey"; vkId: bigint };
+
+export async function getVerifyingKeyPda(
+  mixerProgramId: Address,
+  vkId: bigint
+): Promise<ProgramDerivedAddressBump> {
+  const vkIdBytes = new Uint8Array(8);
+  new DataView(vkIdBytes.buffer).setBigUint64(0, vkId, true);
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("verifying_key"), vkIdBytes],
+  });
+  return [pda, bump];
+}
+
+/**
+ * `CloseAccount`, returning the rent of `target` to `owner`: the buffer
+ * authority or depositor the account belongs to, or the pool authority for
+ * a verifying key. A deposit counter can only be closed after its epoch, and
+ * a verifying key once the pool no longer uses it.
+ */
+export async function buildCloseAccountInstruction(
+  addresses: MixerAddresses,
+  owner: Address,
+  target: CloseTarget
+): Promise<Instruction> {
+  const accounts: Array<{ address: Address; role: AccountRole }> = [
+    { address: owner, role: AccountRole.WRITABLE_SIGNER },
+  ];
+  let data: Uint8Array;
+  switch (target.kind) {
+    case "proofBuffer": {
+      const [buffer] = await getProofBufferPda(addresses.mixerProgramId, owner, target.bufferId);
+      accounts.push({ address: buffer, role: AccountRole.WRITABLE });
+      data = new Uint8Array(2 + 8);
+      data[1] = 0;
+      new DataView(data.buffer).setBigUint64(2, target.bufferId, true);
+      break;
+    }
+    case "depositCounter": {
+      const [counter] = await getDepositCounterPda(addresses.mixerProgramId, owner);
+      accounts.push({ address: counter, role: AccountRole.WRITABLE });
+      data = new Uint8Array([0, 1]);
+      break;
+    }
+    case "verifyingKey": {
+      const [vk] = await getVerifyingKeyPda(addresses.mixerProgramId, target.vkId);
+      accounts.push(
+        { address: vk, role: AccountRole.WRITABLE },
+        { address: addresses.mixerState, role: AccountRole.READONLY }
+      );
+      data = new Uint8Array(2 + 8);
+      data[1] = 2;
+      new DataView(data.buffer).setBigUint64(2, target.vkId, true);
+      break;
+    }
+  }
+  data[0] = 29; // CloseAccount instruction
+  return { programAddress: addresses.mixerProgramId, accounts, data };
+}
+
+// ============================================================================
+// Scheduled withdrawals
+// ============================================================================
+
+// Flow: upload the proof into a proof buffer as above, then
+// `buildScheduleWithdrawInstruction`. From `earliestSlot` on, any keeper can
+// send `buildExecuteScheduledWithdrawInstruction` and earns `bounty` lamports
+// out of the denomination for doing so.
+
+export async function buildScheduleWithdrawInstruction(
+  addresses: MixerAddresses,
+  root: Uint8Array,
+  nullifierHash: Uint8Array,
+  recipientField: Uint8Array,
+  earliestSlot: bigint,
+  bounty: bigint,
+  proofBuffer: Address,
+  recipient: Address,
+  authority: Address,
+  circuitVersion: number = 0,
+  /** The tree `root` is from; pass the same one to `buildExecuteScheduledWithdrawInstruction`. */
+  tree?: CommitmentTreeRef
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [scheduledWithdraw] = await getScheduledWithdrawPda(addresses.mixerProgramId, proofBuffer);
+  const data = new Uint8Array(1 + 98 + 16);
+  data[0] = 8; // ScheduleWithdraw instruction
+  data.set(root, 1);
+  data.set(nullifierHash, 33);
+  data.set(recipientField, 65);
+  const view = new DataView(data.buffer);
+  view.setUint16(97, circuitVersion, true);
+  view.setBigUint64(99, earliestSlot, true);
+  view.setBigUint64(107, bounty, true);
+
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: addresses.mixerState, role: "readonly" },
+      { address: proofBuffer, role: "readonly" },
+      { address: scheduledWithdraw, role: "writable" },
+      { address: recipient, role: bounty > 0n ? "signer" : "readonly" },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: "readonly" },
+    ],
+    // The tree's account is only needed on execution.
+    data: tree === undefined ? data : withTreeId(data, tree.treeId),
+  };
+}
+
+/** `keeper` signs, funds the nullifier marker and receives the bounty. */
+export async function buildExecuteScheduledWithdrawInstruction(
+  addresses: MixerAddresses,
+  nullifierHash: Uint8Array,
+  proofBuffer: Address,
+  recipient: Address,
+  authority: Address,
+  keeper: Address,
+  /** The tree the withdraw was scheduled with, if any. */
+  tree?: CommitmentTreeRef
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [scheduledWithdraw] = await getScheduledWithdrawPda(addresses.mixerProgramId, proofBuffer);
+  const [nullifierPda] = await getNullifierPda(addresses.mixerProgramId, nullifierHash);
+  const withdraw = buildWithdrawInstruction(
+    addresses,
+    new Uint8Array(32),
+    nullifierHash,
+    new Uint8Array(32),
+    new Uint8Array(0),
+    nullifierPda,
+    recipient,
+    keeper
+  );
+
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      ...withdraw.accounts,
+      { address: proofBuffer, role: "writable" },
+      { address: scheduledWithdraw, role: "writable" },
+      { address: authority, role: "writable" },
+      ...(tree === undefined ? [] : [{ address: tree.address, role: "readonly" as const }]),
+    ],
+    data: new Uint8Array([9]), // ExecuteScheduledWithdraw instruction
+  };
+}
+
+export async function buildCancelScheduledWithdrawInstruction(
+  addresses: MixerAddresses,
+  proofBuffer: Address,
+  authority: Address
+): Promise<{
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+}> {
+  const [scheduledWithdraw] = await getScheduledWithdrawPda(addresses.mixerProgramId, proofBuffer);
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [
+      { address: authority, role: "signer" },
+      { address: proofBuffer, role: "writable" },
+      { address: scheduledWithdraw, role: "writable" },
+    ],
+    data: new Uint8Array([10]), // CancelScheduledWithdraw instruction
+  };
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+// Events are emitted as self-CPIs (Anchor `emit_cpi!` framing) and show up as
+// inner instructions of the mixer program:
+//   EVENT_IX_TAG_LE (8) || discriminator (8) || borsh(event)
+export const EVENT_IX_TAG_LE = new Uint8Array([0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d]);
+
+const EVENT_DISCRIMINATORS = {
+  // sha256("event:RootPushed")[..8]
+  RootPushed: new Uint8Array([0x55, 0x83, 0xee, 0xa4, 0xdd, 0x12, 0x7a, 0x3f]),
+  // sha256("event:Withdraw")[..8]
+  Withdraw: new Uint8Array([0xc0, 0xf1, 0xc9, 0xd9, 0x46, 0x96, 0x5a, 0xf7]),
+  // sha256("event:TreeRotated")[..8]
+  TreeRotated: new Uint8Array([0xe0, 0xbb, 0x47, 0xff, 0x6e, 0xa8, 0x1c, 0x28]),
+} as const;
+
+export type MixerEvent =
+  | { name: "RootPushed"; root: Uint8Array; rootIndex: number }
+  | {
+      name: "Withdraw";
+      root: Uint8Array;
+      nullifierHash: Uint8Array;
+      recipient: Uint8Array;
+      amount: bigint;
+    }
+  | { name: "TreeRotated"; treeId: number; root: Uint8Array };
+
+function bytesEqual(a: Uint8Array, b: Uint8Array): boolean {
+  return a.length === b.length && a.every((v, i) => v === b[i]);
+}
+
+/**
+ * Decode the data of a mixer inner instruction into an event. Returns null
+ * for instructions that are not mixer events.
+ */
+export function decodeMixerEvent(data: Uint8Array): MixerEvent | null {
+  if (data.length < 16 || !bytesEqual(data.subarray(0, 8), EVENT_IX_TAG_LE)) {
+    return null;
+  }
+  const discriminator = data.subarray(8, 16);
+  const body = data.subarray(16);
+  if (bytesEqual(discriminator, EVENT_DISCRIMINATORS.RootPushed) && body.length === 33) {
+    return { name: "RootPushed", root: body.slice(0, 32), rootIndex: body[32] };
+  }
+  if (bytesEqual(discriminator, EVENT_DISCRIMINATORS.Withdraw) && body.length === 104) {
+    const view = new DataView(body.buffer, body.byteOffset, body.byteLength);
+    return {
+      name: "Withdraw",
+      root: body.slice(0, 32),
+      nullifierHash: body.slice(32, 64),
+      recipient: body.slice(64, 96),
+      amount: view.getBigUint64(96, true),
+    };
+  }
+  if (bytesEqual(discriminator, EVENT_DISCRIMINATORS.TreeRotated) && body.length === 33) {
+    return { name: "TreeRotated", treeId: body[0], root: body.slice(1, 33) };
+  }
+  return null;
+}
+
+// ============================================================================
+// State view
+// ============================================================================
+
+// `GetState` writes the pool state to the transaction's return data as borsh
+// (must match `StateView` in programs/mixer/src/view.rs):
+//   denomination: u64 | latest_root: [u8; 32] | current_root_index: u8
+//   | latest_root_time: i64 | leaf_count: u64 | tree_depth: u8 | flags: u8
+//   | paused: u8 | curve: u8 | verifier_backend: u8 | protocol_fee_bps: u16
+//   | relayer_fee_cap_bps: u16 | min_withdraw_delay: u64 | protocol_fees: u64
+//   | deposit_count: u64 | withdraw_count: u64 | max_deposits_per_epoch: u32
+//   | max_deposits_per_depositor: u32 | max_vault_lamports: u64 | admin_delay: u64
+//   | authority: [u8; 32] | verifier_program: [u8; 32] | guardian: [u8; 32]
+//   | receipt_tree: [u8; 32] | migration_version: u16 | migration_end_slot: u64
+//   | tree_id: u8 | latest_root_slot: u64
+// all little-endian. Fields are only appended, so bytes past these are ignored.
+const STATE_VIEW_LEN = 269;
+
+export interface StateView {
+  denomination: bigint;
+  latestRoot: Uint8Array;
+  currentRootIndex: number;
+  latestRootTime: bigint;
+  leafCount: bigint;
+  treeDepth: number;
+  flags: number;
+  paused: boolean;
+  curve: number;
+  verifierBackend: number;
+  protocolFeeBps: number;
+  relayerFeeCapBps: number;
+  minWithdrawDelaySecs: bigint;
+  protocolFees: bigint;
+  depositCount: bigint;
+  withdrawCount: bigint;
+  maxDepositsPerEpoch: number;
+  maxDepositsPerDepositor: number;
+  maxVaultLamports: bigint;
+  adminDelaySecs: bigint;
+  authority: Address;
+  /** Null when withdrawals are not pinned to a verifier. */
+  verifierProgram: Address | null;
+  /** Null when the pool has no guardian. */
+  guardian: Address | null;
+  /** Null when the pool mints no deposit receipts. */
+  receiptTree: Address | null;
+  /** Circuit version a pending migration moves to; 0 when none is. */
+  migrationVersion: number;
+  migrationEndSlot: bigint;
+  /** Tree deposits currently go into. */
+  treeId: number;
+  latestRootSlot: bigint;
+}
+
+/**
+ * `GetState` for the pool at `addresses.mixerState`. Simulate a transaction
+ * holding it and decode `returnData` with `decodeStateView`.
+ */
+export function buildGetStateInstruction(addresses: MixerAddresses): {
+  programAddress: Address;
+  accounts: Array<{ address: Address; role: "signer" | "writable" | "readonly" }>;
+  data: Uint8Array;
+} {
+  return {
+    programAddress: addresses.mixerProgramId,
+    accounts: [{ address: addresses.mixerState, role: "readonly" }],
+    data: new Uint8Array([25]), // GetState instruction
+  };
+}
+
+export function decodeStateView(data: Uint8Array): StateView {
+  if (data.length < STATE_VIEW_LEN) {
+    throw new Error(`state view too small: ${data.length} bytes, expected ${STATE_VIEW_LEN}`);
+  }
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  const addressAt = (offset: number) =>
+    getAddressDecoder().decode(data.subarray(offset, offset + 32));
+  const optionalAddressAt = (offset: number) =>
+    data.subarray(offset, offset + 32).every((b) => b === 0) ? null : addressAt(offset);
+  return {
+    denomination: view.getBigUint64(0, true),
+    latestRoot: data.slice(8, 40),
+    currentRootIndex: data[40],
+    latestRootTime: view.getBigInt64(41, true),
+    leafCount: view.getBigUint64(49, true),
+    treeDepth: data[57],
+    flags: data[58],
+    paused: data[59] !== 0,
+    curve: data[60],
+    verifierBackend: data[61],
+    protocolFeeBps: view.getUint16(62, true),
+    relayerFeeCapBps: view.getUint16(64, true),
+    minWithdrawDelaySecs: view.getBigUint64(66, true),
+    protocolFees: view.getBigUint64(74, true),
+    depositCount: view.getBigUint64(82, true),
+    withdrawCount: view.getBigUint64(90, true),
+    maxDepositsPerEpoch: view.getUint32(98, true),
+    maxDepositsPerDepositor: view.getUint32(102, true),
+    maxVaultLamports: view.getBigUint64(106, true),
+    adminDelaySecs: view.getBigUint64(114, true),
+    authority: addressAt(122),
+    verifierProgram: optionalAddressAt(154),
+    guardian: optionalAddressAt(186),
+    receiptTree: optionalAddressAt(218),
+    migrationVersion: view.getUint16(250, true),
+    migrationEndSlot: view.getBigUint64(252, true),
+    treeId: data[260],
+    latestRootSlot: view.getBigUint64(261, true),
+  };
+}
+
+// ============================================================================
+// Memos
+// ============================================================================
+
+// A memo rides along as an SPL Memo instruction in the same transaction as a
+// deposit or withdraw. The memo program logs its content, and the transaction
+// fails as a whole if either instruction fails, so the memo is tied to exactly
+// one mixer event. Content must be valid UTF-8; encrypt invoice references
+// before attaching them and base64-encode the ciphertext.
+export const MEMO_PROGRAM_ADDRESS = address("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+/**
+ * SPL Memo instruction. `signers` are optional; when given, the memo program
+ * requires each to sign, which proves who attached the memo. For withdrawals
+ * this should normally be empty so the memo does not identify the relayer.
+ */
+export function buildMemoInstruction(memo: string, signers: Address[] = []): Instruction {
+  return {
+    programAddress: MEMO_PROGRAM_ADDRESS,
+    accounts: signers.map((signer) => ({ address: signer, role: AccountRole.READONLY_SIGNER })),
+    data: textEncoder.encode(memo),
+  };
+}
+
+/**
+ * Memo contents of the top-level instructions of a transaction, for pairing
+ * with the mixer events it emitted. Returns an empty array if none.
+ */
+export function extractMemos(
+  instructions: ReadonlyArray<{ programAddress: Address; data?: Uint8Array }>
+): string[] {
+  const decoder = new TextDecoder();
+  return instructions
+    .filter((ix) => ix.programAddress === MEMO_PROGRAM_ADDRESS && ix.data)
+    .map((ix) => decoder.decode(ix.data));
+}
+
+// ============================================================================
+// getProgramAccounts filters and account decoding
+// ============================================================================
+
+export type MixerAccountFilter =
+  | GetProgramAccountsDatasizeFilter
+  | GetProgramAccountsMemcmpFilter;
+
+export interface MixerStateAccount {
+  denomination: bigint;
+  roots: Uint8Array[];
+  currentRootIndex: number;
+  latestRoot: Uint8Array;
+  flags: number;
+  authority: Address;
+  /**
+   * Null if the pool does not pin a verifier. For the native backend this is
+   * the verifying key account.
+   */
+  verifierProgram: Address | null;
+  adminDelaySecs: bigint;
+  /** Null if the pool has no guardian. */
+  guardian: Address | null;
+  paused: boolean;
+  protocolFeeBps: number;
+  relayerFeeCapBps: number;
+  minWithdrawDelaySecs: bigint;
+  /** Uncollected protocol fees held in the vault, in lamports. */
+  protocolFees: bigint;
+  /** Unix timestamp at which each entry of `roots` was pushed. */
+  rootTimes: bigint[];
+  /** One of `VERIFIER_BACKEND`. */
+  verifierBackend: number;
+  /** Registered circuit versions besides the default (version 0). */
+  circuitVersions: CircuitVersion[];
+  /** Expected hash of the default verifier; null if unbound. */
+  verifierHash: Uint8Array | null;
+  /** One of `MIXER_CURVE`. */
+  curve: number;
+  /** Merkle tree depth of the pool's circuit. */
+  treeDepth: number;
+  /** Leaves of the current tree under the latest root, as last reported by `PushRoot`. */
+  leafCount: bigint;
+  /** Deposits accepted per epoch; 0 for no limit. */
+  maxDepositsPerEpoch: number;
+  /** Deposits one depositor may make per epoch; 0 for no limit. */
+  maxDepositsPerDepositor: number;
+  /** Epoch that `epochDeposits` counts. */
+  depositEpoch: bigint;
+  /** Deposits made in `depositEpoch`. */
+  epochDeposits: number;
+  /** Most lamports the vault may hold after a deposit; 0 for no cap. */
+  maxVaultLamports: bigint;
+  /** Deposits made, each by a `PushRoot` that paid a denomination into the vault. */
+  depositCount: bigint;
+  /** Notes spent by any withdraw variant. */
+  withdrawCount: bigint;
+  /** Bubblegum tree deposit receipts are minted into; null if disabled. */
+  receiptTree: Address | null;
+  /** Circuit version a pending `MigratePool` moves to; 0 when none is. */
+  migrationVersion: number;
+  /** Slot from which version 0 proofs fail until the migration is finalized. */
+  migrationEndSlot: bigint;
+  /** Tree deposits currently go into; earlier ones are archived in `getCommitmentTreePda`. */
+  treeId: number;
+  /** Slot at which each entry of `roots` was pushed; 0 if pushed before slots were recorded. */
+  rootSlots: bigint[];
+}
+
+/** An entry of the pool's root history; see `getRootHistory`. */
+export interface RootHistoryEntry {
+  root: Uint8Array;
+  /** Unix timestamp of the push. */
+  pushedAt: bigint;
+  /** Slot of the push; 0 if pushed before slots were recorded. */
+  slot: bigint;
+  /**
+   * Roots still to be pushed before this one leaves the history, after which
+   * notes under it can no longer be withdrawn against the current tree.
+   */
+  pushesUntilEvicted: number;
+}
+
+/**
+ * The pool's root history, newest first, without the empty entries of a
+ * pool or tree with fewer than `MIXER_ROOT_HISTORY_SIZE` pushes. With the
+ * pool's deposit rate, `pushesUntilEvicted` predicts when a root rolls out.
+ */
+export function getRootHistory(state: MixerStateAccount): RootHistoryEntry[] {
+  const entries: RootHistoryEntry[] = [];
+  for (let age = 0; age < MIXER_ROOT_HISTORY_SIZE; age++) {
+    const index =
+      (state.currentRootIndex - age + MIXER_ROOT_HISTORY_SIZE) % MIXER_ROOT_HISTORY_SIZE;
+    const root = state.roots[index];
+    if (root.every((b) => b === 0)) {
+      continue;
+    }
+    entries.push({
+      root,
+      pushedAt: state.rootTimes[index],
+      slot: state.rootSlots[index],
+      pushesUntilEvicted: MIXER_ROOT_HISTORY_SIZE - age,
+    });
+  }
+  return entries;
+}
+
+/** A registered circuit version (`verifier::CircuitVersion`). */
+export interface CircuitVersion {
+  version: number;
+  /** One of `VERIFIER_BACKEND`. */
+  backend: number;
+  verifier: Address;
+  activationSlot: bigint;
+  /** `2n ** 64n - 1n` if the version is not deprecated. */
+  deprecationSlot: bigint;
+}
+
+/** Lamports a withdrawal pays out after the protocol fee. */
+export function getWithdrawAmount(state: MixerStateAccount): bigint {
+  return state.denomination - (state.denomination * BigInt(state.protocolFeeBps)) / 10_000n;
+}
+
+function memcmp(offset: number, bytes: Uint8Array): GetProgramAccountsMemcmpFilter {
+  return {
+    memcmp: {
+      offset: BigInt(offset),
+      bytes: getBase64Decoder().decode(bytes) as Base64EncodedBytes,
+      encoding: "base64",
+    },
+  };
+}
+
+/**
+ * Filters selecting mixer state accounts owned by the mixer program,
+ * optionally narrowed to a single denomination.
+ */
+export function getMixerStateFilters(opts: { denomination?: bigint } = {}): MixerAccountFilter[] {
+  const filters: MixerAccountFilter[] = [{ dataSize: BigInt(MIXER_STATE_LEN) }];
+  if (opts.denomination !== undefined) {
+    const bytes = new Uint8Array(8);
+    new DataView(bytes.buffer).setBigUint64(0, opts.denomination, true);
+    filters.push(memcmp(MIXER_STATE_OFFSETS.denomination, bytes));
+  }
+  return filters;
+}
+
+/**
+ * Filters matching nullifier marker accounts. Nullifier PDAs are derived from
+ * the mixer program but owned by the system program with no data, so these
+ * filters only make sense combined with an address check (see
+ * `fetchNullifierStatuses`), never as a standalone scan.
+ */
+export function getNullifierAccountFilters(): MixerAccountFilter[] {
+  return [{ dataSize: 0n }];
+}
+
+/**
+ * Decode a mixer state account. The state of a pool that predates
+ * `MIXER_STATE_LEN` decodes as the program reads it: later fields zeroed and
+ * the default tree depth.
+ */
+export function decodeMixerState(data: Uint8Array): MixerStateAccount {
+  if (data.length < MIXER_STATE_BASELINE_LEN) {
+    throw new Error(
+      `mixer state account too small: ${data.length} bytes, expected ${MIXER_STATE_LEN}`
+    );
+  }
+  const hasTreeDepth = data.length > TREE_DEPTH_OFFSET;
+  if (data.length < MIXER_STATE_LEN) {
+    const padded = new Uint8Array(MIXER_STATE_LEN);
+    padded.set(data);
+    data = padded;
+  }
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  const denomination = view.getBigUint64(MIXER_STATE_OFFSETS.denomination, true);
+  const roots: Uint8Array[] = [];
+  for (let i = 0; i < MIXER_ROOT_HISTORY_SIZE; i++) {
+    const start = MIXER_STATE_OFFSETS.roots + i * 32;
+    roots.push(data.slice(start, start + 32));
+  }
+  const addressAt = (offset: number) =>
+    getAddressDecoder().decode(data.subarray(offset, offset + 32));
+  // All-zero keys mark an unset optional role.
+  const optionalAddressAt = (offset: number) =>
+    data.subarray(offset, offset + 32).every((b) => b === 0) ? null : addressAt(offset);
+  const currentRootIndex = data[MIXER_STATE_OFFSETS.currentRootIndex];
+  if (currentRootIndex >= MIXER_ROOT_HISTORY_SIZE) {
+    throw new Error(`invalid current_root_index: ${currentRootIndex}`);
+  }
+  return {
+    denomination,
+    roots,
+    currentRootIndex,
+    latestRoot: roots[currentRootIndex],
+    flags: data[MIXER_STATE_OFFSETS.flags],
+    authority: addressAt(MIXER_STATE_OFFSETS.authority),
+    verifierProgram: optionalAddressAt(MIXER_STATE_OFFSETS.verifierProgram),
+    adminDelaySecs: view.getBigUint64(MIXER_STATE_OFFSETS.adminDelay, true),
+    guardian: optionalAddressAt(MIXER_STATE_OFFSETS.guardian),
+    paused: data[MIXER_STATE_OFFSETS.paused] !== 0,
+    protocolFeeBps: view.getUint16(MIXER_STATE_OFFSETS.protocolFeeBps, true),
+    relayerFeeCapBps: view.getUint16(MIXER_STATE_OFFSETS.relayerFeeCapBps, true),
+    minWithdrawDelaySecs: view.getBigUint64(MIXER_STATE_OFFSETS.minWithdrawDelay, true),
+    protocolFees: view.getBigUint64(MIXER_STATE_OFFSETS.protocolFees, true),
+    rootTimes: Array.from({ length: MIXER_ROOT_HISTORY_SIZE }, (_, i) =>
+      view.getBigInt64(MIXER_STATE_OFFSETS.rootTimes + i * 8, true)
+    ),
+    verifierBackend: data[MIXER_STATE_OFFSETS.verifierBackend],
+    circuitVersions: Array.from({ length: MAX_CIRCUIT_VERSIONS }, (_, i) => {
+      const start = MIXER_STATE_OFFSETS.circuitVersions + i * CIRCUIT_VERSION_LEN;
+      return {
+        version: view.getUint16(start, true),
+        backend: data[start + 2],
+        verifier: addressAt(start + 3),
+        activationSlot: view.getBigUint64(start + 35, true),
+        deprecationSlot: view.getBigUint64(start + 43, true),
+      };
+    }).filter((entry) => entry.version !== 0),
+    verifierHash: data.subarray(VERIFIER_HASH_OFFSET, VERIFIER_HASH_OFFSET + 32).every((b) => b === 0)
+      ? null
+      : data.slice(VERIFIER_HASH_OFFSET, VERIFIER_HASH_OFFSET + 32),
+    curve: data[CURVE_OFFSET],
+    treeDepth: hasTreeDepth ? data[TREE_DEPTH_OFFSET] : DEFAULT_TREE_DEPTH,
+    leafCount: view.getBigUint64(LEAF_COUNT_OFFSET, true),
+    maxDepositsPerEpoch: view.getUint32(MAX_DEPOSITS_PER_EPOCH_OFFSET, true),
+    maxDepositsPerDepositor: view.getUint32(MAX_DEPOSITS_PER_DEPOSITOR_OFFSET, true),
+    depositEpoch: view.getBigUint64(DEPOSIT_EPOCH_OFFSET, true),
+    epochDeposits: view.getUint32(EPOCH_DEPOSITS_OFFSET, true),
+    maxVaultLamports: view.getBigUint64(MAX_VAULT_LAMPORTS_OFFSET, true),
+    depositCount: view.getBigUint64(DEPOSIT_COUNT_OFFSET, true),
+    withdrawCount: view.getBigUint64(WITHDRAW_COUNT_OFFSET, true),
+    receiptTree: optionalAddressAt(RECEIPT_TREE_OFFSET),
+    migrationVersion: view.getUint16(MIGRATION_VERSION_OFFSET, true),
+    migrationEndSlot: view.getBigUint64(MIGRATION_END_SLOT_OFFSET, true),
+    treeId: data[TREE_ID_OFFSET],
+    rootSlots: Array.from({ length: MIXER_ROOT_HISTORY_SIZE }, (_, i) =>
+      view.getBigUint64(ROOT_SLOTS_OFFSET + i * 8, true)
+    ),
+  };
+}
+
+// Latest root account layout (must match `LatestRoot` in programs/mixer/src/lib.rs):
+//   root: [u8; 32] | leaf_count: u64 LE | slot: u64 LE
+export const LATEST_ROOT_LEN = 48;
+
+export interface LatestRootAccount {
+  root: Uint8Array;
+  leafCount: bigint;
+  /** Slot of the `PushRoot` that wrote `root`. */
+  slot: bigint;
+}
+
+export function decodeLatestRoot(data: Uint8Array): LatestRootAccount {
+  if (data.length < LATEST_ROOT_LEN) {
+    throw new Error(
+      `latest root account too small: ${data.length} bytes, expected ${LATEST_ROOT_LEN}`
+    );
+  }
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  return {
+    root: data.slice(0, 32),
+    leafCount: view.getBigUint64(32, true),
+    slot: view.getBigUint64(40, true),
+  };
+}
+
+// Pool stats account layout (must match `PoolStats` in programs/mixer/src/lib.rs):
+//   deposits | withdrawals | deposit_volume | withdraw_volume | fee_revenue |
+//   last_activity_slot, each u64 LE
+export const POOL_STATS_LEN = 48;
+
+export interface PoolStatsAccount {
+  deposits: bigint;
+  withdrawals: bigint;
+  /** Lamports deposited. */
+  depositVolume: bigint;
+  /** Lamports paid out to recipients and relayers. */
+  withdrawVolume: bigint;
+  /** Protocol fees accrued, whether collected since or not. */
+  feeRevenue: bigint;
+  /** Slot of the latest deposit or withdrawal. */
+  lastActivitySlot: bigint;
+}
+
+export function decodePoolStats(data: Uint8Array): PoolStatsAccount {
+  if (data.length < POOL_STATS_LEN) {
+    throw new Error(
+      `pool stats account too small: ${data.length} bytes, expected ${POOL_STATS_LEN}`
+    );
+  }
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  return {
+    deposits: view.getBigUint64(0, true),
+    withdrawals: view.getBigUint64(8, true),
+    depositVolume: view.getBigUint64(16, true),
+    withdrawVolume: view.getBigUint64(24, true),
+    feeRevenue: view.getBigUint64(32, true),
+    lastActivitySlot: view.getBigUint64(40, true),
+  };
+}
+
+// Withdraw receipt account layout (must match `WithdrawReceipt` in programs/mixer/src/lib.rs):
+//   recipient: [u8; 32] | amount: u64 LE | slot: u64 LE
+export const WITHDRAW_RECEIPT_LEN = 48;
+
+export interface WithdrawReceiptAccount {
+  recipient: Address;
+  /** Lamports paid to the recipient, after the relayer fee. */
+  amount: bigint;
+  /** Slot of the withdrawal. */
+  slot: bigint;
+}
+
+export function decodeWithdrawReceipt(data: Uint8Array): WithdrawReceiptAccount {
+  if (data.length < WITHDRAW_RECEIPT_LEN) {
+    throw new Error(
+      `withdraw receipt account too small: ${data.length} bytes, expected ${WITHDRAW_RECEIPT_LEN}`
+    );
+  }
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  return {
+    recipient: getAddressDecoder().decode(data.subarray(0, 32)),
+    amount: view.getBigUint64(32, true),
+    slot: view.getBigUint64(40, true),
+  };
+}
+
+/** Fetch and decode every mixer state account matching the given filters. */
+export async function fetchMixerStateAccounts(
+  rpc: Rpc<GetProgramAccountsApi>,
+  mixerProgramId: Address,
+  opts: { denomination?: bigint } = {}
+): Promise<Array<{ address: Address; state: MixerStateAccount }>> {
+  const accounts = await rpc
+    .getProgramAccounts(mixerProgramId, {
+      encoding: "base64",
+      filters: getMixerStateFilters(opts),
+    })
+    .send();
+  return accounts.map(({ pubkey, account }) => ({
+    address: pubkey,
+    state: decodeMixerState(new Uint8Array(getBase64Encoder().encode(account.data[0]))),
+  }));
+}
+
+/**
+ * Check which nullifier hashes have already been spent. A nullifier is spent
+ * once its marker PDA exists on chain.
+ */
+export async function fetchNullifierStatuses(
+  rpc: Rpc<GetMultipleAccountsApi>,
+  mixerProgramId: Address,
+  nullifierHashes: Uint8Array[]
+): Promise<Array<{ nullifierHash: Uint8Array; address: Address; spent: boolean }>> {
+  const addresses = await Promise.all(
+    nullifierHashes.map(async (hash) => (await getNullifierPda(mixerProgramId, hash))[0])
+  );
+  const { value } = await rpc
+    .getMultipleAccounts(addresses, { encoding: "base64" })
+    .send();
+  return nullifierHashes.map((nullifierHash, i) => ({
+    nullifierHash,
+    address: addresses[i],
+    spent: value[i] !== null,
+  }));
+}
@@ -0,0 +1,203 @@
+// Encrypted note backups in an on-chain backup vault
+//
+// A viewing key is any 32-byte secret the wallet can regenerate, e.g. one
+// derived from its seed phrase. Two values are derived from it with HKDF:
+// the `vault_id` that addresses the backup vault PDA
+// (`["backup_vault", vault_id]`), and an AES-256-GCM key for the notes. A
+// depositor adds `buildAppendBackupInstruction` to each deposit transaction,
+// and `recoverBackupNotes` later finds and decrypts every note from the key
+// alone.
+//
+// Every note backed up to one vault is linkable to the others through the
+// vault address, even when the deposits came from different wallets. Use a
+// separate viewing key where that matters.
+//
+// Entry format (must match programs/mixer/src/backup.rs):
+//   u16 LE length || nonce: [u8; 12] || AES-GCM(plaintext) with its 16-byte tag
+// where plaintext is version: u8 (= 1) || nullifier: [u8; 32] || secret: [u8; 32]
+//   || leaf_index: u64 LE
+import {
+  AccountRole,
+  getBase64Encoder,
+  getProgramDerivedAddress,
+  type Address,
+  type GetAccountInfoApi,
+  type Instruction,
+  type ProgramDerivedAddressBump,
+  type Rpc,
+} from "@solana/kit";
+import { SYSTEM_PROGRAM_ADDRESS } from "@solana-program/system";
+
+const textEncoder = new TextEncoder();
+
+/** Largest ciphertext one `AppendBackup` stores (`MAX_BACKUP_CIPHERTEXT_LEN`). */
+export const MAX_BACKUP_CIPHERTEXT_LEN = 256;
+
+const NOTE_VERSION = 1;
+const NOTE_PLAINTEXT_LEN = 1 + 32 + 32 + 8;
+const NONCE_LEN = 12;
+
+/** What a wallet needs to withdraw a note: its secrets and its leaf. */
+export interface BackupNote {
+  /** 32-byte big-endian field element. */
+  nullifier: Uint8Array;
+  /** 32-byte big-endian field element. */
+  secret: Uint8Array;
+  /** Position of the note's commitment in the deposit tree. */
+  leafIndex: bigint;
+}
+
+export interface BackupKeys {
+  /** Seed of the backup vault PDA. */
+  vaultId: Uint8Array;
+  key: CryptoKey;
+}
+
+async function hkdf(viewingKey: Uint8Array, info: string, bits: number): Promise<Uint8Array> {
+  const base = await crypto.subtle.importKey("raw", viewingKey, "HKDF", false, ["deriveBits"]);
+  const derived = await crypto.subtle.deriveBits(
+    { name: "HKDF", hash: "SHA-256", salt: new Uint8Array(0), info: textEncoder.encode(info) },
+    base,
+    bits
+  );
+  return new Uint8Array(derived);
+}
+
+export async function deriveBackupKeys(viewingKey: Uint8Array): Promise<BackupKeys> {
+  if (viewingKey.length !== 32) {
+    throw new Error("viewing key must be 32 bytes");
+  }
+  const vaultId = await hkdf(viewingKey, "hollow note backup vault id", 256);
+  const keyBytes = await hkdf(viewingKey, "hollow note backup key", 256);
+  const key = await crypto.subtle.importKey("raw", keyBytes, "AES-GCM", false, [
+    "encrypt",
+    "decrypt",
+  ]);
+  return { vaultId, key };
+}
+
+export async function getBackupVaultPda(
+  mixerProgramId: Address,
+  vaultId: Uint8Array
+): Promise<ProgramDerivedAddressBump> {
+  const [pda, bump] = await getProgramDerivedAddress({
+    programAddress: mixerProgramId,
+    seeds: [textEncoder.encode("backup_vault"), vaultId],
+  });
+  return [pda, bump];
+}
+
+export async function encryptBackupNote(keys: BackupKeys, note: BackupNote): Promise<Uint8Array> {
+  if (note.nullifier.length !== 32 || note.secret.length !== 32) {
+    throw new Error("nullifier and secret must be 32 bytes");
+  }
+  const plaintext = new Uint8Array(NOTE_PLAINTEXT_LEN);
+  plaintext[0] = NOTE_VERSION;
+  plaintext.set(note.nullifier, 1);
+  plaintext.set(note.secret, 33);
+  new DataView(plaintext.buffer).setBigUint64(65, note.leafIndex, true);
+  const nonce = crypto.getRandomValues(new Uint8Array(NONCE_LEN));
+  const sealed = await crypto.subtle.encrypt({ name: "AES-GCM", iv: nonce }, keys.key, plaintext);
+  const out = new Uint8Array(NONCE_LEN + sealed.byteLength);
+  out.set(nonce);
+  out.set(new Uint8Array(sealed), NONCE_LEN);
+  return out;
+}
+
+/** The note in `ciphertext`, or null if it was not encrypted under `keys`. */
+export async function decryptBackupNote(
+  keys: BackupKeys,
+  ciphertext: Uint8Array
+): Promise<BackupNote | null> {
+  if (ciphertext.length <= NONCE_LEN) {
+    return null;
+  }
+  let plaintext: Uint8Array;
+  try {
+    plaintext = new Uint8Array(
+      await crypto.subtle.decrypt(
+        { name: "AES-GCM", iv: ciphertext.subarray(0, NONCE_LEN) },
+        keys.key,
+        ciphertext.subarray(NONCE_LEN)
+      )
+    );
+  } catch {
+    return null;
+  }
+  if (plaintext.length !== NOTE_PLAINTEXT_LEN || plaintext[0] !== NOTE_VERSION) {
+    return null;
+  }
+  return {
+    nullifier: plaintext.slice(1, 33),
+    secret: plaintext.slice(33, 65),
+    leafIndex: new DataView(plaintext.buffer).getBigUint64(65, true),
+  };
+}
+
+/**
+ * `AppendBackup` instruction storing `ciphertext` in the vault of `vaultId`.
+ * `depositor` signs and pays the rent; add it to the deposit transaction.
+ */
+export async function buildAppendBackupInstruction(
+  mixerProgramId: Address,
+  depositor: Address,
+  vaultId: Uint8Array,
+  ciphertext: Uint8Array
+): Promise<Instruction> {
+  if (ciphertext.length === 0 || ciphertext.length > MAX_BACKUP_CIPHERTEXT_LEN) {
+    throw new Error(`backup ciphertext must be 1 to ${MAX_BACKUP_CIPHERTEXT_LEN} bytes`);
+  }
+  const [backupVault] = await getBackupVaultPda(mixerProgramId, vaultId);
+  const data = new Uint8Array(1 + 32 + ciphertext.length);
+  data[0] = 24; // AppendBackup instruction
+  data.set(vaultId, 1);
+  data.set(ciphertext, 33);
+  return {
+    programAddress: mixerProgramId,
+    accounts: [
+      { address: depositor, role: AccountRole.WRITABLE_SIGNER },
+      { address: backupVault, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ADDRESS, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+/** Ciphertexts in backup vault account data, in append order (`backup_entries`). */
+export function parseBackupEntries(data: Uint8Array): Uint8Array[] {
+  const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
+  const entries: Uint8Array[] = [];
+  let offset = 0;
+  while (offset + 2 <= data.length) {
+    const len = view.getUint16(offset, true);
+    if (offset + 2 + len > data.length) {
+      break;
+    }
+    entries.push(data.subarray(offset + 2, offset + 2 + len));
+    offset += 2 + len;
+  }
+  return entries;
+}
+
+/**
+ * Every note backed up under `viewingKey`, in deposit order. Entries that do
+ * not decrypt, such as ones appended by others, are skipped. Returns an
+ * empty array if the vault does not exist.
+ */
+export async function recoverBackupNotes(
+  rpc: Rpc<GetAccountInfoApi>,
+  mixerProgramId: Address,
+  viewingKey: Uint8Array
+): Promise<BackupNote[]> {
+  const keys = await deriveBackupKeys(viewingKey);
+  const [backupVault] = await getBackupVaultPda(mixerProgramId, keys.vaultId);
+  const { value } = await rpc.getAccountInfo(backupVault, { encoding: "base64" }).send();
+  if (value === null || value.owner !== mixerProgramId) {
+    return [];
+  }
+  const data = new Uint8Array(getBase64Encoder().encode(value.data[0]));
+  const notes = await Promise.all(
+    parseBackupEntries(data).map((entry) => decryptBackupNote(keys, entry))
+  );
+  return notes.filter((note): note is BackupNote => note !== null);
+}
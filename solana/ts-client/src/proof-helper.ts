@@ -11,6 +11,12 @@ export interface ProofResult {
 export interface CircuitConfig {
   circuitDir: string;
   circuitName: string;
+  /**
+   * Merkle tree depth the circuit was compiled for, i.e. the pool's
+   * `treeDepth`. When set, inputs with a path of another length are rejected
+   * before running nargo.
+   */
+  treeDepth?: number;
 }
 
 export interface MixerInputs {
@@ -56,6 +62,18 @@ function getPublicWitnessPath(config: CircuitConfig): string {
 }
 
 export function writeProverToml(config: CircuitConfig, inputs: MixerInputs): void {
+  if (config.treeDepth !== undefined) {
+    for (const [name, levels] of [
+      ["merkle_proof", inputs.merkle_proof],
+      ["is_even", inputs.is_even],
+    ] as const) {
+      if (levels.length !== config.treeDepth) {
+        throw new Error(
+          `${name} has ${levels.length} levels, the pool's tree has ${config.treeDepth}`
+        );
+      }
+    }
+  }
   const toml = `# Mixer Circuit Prover Inputs
 
 # Public inputs
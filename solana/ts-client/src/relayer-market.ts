@@ -0,0 +1,122 @@
+// Relayer selection for mixer withdrawals
+//
+// The relayer fee in a withdraw is not bound by the proof, so the program
+// only pays a nonzero fee if the recipient signs the withdraw, and never more
+// than the pool's on-chain cap (`relayer_fee_cap_bps` of the denomination).
+// Within that cap relayers compete. The client asks
+// every relayer it knows for a quote, drops those that do not answer in time
+// or quote above the cap, and hands its proof to the cheapest.
+//
+// Quote protocol: `GET <url>/quote` answers with JSON
+//   { "relayer": "<base58 address>", "feeLamports": "<u64 as a decimal string>" }
+// where `relayer` is the signer that will send the withdraw and receive the
+// fee. Pass both to `buildWithdrawInstruction`, have the recipient sign the
+// transaction, and send it to the relayer to add its signature.
+import { address, type Address } from "@solana/kit";
+import type { MixerStateAccount } from "./mixer-client.js";
+
+export interface RegisteredRelayer {
+  url: string;
+  /** If set, quotes naming any other signer are rejected. */
+  address?: Address;
+}
+
+export interface RelayerQuote {
+  url: string;
+  relayer: Address;
+  feeLamports: bigint;
+  /** Time the quote took to arrive, in milliseconds. */
+  latencyMs: number;
+}
+
+export interface RelayerQuoteOptions {
+  /** Time to wait for each relayer; defaults to 3 seconds. */
+  timeoutMs?: number;
+  /** Defaults to the global `fetch`. */
+  fetch?: typeof fetch;
+}
+
+const DEFAULT_QUOTE_TIMEOUT_MS = 3_000;
+
+/** Largest relayer fee the pool accepts, in lamports (`MixerState::max_relayer_fee`). */
+export function maxRelayerFee(state: MixerStateAccount): bigint {
+  return (state.denomination * BigInt(state.relayerFeeCapBps)) / 10_000n;
+}
+
+/** Ask `relayer` for a quote. Throws if it does not answer in time or answers malformed JSON. */
+export async function fetchRelayerQuote(
+  relayer: RegisteredRelayer,
+  opts: RelayerQuoteOptions = {}
+): Promise<RelayerQuote> {
+  const fetchQuote = opts.fetch ?? fetch;
+  const started = Date.now();
+  const response = await fetchQuote(new URL("quote", relayer.url.replace(/\/?$/, "/")), {
+    signal: AbortSignal.timeout(opts.timeoutMs ?? DEFAULT_QUOTE_TIMEOUT_MS),
+  });
+  if (!response.ok) {
+    throw new Error(`quote request failed with HTTP ${response.status}`);
+  }
+  const body = (await response.json()) as { relayer?: unknown; feeLamports?: unknown };
+  if (typeof body.relayer !== "string" || typeof body.feeLamports !== "string") {
+    throw new Error("quote must have string `relayer` and `feeLamports` fields");
+  }
+  const signer = address(body.relayer);
+  if (relayer.address !== undefined && signer !== relayer.address) {
+    throw new Error(`quote names signer ${signer}, expected ${relayer.address}`);
+  }
+  const feeLamports = BigInt(body.feeLamports);
+  if (feeLamports < 0n || feeLamports >= 1n << 64n) {
+    throw new Error("quoted fee is not a u64");
+  }
+  return { url: relayer.url, relayer: signer, feeLamports, latencyMs: Date.now() - started };
+}
+
+/**
+ * Quotes from every relayer in `relayers`, cheapest first and, at equal fees,
+ * fastest first. Relayers that fail to answer or quote above `maxFee` are
+ * listed in `rejected` with the reason.
+ */
+export async function compareRelayerQuotes(
+  relayers: RegisteredRelayer[],
+  maxFee: bigint,
+  opts: RelayerQuoteOptions = {}
+): Promise<{ quotes: RelayerQuote[]; rejected: Array<{ url: string; reason: string }> }> {
+  const settled = await Promise.allSettled(relayers.map((r) => fetchRelayerQuote(r, opts)));
+  const quotes: RelayerQuote[] = [];
+  const rejected: Array<{ url: string; reason: string }> = [];
+  settled.forEach((result, i) => {
+    const url = relayers[i].url;
+    if (result.status === "rejected") {
+      rejected.push({ url, reason: String(result.reason) });
+    } else if (result.value.feeLamports > maxFee) {
+      rejected.push({ url, reason: `fee ${result.value.feeLamports} exceeds the cap of ${maxFee}` });
+    } else {
+      quotes.push(result.value);
+    }
+  });
+  quotes.sort((a, b) =>
+    a.feeLamports === b.feeLamports
+      ? a.latencyMs - b.latencyMs
+      : a.feeLamports < b.feeLamports
+        ? -1
+        : 1
+  );
+  return { quotes, rejected };
+}
+
+/**
+ * The cheapest responsive relayer for a withdraw from the pool in `state`.
+ * Throws if no relayer answered with a fee within the pool's cap.
+ */
+export async function chooseRelayer(
+  relayers: RegisteredRelayer[],
+  state: MixerStateAccount,
+  opts: RelayerQuoteOptions = {}
+): Promise<RelayerQuote> {
+  const { quotes, rejected } = await compareRelayerQuotes(relayers, maxRelayerFee(state), opts);
+  if (quotes.length === 0) {
+    const reasons = rejected.map((r) => `${r.url}: ${r.reason}`).join("; ");
+    throw new Error(`no relayer quoted within the pool's fee cap (${reasons || "none registered"})`);
+  }
+  return quotes[0];
+}
@@ -0,0 +1,196 @@
+// Keeping a withdraw proof's root inside the pool's root history
+//
+// The pool only accepts proofs against one of its last
+// `MIXER_ROOT_HISTORY_SIZE` roots. A proof generated against an older root
+// fails on chain with `UnknownRoot`, after the transaction fee was paid. A
+// wallet that holds a proof for a while, or proves against a stale local
+// tree, should check the root against the pool first and re-prove if it has
+// rolled out.
+//
+// `proveAgainstKnownRoot` does that: it keeps a cached proof whose root is
+// still known, and otherwise re-syncs the tree, regenerates the witness and
+// proves again, reporting each step through `onStatus`. Tree sync and proving
+// are supplied by the caller, since where leaves come from and how proofs are
+// made (see proof-helper.ts) depend on the deployment.
+//
+// `watchLatestRoot` follows the pool's 48-byte latest root account instead of
+// refetching the state account, so a wallet learns of each deposit as it
+// lands and can sync its tree then.
+import {
+  getBase64Encoder,
+  type AccountNotificationsApi,
+  type Address,
+  type GetAccountInfoApi,
+  type Rpc,
+  type RpcSubscriptions,
+} from "@solana/kit";
+import {
+  decodeLatestRoot,
+  decodeMixerState,
+  MIXER_ROOT_HISTORY_SIZE,
+  type LatestRootAccount,
+  type MixerStateAccount,
+} from "./mixer-client.js";
+
+/** Roots a proof's root should survive by default before it is refreshed. */
+export const DEFAULT_MIN_REMAINING_ROOTS = 2;
+
+const DEFAULT_MAX_ATTEMPTS = 3;
+
+export type RootRefreshStatus =
+  | { kind: "root-known"; root: Uint8Array; remaining: number }
+  | { kind: "root-evicted"; root: Uint8Array }
+  | { kind: "root-expiring"; root: Uint8Array; remaining: number }
+  | { kind: "syncing-tree" }
+  | { kind: "proving"; root: Uint8Array };
+
+export class RootEvictedError extends Error {
+  constructor(readonly root: Uint8Array) {
+    super("the proof's root has rolled out of the pool's root history");
+    this.name = "RootEvictedError";
+  }
+}
+
+function bytesEqual(a: Uint8Array, b: Uint8Array): boolean {
+  return a.length === b.length && a.every((byte, i) => byte === b[i]);
+}
+
+/**
+ * How many more `PushRoot`s `root` survives in the pool's history, or null
+ * if the pool does not know it (`MixerState::is_known_root`). The latest root
+ * has `MIXER_ROOT_HISTORY_SIZE - 1` remaining; a root with 0 remaining is
+ * evicted by the next deposit.
+ */
+export function rootRemaining(state: MixerStateAccount, root: Uint8Array): number | null {
+  if (root.every((byte) => byte === 0)) {
+    return null;
+  }
+  const index = state.roots.findIndex((known) => bytesEqual(known, root));
+  if (index < 0) {
+    return null;
+  }
+  const age = (state.currentRootIndex - index + MIXER_ROOT_HISTORY_SIZE) % MIXER_ROOT_HISTORY_SIZE;
+  return MIXER_ROOT_HISTORY_SIZE - 1 - age;
+}
+
+export async function fetchMixerState(
+  rpc: Rpc<GetAccountInfoApi>,
+  mixerState: Address
+): Promise<MixerStateAccount> {
+  const { value } = await rpc.getAccountInfo(mixerState, { encoding: "base64" }).send();
+  if (value === null) {
+    throw new Error(`mixer state account ${mixerState} not found`);
+  }
+  return decodeMixerState(new Uint8Array(getBase64Encoder().encode(value.data[0])));
+}
+
+/** Throws `RootEvictedError` unless the pool at `mixerState` still knows `root`. */
+export async function assertRootKnown(
+  rpc: Rpc<GetAccountInfoApi>,
+  mixerState: Address,
+  root: Uint8Array
+): Promise<void> {
+  if (rootRemaining(await fetchMixerState(rpc, mixerState), root) === null) {
+    throw new RootEvictedError(root);
+  }
+}
+
+export interface ProveAgainstKnownRootOptions<P> {
+  rpc: Rpc<GetAccountInfoApi>;
+  mixerState: Address;
+  /** A proof made earlier, kept if its root is still known. */
+  cached?: { root: Uint8Array; proof: P };
+  /**
+   * Bring the local tree up to date and return its root. Called when the
+   * cached root is missing or evicted, and again if the synced root is not
+   * one the pool knows yet.
+   */
+  syncTree: () => Promise<Uint8Array>;
+  /** Regenerate the witness for `root`'s tree and prove it. */
+  prove: (root: Uint8Array) => Promise<P>;
+  /**
+   * Refresh a cached proof whose root survives fewer further deposits than
+   * this. Defaults to `DEFAULT_MIN_REMAINING_ROOTS`.
+   */
+  minRemaining?: number;
+  /** Sync attempts before giving up; defaults to 3. */
+  maxAttempts?: number;
+  onStatus?: (status: RootRefreshStatus) => void;
+}
+
+/**
+ * A proof against a root the pool currently knows: the cached one if its
+ * root is still known and not about to expire, a fresh one otherwise. The
+ * new root is checked against the pool again after proving, since deposits
+ * may have evicted it meanwhile. Throws `RootEvictedError` if no synced root
+ * stays known within `maxAttempts`.
+ */
+export async function proveAgainstKnownRoot<P>(
+  opts: ProveAgainstKnownRootOptions<P>
+): Promise<{ root: Uint8Array; proof: P }> {
+  const report = opts.onStatus ?? (() => {});
+  const minRemaining = opts.minRemaining ?? DEFAULT_MIN_REMAINING_ROOTS;
+  const maxAttempts = opts.maxAttempts ?? DEFAULT_MAX_ATTEMPTS;
+
+  if (opts.cached !== undefined) {
+    const { root } = opts.cached;
+    const remaining = rootRemaining(await fetchMixerState(opts.rpc, opts.mixerState), root);
+    if (remaining === null) {
+      report({ kind: "root-evicted", root });
+    } else if (remaining < minRemaining) {
+      report({ kind: "root-expiring", root, remaining });
+    } else {
+      report({ kind: "root-known", root, remaining });
+      return opts.cached;
+    }
+  }
+
+  let root: Uint8Array = new Uint8Array(32);
+  for (let attempt = 0; attempt < maxAttempts; attempt++) {
+    report({ kind: "syncing-tree" });
+    root = await opts.syncTree();
+    if (rootRemaining(await fetchMixerState(opts.rpc, opts.mixerState), root) === null) {
+      // The tree is behind the pool past its history, or ahead of the last
+      // PushRoot the node has seen; sync again.
+      continue;
+    }
+    report({ kind: "proving", root });
+    const proof = await opts.prove(root);
+    const remaining = rootRemaining(await fetchMixerState(opts.rpc, opts.mixerState), root);
+    if (remaining !== null) {
+      report({ kind: "root-known", root, remaining });
+      return { root, proof };
+    }
+    report({ kind: "root-evicted", root });
+  }
+  throw new RootEvictedError(root);
+}
+
+export async function fetchLatestRoot(
+  rpc: Rpc<GetAccountInfoApi>,
+  latestRoot: Address
+): Promise<LatestRootAccount> {
+  const { value } = await rpc.getAccountInfo(latestRoot, { encoding: "base64" }).send();
+  if (value === null) {
+    throw new Error(`latest root account ${latestRoot} not found`);
+  }
+  return decodeLatestRoot(new Uint8Array(getBase64Encoder().encode(value.data[0])));
+}
+
+/**
+ * The pool's latest root each time a `PushRoot` rewrites it, from an account
+ * subscription on `latestRoot` (`getLatestRootPda`). Ends when `abortSignal`
+ * fires.
+ */
+export async function* watchLatestRoot(
+  rpcSubscriptions: RpcSubscriptions<AccountNotificationsApi>,
+  latestRoot: Address,
+  abortSignal: AbortSignal
+): AsyncGenerator<LatestRootAccount> {
+  const notifications = await rpcSubscriptions
+    .accountNotifications(latestRoot, { commitment: "confirmed", encoding: "base64" })
+    .subscribe({ abortSignal });
+  for await (const { value } of notifications) {
+    yield decodeLatestRoot(new Uint8Array(getBase64Encoder().encode(value.data[0])));
+  }
+}
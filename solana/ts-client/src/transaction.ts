@@ -0,0 +1,210 @@
+// Transaction message construction for mixer instructions
+//
+// Everything is built as a v0 message by default so lookup tables (see
+// lookup-table.ts) can be applied; legacy messages remain available for RPC
+// nodes or wallets that cannot handle versioned transactions.
+import {
+  appendTransactionMessageInstructions,
+  compileTransaction,
+  compressTransactionMessageUsingAddressLookupTables,
+  createTransactionMessage,
+  getBase64EncodedWireTransaction,
+  getTransactionEncoder,
+  pipe,
+  setTransactionMessageFeePayerSigner,
+  setTransactionMessageLifetimeUsingBlockhash,
+  type AddressesByLookupTableAddress,
+  type GetVersionApi,
+  type Instruction,
+  type Rpc,
+  type SimulateTransactionApi,
+  type TransactionSigner,
+} from "@solana/kit";
+import {
+  COMPUTE_BUDGET_PROGRAM_ADDRESS,
+  getSetComputeUnitLimitInstruction,
+  getSetComputeUnitPriceInstruction,
+} from "@solana-program/compute-budget";
+
+export type MixerTransactionVersion = 0 | "legacy";
+
+/**
+ * Pick the newest message version the connected RPC node understands.
+ * Versioned transactions shipped in solana-core 1.10; anything older, or a
+ * node that refuses `getVersion`, falls back to legacy.
+ */
+export async function detectTransactionVersion(
+  rpc: Rpc<GetVersionApi>
+): Promise<MixerTransactionVersion> {
+  try {
+    const version = await rpc.getVersion().send();
+    const [major, minor] = version["solana-core"].split(".").map(Number);
+    if (major > 1 || (major === 1 && minor >= 10)) {
+      return 0;
+    }
+    return "legacy";
+  } catch {
+    return "legacy";
+  }
+}
+
+export interface MixerTransactionMessageOptions {
+  feePayer: TransactionSigner;
+  latestBlockhash: Parameters<typeof setTransactionMessageLifetimeUsingBlockhash>[0];
+  instructions: Instruction[];
+  /** Defaults to 0. */
+  version?: MixerTransactionVersion;
+  /** Only applied to v0 messages. */
+  lookupTables?: AddressesByLookupTableAddress;
+}
+
+export function buildMixerTransactionMessage(opts: MixerTransactionMessageOptions) {
+  const { feePayer, latestBlockhash, instructions, lookupTables } = opts;
+  const version = opts.version ?? 0;
+
+  if (version === "legacy") {
+    if (lookupTables && Object.keys(lookupTables).length > 0) {
+      throw new Error("address lookup tables require a v0 transaction message");
+    }
+    return pipe(
+      createTransactionMessage({ version: "legacy" }),
+      (m) => setTransactionMessageFeePayerSigner(feePayer, m),
+      (m) => setTransactionMessageLifetimeUsingBlockhash(latestBlockhash, m),
+      (m) => appendTransactionMessageInstructions(instructions, m)
+    );
+  }
+
+  const message = pipe(
+    createTransactionMessage({ version: 0 }),
+    (m) => setTransactionMessageFeePayerSigner(feePayer, m),
+    (m) => setTransactionMessageLifetimeUsingBlockhash(latestBlockhash, m),
+    (m) => appendTransactionMessageInstructions(instructions, m)
+  );
+  return lookupTables
+    ? compressTransactionMessageUsingAddressLookupTables(message, lookupTables)
+    : message;
+}
+
+// ============================================================================
+// Transaction size guard
+// ============================================================================
+
+/** Maximum serialized transaction size accepted by the cluster (IPv6 MTU minus headers). */
+export const TRANSACTION_SIZE_LIMIT = 1232;
+
+export class TransactionTooLargeError extends Error {
+  readonly size: number;
+  readonly limit: number;
+
+  constructor(size: number, limit: number) {
+    super(
+      `transaction is ${size} bytes, exceeding the ${limit}-byte limit; ` +
+        "upload the proof with CreateProofBuffer/WriteProofChunk and use WithdrawFromBuffer, " +
+        "or add an address lookup table"
+    );
+    this.name = "TransactionTooLargeError";
+    this.size = size;
+    this.limit = limit;
+  }
+}
+
+type CompilableTransactionMessage = Parameters<typeof compileTransaction>[0];
+
+/** Serialized wire size of the message once signed, including signature slots. */
+export function getTransactionMessageSize(message: CompilableTransactionMessage): number {
+  return getTransactionEncoder().encode(compileTransaction(message)).length;
+}
+
+/** Throw `TransactionTooLargeError` if the message would not fit in a transaction. */
+export function assertTransactionMessageFitsSize(
+  message: CompilableTransactionMessage,
+  limit: number = TRANSACTION_SIZE_LIMIT
+): void {
+  const size = getTransactionMessageSize(message);
+  if (size > limit) {
+    throw new TransactionTooLargeError(size, limit);
+  }
+}
+
+// ============================================================================
+// Compute budget sizing
+// ============================================================================
+
+/** Per-transaction compute unit ceiling enforced by the runtime. */
+export const MAX_COMPUTE_UNITS = 1_400_000;
+
+/** Default headroom added on top of simulated consumption. */
+export const DEFAULT_COMPUTE_UNIT_MARGIN = 0.2;
+
+export interface ComputeBudgetOptions {
+  /** Fractional headroom over simulated units, e.g. 0.2 for +20%. */
+  computeUnitMargin?: number;
+  /** Priority fee; omitted from the transaction when undefined. */
+  computeUnitPriceMicroLamports?: bigint;
+}
+
+function withoutComputeBudgetInstructions(instructions: Instruction[]): Instruction[] {
+  return instructions.filter((ix) => ix.programAddress !== COMPUTE_BUDGET_PROGRAM_ADDRESS);
+}
+
+/**
+ * Simulate the transaction with the maximum compute limit and return the
+ * units it consumed. Throws with the program logs if simulation fails, so
+ * errors surface before anything is signed or paid for.
+ */
+export async function simulateComputeUnits(
+  rpc: Rpc<SimulateTransactionApi>,
+  opts: MixerTransactionMessageOptions
+): Promise<number> {
+  const message = buildMixerTransactionMessage({
+    ...opts,
+    instructions: [
+      getSetComputeUnitLimitInstruction({ units: MAX_COMPUTE_UNITS }),
+      ...withoutComputeBudgetInstructions(opts.instructions),
+    ],
+  });
+  // An oversize transaction would come back from the RPC as an opaque error.
+  assertTransactionMessageFitsSize(message);
+  const wireTransaction = getBase64EncodedWireTransaction(compileTransaction(message));
+  const { value } = await rpc
+    .simulateTransaction(wireTransaction, {
+      encoding: "base64",
+      sigVerify: false,
+      replaceRecentBlockhash: true,
+    })
+    .send();
+  if (value.err) {
+    const logs = value.logs?.join("\n") ?? "(no logs)";
+    throw new Error(`simulation failed: ${JSON.stringify(value.err)}\n${logs}`);
+  }
+  if (value.unitsConsumed === undefined) {
+    throw new Error("simulation did not report consumed compute units");
+  }
+  return Number(value.unitsConsumed);
+}
+
+/**
+ * Build a transaction message whose compute limit comes from simulation
+ * plus a safety margin, replacing any compute budget instructions already
+ * present in `opts.instructions`.
+ */
+export async function buildMixerTransactionMessageWithComputeBudget(
+  rpc: Rpc<SimulateTransactionApi>,
+  opts: MixerTransactionMessageOptions & ComputeBudgetOptions
+) {
+  const { computeUnitMargin, computeUnitPriceMicroLamports, ...messageOpts } = opts;
+  const margin = computeUnitMargin ?? DEFAULT_COMPUTE_UNIT_MARGIN;
+  const consumed = await simulateComputeUnits(rpc, messageOpts);
+  const units = Math.min(MAX_COMPUTE_UNITS, Math.ceil(consumed * (1 + margin)));
+
+  const budget: Instruction[] = [getSetComputeUnitLimitInstruction({ units })];
+  if (computeUnitPriceMicroLamports !== undefined) {
+    budget.push(
+      getSetComputeUnitPriceInstruction({ microLamports: computeUnitPriceMicroLamports })
+    );
+  }
+  return buildMixerTransactionMessage({
+    ...messageOpts,
+    instructions: [...budget, ...withoutComputeBudgetInstructions(messageOpts.instructions)],
+  });
+}
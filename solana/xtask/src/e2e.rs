@@ -0,0 +1,409 @@
+//! End-to-end run of the full prove-and-withdraw flow against a deployed
+//! pool, the only test that exercises the real Sunspot verifier.
+//!
+//! 1. Sync the tree: rebuild it from the commitments earlier runs deposited,
+//!    recorded in the leaves file, and check its root is the pool's latest.
+//! 2. Deposit a fresh note: transfer one denomination into the vault and push
+//!    the new root, signed by the pool authority.
+//! 3. Prove: write the note's inputs for the circuit, run `nargo execute` and
+//!    `sunspot prove`, and check the public witness matches the withdraw.
+//! 4. Withdraw through a relayer, which signs and pays for the transaction,
+//!    to a fresh recipient.
+//! 5. Assert the recipient received the withdraw amount, the vault paid it,
+//!    the nullifier is marked, and a second withdraw of the note fails.
+//!
+//! `PushRoot` takes its root from the caller, so only commitments this
+//! runner deposited can be reproduced; the pool's leaf count must equal the
+//! number in the leaves file. Run it against a pool of its own, e.g. one
+//! created with `mixer-deploy init` on devnet or a localnet.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use ark_bn254::Fr;
+use ark_ff::UniformRand;
+use mixer::{
+    cpi,
+    curve::Curve,
+    public_inputs::{self, fr_to_field, PublicInputs},
+    verifier::VerifierBackend,
+    MixerError, MixerState,
+};
+use mixer_vectors::tree::{self, from_hex, to_hex, Tree};
+use rand::rngs::OsRng;
+use solana_commitment_config::CommitmentConfig;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_program::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_signer::Signer;
+use solana_system_interface::instruction as system_instruction;
+use solana_transaction::Transaction;
+use solana_transaction_error::TransactionError;
+
+use crate::Result;
+
+/// Sunspot verification runs close to the transaction limit.
+const WITHDRAW_COMPUTE_UNITS: u32 = 1_400_000;
+/// Inputs file for `nargo execute --prover-name`, next to `Prover.toml`.
+const PROVER_NAME: &str = "E2E";
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// RPC URL or Solana CLI moniker.
+    #[arg(long, default_value = "devnet")]
+    url: String,
+    /// Pool authority; pays for the deposit and funds the relayer. Defaults
+    /// to the Solana CLI keypair.
+    #[arg(long)]
+    payer: Option<PathBuf>,
+    #[arg(long)]
+    mixer_program_id: Pubkey,
+    /// Verifier of a pool that does not pin one.
+    #[arg(long)]
+    verifier_program_id: Option<Pubkey>,
+    /// Relayer that sends the withdraw; a fresh one funded by the payer if
+    /// not given.
+    #[arg(long)]
+    relayer: Option<PathBuf>,
+    /// Lamports a fresh relayer is funded with, for fees and the nullifier
+    /// marker's rent.
+    #[arg(long, default_value_t = 10_000_000)]
+    relayer_funding: u64,
+    /// Noir circuit, compiled and set up with Sunspot. Defaults to the
+    /// repository's `circuits/`.
+    #[arg(long)]
+    circuit_dir: Option<PathBuf>,
+    /// Commitments deposited by earlier runs, updated by this one.
+    #[arg(long, default_value = "e2e-leaves.json")]
+    leaves: PathBuf,
+}
+
+struct Pool {
+    program_id: Pubkey,
+    state: Pubkey,
+    vault: Pubkey,
+    verifier: Pubkey,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let rpc =
+        RpcClient::new_with_commitment(rpc_url(&args.url).to_string(), CommitmentConfig::confirmed());
+    let payer = keypair(&args.payer.clone().unwrap_or_else(default_keypair))?;
+    let program_id = args.mixer_program_id;
+    let state_address = Pubkey::find_program_address(&[b"mixer_state"], &program_id).0;
+    let state = fetch_state(&rpc, &state_address)?;
+    if state.authority != payer.pubkey() {
+        return Err(format!(
+            "the pool authority is {}, not the payer {}; PushRoot needs the authority",
+            state.authority,
+            payer.pubkey()
+        )
+        .into());
+    }
+    if VerifierBackend::try_from(state.verifier_backend)? != VerifierBackend::SunspotCpi
+        || state.curve()? != Curve::Bn254
+    {
+        return Err("the pool does not verify BN254 proofs with a Sunspot verifier".into());
+    }
+    let verifier = match (state.verifier_program, args.verifier_program_id) {
+        (pinned, _) if pinned != Pubkey::default() => pinned,
+        (_, Some(verifier)) => verifier,
+        _ => return Err("the pool pins no verifier; pass --verifier-program-id".into()),
+    };
+    let pool = Pool {
+        program_id,
+        state: state_address,
+        vault: Pubkey::find_program_address(&[b"mixer_vault"], &program_id).0,
+        verifier,
+    };
+
+    println!("Syncing the tree from {}", args.leaves.display());
+    let mut leaves = read_leaves(&args.leaves)?;
+    check_synced(&state, &leaves)?;
+    println!("  {} leaves, latest root matches", leaves.len());
+
+    let nullifier = Fr::rand(&mut OsRng);
+    let secret = Fr::rand(&mut OsRng);
+    leaves.push(tree::commitment(nullifier, secret));
+    let index = leaves.len() - 1;
+    let tree = Tree::new(state.tree_depth.into(), &leaves);
+    let root = fr_to_field(&tree.root());
+    println!("Depositing leaf {index}");
+    let mut deposit = Vec::new();
+    let latest_root = mixer::LatestRoot::find_address(&program_id).0;
+    if rpc.get_account_with_commitment(&latest_root, rpc.commitment())?.value.is_none() {
+        deposit.push(cpi::create_latest_root_instruction(&program_id, &payer.pubkey()));
+    }
+    let pool_stats = mixer::PoolStats::find_address(&program_id).0;
+    if rpc.get_account_with_commitment(&pool_stats, rpc.commitment())?.value.is_none() {
+        deposit.push(cpi::create_pool_stats_instruction(&program_id, &payer.pubkey()));
+    }
+    deposit.push(cpi::push_root_instruction(
+        &program_id,
+        &payer.pubkey(),
+        &pool.state,
+        root,
+        Some(leaves.len() as u64),
+    ));
+    println!("  {}", send(&rpc, &deposit, &payer)?);
+    write_leaves(&args.leaves, &leaves)?;
+    if state.min_withdraw_delay > 0 {
+        println!("Waiting {}s for the root to age", state.min_withdraw_delay);
+        thread::sleep(Duration::from_secs(state.min_withdraw_delay + 2));
+    }
+
+    let recipient = Keypair::new().pubkey();
+    let inputs = PublicInputs {
+        root,
+        nullifier_hash: fr_to_field(&tree::nullifier_hash(nullifier)),
+        recipient_field: public_inputs::reduce(&recipient.to_bytes()),
+    };
+    let circuit_dir = args
+        .circuit_dir
+        .clone()
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("../../circuits"));
+    println!("Proving with {}", circuit_dir.display());
+    let path = tree.path(index);
+    let proof = prove(&circuit_dir, &inputs, nullifier, secret, &path)?;
+
+    let relayer = match &args.relayer {
+        Some(path) => keypair(path)?,
+        None => {
+            let relayer = Keypair::new();
+            println!("Funding relayer {}", relayer.pubkey());
+            let funding = args.relayer_funding;
+            let transfer = system_instruction::transfer(&payer.pubkey(), &relayer.pubkey(), funding);
+            send(&rpc, &[transfer], &payer)?;
+            relayer
+        }
+    };
+    let nullifier_address =
+        Pubkey::find_program_address(&[b"nullifier", &inputs.nullifier_hash], &program_id).0;
+    let withdraw = [
+        ComputeBudgetInstruction::set_compute_unit_limit(WITHDRAW_COMPUTE_UNITS),
+        withdraw_instruction(&pool, &relayer.pubkey(), &recipient, &inputs, &proof),
+    ];
+    let vault_before = rpc.get_balance(&pool.vault)?;
+    println!("Withdrawing to {recipient} through the relayer");
+    println!("  {}", send(&rpc, &withdraw, &relayer)?);
+
+    let amount = state.withdraw_amount();
+    let received = rpc.get_balance(&recipient)?;
+    let paid = vault_before.saturating_sub(rpc.get_balance(&pool.vault)?);
+    if received != amount || paid != amount {
+        return Err(format!(
+            "expected {amount} lamports to move from the vault to the recipient; the recipient \
+             received {received} and the vault paid {paid}"
+        )
+        .into());
+    }
+    if rpc.get_balance(&nullifier_address)? == 0 {
+        return Err(format!("nullifier {nullifier_address} is not marked").into());
+    }
+    let replay = Transaction::new_signed_with_payer(
+        &withdraw,
+        Some(&relayer.pubkey()),
+        &[&relayer],
+        rpc.get_latest_blockhash()?,
+    );
+    let nullifier_used = TransactionError::InstructionError(
+        1,
+        InstructionError::Custom(MixerError::NullifierUsed as u32),
+    );
+    match rpc.simulate_transaction(&replay)?.value.err {
+        Some(err) if err == nullifier_used.into() => {}
+        err => return Err(format!("a second withdraw of the note gave {err:?}").into()),
+    }
+    println!("Recipient received {amount} lamports; the note cannot be withdrawn twice");
+    Ok(())
+}
+
+/// JSON-RPC endpoint for `url`, expanding CLI monikers.
+fn rpc_url(url: &str) -> &str {
+    match url {
+        "localhost" => "http://127.0.0.1:8899",
+        "devnet" => "https://api.devnet.solana.com",
+        "testnet" => "https://api.testnet.solana.com",
+        "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+        url => url,
+    }
+}
+
+fn default_keypair() -> PathBuf {
+    Path::new(&env::var("HOME").unwrap_or_default()).join(".config/solana/id.json")
+}
+
+fn keypair(path: &Path) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|e| format!("reading keypair {}: {e}", path.display()).into())
+}
+
+fn fetch_state(rpc: &RpcClient, address: &Pubkey) -> Result<MixerState> {
+    let data = rpc
+        .get_account_data(address)
+        .map_err(|e| format!("fetching pool state {address}: {e}"))?;
+    Ok(mixer::unpack_state(&data)?)
+}
+
+fn read_leaves(path: &Path) -> Result<Vec<Fr>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    let hex: Vec<String> =
+        serde_json::from_str(&text).map_err(|e| format!("parsing {}: {e}", path.display()))?;
+    Ok(hex.iter().map(|h| from_hex(h)).collect::<std::result::Result<_, _>>()?)
+}
+
+fn write_leaves(path: &Path, leaves: &[Fr]) -> Result<()> {
+    let hex: Vec<String> = leaves.iter().map(to_hex).collect();
+    fs::write(path, serde_json::to_string_pretty(&hex)? + "\n")
+        .map_err(|e| format!("writing {}: {e}", path.display()).into())
+}
+
+/// Check the recorded leaves reproduce the pool's latest root.
+fn check_synced(state: &MixerState, leaves: &[Fr]) -> Result<()> {
+    if state.leaf_count != leaves.len() as u64 {
+        return Err(format!(
+            "the pool has {} leaves but the leaves file records {}; run against a pool only \
+             this runner deposits into",
+            state.leaf_count,
+            leaves.len()
+        )
+        .into());
+    }
+    if leaves.is_empty() {
+        return Ok(());
+    }
+    let root = fr_to_field(&Tree::new(state.tree_depth.into(), leaves).root());
+    if root != state.roots[state.current_root_index as usize] {
+        return Err("the recorded leaves do not reproduce the pool's latest root".into());
+    }
+    Ok(())
+}
+
+/// Prove the note with `nargo execute` and `sunspot prove` and return
+/// `proof || public_witness`, the payload of a Sunspot withdraw.
+fn prove(
+    dir: &Path,
+    inputs: &PublicInputs,
+    nullifier: Fr,
+    secret: Fr,
+    path: &tree::Path,
+) -> Result<Vec<u8>> {
+    let hex = |field: &[u8; 32]| {
+        format!("0x{}", field.iter().map(|b| format!("{b:02x}")).collect::<String>())
+    };
+    let siblings: Vec<String> = path.siblings.iter().map(|s| format!("  \"{}\"", to_hex(s))).collect();
+    let is_even: Vec<String> = path.is_even.iter().map(|b| format!("  {b}")).collect();
+    let toml = format!(
+        "root = \"{}\"\nnullifier_hash = \"{}\"\nrecipient = \"{}\"\nnullifier = \"{}\"\nsecret = \"{}\"\nmerkle_proof = [\n{}\n]\nis_even = [\n{}\n]\n",
+        hex(&inputs.root),
+        hex(&inputs.nullifier_hash),
+        hex(&inputs.recipient_field),
+        to_hex(&nullifier),
+        to_hex(&secret),
+        siblings.join(",\n"),
+        is_even.join(",\n"),
+    );
+    let prover_toml = dir.join(format!("{PROVER_NAME}.toml"));
+    fs::write(&prover_toml, toml)?;
+    let nargo = env::var("NARGO").unwrap_or_else(|_| "nargo".into());
+    let executed = tool(dir, &nargo, &["execute", "--prover-name", PROVER_NAME]);
+    let _ = fs::remove_file(&prover_toml);
+    executed?;
+
+    let name = circuit_name(dir)?;
+    let target = dir.join("target");
+    let artifact = |ext: &str| target.join(format!("{name}.{ext}"));
+    let sunspot_args = ["json", "gz", "ccs", "pk"].map(artifact);
+    let sunspot_args: Vec<&str> = sunspot_args
+        .iter()
+        .map(|p| p.to_str().ok_or("non UTF-8 circuit path"))
+        .collect::<std::result::Result<_, _>>()?;
+    tool(dir, "sunspot", &[&["prove"], sunspot_args.as_slice()].concat())?;
+
+    let mut proof = fs::read(artifact("proof"))?;
+    let witness = fs::read(artifact("pw"))?;
+    if PublicInputs::from_sunspot_witness(&witness)? != *inputs {
+        return Err("the public witness does not match the withdraw's public inputs".into());
+    }
+    proof.extend_from_slice(&witness);
+    Ok(proof)
+}
+
+/// Package name in `Nargo.toml`, which names the compiled artifacts.
+fn circuit_name(dir: &Path) -> Result<String> {
+    let manifest = dir.join("Nargo.toml");
+    let text =
+        fs::read_to_string(&manifest).map_err(|e| format!("reading {}: {e}", manifest.display()))?;
+    text.lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "name").then(|| value.trim().trim_matches('"').to_string())
+        })
+        .ok_or_else(|| format!("no package name in {}", manifest.display()).into())
+}
+
+fn tool(dir: &Path, program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("running {program}: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn withdraw_instruction(
+    pool: &Pool,
+    relayer: &Pubkey,
+    recipient: &Pubkey,
+    inputs: &PublicInputs,
+    proof: &[u8],
+) -> Instruction {
+    let nullifier =
+        Pubkey::find_program_address(&[b"nullifier", &inputs.nullifier_hash], &pool.program_id).0;
+    cpi::withdraw_instruction(
+        &pool.program_id,
+        relayer,
+        &pool.state,
+        &nullifier,
+        &pool.vault,
+        recipient,
+        &pool.verifier,
+        inputs.root,
+        inputs.nullifier_hash,
+        inputs.recipient_field,
+        0,
+        0,
+        0,
+        proof,
+    )
+}
+
+/// Send `instructions` paid for by `payer` and return the signature.
+fn send(rpc: &RpcClient, instructions: &[Instruction], payer: &Keypair) -> Result<String> {
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        rpc.get_latest_blockhash()?,
+    );
+    Ok(rpc.send_and_confirm_transaction(&tx)?.to_string())
+}
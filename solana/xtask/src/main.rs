@@ -0,0 +1,37 @@
+//! Development tasks for the workspace, run as `cargo xtask <task>`.
+//!
+//! - `e2e`: deposit into a deployed pool, prove the note with the real
+//!   circuit and withdraw it through a relayer; see [`e2e`].
+
+mod e2e;
+
+use std::{error::Error, process};
+
+use clap::{Parser, Subcommand};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(about = "Development tasks for the mixer workspace")]
+struct Cli {
+    #[command(subcommand)]
+    task: Task,
+}
+
+#[derive(Subcommand)]
+enum Task {
+    /// Deposit, prove and withdraw against a deployed pool and its Sunspot
+    /// verifier.
+    E2e(e2e::Args),
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = match cli.task {
+        Task::E2e(args) => e2e::run(args),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        process::exit(1);
+    }
+}